@@ -17,13 +17,21 @@
 
 #![stable(feature = "rust1", since = "1.0.0")]
 
+use ascii::AsciiExt;
+use boxed::Box;
+use collections::HashMap;
+use env;
 use fmt;
-use ffi::OsString;
+use ffi::{OsString, OsStr};
+use hash::{Hasher, SipHasher};
 use io::{self, SeekFrom, Seek, Read, Write};
-use path::{Path, PathBuf};
+use ops::{Deref, DerefMut, Range};
+use path::{Component, Path, PathBuf};
+use string::String;
+use sync::{Mutex, MutexGuard};
 use sys::fs as fs_imp;
-use sys_common::io::read_to_end_uninitialized;
 use sys_common::{AsInnerMut, FromInner, AsInner, IntoInner};
+use time::Duration;
 use vec::Vec;
 
 /// A reference to an open file on the filesystem.
@@ -62,6 +70,93 @@ pub struct File {
 #[stable(feature = "rust1", since = "1.0.0")]
 pub struct Metadata(fs_imp::FileAttr);
 
+/// The properties of a file that tell whether it was changed or replaced,
+/// as returned by `Metadata::identity`.
+///
+/// Two identities compare equal if they refer to the same file with the
+/// same contents, as far as the file system can tell.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[unstable(feature = "metadata_identity", reason = "recently added API", issue = "0")]
+pub struct FileIdentity(fs_imp::FileIdentity);
+
+/// What a symbolic link points to, as returned by `read_link_full`.
+#[derive(Clone, Debug)]
+#[unstable(feature = "fs_read_link_full", reason = "recently added API", issue = "0")]
+pub struct SymlinkTarget {
+    target: PathBuf,
+    is_relative: bool,
+    is_directory: Option<bool>,
+}
+
+/// The type of a file system, as returned by `filesystem_type`.
+#[unstable(feature = "fs_filesystem_type", reason = "recently added API",
+           issue = "0")]
+#[derive(Clone, Debug)]
+pub struct FsType {
+    name: String,
+    magic: Option<u64>,
+}
+
+/// The file systems which `FsType::kind` recognizes.
+#[unstable(feature = "fs_filesystem_type", reason = "recently added API",
+           issue = "0")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FsKind {
+    /// ext2, ext3 or ext4.
+    Ext,
+    /// XFS.
+    Xfs,
+    /// Btrfs.
+    Btrfs,
+    /// ZFS.
+    Zfs,
+    /// A file system living in memory, such as tmpfs or ramfs.
+    Tmpfs,
+    /// NFS.
+    Nfs,
+    /// SMB or CIFS.
+    Smb,
+    /// A file system implemented in user space with FUSE.
+    Fuse,
+    /// OverlayFS.
+    Overlay,
+    /// FAT12, FAT16 or FAT32.
+    Fat,
+    /// exFAT.
+    ExFat,
+    /// NTFS.
+    Ntfs,
+    /// ReFS.
+    ReFs,
+    /// APFS.
+    Apfs,
+    /// HFS or HFS+.
+    Hfs,
+    /// Any file system not part of this list.
+    Other,
+
+    #[unstable(feature = "fs_filesystem_type_internals",
+               reason = "better expressed through extensible enums that this \
+                         enum cannot be exhaustively matched against",
+               issue = "0")]
+    #[doc(hidden)]
+    __Nonexhaustive,
+}
+
+/// A cache of canonicalized paths, for use with `canonicalize_cached`.
+///
+/// The cache assumes that the file system doesn't change while it is in use:
+/// once a path has been canonicalized, later lookups return the same result
+/// even if a symbolic link along the way has since been repointed or the
+/// file removed. It is up to its owner to keep it short-lived, or to call
+/// `remove` or `clear` after changing the file system.
+#[unstable(feature = "fs_canonicalize_cached", reason = "recently added API",
+           issue = "0")]
+#[derive(Debug)]
+pub struct PathCache {
+    map: Mutex<HashMap<PathBuf, PathBuf>>,
+}
+
 /// Iterator over the entries in a directory.
 ///
 /// This iterator is returned from the `read_dir` function of this module and
@@ -73,9 +168,61 @@ pub struct Metadata(fs_imp::FileAttr);
 ///
 /// This `io::Result` will be an `Err` if there's some sort of intermittent
 /// IO error during iteration.
+///
+/// Where the error only concerns a single entry, iteration carries on with
+/// the next one after the `Err` has been yielded. On Unix this is the case
+/// for `ENAMETOOLONG`, reported for a name too long for the platform's
+/// `dirent`. Any other error leaves the directory stream in an unknown
+/// position, so the iterator is fused after it and only returns `None` from
+/// then on. On Windows every error is of the second kind.
 #[stable(feature = "rust1", since = "1.0.0")]
 pub struct ReadDir(fs_imp::ReadDir);
 
+/// Iterator over the entries in a directory whose names match a predicate.
+///
+/// This iterator is returned from the `read_dir_filtered` function of this
+/// module and otherwise behaves like `ReadDir`.
+#[unstable(feature = "read_dir_filtered", reason = "recently added API",
+           issue = "0")]
+pub struct ReadDirFiltered<F> {
+    inner: fs_imp::ReadDir,
+    predicate: F,
+}
+
+/// Iterator over the entries in a directory along with their metadata.
+///
+/// This iterator is returned from the `read_dir_with_metadata` function of
+/// this module.
+#[unstable(feature = "read_dir_with_metadata", reason = "recently added API",
+           issue = "0")]
+pub struct ReadDirWithMetadata(fs_imp::ReadDir);
+
+/// A read-only memory map of part of a file, created by `File::memory_map`.
+///
+/// It dereferences to the mapped bytes and unmaps them when dropped.
+#[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+pub struct Mmap(fs_imp::Mmap);
+
+/// A writable memory map of part of a file, created by
+/// `File::memory_map_mut`.
+///
+/// It dereferences to the mapped bytes and unmaps them when dropped, without
+/// waiting for changes to be written back to the file; see `flush`.
+#[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+pub struct MmapMut(fs_imp::Mmap);
+
+/// A file that refuses writes past a size limit, created by `create_capped`.
+///
+/// Only writes through this wrapper are checked against the limit; the file
+/// itself can still grow without bound through other handles.
+#[unstable(feature = "fs_create_capped", reason = "recently added API", issue = "0")]
+#[derive(Debug)]
+pub struct CappedFile {
+    inner: File,
+    cap: u64,
+    pos: u64,
+}
+
 /// Entries returned by the `ReadDir` iterator.
 ///
 /// An instance of `DirEntry` represents an entry inside of a directory on the
@@ -91,8 +238,8 @@ pub struct DirEntry(fs_imp::DirEntry);
                      as symlinks differently",
            issue = "27707")]
 pub struct WalkDir {
-    cur: Option<ReadDir>,
-    stack: Vec<io::Result<ReadDir>>,
+    cur: Option<(ReadDir, Vec<(u64, u64)>)>,
+    stack: Vec<io::Result<(ReadDir, Vec<(u64, u64)>)>>,
 }
 
 /// Options and flags which can be used to configure how a file is opened.
@@ -162,6 +309,55 @@ pub struct DirBuilder {
     recursive: bool,
 }
 
+/// Options which can be used to configure how a file is copied with
+/// `copy_with_options`.
+#[unstable(feature = "fs_copy_with_options", reason = "recently added API",
+           issue = "0")]
+#[derive(Clone, Debug)]
+pub struct CopyOptions {
+    preserve_xattrs: bool,
+    verify: Option<fn() -> Box<Hasher>>,
+    copy_symlinks_as_links: bool,
+}
+
+/// Options which can be used to configure how a file or directory is renamed
+/// with `rename_with`.
+#[unstable(feature = "fs_rename_with", reason = "recently added API",
+           issue = "0")]
+#[derive(Clone, Debug)]
+pub struct RenameOptions {
+    create_parents: bool,
+    overwrite: bool,
+}
+
+/// The reason `metadata_detailed` failed.
+#[unstable(feature = "fs_metadata_detailed", reason = "recently added API",
+           issue = "0")]
+#[derive(Debug)]
+pub enum MissingKind {
+    /// The parent directory exists, but the last component of the path
+    /// doesn't, or it's a dangling symbolic link.
+    Final,
+    /// The parent directory doesn't exist, or one of the components leading
+    /// up to it isn't a directory.
+    Parent,
+    /// Looking up the path failed for a reason other than something being
+    /// missing, such as a lack of permissions.
+    Other(io::Error),
+}
+
+/// What `hard_link_or_copy` ended up doing.
+#[unstable(feature = "fs_hard_link_or_copy", reason = "recently added API",
+           issue = "0")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LinkResult {
+    /// The destination is a hard link to the source.
+    Linked,
+    /// The destination is a copy of the source, with the given number of
+    /// bytes.
+    Copied(u64),
+}
+
 impl File {
     /// Attempts to open a file in read-only mode.
     ///
@@ -214,6 +410,11 @@ impl File {
     /// This function will attempt to ensure that all in-core data reaches the
     /// filesystem before returning.
     ///
+    /// On OS X this corresponds to `fsync`, which hands the data to the drive
+    /// but doesn't wait for the drive to write out its own cache, so it isn't
+    /// a barrier against writes being reordered or lost on power failure.
+    /// Use `sync_barrier` where that matters.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -262,6 +463,47 @@ impl File {
         self.inner.datasync()
     }
 
+    /// Makes all data and metadata written to this file so far durable before
+    /// returning, so that nothing written afterwards can reach stable storage
+    /// ahead of it.
+    ///
+    /// This is the barrier needed to order writes, e.g. to make a journal
+    /// entry durable before the change it describes is written. Unlike
+    /// `sync_all` it flushes the drive's write cache on every platform.
+    ///
+    /// # Platform-specific behavior
+    ///
+    /// This corresponds to `fcntl(F_FULLFSYNC)` on OS X and iOS, to `fsync`
+    /// on Linux and the other Unix platforms, where `fsync` already flushes
+    /// the drive cache, and to `FlushFileBuffers` on Windows.
+    ///
+    /// # Errors
+    ///
+    /// On OS X some filesystems, such as network mounts, don't support
+    /// `F_FULLFSYNC` and an error is returned rather than silently falling
+    /// back to a weaker sync.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(file_sync_barrier)]
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    ///
+    /// # fn foo() -> std::io::Result<()> {
+    /// let mut journal = try!(File::create("journal"));
+    /// try!(journal.write_all(b"set x = 1"));
+    /// try!(journal.sync_barrier());
+    ///
+    /// // Only now is it safe to apply the change itself.
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[unstable(feature = "file_sync_barrier", reason = "recently added API", issue = "0")]
+    pub fn sync_barrier(&self) -> io::Result<()> {
+        self.inner.full_fsync()
+    }
+
     /// Truncates or extends the underlying file, updating the size of
     /// this file to become `size`.
     ///
@@ -290,1853 +532,7230 @@ impl File {
         self.inner.truncate(size)
     }
 
-    /// Queries metadata about the underlying file.
+    /// Truncates or extends the underlying file like `set_len`, and then
+    /// makes sure the new size has reached the disk before returning.
+    ///
+    /// This is `set_len` followed by `sync_data` and, on Unix, an `fsync` of
+    /// the directory containing the file, in that order. The directory sync
+    /// is the step that's easy to forget: some file systems only make a
+    /// size change durable together with the directory's metadata. The
+    /// directory is found through the file's current path, which Linux and
+    /// OS X can tell; elsewhere this step is skipped. On Windows
+    /// `FlushFileBuffers` already covers the size.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file is not opened for
+    /// writing. If the truncation succeeds but syncing fails the file may or
+    /// may not have its new size after a crash.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use std::fs::File;
+    /// #![feature(file_set_len_sync)]
+    /// use std::fs::OpenOptions;
     ///
     /// # fn foo() -> std::io::Result<()> {
-    /// let mut f = try!(File::open("foo.txt"));
-    /// let metadata = try!(f.metadata());
+    /// let f = try!(OpenOptions::new().write(true).open("app.log"));
+    /// try!(f.set_len_sync(0));
     /// # Ok(())
     /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn metadata(&self) -> io::Result<Metadata> {
-        self.inner.file_attr().map(Metadata)
+    #[unstable(feature = "file_set_len_sync", reason = "recently added API",
+               issue = "0")]
+    pub fn set_len_sync(&self, size: u64) -> io::Result<()> {
+        truncate_then_sync(|| self.inner.truncate(size),
+                           || self.inner.datasync(),
+                           || self.inner.sync_parent_dir())
     }
-}
-
-impl AsInner<fs_imp::File> for File {
-    fn as_inner(&self) -> &fs_imp::File { &self.inner }
-}
-impl FromInner<fs_imp::File> for File {
-    fn from_inner(f: fs_imp::File) -> File {
-        File { inner: f }
-    }
-}
-impl IntoInner<fs_imp::File> for File {
-    fn into_inner(self) -> fs_imp::File {
-        self.inner
-    }
-}
-
-impl fmt::Debug for File {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.inner.fmt(f)
-    }
-}
-
-#[stable(feature = "rust1", since = "1.0.0")]
-impl Read for File {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
-    }
-    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
-        unsafe { read_to_end_uninitialized(self, buf) }
-    }
-}
-#[stable(feature = "rust1", since = "1.0.0")]
-impl Write for File {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner.write(buf)
-    }
-    fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
-}
-#[stable(feature = "rust1", since = "1.0.0")]
-impl Seek for File {
-    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        self.inner.seek(pos)
-    }
-}
-#[stable(feature = "rust1", since = "1.0.0")]
-impl<'a> Read for &'a File {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
-    }
-}
-#[stable(feature = "rust1", since = "1.0.0")]
-impl<'a> Write for &'a File {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner.write(buf)
-    }
-    fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
-}
-#[stable(feature = "rust1", since = "1.0.0")]
-impl<'a> Seek for &'a File {
-    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        self.inner.seek(pos)
-    }
-}
 
-impl OpenOptions {
-    /// Creates a blank net set of options ready for configuration.
+    /// Empties the file and moves its cursor back to the start.
     ///
-    /// All options are initially set to `false`.
+    /// `set_len(0)` on its own leaves the cursor where it was, so the next
+    /// write would leave a hole of zeros before it, up to the old position.
+    /// This is what emptying a log file which is written to afterwards
+    /// usually needs. Files opened in append mode write at the end anyway.
+    ///
+    /// Like `set_len`, this doesn't wait for the new size to reach the disk;
+    /// call `sync_data` afterwards for that.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file is not opened for
+    /// writing. If truncating succeeds but seeking fails, the file is empty
+    /// and the cursor is wherever it was.
     ///
     /// # Examples
     ///
     /// ```no_run
+    /// #![feature(file_clear)]
     /// use std::fs::OpenOptions;
+    /// use std::io::Write;
     ///
-    /// let file = OpenOptions::new().open("foo.txt");
+    /// # fn foo() -> std::io::Result<()> {
+    /// let mut f = try!(OpenOptions::new().write(true).open("app.log"));
+    /// try!(f.clear());
+    /// try!(f.write_all(b"log restarted\n"));
+    /// # Ok(())
+    /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn new() -> OpenOptions {
-        OpenOptions(fs_imp::OpenOptions::new())
+    #[unstable(feature = "file_clear", reason = "recently added API",
+               issue = "0")]
+    pub fn clear(&self) -> io::Result<()> {
+        try!(self.inner.truncate(0));
+        self.inner.seek_discard(SeekFrom::Start(0))
     }
 
-    /// Sets the option for read access.
+    /// Returns the length of this file in bytes without moving its cursor.
     ///
-    /// This option, when true, will indicate that the file should be
-    /// `read`-able if opened.
+    /// Unlike seeking to the end and back, this doesn't touch the current
+    /// position, so it's safe to call on a `File` shared between several
+    /// readers.
+    ///
+    /// The length is what the operating system reports for the underlying
+    /// object, which is only meaningful for regular files. Pipes, sockets and
+    /// character devices typically report 0 regardless of how much data is
+    /// available.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use std::fs::OpenOptions;
+    /// #![feature(file_stream_len)]
+    /// use std::fs::File;
     ///
-    /// let file = OpenOptions::new().read(true).open("foo.txt");
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(File::open("foo.txt"));
+    /// println!("{} bytes", try!(f.stream_len()));
+    /// # Ok(())
+    /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn read(&mut self, read: bool) -> &mut OpenOptions {
-        self.0.read(read); self
+    #[unstable(feature = "file_stream_len", reason = "recently added API",
+               issue = "0")]
+    pub fn stream_len(&self) -> io::Result<u64> {
+        self.inner.size()
     }
 
-    /// Sets the option for write access.
-    ///
-    /// This option, when true, will indicate that the file should be
-    /// `write`-able if opened.
-    ///
-    /// # Examples
+    /// Returns the current position of this file's cursor, measured in bytes
+    /// from the start of the file.
     ///
-    /// ```no_run
-    /// use std::fs::OpenOptions;
+    /// This is equivalent to `seek(SeekFrom::Current(0))` but only requires
+    /// a shared reference.
+    #[unstable(feature = "file_stream_len", reason = "recently added API",
+               issue = "0")]
+    pub fn stream_position(&self) -> io::Result<u64> {
+        self.inner.seek(SeekFrom::Current(0))
+    }
+
+    /// Moves this file's cursor back to the start of the file.
     ///
-    /// let file = OpenOptions::new().write(true).open("foo.txt");
-    /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn write(&mut self, write: bool) -> &mut OpenOptions {
-        self.0.write(write); self
+    /// This is equivalent to `seek(SeekFrom::Start(0))` but only requires a
+    /// shared reference, and doesn't ask the OS for the new position.
+    #[unstable(feature = "file_seek_relative", reason = "recently added API",
+               issue = "0")]
+    pub fn rewind(&self) -> io::Result<()> {
+        self.inner.seek_discard(SeekFrom::Start(0))
     }
 
-    /// Sets the option for the append mode.
+    /// Moves this file's cursor by `offset` bytes from its current position.
     ///
-    /// This option, when true, means that writes will append to a file instead
-    /// of overwriting previous contents.
+    /// This is equivalent to `seek(SeekFrom::Current(offset))` but only
+    /// requires a shared reference, and doesn't ask the OS for the new
+    /// position. Seeking before the start of the file is an error.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use std::fs::OpenOptions;
+    /// #![feature(file_seek_relative)]
+    /// use std::fs::File;
     ///
-    /// let file = OpenOptions::new().write(true).append(true).open("foo.txt");
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(File::open("foo.bin"));
+    /// // Skip the header.
+    /// try!(f.seek_relative(16));
+    /// # Ok(())
+    /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn append(&mut self, append: bool) -> &mut OpenOptions {
-        self.0.append(append); self
+    #[unstable(feature = "file_seek_relative", reason = "recently added API",
+               issue = "0")]
+    pub fn seek_relative(&self, offset: i64) -> io::Result<()> {
+        self.inner.seek_discard(SeekFrom::Current(offset))
     }
 
-    /// Sets the option for truncating a previous file.
+    /// Returns the byte ranges of this file that are backed by allocated
+    /// storage, sorted by offset and non-overlapping.
     ///
-    /// If a file is successfully opened with this option set it will truncate
-    /// the file to 0 length if it already exists.
+    /// Anything between two ranges is a hole which reads back as zeroes
+    /// without taking up space on disk. A file without holes, including
+    /// every file on a platform or file system that doesn't support sparse
+    /// files, returns a single range covering `0..len`.
+    ///
+    /// # Platform-specific behavior
+    ///
+    /// This function currently uses `SEEK_DATA` and `SEEK_HOLE` on Linux and
+    /// `FSCTL_QUERY_ALLOCATED_RANGES` on Windows. The Linux implementation
+    /// moves the cursor while walking the file, but restores it before
+    /// returning. Note that this [may change in the future][changes].
+    ///
+    /// [changes]: ../io/index.html#platform-specific-behavior
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use std::fs::OpenOptions;
+    /// #![feature(file_allocated_ranges)]
+    /// use std::fs::File;
     ///
-    /// let file = OpenOptions::new().write(true).truncate(true).open("foo.txt");
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(File::open("disk.img"));
+    /// let ranges = try!(f.allocated_ranges());
+    /// let used = ranges.iter().fold(0, |sum, r| sum + r.end - r.start);
+    /// println!("{} bytes in use", used);
+    /// # Ok(())
+    /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn truncate(&mut self, truncate: bool) -> &mut OpenOptions {
-        self.0.truncate(truncate); self
+    #[unstable(feature = "file_allocated_ranges", reason = "recently added API",
+               issue = "0")]
+    pub fn allocated_ranges(&self) -> io::Result<Vec<Range<u64>>> {
+        self.inner.allocated_ranges()
     }
 
-    /// Sets the option for creating a new file.
+    /// Maps `len` bytes of this file starting at `offset` into memory for
+    /// reading, or everything from `offset` to the end of the file if `len`
+    /// is `None`.
     ///
-    /// This option indicates whether a new file will be created if the file
-    /// does not yet already exist.
+    /// For random access to large files this avoids a system call and a copy
+    /// per read compared to `read_at`. The operating system only maps at
+    /// page (Unix) or allocation granularity (Windows) boundaries, so the
+    /// mapping itself may start before `offset`, but the returned `Mmap`
+    /// always starts exactly at `offset`. The file needs to be open for
+    /// reading and may be closed while the map is alive.
+    ///
+    /// # Safety
+    ///
+    /// The bytes aren't copied, so the returned `Mmap` is only a `&[u8]` in
+    /// name: the caller has to make sure that nothing modifies the mapped
+    /// part of the file while the map is alive, neither through this or
+    /// another `File` nor from another process. Such changes show up in the
+    /// map, breaking the promise of `&[u8]` that the bytes don't change.
+    ///
+    /// The file also mustn't be truncated while it's mapped. Touching a part
+    /// of the map which no longer lies within the file kills the process
+    /// with `SIGBUS` on Unix.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the range doesn't lie
+    /// within the file, or if it doesn't fit into the address space.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use std::fs::OpenOptions;
+    /// #![feature(file_memory_map)]
+    /// use std::fs::File;
     ///
-    /// let file = OpenOptions::new().create(true).open("foo.txt");
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(File::open("archive.zip"));
+    /// let map = try!(unsafe { f.memory_map(0, None) });
+    /// assert_eq!(&map[..4], b"PK\x03\x04");
+    /// # Ok(())
+    /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn create(&mut self, create: bool) -> &mut OpenOptions {
-        self.0.create(create); self
+    #[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+    pub unsafe fn memory_map(&self, offset: u64, len: Option<usize>) -> io::Result<Mmap> {
+        let len = try!(self.map_len(offset, len));
+        self.inner.memory_map(offset, len, false).map(Mmap)
     }
 
-    /// Opens a file at `path` with the options specified by `self`.
+    /// Maps `len` bytes of this file starting at `offset` into memory for
+    /// reading and writing, or everything from `offset` to the end of the
+    /// file if `len` is `None`.
     ///
-    /// # Errors
+    /// This works like `memory_map`, except that the file has to be open
+    /// for writing as well and that writes to the map end up in the file.
+    /// The map can't grow the file; use `set_len` beforehand to make room.
     ///
-    /// This function will return an error under a number of different
-    /// circumstances, to include but not limited to:
+    /// Changes are written back by the operating system whenever it sees
+    /// fit, which may be well after the `MmapMut` has been dropped. Dropping
+    /// it doesn't write anything back itself, so call `MmapMut::flush`
+    /// first for the changes to be on disk, just like `sync_data` for
+    /// regular writes.
     ///
-    /// * Opening a file that does not exist with read access.
-    /// * Attempting to open a file with access that the user lacks
-    ///   permissions for
-    /// * Filesystem-level errors (full disk, etc)
+    /// # Safety
+    ///
+    /// The returned `MmapMut` dereferences to a `&mut [u8]`, which has to be
+    /// the only way to reach those bytes for as long as the map is alive.
+    /// The caller has to make sure that nothing else reads or writes the
+    /// mapped part of the file in the meantime: not this or another `File`,
+    /// not another map of the same range, and not another process.
+    ///
+    /// As with `memory_map`, the file mustn't be truncated while it's mapped
+    /// either. Touching a part of the map which no longer lies within the
+    /// file kills the process with `SIGBUS` on Unix.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the range doesn't lie
+    /// within the file, or if it doesn't fit into the address space.
     ///
     /// # Examples
     ///
     /// ```no_run
+    /// #![feature(file_memory_map)]
     /// use std::fs::OpenOptions;
     ///
-    /// let file = OpenOptions::new().open("foo.txt");
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(OpenOptions::new().read(true).write(true).open("counter.bin"));
+    /// let mut map = try!(unsafe { f.memory_map_mut(0, Some(8)) });
+    /// map[7] += 1;
+    /// try!(map.flush(0..8));
+    /// # Ok(())
+    /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<File> {
-        self._open(path.as_ref())
-    }
-
-    fn _open(&self, path: &Path) -> io::Result<File> {
-        let inner = try!(fs_imp::File::open(path, &self.0));
-        Ok(File { inner: inner })
+    #[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+    pub unsafe fn memory_map_mut(&self, offset: u64, len: Option<usize>)
+                                 -> io::Result<MmapMut> {
+        let len = try!(self.map_len(offset, len));
+        self.inner.memory_map(offset, len, true).map(MmapMut)
     }
-}
-
-impl AsInnerMut<fs_imp::OpenOptions> for OpenOptions {
-    fn as_inner_mut(&mut self) -> &mut fs_imp::OpenOptions { &mut self.0 }
-}
 
-impl Metadata {
-    /// Returns the file type for this metadata.
-    #[stable(feature = "file_type", since = "1.1.0")]
-    pub fn file_type(&self) -> FileType {
-        FileType(self.0.file_type())
+    fn map_len(&self, offset: u64, len: Option<usize>) -> io::Result<usize> {
+        let size = try!(self.inner.file_attr()).size();
+        match len {
+            Some(len) if offset.checked_add(len as u64).map_or(false, |end| end <= size) => {
+                Ok(len)
+            }
+            None if offset <= size && size - offset <= usize::max_value() as u64 => {
+                Ok((size - offset) as usize)
+            }
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                    "range to map lies outside the file")),
+        }
     }
 
-    /// Returns whether this metadata is for a directory.
+    /// Changes whether this file is read-only, leaving every other permission
+    /// bit or attribute of the file as it is.
     ///
-    /// # Examples
+    /// This is a shortcut for fetching the file's `Permissions`, calling
+    /// `set_readonly` on them and writing them back, without the risk of
+    /// building `Permissions` from scratch and clearing other bits such as the
+    /// hidden and system attributes on Windows.
     ///
-    /// ```
-    /// # fn foo() -> std::io::Result<()> {
-    /// use std::fs;
+    /// One thing differs on Unix: making the file writable only sets the
+    /// owner's write bit, where `Permissions::set_readonly(false)` sets all
+    /// three, so the file doesn't become writable for its group and
+    /// everyone else. Making it read-only clears all three as usual.
     ///
-    /// let metadata = try!(fs::metadata("foo.txt"));
+    /// # Errors
     ///
-    /// assert!(!metadata.is_dir());
-    /// # Ok(())
-    /// # }
-    /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn is_dir(&self) -> bool { self.file_type().is_dir() }
-
-    /// Returns whether this metadata is for a regular file.
+    /// On Unix the calling process must own the file. On Windows the file
+    /// must have been opened with write access.
     ///
     /// # Examples
     ///
-    /// ```
-    /// # fn foo() -> std::io::Result<()> {
-    /// use std::fs;
-    ///
-    /// let metadata = try!(fs::metadata("foo.txt"));
+    /// ```no_run
+    /// #![feature(file_set_readonly)]
+    /// use std::fs::File;
     ///
-    /// assert!(metadata.is_file());
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(File::create("foo.txt"));
+    /// try!(f.set_readonly(true));
     /// # Ok(())
     /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn is_file(&self) -> bool { self.file_type().is_file() }
+    #[unstable(feature = "file_set_readonly", reason = "recently added API",
+               issue = "0")]
+    pub fn set_readonly(&self, readonly: bool) -> io::Result<()> {
+        self.inner.set_readonly(readonly)
+    }
 
-    /// Returns the size of the file, in bytes, this metadata is for.
+    /// Reads bytes into the spare capacity of `buf`, returning how many bytes
+    /// were read.
     ///
-    /// # Examples
+    /// The bytes are appended after the current contents of `buf` and its
+    /// length is extended accordingly. Unlike `Read::read`, the unused
+    /// capacity doesn't have to be initialized beforehand; it is passed
+    /// directly to the OS. If `buf` has no spare capacity this will read
+    /// nothing, so callers should `reserve` first.
     ///
-    /// ```
-    /// # fn foo() -> std::io::Result<()> {
-    /// use std::fs;
+    /// # Examples
     ///
-    /// let metadata = try!(fs::metadata("foo.txt"));
+    /// ```no_run
+    /// #![feature(file_read_buf)]
+    /// use std::fs::File;
     ///
-    /// assert_eq!(0, metadata.len());
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(File::open("foo.txt"));
+    /// let mut buf = Vec::with_capacity(4096);
+    /// let n = try!(f.read_buf(&mut buf));
+    /// assert_eq!(buf.len(), n);
     /// # Ok(())
     /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn len(&self) -> u64 { self.0.size() }
+    #[unstable(feature = "file_read_buf", reason = "recently added API",
+               issue = "0")]
+    pub fn read_buf(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        self.inner.read_to_spare(buf)
+    }
 
-    /// Returns the permissions of the file this metadata is for.
+    /// Reads into several buffers, filling each one before moving on to the
+    /// next, and returns how many bytes were read in total.
     ///
-    /// # Examples
+    /// Like `Read::read` this may read less than would fit into the buffers,
+    /// so only the first buffers, and a prefix of the one after them, may
+    /// have been filled; a return value of 0 means end of file. The file's
+    /// cursor advances by the number of bytes read. On Unix this is a single
+    /// `readv` call, which fills at most the first 1024 buffers. On Windows
+    /// the buffers are filled with one `ReadFile` call each.
     ///
-    /// ```
-    /// # fn foo() -> std::io::Result<()> {
-    /// use std::fs;
+    /// # Examples
     ///
-    /// let metadata = try!(fs::metadata("foo.txt"));
+    /// ```no_run
+    /// #![feature(file_read_vectored)]
+    /// use std::fs::File;
     ///
-    /// assert!(!metadata.permissions().readonly());
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(File::open("foo.txt"));
+    /// let mut header = [0; 16];
+    /// let mut body = [0; 4096];
+    /// let n = try!(f.read_vectored(&mut [&mut header[..], &mut body[..]]));
     /// # Ok(())
     /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn permissions(&self) -> Permissions {
-        Permissions(self.0.perm())
+    #[unstable(feature = "file_read_vectored", reason = "recently added API",
+               issue = "0")]
+    pub fn read_vectored(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+        self.inner.read_vectored(bufs)
     }
-}
 
-impl AsInner<fs_imp::FileAttr> for Metadata {
-    fn as_inner(&self) -> &fs_imp::FileAttr { &self.0 }
-}
+    /// Writes the contents of several buffers, one after the other, returning
+    /// how many bytes were written in total.
+    ///
+    /// Like `Write::write` this may write less than all of the buffers, in
+    /// which case the bytes written are always a prefix of their
+    /// concatenation. On Unix this is a single `writev` call, which passes
+    /// at most 1024 buffers at a time. On Windows the buffers are written
+    /// with one `WriteFile` call each.
+    #[unstable(feature = "file_write_vectored", reason = "recently added API",
+               issue = "0")]
+    pub fn write_vectored(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+        self.inner.write_vectored(bufs)
+    }
 
-impl Permissions {
-    /// Returns whether these permissions describe a readonly file.
+    /// Writes all of the given buffers, one after the other, calling
+    /// `write_vectored` until everything has been written.
+    ///
+    /// This lets a header and several separately built pieces of a body go
+    /// out without first copying them into one buffer, and on Unix usually
+    /// in a single system call.
+    ///
+    /// # Errors
+    ///
+    /// Errors of kind `Interrupted` are retried. Any other error is returned
+    /// as is, and an error of kind `WriteZero` is returned if a write makes
+    /// no progress. In both cases it is unspecified how much was written.
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
+    /// #![feature(file_write_vectored)]
     /// use std::fs::File;
     ///
     /// # fn foo() -> std::io::Result<()> {
-    /// let mut f = try!(File::create("foo.txt"));
-    /// let metadata = try!(f.metadata());
-    ///
-    /// assert_eq!(false, metadata.permissions().readonly());
+    /// let f = try!(File::create("foo.txt"));
+    /// let body = b"body";
+    /// try!(f.write_all_vectored(&[b"header\n", &body[..]]));
     /// # Ok(())
     /// # }
     /// ```
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn readonly(&self) -> bool { self.0.readonly() }
+    #[unstable(feature = "file_write_vectored", reason = "recently added API",
+               issue = "0")]
+    pub fn write_all_vectored(&self, bufs: &[&[u8]]) -> io::Result<()> {
+        let mut bufs = bufs.to_vec();
+        let mut start = 0;
+        advance_slices(&mut bufs, &mut start, 0);
+        while start < bufs.len() {
+            match self.write_vectored(&bufs[start..]) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                   "failed to write whole buffer")),
+                Ok(n) => advance_slices(&mut bufs, &mut start, n),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
 
-    /// Modifies the readonly flag for this set of permissions.
-    ///
-    /// This operation does **not** modify the filesystem. To modify the
-    /// filesystem use the `fs::set_permissions` function.
+    /// Queries metadata about the underlying file.
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
     /// use std::fs::File;
     ///
     /// # fn foo() -> std::io::Result<()> {
-    /// let f = try!(File::create("foo.txt"));
+    /// let mut f = try!(File::open("foo.txt"));
     /// let metadata = try!(f.metadata());
-    /// let mut permissions = metadata.permissions();
-    ///
-    /// permissions.set_readonly(true);
-    ///
-    /// // filesystem doesn't change
-    /// assert_eq!(false, metadata.permissions().readonly());
-    ///
-    /// // just this particular `permissions`.
-    /// assert_eq!(true, permissions.readonly());
     /// # Ok(())
     /// # }
     /// ```
     #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn set_readonly(&mut self, readonly: bool) {
-        self.0.set_readonly(readonly)
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        self.inner.file_attr().map(Metadata)
     }
 }
 
-impl FileType {
-    /// Test whether this file type represents a directory.
-    #[stable(feature = "file_type", since = "1.1.0")]
-    pub fn is_dir(&self) -> bool { self.0.is_dir() }
-
-    /// Test whether this file type represents a regular file.
-    #[stable(feature = "file_type", since = "1.1.0")]
-    pub fn is_file(&self) -> bool { self.0.is_file() }
+// Like `sys_common::io::read_to_end_uninitialized`, but the spare capacity of
+// the vector is handed directly to the OS instead of going through a slice of
+// uninitialized memory.
+fn read_to_end_spare(f: &fs_imp::File, buf: &mut Vec<u8>) -> io::Result<usize> {
+    let start_len = buf.len();
+    buf.reserve(16);
+    loop {
+        if buf.len() == buf.capacity() {
+            buf.reserve(1);
+        }
+        match f.read_to_spare(buf) {
+            Ok(0) => return Ok(buf.len() - start_len),
+            Ok(_) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
 
-    /// Test whether this file type represents a symbolic link.
-    #[stable(feature = "file_type", since = "1.1.0")]
-    pub fn is_symlink(&self) -> bool { self.0.is_symlink() }
+// Moves `start` past the first `n` bytes of `bufs[*start..]`, trimming the
+// slice the write stopped in and skipping any empty slices after it, so that
+// `bufs[*start..]` is exactly what is left to write.
+fn advance_slices<'a>(bufs: &mut [&'a [u8]], start: &mut usize, mut n: usize) {
+    while *start < bufs.len() && n >= bufs[*start].len() {
+        n -= bufs[*start].len();
+        *start += 1;
+    }
+    if *start < bufs.len() {
+        let buf = bufs[*start];
+        bufs[*start] = &buf[n..];
+    }
 }
 
-impl AsInner<fs_imp::FileType> for FileType {
-    fn as_inner(&self) -> &fs_imp::FileType { &self.0 }
+// The steps of `File::set_len_sync`, split out so that their order can be
+// tested. Each one only runs if the previous one succeeded.
+fn truncate_then_sync<T, S, D>(truncate: T, sync: S, sync_dir: D) -> io::Result<()>
+    where T: FnOnce() -> io::Result<()>,
+          S: FnOnce() -> io::Result<()>,
+          D: FnOnce() -> io::Result<()>
+{
+    try!(truncate());
+    try!(sync());
+    sync_dir()
 }
 
-impl FromInner<fs_imp::FilePermissions> for Permissions {
-    fn from_inner(f: fs_imp::FilePermissions) -> Permissions {
-        Permissions(f)
+impl AsInner<fs_imp::File> for File {
+    fn as_inner(&self) -> &fs_imp::File { &self.inner }
+}
+impl FromInner<fs_imp::File> for File {
+    fn from_inner(f: fs_imp::File) -> File {
+        File { inner: f }
+    }
+}
+impl IntoInner<fs_imp::File> for File {
+    fn into_inner(self) -> fs_imp::File {
+        self.inner
     }
 }
 
-impl AsInner<fs_imp::FilePermissions> for Permissions {
-    fn as_inner(&self) -> &fs_imp::FilePermissions { &self.0 }
+impl fmt::Debug for File {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
 }
 
-#[stable(feature = "rust1", since = "1.0.0")]
-impl Iterator for ReadDir {
-    type Item = io::Result<DirEntry>;
+#[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+impl Deref for Mmap {
+    type Target = [u8];
 
-    fn next(&mut self) -> Option<io::Result<DirEntry>> {
-        self.0.next().map(|entry| entry.map(DirEntry))
+    fn deref(&self) -> &[u8] { self.0.as_slice() }
+}
+
+#[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+impl fmt::Debug for Mmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mmap").field("len", &self.len()).finish()
     }
 }
 
-#[stable(feature = "rust1", since = "1.0.0")]
-impl DirEntry {
-    /// Returns the full path to the file that this entry represents.
-    ///
-    /// The full path is created by joining the original path to `read_dir` or
-    /// `walk_dir` with the filename of this entry.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use std::fs;
-    /// # fn foo() -> std::io::Result<()> {
-    /// for entry in try!(fs::read_dir(".")) {
-    ///     let dir = try!(entry);
-    ///     println!("{:?}", dir.path());
-    /// }
-    /// # Ok(())
-    /// # }
-    /// ```
-    ///
-    /// This prints output like:
-    ///
-    /// ```text
-    /// "./whatever.txt"
-    /// "./foo.html"
-    /// "./hello_world.rs"
-    /// ```
+impl MmapMut {
+    /// Writes the changes made to `range` of this map back to the file and
+    /// waits for them to reach the disk.
     ///
-    /// The exact text, of course, depends on what files you have in `.`.
-    #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn path(&self) -> PathBuf { self.0.path() }
-
-    /// Return the metadata for the file that this entry points at.
+    /// # Platform-specific behavior
     ///
-    /// This function will not traverse symlinks if this entry points at a
-    /// symlink.
+    /// This uses `msync` with `MS_SYNC` on Unix, and `FlushViewOfFile`
+    /// followed by `FlushFileBuffers` on Windows.
     ///
-    /// # Platform behavior
+    /// # Errors
     ///
-    /// On Windows this function is cheap to call (no extra system calls
-    /// needed), but on Unix platforms this function is the equivalent of
-    /// calling `symlink_metadata` on the path.
-    #[stable(feature = "dir_entry_ext", since = "1.1.0")]
-    pub fn metadata(&self) -> io::Result<Metadata> {
-        self.0.metadata().map(Metadata)
+    /// Returns an error of kind `InvalidInput` if `range` doesn't lie within
+    /// the map.
+    #[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+    pub fn flush(&self, range: Range<usize>) -> io::Result<()> {
+        if range.start > range.end || range.end > self.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "range to flush lies outside the map"))
+        }
+        self.0.flush(range.start, range.end - range.start)
     }
+}
 
-    /// Return the file type for the file that this entry points at.
-    ///
-    /// This function will not traverse symlinks if this entry points at a
-    /// symlink.
-    ///
-    /// # Platform behavior
-    ///
-    /// On Windows and most Unix platforms this function is free (no extra
-    /// system calls needed), but some Unix platforms may require the equivalent
-    /// call to `symlink_metadata` to learn about the target file type.
-    #[stable(feature = "dir_entry_ext", since = "1.1.0")]
-    pub fn file_type(&self) -> io::Result<FileType> {
-        self.0.file_type().map(FileType)
+#[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+impl Deref for MmapMut {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] { self.0.as_slice() }
+}
+
+#[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+impl DerefMut for MmapMut {
+    fn deref_mut(&mut self) -> &mut [u8] { self.0.as_mut_slice() }
+}
+
+#[unstable(feature = "file_memory_map", reason = "recently added API", issue = "0")]
+impl fmt::Debug for MmapMut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MmapMut").field("len", &self.len()).finish()
     }
+}
 
-    /// Returns the bare file name of this directory entry without any other
-    /// leading path component.
-    #[stable(feature = "dir_entry_ext", since = "1.1.0")]
-    pub fn file_name(&self) -> OsString {
-        self.0.file_name()
+impl CappedFile {
+    /// Returns the size limit of this file.
+    #[unstable(feature = "fs_create_capped", reason = "recently added API", issue = "0")]
+    pub fn cap(&self) -> u64 { self.cap }
+
+    /// Returns how many more bytes can be written from the current position
+    /// before the limit is reached.
+    #[unstable(feature = "fs_create_capped", reason = "recently added API", issue = "0")]
+    pub fn remaining(&self) -> u64 { self.cap.saturating_sub(self.pos) }
+
+    /// Gets a reference to the underlying file.
+    #[unstable(feature = "fs_create_capped", reason = "recently added API", issue = "0")]
+    pub fn get_ref(&self) -> &File { &self.inner }
+
+    /// Unwraps this `CappedFile`, returning the underlying file.
+    #[unstable(feature = "fs_create_capped", reason = "recently added API", issue = "0")]
+    pub fn into_inner(self) -> File { self.inner }
+}
+
+#[unstable(feature = "fs_create_capped", reason = "recently added API", issue = "0")]
+impl Write for CappedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        let remaining = self.remaining();
+        if remaining == 0 {
+            return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                      "file size limit reached"))
+        }
+        let len = if (buf.len() as u64) > remaining { remaining as usize } else { buf.len() };
+        let n = try!(self.inner.write(&buf[..len]));
+        self.pos += n as u64;
+        Ok(n)
     }
+    fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
 }
 
-impl AsInner<fs_imp::DirEntry> for DirEntry {
-    fn as_inner(&self) -> &fs_imp::DirEntry { &self.0 }
+#[unstable(feature = "fs_create_capped", reason = "recently added API", issue = "0")]
+impl Seek for CappedFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = try!(self.inner.seek(pos));
+        self.pos = pos;
+        Ok(pos)
+    }
 }
 
-/// Removes a file from the filesystem.
-///
-/// Note that there is no
-/// guarantee that the file is immediately deleted (e.g. depending on
-/// platform, other open file descriptors may prevent immediate removal).
-///
-/// # Errors
-///
-/// This function will return an error if `path` points to a directory, if the
-/// user lacks permissions to remove the file, or if some other filesystem-level
-/// error occurs.
-///
-/// # Examples
-///
-/// ```
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// try!(fs::remove_file("a.txt"));
-/// # Ok(())
-/// # }
-/// ```
-#[stable(feature = "rust1", since = "1.0.0")]
-pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
-    fs_imp::unlink(path.as_ref())
+#[unstable(feature = "fs_metadata_detailed", reason = "recently added API",
+           issue = "0")]
+impl From<MissingKind> for io::Error {
+    fn from(kind: MissingKind) -> io::Error {
+        match kind {
+            MissingKind::Final => {
+                io::Error::new(io::ErrorKind::NotFound, "no such file or directory")
+            }
+            MissingKind::Parent => {
+                io::Error::new(io::ErrorKind::NotFound, "parent directory does not exist")
+            }
+            MissingKind::Other(e) => e,
+        }
+    }
 }
 
-/// Given a path, query the file system to get information about a file,
-/// directory, etc.
-///
-/// This function will traverse symbolic links to query information about the
-/// destination file.
-///
-/// # Examples
-///
-/// ```rust
-/// # fn foo() -> std::io::Result<()> {
-/// use std::fs;
-///
-/// let attr = try!(fs::metadata("/some/file/path.txt"));
-/// // inspect attr ...
-/// # Ok(())
-/// # }
-/// ```
-///
-/// # Errors
-///
-/// This function will return an error if the user lacks the requisite
-/// permissions to perform a `metadata` call on the given `path` or if there
-/// is no entry in the filesystem at the provided path.
 #[stable(feature = "rust1", since = "1.0.0")]
-pub fn metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
-    fs_imp::stat(path.as_ref()).map(Metadata)
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        read_to_end_spare(&self.inner, buf)
+    }
 }
-
-/// Query the metadata about a file without following symlinks.
-///
-/// # Examples
-///
-/// ```rust
-/// # fn foo() -> std::io::Result<()> {
-/// use std::fs;
-///
-/// let attr = try!(fs::symlink_metadata("/some/file/path.txt"));
-/// // inspect attr ...
-/// # Ok(())
-/// # }
-/// ```
-#[stable(feature = "symlink_metadata", since = "1.1.0")]
-pub fn symlink_metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
-    fs_imp::lstat(path.as_ref()).map(Metadata)
+#[stable(feature = "rust1", since = "1.0.0")]
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
 }
-
-/// Rename a file or directory to a new name.
-///
-/// This will not work if the new name is on a different mount point.
-///
-/// # Errors
-///
-/// This function will return an error if the provided `from` doesn't exist, if
-/// the process lacks permissions to view the contents, if `from` and `to`
-/// reside on separate filesystems, or if some other intermittent I/O error
-/// occurs.
-///
-/// # Examples
-///
-/// ```
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// try!(fs::rename("a.txt", "b.txt"));
-/// # Ok(())
-/// # }
-/// ```
 #[stable(feature = "rust1", since = "1.0.0")]
-pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
-    fs_imp::rename(from.as_ref(), to.as_ref())
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
 }
-
-/// Copies the contents of one file to another. This function will also
-/// copy the permission bits of the original file to the destination file.
-///
-/// This function will **overwrite** the contents of `to`.
-///
-/// Note that if `from` and `to` both point to the same file, then the file
-/// will likely get truncated by this operation.
-///
-/// On success, the total number of bytes copied is returned.
-///
-/// # Errors
-///
-/// This function will return an error in the following situations, but is not
-/// limited to just these cases:
-///
-/// * The `from` path is not a file
-/// * The `from` file does not exist
-/// * The current process does not have the permission rights to access
-///   `from` or write `to`
-///
-/// # Examples
-///
-/// ```no_run
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// try!(fs::copy("foo.txt", "bar.txt"));
-/// # Ok(()) }
-/// ```
 #[stable(feature = "rust1", since = "1.0.0")]
-pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<u64> {
-    fs_imp::copy(from.as_ref(), to.as_ref())
+impl<'a> Read for &'a File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        read_to_end_spare(&self.inner, buf)
+    }
 }
-
-/// Creates a new hard link on the filesystem.
-///
-/// The `dst` path will be a link pointing to the `src` path. Note that systems
-/// often require these two paths to both be located on the same filesystem.
-///
-/// # Examples
-///
-/// ```
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// try!(fs::hard_link("a.txt", "b.txt"));
-/// # Ok(())
-/// # }
-/// ```
 #[stable(feature = "rust1", since = "1.0.0")]
-pub fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<()> {
-    fs_imp::link(src.as_ref(), dst.as_ref())
+impl<'a> Write for &'a File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
 }
-
-/// Creates a new symbolic link on the filesystem.
-///
-/// The `dst` path will be a symbolic link pointing to the `src` path.
-/// On Windows, this will be a file symlink, not a directory symlink;
-/// for this reason, the platform-specific `std::os::unix::fs::symlink`
-/// and `std::os::windows::fs::{symlink_file, symlink_dir}` should be
-/// used instead to make the intent explicit.
-///
-/// # Examples
-///
-/// ```
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// try!(fs::soft_link("a.txt", "b.txt"));
-/// # Ok(())
-/// # }
-/// ```
-#[deprecated(since = "1.1.0",
-             reason = "replaced with std::os::unix::fs::symlink and \
-                       std::os::windows::fs::{symlink_file, symlink_dir}")]
 #[stable(feature = "rust1", since = "1.0.0")]
-pub fn soft_link<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<()> {
-    fs_imp::symlink(src.as_ref(), dst.as_ref())
+impl<'a> Seek for &'a File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
 }
 
-/// Reads a symbolic link, returning the file that the link points to.
-///
-/// # Errors
-///
-/// This function will return an error on failure. Failure conditions include
-/// reading a file that does not exist or reading a file that is not a symbolic
-/// link.
-///
-/// # Examples
-///
-/// ```
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// let path = try!(fs::read_link("a.txt"));
-/// # Ok(())
-/// # }
-/// ```
-#[stable(feature = "rust1", since = "1.0.0")]
-pub fn read_link<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
-    fs_imp::readlink(path.as_ref())
-}
+impl OpenOptions {
+    /// Creates a blank net set of options ready for configuration.
+    ///
+    /// All options are initially set to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().open("foo.txt");
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn new() -> OpenOptions {
+        OpenOptions(fs_imp::OpenOptions::new())
+    }
+
+    /// Sets the option for read access.
+    ///
+    /// This option, when true, will indicate that the file should be
+    /// `read`-able if opened.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().read(true).open("foo.txt");
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn read(&mut self, read: bool) -> &mut OpenOptions {
+        self.0.read(read); self
+    }
+
+    /// Sets the option for write access.
+    ///
+    /// This option, when true, will indicate that the file should be
+    /// `write`-able if opened.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().write(true).open("foo.txt");
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn write(&mut self, write: bool) -> &mut OpenOptions {
+        self.0.write(write); self
+    }
+
+    /// Sets the option for the append mode.
+    ///
+    /// This option, when true, means that writes will append to a file instead
+    /// of overwriting previous contents.
+    ///
+    /// Moving to the end of the file and writing happen as one step, so
+    /// several handles, in one process or many, can append to the same file
+    /// without overwriting each other's data. On Unix this opens the file
+    /// with `O_APPEND`, on Windows with `FILE_APPEND_DATA` access but without
+    /// `FILE_WRITE_DATA`.
+    ///
+    /// Each successful `write` call lands in the file in one piece, which
+    /// lets concurrent writers append whole records, as long as:
+    ///
+    /// * every record goes out in a single `write` call whose return value
+    ///   is checked. `write_all` retries a short write with a second call,
+    ///   between which another writer may append, and `BufWriter` splits
+    ///   records at its buffer boundaries.
+    /// * the file is on a local file system. NFS emulates appending on the
+    ///   client, and writes from different machines can overwrite each
+    ///   other. SMB shares make no promise either.
+    ///
+    /// Short writes of regular files are rare in practice, e.g. when the
+    /// disk fills up or, on Unix, when a large write is interrupted by a
+    /// signal. Linux writes at most 2147479552 bytes per call.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().write(true).append(true).open("foo.txt");
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn append(&mut self, append: bool) -> &mut OpenOptions {
+        self.0.append(append); self
+    }
+
+    /// Sets the option for truncating a previous file.
+    ///
+    /// If a file is successfully opened with this option set it will truncate
+    /// the file to 0 length if it already exists.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().write(true).truncate(true).open("foo.txt");
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn truncate(&mut self, truncate: bool) -> &mut OpenOptions {
+        self.0.truncate(truncate); self
+    }
+
+    /// Sets the option for creating a new file.
+    ///
+    /// This option indicates whether a new file will be created if the file
+    /// does not yet already exist.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().create(true).open("foo.txt");
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn create(&mut self, create: bool) -> &mut OpenOptions {
+        self.0.create(create); self
+    }
+
+    /// Sets the option to always create a new file, failing if something
+    /// already exists at the path.
+    ///
+    /// The check for existence and the creation of the file happen
+    /// atomically, so this is the safe way to create files in shared
+    /// directories like `/tmp`. If `create_new` is set, `create` and
+    /// `truncate` are ignored.
+    ///
+    /// A symbolic link at the path counts as existing even if it is
+    /// dangling, and it is never followed: opening fails with an error of
+    /// kind `AlreadyExists` instead of creating the link's target. This uses
+    /// `O_CREAT | O_EXCL` on Unix and `CREATE_NEW` on Windows.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(open_options_create_new)]
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().write(true).create_new(true).open("foo.txt");
+    /// ```
+    #[unstable(feature = "open_options_create_new", reason = "recently added API",
+               issue = "0")]
+    pub fn create_new(&mut self, create_new: bool) -> &mut OpenOptions {
+        self.0.create_new(create_new); self
+    }
+
+    /// Opens a file at `path` with the options specified by `self`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under a number of different
+    /// circumstances, to include but not limited to:
+    ///
+    /// * Opening a file that does not exist with read access.
+    /// * Attempting to open a file with access that the user lacks
+    ///   permissions for
+    /// * Filesystem-level errors (full disk, etc)
+    ///
+    /// Opening a running executable for writing fails with an error of kind
+    /// `ExecutableBusy`. This is `ETXTBSY` on Unix. On Windows it is an
+    /// `ERROR_SHARING_VIOLATION` while opening an executable image (a file
+    /// starting with the `MZ` header) for writing. Such an error doesn't
+    /// carry `raw_os_error` itself; the original error is available through
+    /// `get_ref`. Sharing violations on any other file are returned as they
+    /// are.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// let file = OpenOptions::new().open("foo.txt");
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<File> {
+        self._open(path.as_ref())
+    }
+
+    fn _open(&self, path: &Path) -> io::Result<File> {
+        let inner = try!(fs_imp::File::open(path, &self.0));
+        Ok(File { inner: inner })
+    }
+}
+
+impl AsInner<fs_imp::OpenOptions> for OpenOptions {
+    fn as_inner(&self) -> &fs_imp::OpenOptions { &self.0 }
+}
+
+impl AsInnerMut<fs_imp::OpenOptions> for OpenOptions {
+    fn as_inner_mut(&mut self) -> &mut fs_imp::OpenOptions { &mut self.0 }
+}
+
+impl Metadata {
+    /// Returns the file type for this metadata.
+    #[stable(feature = "file_type", since = "1.1.0")]
+    pub fn file_type(&self) -> FileType {
+        FileType(self.0.file_type())
+    }
+
+    /// Returns whether this metadata is for a directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> std::io::Result<()> {
+    /// use std::fs;
+    ///
+    /// let metadata = try!(fs::metadata("foo.txt"));
+    ///
+    /// assert!(!metadata.is_dir());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn is_dir(&self) -> bool { self.file_type().is_dir() }
+
+    /// Returns whether this metadata is for a regular file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> std::io::Result<()> {
+    /// use std::fs;
+    ///
+    /// let metadata = try!(fs::metadata("foo.txt"));
+    ///
+    /// assert!(metadata.is_file());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn is_file(&self) -> bool { self.file_type().is_file() }
+
+    /// Returns whether this metadata is for a symbolic link.
+    ///
+    /// Only metadata obtained without following symbolic links, such as from
+    /// `symlink_metadata` or `DirEntry::metadata`, can ever describe a
+    /// symbolic link. `metadata` and `File::metadata` always resolve the
+    /// link first, so for their results this returns `false` even when the
+    /// original path was a symbolic link.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(metadata_is_symlink)]
+    /// # fn foo() -> std::io::Result<()> {
+    /// use std::fs;
+    ///
+    /// let metadata = try!(fs::symlink_metadata("foo.txt"));
+    ///
+    /// assert!(!metadata.is_symlink());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[unstable(feature = "metadata_is_symlink", reason = "recently added API",
+               issue = "0")]
+    pub fn is_symlink(&self) -> bool { self.file_type().is_symlink() }
+
+    /// Returns the size of the file, in bytes, this metadata is for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> std::io::Result<()> {
+    /// use std::fs;
+    ///
+    /// let metadata = try!(fs::metadata("foo.txt"));
+    ///
+    /// assert_eq!(0, metadata.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn len(&self) -> u64 { self.0.size() }
+
+    /// Returns the permissions of the file this metadata is for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> std::io::Result<()> {
+    /// use std::fs;
+    ///
+    /// let metadata = try!(fs::metadata("foo.txt"));
+    ///
+    /// assert!(!metadata.permissions().readonly());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn permissions(&self) -> Permissions {
+        Permissions(self.0.perm())
+    }
+
+    /// Returns the block size the file system prefers for I/O on this file,
+    /// e.g. for sizing the buffer of a copy.
+    ///
+    /// This is a hint only; any buffer size works, but multiples of this one
+    /// avoid partial block reads and writes. 8 KiB is returned if the block
+    /// size can't be determined.
+    ///
+    /// # Platform-specific behavior
+    ///
+    /// This is `st_blksize` on Unix. On Windows it's the cluster size of the
+    /// volume the file is on, which is looked up with `GetDiskFreeSpaceW`
+    /// the first time a volume is seen and cached from then on, see
+    /// `os::windows::fs::clear_volume_cache`. Metadata from
+    /// `DirEntry::metadata` doesn't know its volume, and neither do some
+    /// network shares, so those always get the default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(metadata_preferred_io_block_size)]
+    /// # fn foo() -> std::io::Result<()> {
+    /// use std::fs;
+    ///
+    /// let metadata = try!(fs::metadata("foo.txt"));
+    /// let mut buf = vec![0; metadata.preferred_io_block_size() as usize];
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[unstable(feature = "metadata_preferred_io_block_size",
+               reason = "recently added API", issue = "0")]
+    pub fn preferred_io_block_size(&self) -> u64 {
+        self.0.preferred_io_block_size().unwrap_or(8 * 1024)
+    }
+
+    /// Returns what identifies this file and its current state, for telling
+    /// cheaply whether a file changed between two calls to `metadata`.
+    ///
+    /// The identity is made of the file's device and inode number, its size,
+    /// and both its modification and status change times. The latter also
+    /// changes when only the metadata does, e.g. on `chmod`, or when a
+    /// program restores the modification time after writing.
+    ///
+    /// Changes that happen within the resolution of the file system's
+    /// timestamps and don't alter the size can go unnoticed.
+    ///
+    /// # Platform-specific behavior
+    ///
+    /// On Windows this is the volume serial number, file index, size and
+    /// last write time. The first two are only known for metadata obtained
+    /// through `File::metadata`, or `metadata` on a symbolic link, so a file
+    /// that is replaced by another one of the same size and write time isn't
+    /// noticed otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(metadata_identity)]
+    /// # fn foo() -> std::io::Result<()> {
+    /// use std::fs;
+    ///
+    /// let before = try!(fs::metadata("foo.txt")).identity();
+    /// // ...
+    /// if try!(fs::metadata("foo.txt")).identity() != before {
+    ///     println!("foo.txt changed");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[unstable(feature = "metadata_identity", reason = "recently added API", issue = "0")]
+    pub fn identity(&self) -> FileIdentity {
+        FileIdentity(self.0.identity())
+    }
+}
+
+impl AsInner<fs_imp::FileAttr> for Metadata {
+    fn as_inner(&self) -> &fs_imp::FileAttr { &self.0 }
+}
+
+impl Permissions {
+    /// Returns whether these permissions describe a readonly file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    ///
+    /// # fn foo() -> std::io::Result<()> {
+    /// let mut f = try!(File::create("foo.txt"));
+    /// let metadata = try!(f.metadata());
+    ///
+    /// assert_eq!(false, metadata.permissions().readonly());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn readonly(&self) -> bool { self.0.readonly() }
+
+    /// Modifies the readonly flag for this set of permissions.
+    ///
+    /// This operation does **not** modify the filesystem. To modify the
+    /// filesystem use the `fs::set_permissions` function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    ///
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(File::create("foo.txt"));
+    /// let metadata = try!(f.metadata());
+    /// let mut permissions = metadata.permissions();
+    ///
+    /// permissions.set_readonly(true);
+    ///
+    /// // filesystem doesn't change
+    /// assert_eq!(false, metadata.permissions().readonly());
+    ///
+    /// // just this particular `permissions`.
+    /// assert_eq!(true, permissions.readonly());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn set_readonly(&mut self, readonly: bool) {
+        self.0.set_readonly(readonly)
+    }
+}
+
+impl FileType {
+    /// Test whether this file type represents a directory.
+    #[stable(feature = "file_type", since = "1.1.0")]
+    pub fn is_dir(&self) -> bool { self.0.is_dir() }
+
+    /// Test whether this file type represents a regular file.
+    #[stable(feature = "file_type", since = "1.1.0")]
+    pub fn is_file(&self) -> bool { self.0.is_file() }
+
+    /// Test whether this file type represents a symbolic link.
+    #[stable(feature = "file_type", since = "1.1.0")]
+    pub fn is_symlink(&self) -> bool { self.0.is_symlink() }
+
+    /// Returns a short, human readable name for this file type, suitable for
+    /// log and error messages.
+    ///
+    /// The names are `"directory"`, `"file"` and `"symlink"` everywhere.
+    /// Unix additionally has `"fifo"`, `"socket"`, `"block device"` and
+    /// `"char device"`, and Windows has `"junction"` and `"reparse point"`.
+    /// Any other type is described as `"unknown"`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(file_type_describe)]
+    /// # fn foo() -> std::io::Result<()> {
+    /// use std::fs;
+    ///
+    /// let ty = try!(fs::metadata(".")).file_type();
+    /// assert_eq!(ty.describe(), "directory");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[unstable(feature = "file_type_describe", reason = "recently added API",
+               issue = "0")]
+    pub fn describe(&self) -> &'static str { self.0.describe() }
+}
+
+impl AsInner<fs_imp::FileType> for FileType {
+    fn as_inner(&self) -> &fs_imp::FileType { &self.0 }
+}
+
+impl FromInner<fs_imp::FileType> for FileType {
+    fn from_inner(f: fs_imp::FileType) -> FileType {
+        FileType(f)
+    }
+}
+
+impl FromInner<fs_imp::FileAttr> for Metadata {
+    fn from_inner(attr: fs_imp::FileAttr) -> Metadata {
+        Metadata(attr)
+    }
+}
+
+impl FromInner<fs_imp::FilePermissions> for Permissions {
+    fn from_inner(f: fs_imp::FilePermissions) -> Permissions {
+        Permissions(f)
+    }
+}
+
+impl AsInner<fs_imp::FilePermissions> for Permissions {
+    fn as_inner(&self) -> &fs_imp::FilePermissions { &self.0 }
+}
+
+impl AsInnerMut<fs_imp::FilePermissions> for Permissions {
+    fn as_inner_mut(&mut self) -> &mut fs_imp::FilePermissions { &mut self.0 }
+}
+
+#[stable(feature = "rust1", since = "1.0.0")]
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+        self.0.next().map(|entry| entry.map(DirEntry))
+    }
+}
+
+#[unstable(feature = "read_dir_filtered", reason = "recently added API",
+           issue = "0")]
+impl<F: FnMut(&OsStr) -> bool> Iterator for ReadDirFiltered<F> {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+        self.inner.next_matching(&mut self.predicate).map(|entry| entry.map(DirEntry))
+    }
+}
+
+#[unstable(feature = "read_dir_with_metadata", reason = "recently added API",
+           issue = "0")]
+impl Iterator for ReadDirWithMetadata {
+    type Item = io::Result<(DirEntry, io::Result<Metadata>)>;
+
+    fn next(&mut self) -> Option<io::Result<(DirEntry, io::Result<Metadata>)>> {
+        self.0.next().map(|entry| entry.map(|entry| {
+            let metadata = self.0.entry_metadata(&entry).map(Metadata);
+            (DirEntry(entry), metadata)
+        }))
+    }
+}
+
+#[stable(feature = "rust1", since = "1.0.0")]
+impl DirEntry {
+    /// Returns the full path to the file that this entry represents.
+    ///
+    /// The full path is created by joining the original path to `read_dir` or
+    /// `walk_dir` with the filename of this entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// # fn foo() -> std::io::Result<()> {
+    /// for entry in try!(fs::read_dir(".")) {
+    ///     let dir = try!(entry);
+    ///     println!("{:?}", dir.path());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// This prints output like:
+    ///
+    /// ```text
+    /// "./whatever.txt"
+    /// "./foo.html"
+    /// "./hello_world.rs"
+    /// ```
+    ///
+    /// The exact text, of course, depends on what files you have in `.`.
+    #[stable(feature = "rust1", since = "1.0.0")]
+    pub fn path(&self) -> PathBuf { self.0.path() }
+
+    /// Consumes this entry, returning the full path to the file it
+    /// represents.
+    ///
+    /// This is the same as `path`, except that if this is the last remaining
+    /// reference to the directory's path (the `ReadDir` and every other
+    /// entry from it having been dropped), that path is reused instead of
+    /// copied.
+    #[unstable(feature = "dir_entry_into_path", reason = "recently added API",
+               issue = "0")]
+    pub fn into_path(self) -> PathBuf { self.0.into_path() }
+
+    /// Return the metadata for the file that this entry points at.
+    ///
+    /// This function will not traverse symlinks if this entry points at a
+    /// symlink.
+    ///
+    /// # Platform behavior
+    ///
+    /// On Windows this function is cheap to call (no extra system calls
+    /// needed), but on Unix platforms this function is the equivalent of
+    /// calling `symlink_metadata` on the path.
+    #[stable(feature = "dir_entry_ext", since = "1.1.0")]
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        self.0.metadata().map(Metadata)
+    }
+
+    /// Returns the metadata of the file this entry points at, following
+    /// symbolic links, or `None` if it is a dangling symbolic link.
+    ///
+    /// Unlike `metadata`, a symlink is resolved to its target here. Instead
+    /// of failing when the target doesn't exist this returns `Ok(None)`, so
+    /// that a directory walker can report broken links and carry on. Other
+    /// errors, such as lacking permission to look at the target, are still
+    /// returned as errors.
+    ///
+    /// `None` is also returned if the entry itself has been removed since
+    /// the directory was read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(dir_entry_metadata_opt)]
+    /// use std::fs;
+    ///
+    /// # fn foo() -> std::io::Result<()> {
+    /// for entry in try!(fs::read_dir(".")) {
+    ///     let entry = try!(entry);
+    ///     if try!(entry.metadata_opt()).is_none() {
+    ///         println!("broken link: {}", entry.path().display());
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[unstable(feature = "dir_entry_metadata_opt", reason = "recently added API",
+               issue = "0")]
+    pub fn metadata_opt(&self) -> io::Result<Option<Metadata>> {
+        match fs_imp::stat(&self.path()) {
+            Ok(attr) => Ok(Some(Metadata(attr))),
+            Err(ref e) if fs_imp::is_not_found(e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Return the file type for the file that this entry points at.
+    ///
+    /// This function will not traverse symlinks if this entry points at a
+    /// symlink.
+    ///
+    /// # Platform behavior
+    ///
+    /// On Windows and most Unix platforms this function is free (no extra
+    /// system calls needed), but some Unix platforms may require the equivalent
+    /// call to `symlink_metadata` to learn about the target file type.
+    #[stable(feature = "dir_entry_ext", since = "1.1.0")]
+    pub fn file_type(&self) -> io::Result<FileType> {
+        self.0.file_type().map(FileType)
+    }
+
+    /// Returns the bare file name of this directory entry without any other
+    /// leading path component.
+    #[stable(feature = "dir_entry_ext", since = "1.1.0")]
+    pub fn file_name(&self) -> OsString {
+        self.0.file_name()
+    }
+}
+
+impl AsInner<fs_imp::DirEntry> for DirEntry {
+    fn as_inner(&self) -> &fs_imp::DirEntry { &self.0 }
+}
+
+/// Removes a file from the filesystem.
+///
+/// Note that there is no
+/// guarantee that the file is immediately deleted (e.g. depending on
+/// platform, other open file descriptors may prevent immediate removal).
+///
+/// # Platform-specific behavior
+///
+/// On Unix, whether a file can be removed only depends on the permissions of
+/// the directory containing it. On Windows a file which has its read-only
+/// attribute set can't be removed; `force_remove_file` takes care of that.
+///
+/// # Errors
+///
+/// This function will return an error if `path` points to a directory, if the
+/// user lacks permissions to remove the file, or if some other filesystem-level
+/// error occurs.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::remove_file("a.txt"));
+/// # Ok(())
+/// # }
+/// ```
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    fs_imp::unlink(path.as_ref())
+}
+
+/// Removes a file from the filesystem if it exists, returning whether there
+/// was one to remove.
+///
+/// This is `remove_file` with a missing file not counting as an error, which
+/// is what cleanup code usually wants. A path whose parent is missing, or
+/// goes through something that isn't a directory, counts as missing too. All
+/// other errors, like a lack of permissions, are returned as usual.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(fs_remove_if_exists)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// if try!(fs::remove_file_if_exists("a.lock")) {
+///     println!("removed a stale lock file");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_remove_if_exists", reason = "recently added API",
+           issue = "0")]
+pub fn remove_file_if_exists<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    removed_if_exists(fs_imp::unlink(path.as_ref()))
+}
+
+fn removed_if_exists(ret: io::Result<()>) -> io::Result<bool> {
+    match ret {
+        Ok(()) => Ok(true),
+        Err(ref e) if fs_imp::is_not_found(e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Removes a file from the filesystem even if it is read-only.
+///
+/// On Windows, if the file can't be deleted because it's read-only, the
+/// read-only attribute is cleared and the deletion tried again. Should that
+/// fail too, the attribute is restored. This makes removing a file behave
+/// the same as on Unix, where this is identical to `remove_file`.
+///
+/// # Errors
+///
+/// This function returns an error under the same conditions as
+/// `remove_file`, apart from the file being read-only.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_force_remove_file)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::force_remove_file("a.txt"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_force_remove_file", reason = "recently added API",
+           issue = "0")]
+pub fn force_remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    fs_imp::unlink_force(path.as_ref())
+}
+
+/// Removes a file from the filesystem, retrying for a while if it's in use.
+///
+/// On Windows a file can't be deleted while another process has it open
+/// without having allowed deletion, and deleting a file which is still being
+/// deleted fails as well. This function retries the removal up to `attempts`
+/// times in total, sleeping for `delay` in between, for as long as it fails
+/// for one of those reasons. A read-only file also has its read-only
+/// attribute cleared before being deleted.
+///
+/// On Unix nothing can prevent an open file from being removed, so this
+/// makes a single attempt, exactly like `remove_file`.
+///
+/// # Errors
+///
+/// Returns the error of the last attempt, or right away if the removal
+/// fails for any other reason.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_remove_file_retry)]
+/// use std::fs;
+/// use std::time::Duration;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::remove_file_retry("build.log", 10, Duration::from_millis(100)));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_remove_file_retry", reason = "recently added API",
+           issue = "0")]
+pub fn remove_file_retry<P: AsRef<Path>>(path: P, attempts: u32, delay: Duration)
+                                         -> io::Result<()> {
+    fs_imp::unlink_retry(path.as_ref(), attempts, delay)
+}
+
+/// Given a path, query the file system to get information about a file,
+/// directory, etc.
+///
+/// This function will traverse symbolic links to query information about the
+/// destination file.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn foo() -> std::io::Result<()> {
+/// use std::fs;
+///
+/// let attr = try!(fs::metadata("/some/file/path.txt"));
+/// // inspect attr ...
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This function will return an error if the user lacks the requisite
+/// permissions to perform a `metadata` call on the given `path` or if there
+/// is no entry in the filesystem at the provided path.
+///
+/// A `path` ending with a separator, such as `"file.txt/"`, has to name a
+/// directory (or a symbolic link to one); otherwise an error of kind
+/// `NotADirectory` is returned. The same holds for `File::open`.
+///
+/// # Platform-specific behavior
+///
+/// This function currently corresponds to the `stat` function on Unix and
+/// `GetFileAttributesEx` on Windows. On Windows, if `path` is a symbolic link
+/// or another kind of reparse point, the file additionally has to be opened
+/// to look at its target. If a `File` is open already, `metadata_of` (or
+/// `File::metadata`) avoids looking the path up again. Note that this [may
+/// change in the future][changes].
+///
+/// [changes]: ../io/index.html#platform-specific-behavior
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    fs_imp::stat(path.as_ref()).map(Metadata)
+}
+
+/// Like `metadata`, but tells apart a missing file from a missing parent
+/// directory.
+///
+/// When `metadata` fails because nothing exists at `path`, the parent
+/// directory is looked up as well to find out which of the two is the case,
+/// e.g. to report "directory /a/b does not exist" instead of just "no such
+/// file". The successful path costs no more than `metadata` does.
+///
+/// # Errors
+///
+/// Returns `MissingKind::Final` or `MissingKind::Parent` depending on what's
+/// missing, and `MissingKind::Other` for any other error. These convert
+/// into an `io::Error` of kind `NotFound` in the first two cases.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_metadata_detailed)]
+/// use std::fs::{self, MissingKind};
+///
+/// match fs::metadata_detailed("/a/b/c") {
+///     Ok(m) => println!("{} bytes", m.len()),
+///     Err(MissingKind::Final) => println!("no file c in /a/b"),
+///     Err(MissingKind::Parent) => println!("directory /a/b does not exist"),
+///     Err(MissingKind::Other(e)) => println!("error: {}", e),
+/// }
+/// ```
+#[unstable(feature = "fs_metadata_detailed", reason = "recently added API",
+           issue = "0")]
+pub fn metadata_detailed<P: AsRef<Path>>(path: P) -> Result<Metadata, MissingKind> {
+    let path = path.as_ref();
+    match metadata(path) {
+        Ok(m) => Ok(m),
+        Err(ref e) if fs_imp::is_not_found(e) => {
+            let parent_exists = match path.parent() {
+                // A relative path with a single component lives in the
+                // current directory, which is there as far as we care.
+                Some(parent) if parent.as_os_str().is_empty() => true,
+                Some(parent) => metadata(parent).map(|m| m.is_dir()).unwrap_or(false),
+                None => true,
+            };
+            Err(if parent_exists { MissingKind::Final } else { MissingKind::Parent })
+        }
+        Err(e) => Err(MissingKind::Other(e)),
+    }
+}
+
+/// Queries metadata about the file underlying an open `File`.
+///
+/// This is the same as `File::metadata`: it works on the handle directly,
+/// using `fstat` on Unix and `GetFileInformationByHandle` on Windows,
+/// without resolving any path.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_metadata_of)]
+/// use std::fs::{self, File};
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let f = try!(File::open("foo.txt"));
+/// let attr = try!(fs::metadata_of(&f));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_metadata_of", reason = "recently added API",
+           issue = "0")]
+pub fn metadata_of(file: &File) -> io::Result<Metadata> {
+    file.metadata()
+}
+
+/// Opens a handle to `path` that only refers to the file, without any access
+/// to its contents.
+///
+/// Such a handle is useful for asking about the file with `File::metadata`,
+/// for anchoring lookups relative to a directory (e.g. by turning it into an
+/// `os::unix::fs::Dir` through its raw descriptor), and for holding on to a
+/// file that may get renamed. Reading, writing, and changing the file's
+/// length, times or permissions through it fail. Since no access is asked
+/// for, it can be opened even when the file can't be read or written.
+///
+/// If `no_follow` is `true` and the last component of `path` is a symbolic
+/// link, the handle refers to the link itself instead of its target.
+///
+/// # Platform-specific behavior
+///
+/// This uses `O_PATH` on Linux, plus `O_NOFOLLOW` if `no_follow` is set. On
+/// Windows it opens the file with an access mode of 0 and
+/// `FILE_FLAG_BACKUP_SEMANTICS`, plus `FILE_FLAG_OPEN_REPARSE_POINT` if
+/// `no_follow` is set. Other Unix platforms lack `O_PATH`, so the file is
+/// opened read-only instead, which does require read permission and fails
+/// for a symbolic link when `no_follow` is set. Note that this [may change
+/// in the future][changes].
+///
+/// [changes]: ../io/index.html#platform-specific-behavior
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_open_path_handle)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let handle = try!(fs::open_path_handle("/etc/shadow", true));
+/// println!("{} bytes", try!(handle.metadata()).len());
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_open_path_handle", reason = "recently added API",
+           issue = "0")]
+pub fn open_path_handle<P: AsRef<Path>>(path: P, no_follow: bool) -> io::Result<File> {
+    fs_imp::File::open_path(path.as_ref(), no_follow).map(|inner| File { inner: inner })
+}
+
+/// Query the metadata about a file without following symlinks.
+///
+/// If `path` is a symbolic link, the metadata describes the link itself, so
+/// its `len` is not the size of the file it points to. On Unix it's the
+/// length of the link's target path in bytes, while on Windows it's always
+/// zero, since a symbolic link is an empty file with a reparse point
+/// attached. Use `metadata` for the size of the target, or
+/// `symlink_target_len` for the length of the target path on any platform.
+///
+/// On Linux, querying an automount point, such as a host's directory below
+/// `/net`, may mount the file system behind it even though this function
+/// doesn't follow symbolic links. Scanners that must not trigger mounts can
+/// use `os::linux::fs::symlink_metadata_no_automount` for the last component
+/// of the path; automount points in the directories leading up to it are
+/// always mounted, as looking anything up below them requires.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn foo() -> std::io::Result<()> {
+/// use std::fs;
+///
+/// let attr = try!(fs::symlink_metadata("/some/file/path.txt"));
+/// // inspect attr ...
+/// # Ok(())
+/// # }
+/// ```
+#[stable(feature = "symlink_metadata", since = "1.1.0")]
+pub fn symlink_metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    fs_imp::lstat(path.as_ref()).map(Metadata)
+}
+
+/// Returns whether there is a directory entry at `path`, without following
+/// a symbolic link there.
+///
+/// Unlike `PathExt::exists`, which looks at what a link points to, this
+/// returns `true` for a dangling symbolic link, so it tells whether creating
+/// something at `path` would collide with an existing entry. Symbolic links
+/// in the parent directories of `path` are still followed.
+///
+/// Any error from `symlink_metadata`, including a lack of permission to
+/// look into a parent directory, is reported as `false`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_symlink_exists)]
+/// use std::fs;
+///
+/// if fs::symlink_exists("current") {
+///     println!("`current` is taken, if only by a broken link");
+/// }
+/// ```
+#[unstable(feature = "fs_symlink_exists", reason = "recently added API",
+           issue = "0")]
+pub fn symlink_exists<P: AsRef<Path>>(path: P) -> bool {
+    symlink_metadata(path).is_ok()
+}
+
+/// Returns the type of the file system that `path` is on.
+///
+/// This lets a program adapt to what the file system is good at, for
+/// instance not bothering to preallocate space on tmpfs, or avoiding hard
+/// links on FAT. Symbolic links in `path` are followed. See `FsType` for
+/// what is reported on each platform.
+///
+/// # Errors
+///
+/// Fails if `path` doesn't exist. On Unix platforms other than Linux,
+/// Android, OS X, iOS and FreeBSD this always fails with an error of kind
+/// `Other`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_filesystem_type)]
+/// use std::fs::{self, FsKind};
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let fs_type = try!(fs::filesystem_type("/tmp"));
+/// if fs_type.kind() == FsKind::Tmpfs {
+///     println!("/tmp is in memory");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_filesystem_type", reason = "recently added API",
+           issue = "0")]
+pub fn filesystem_type<P: AsRef<Path>>(path: P) -> io::Result<FsType> {
+    let (name, magic) = try!(fs_imp::fs_type(path.as_ref()));
+    Ok(FsType { name: name, magic: magic })
+}
+
+/// Returns the number of subdirectories of the directory at `path`, as
+/// derived from its link count, without reading the directory.
+///
+/// This is a heuristic for progress bars and the like, not an exact count.
+/// It relies on the traditional Unix file system layout where a directory's
+/// link count is 2 plus its number of subdirectories, which is kept up by
+/// ext2/3/4, XFS and tmpfs among others.
+///
+/// # Platform-specific behavior
+///
+/// Returns `None` on file systems which report a link count of 1 for
+/// directories, such as btrfs, and always on OS X and Windows, where link
+/// counts say nothing about subdirectories. Network and FUSE file systems
+/// may report anything, so even a `Some` can't be fully trusted. Also
+/// returns `None` if `path` isn't a directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_subdir_count)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// match try!(fs::subdir_count("src")) {
+///     Some(n) => println!("about {} subdirectories", n),
+///     None => println!("unknown number of subdirectories"),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_subdir_count", reason = "recently added API",
+           issue = "0")]
+pub fn subdir_count<P: AsRef<Path>>(path: P) -> io::Result<Option<u64>> {
+    metadata(path).map(|m| m.0.subdir_count())
+}
+
+/// Queries the metadata of many paths at once.
+///
+/// The returned vector has one entry per element of `paths`, in the same
+/// order, so a failure for one path doesn't affect the others. Each entry is
+/// exactly what `metadata` would have returned for that path.
+///
+/// On Linux 5.6 and later all of the lookups are submitted to the kernel at
+/// once through `io_uring`, which saves a system call per path. Elsewhere,
+/// and on older kernels, the paths are looked up one after the other.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(fs_metadata_batch)]
+/// use std::fs;
+/// use std::path::Path;
+///
+/// let paths = [Path::new("a.txt"), Path::new("b.txt")];
+/// for (path, meta) in paths.iter().zip(fs::metadata_batch(&paths)) {
+///     match meta {
+///         Ok(meta) => println!("{}: {} bytes", path.display(), meta.len()),
+///         Err(e) => println!("{}: {}", path.display(), e),
+///     }
+/// }
+/// ```
+#[unstable(feature = "fs_metadata_batch", reason = "recently added API",
+           issue = "0")]
+pub fn metadata_batch<P: AsRef<Path>>(paths: &[P]) -> Vec<io::Result<Metadata>> {
+    let paths: Vec<&Path> = paths.iter().map(|p| p.as_ref()).collect();
+    fs_imp::stat_batch(&paths).into_iter().map(|r| r.map(Metadata)).collect()
+}
+
+/// Reads the entire contents of a file into a bytes vector.
+///
+/// The buffer is preallocated from the size reported by the file's metadata,
+/// but that size is only used as a hint: the file is always read until the
+/// end. This matters for files such as those in `/proc` and `/sys` on Linux,
+/// which report a size of 0 while still having contents, and for files
+/// which grow while they are being read.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_read)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let status = try!(fs::read("/proc/self/status"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_read", reason = "recently added API", issue = "0")]
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    try!(read_into(path, &mut bytes));
+    Ok(bytes)
+}
+
+/// Reads the entire contents of a file, appending them to `buf`, and returns
+/// the number of bytes read.
+///
+/// This is `read` for callers who want to reuse a buffer across many files:
+/// whatever `buf` already contains is kept, and it only grows if its spare
+/// capacity isn't enough for the file's size as reported by its metadata.
+/// As with `read`, that size is only a hint and the file is always read
+/// until the end.
+///
+/// # Errors
+///
+/// If reading fails partway, the bytes read so far are left at the end of
+/// `buf`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_read)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let mut buf = Vec::new();
+/// for name in &["a.txt", "b.txt"] {
+///     buf.clear();
+///     let n = try!(fs::read_into(name, &mut buf));
+///     println!("{}: {} bytes", name, n);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_read", reason = "recently added API", issue = "0")]
+pub fn read_into<P: AsRef<Path>>(path: P, buf: &mut Vec<u8>) -> io::Result<usize> {
+    let mut f = try!(File::open(path));
+    let len = f.metadata().map(|m| m.len() as usize).unwrap_or(0);
+    buf.reserve(len);
+    // Don't stop after `len` bytes, read_to_end keeps going until EOF.
+    f.read_to_end(buf)
+}
+
+/// Reads the entire contents of a file into a string.
+///
+/// A leading UTF-8 byte order mark (`EF BB BF`), as commonly written by
+/// Windows editors, is removed from the result. Use `read_to_string_opts`
+/// to keep it.
+///
+/// # Errors
+///
+/// This function will return an error if `path` cannot be opened or read,
+/// and an error of kind `InvalidData` if its contents are not valid UTF-8.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_read_to_string)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let config = try!(fs::read_to_string("config.ini"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_read_to_string", reason = "recently added API",
+           issue = "0")]
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    read_to_string_opts(path, true)
+}
+
+/// Same as `read_to_string`, but only strips a leading byte order mark if
+/// `strip_bom` is `true`.
+#[unstable(feature = "fs_read_to_string", reason = "recently added API",
+           issue = "0")]
+pub fn read_to_string_opts<P: AsRef<Path>>(path: P, strip_bom: bool)
+                                           -> io::Result<String> {
+    let bytes = try!(read(path));
+    let mut s = try!(String::from_utf8(bytes).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData,
+                       "stream did not contain valid UTF-8")
+    }));
+    if strip_bom && s.starts_with('\u{feff}') {
+        s.remove(0);
+    }
+    Ok(s)
+}
+
+/// Rename a file or directory to a new name.
+///
+/// This will not work if the new name is on a different mount point.
+///
+/// # Errors
+///
+/// This function will return an error if the provided `from` doesn't exist, if
+/// the process lacks permissions to view the contents, if `from` and `to`
+/// reside on separate filesystems, or if some other intermittent I/O error
+/// occurs.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::rename("a.txt", "b.txt"));
+/// # Ok(())
+/// # }
+/// ```
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    fs_imp::rename(from.as_ref(), to.as_ref())
+}
+
+/// Renames a file or directory, retrying for a while if something has it
+/// open.
+///
+/// On Windows a file can't be moved while another process has it open
+/// without having allowed deletion, and a directory can't be moved while any
+/// file inside it is open. Virus scanners and indexers routinely hold such
+/// handles for a moment. This function retries the rename up to `attempts`
+/// times in total, sleeping for `delay` in between, for as long as it fails
+/// with `ERROR_SHARING_VIOLATION` or `ERROR_ACCESS_DENIED`.
+///
+/// `ERROR_ACCESS_DENIED` is also what a rename fails with for good, for
+/// instance when the process lacks permission or `to` is an existing
+/// directory, and those cases use up all the attempts before failing.
+///
+/// On Unix nothing can prevent an open file from being renamed, so this
+/// makes a single attempt, exactly like `rename`.
+///
+/// # Errors
+///
+/// Returns the error of the last attempt, or right away if the rename fails
+/// for any other reason, e.g. because `from` doesn't exist or `to` is on
+/// another volume.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_rename_retry)]
+/// use std::fs;
+/// use std::time::Duration;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::rename_retry("build", "build.old", 10, Duration::from_millis(100)));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_rename_retry", reason = "recently added API",
+           issue = "0")]
+pub fn rename_retry<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q, attempts: u32,
+                                                    delay: Duration) -> io::Result<()> {
+    fs_imp::rename_retry(from.as_ref(), to.as_ref(), attempts, delay)
+}
+
+impl RenameOptions {
+    /// Creates a blank set of options, which behaves exactly like `rename`.
+    #[unstable(feature = "fs_rename_with", reason = "recently added API",
+               issue = "0")]
+    pub fn new() -> RenameOptions {
+        RenameOptions { create_parents: false, overwrite: true }
+    }
+
+    /// Sets whether the parent directories of the destination are created
+    /// first if they don't exist yet.
+    ///
+    /// Directories created this way are left in place if the rename itself
+    /// then fails.
+    ///
+    /// This option defaults to `false`.
+    #[unstable(feature = "fs_rename_with", reason = "recently added API",
+               issue = "0")]
+    pub fn create_parents(&mut self, create_parents: bool) -> &mut RenameOptions {
+        self.create_parents = create_parents;
+        self
+    }
+
+    /// Sets whether an existing destination is replaced.
+    ///
+    /// If `false`, the rename fails with an error of kind `AlreadyExists`
+    /// when the destination exists. On Linux this check is atomic if the
+    /// kernel and file system support `renameat2`; elsewhere on Unix the
+    /// destination could still be replaced if it's created concurrently.
+    ///
+    /// This option defaults to `true`.
+    #[unstable(feature = "fs_rename_with", reason = "recently added API",
+               issue = "0")]
+    pub fn overwrite(&mut self, overwrite: bool) -> &mut RenameOptions {
+        self.overwrite = overwrite;
+        self
+    }
+}
+
+/// Renames a file or directory like `rename`, with the behavior adjusted by
+/// `options`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_rename_with)]
+/// use std::fs::{self, RenameOptions};
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let mut opts = RenameOptions::new();
+/// opts.create_parents(true).overwrite(false);
+/// try!(fs::rename_with("a.txt", "archive/2015/a.txt", &opts));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_rename_with", reason = "recently added API",
+           issue = "0")]
+pub fn rename_with<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q, options: &RenameOptions)
+                                                   -> io::Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if options.create_parents {
+        if let Some(parent) = to.parent() {
+            try!(create_dir_all(parent));
+        }
+    }
+    if options.overwrite {
+        fs_imp::rename(from, to)
+    } else {
+        fs_imp::rename_noreplace(from, to)
+    }
+}
+
+/// Copies the contents of one file to another. This function will also
+/// copy the permission bits of the original file to the destination file.
+///
+/// This function will **overwrite** the contents of `to`.
+///
+/// Note that if `from` and `to` both point to the same file, then the file
+/// will likely get truncated by this operation.
+///
+/// On success, the total number of bytes copied is returned.
+///
+/// # Errors
+///
+/// This function will return an error in the following situations, but is not
+/// limited to just these cases:
+///
+/// * The `from` path is not a file
+/// * The `from` file does not exist
+/// * The current process does not have the permission rights to access
+///   `from` or write `to`
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::copy("foo.txt", "bar.txt"));
+/// # Ok(()) }
+/// ```
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<u64> {
+    fs_imp::copy(from.as_ref(), to.as_ref())
+}
+
+/// Same as `copy`, but also accepts sources which aren't regular files, such
+/// as named pipes and character devices.
+///
+/// A regular file is copied exactly as `copy` does it. Anything else is
+/// opened for reading and streamed into a newly created or truncated `to`
+/// until it reports end of file, so this blocks until, for instance, every
+/// writer of a named pipe has closed it, and never returns for a device like
+/// `/dev/zero`. The returned count is the number of bytes streamed, and the
+/// source's permissions aren't copied.
+///
+/// # Errors
+///
+/// Fails with an error of kind `InvalidInput` if `from` is a directory.
+/// Sources which can't be opened for reading, like Unix domain sockets,
+/// fail with the error from opening them.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_copy_any)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// // Captures everything written to the pipe until its writers are done.
+/// let n = try!(fs::copy_any("/run/app/output.fifo", "output.log"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_copy_any", reason = "recently added API",
+           issue = "0")]
+pub fn copy_any<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<u64> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    let meta = try!(metadata(from));
+    if meta.is_file() {
+        return fs_imp::copy(from, to)
+    }
+    if meta.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "the source path is a directory"))
+    }
+    let mut reader = try!(File::open(from));
+    let mut writer = try!(File::create(to));
+    io::copy(&mut reader, &mut writer)
+}
+
+impl CopyOptions {
+    /// Creates a blank set of options, which behaves exactly like `copy`.
+    #[unstable(feature = "fs_copy_with_options", reason = "recently added API",
+               issue = "0")]
+    pub fn new() -> CopyOptions {
+        CopyOptions { preserve_xattrs: false, verify: None, copy_symlinks_as_links: false }
+    }
+
+    /// Sets whether the extended attributes of the source are copied to the
+    /// destination as well.
+    ///
+    /// Attributes which the destination refuses, because the process lacks
+    /// the privilege to set them (e.g. `security.*` or `trusted.*` on Linux)
+    /// or because its file system doesn't support them, are skipped rather
+    /// than failing the copy.
+    ///
+    /// This has no effect on Windows, and on Unix platforms other than Linux,
+    /// Android and OS X.
+    ///
+    /// This option defaults to `false`.
+    #[unstable(feature = "fs_copy_with_options", reason = "recently added API",
+               issue = "0")]
+    pub fn preserve_xattrs(&mut self, preserve: bool) -> &mut CopyOptions {
+        self.preserve_xattrs = preserve;
+        self
+    }
+
+    /// Sets whether the copy is checked by reading the destination back and
+    /// comparing its checksum to that of the data read from the source.
+    ///
+    /// The source's checksum is computed while copying, so only the
+    /// destination is read a second time. Both are hashed in blocks of a
+    /// fixed size. If the checksums differ the destination is removed and
+    /// the copy fails with `InvalidData`. Verified copies are always made
+    /// by reading and writing the data, never with an OS copy function such
+    /// as `CopyFileEx`, since the data has to pass through the hasher.
+    ///
+    /// The checksum is SipHash-2-4 with fixed keys; `verify_with` picks a
+    /// different algorithm. Note that the destination is read back through
+    /// the page cache, so this catches data that got lost or changed on its
+    /// way through the program and the file system, such as a source that
+    /// was modified during the copy, but not necessarily faulty storage.
+    ///
+    /// This option defaults to `false`.
+    #[unstable(feature = "fs_copy_with_options", reason = "recently added API",
+               issue = "0")]
+    pub fn verify(&mut self, verify: bool) -> &mut CopyOptions {
+        self.verify = if verify { Some(default_hasher) } else { None };
+        self
+    }
+
+    /// Turns on verification as with `verify`, computing the checksums with
+    /// hashers created by `new_hasher`, one for each file.
+    ///
+    /// The data is written to the hasher in blocks of a fixed size, so the
+    /// hasher doesn't need to give the same result for differently split
+    /// input.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(fs_copy_with_options)]
+    /// use std::fs::{self, CopyOptions};
+    /// use std::hash::{Hasher, SipHasher};
+    ///
+    /// fn keyed() -> Box<Hasher> {
+    ///     Box::new(SipHasher::new_with_keys(0x1234, 0x5678))
+    /// }
+    ///
+    /// # fn foo() -> std::io::Result<()> {
+    /// try!(fs::copy_with_options("foo.txt", "bar.txt",
+    ///                            CopyOptions::new().verify_with(keyed)));
+    /// # Ok(()) }
+    /// ```
+    #[unstable(feature = "fs_copy_with_options", reason = "recently added API",
+               issue = "0")]
+    pub fn verify_with(&mut self, new_hasher: fn() -> Box<Hasher>) -> &mut CopyOptions {
+        self.verify = Some(new_hasher);
+        self
+    }
+
+    /// Sets whether `copy_dir_all` recreates symbolic links in the source as
+    /// symbolic links with the same target, instead of copying what they
+    /// point to.
+    ///
+    /// Links are copied verbatim, so a relative link still resolves relative
+    /// to its new location and may point somewhere else, or nowhere, there.
+    /// On Windows a copy of a directory link, or junction, is a directory
+    /// symlink, and creating it needs the same privilege as
+    /// `os::windows::fs::symlink_dir`.
+    ///
+    /// This has no effect on `copy_with_options`. This option defaults to
+    /// `false`.
+    #[unstable(feature = "fs_copy_with_options", reason = "recently added API",
+               issue = "0")]
+    pub fn copy_symlinks_as_links(&mut self, as_links: bool) -> &mut CopyOptions {
+        self.copy_symlinks_as_links = as_links;
+        self
+    }
+}
+
+impl FsType {
+    /// Returns the name of the file system, as the platform calls it.
+    ///
+    /// On Linux the name is derived from `magic`, as `statfs` doesn't
+    /// report one, and is `"unknown"` for file systems this module doesn't
+    /// know about. ext2 and ext3 are reported as `"ext4"`, which they share
+    /// their magic number with. On OS X and FreeBSD this is `f_fstypename`,
+    /// e.g. `"apfs"` or `"ufs"`, and on Windows the name reported by
+    /// `GetVolumeInformationW`, e.g. `"NTFS"` or `"exFAT"`.
+    #[unstable(feature = "fs_filesystem_type", reason = "recently added API",
+               issue = "0")]
+    pub fn name(&self) -> &str { &self.name }
+
+    /// Returns the `f_type` magic number reported by `statfs` on Linux, such
+    /// as `0x01021994` for tmpfs. Always `None` on other platforms.
+    #[unstable(feature = "fs_filesystem_type", reason = "recently added API",
+               issue = "0")]
+    pub fn magic(&self) -> Option<u64> { self.magic }
+
+    /// Returns which of the well-known file systems this is.
+    #[unstable(feature = "fs_filesystem_type", reason = "recently added API",
+               issue = "0")]
+    pub fn kind(&self) -> FsKind {
+        // The names differ in case between platforms, e.g. "ntfs" on Linux
+        // and "NTFS" on Windows.
+        let name = self.name.to_ascii_lowercase();
+        match &name[..] {
+            "ext2" | "ext3" | "ext4" => FsKind::Ext,
+            "xfs" => FsKind::Xfs,
+            "btrfs" => FsKind::Btrfs,
+            "zfs" => FsKind::Zfs,
+            "tmpfs" | "ramfs" => FsKind::Tmpfs,
+            "nfs" | "nfs4" => FsKind::Nfs,
+            "cifs" | "smb2" | "smbfs" => FsKind::Smb,
+            "fuse" | "fuseblk" | "osxfuse" | "macfuse" | "fusefs" => FsKind::Fuse,
+            "overlay" => FsKind::Overlay,
+            "vfat" | "msdos" | "msdosfs" | "fat" | "fat12" | "fat16" | "fat32" => FsKind::Fat,
+            "exfat" => FsKind::ExFat,
+            "ntfs" => FsKind::Ntfs,
+            "refs" => FsKind::ReFs,
+            "apfs" => FsKind::Apfs,
+            "hfs" => FsKind::Hfs,
+            _ => FsKind::Other,
+        }
+    }
+}
+
+impl PathCache {
+    /// Creates an empty cache.
+    #[unstable(feature = "fs_canonicalize_cached", reason = "recently added API",
+               issue = "0")]
+    pub fn new() -> PathCache {
+        PathCache { map: Mutex::new(HashMap::new()) }
+    }
+
+    /// Returns the number of paths in the cache.
+    #[unstable(feature = "fs_canonicalize_cached", reason = "recently added API",
+               issue = "0")]
+    pub fn len(&self) -> usize {
+        self.map().len()
+    }
+
+    /// Forgets the cached result for `path`, returning whether there was
+    /// one.
+    ///
+    /// Only an entry for exactly this input is removed; other inputs which
+    /// canonicalized to, or through, the same path are kept.
+    #[unstable(feature = "fs_canonicalize_cached", reason = "recently added API",
+               issue = "0")]
+    pub fn remove<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.map().remove(path.as_ref()).is_some()
+    }
+
+    /// Forgets all cached results.
+    #[unstable(feature = "fs_canonicalize_cached", reason = "recently added API",
+               issue = "0")]
+    pub fn clear(&self) {
+        self.map().clear()
+    }
+
+    // The map is never left half-updated, so a panic while it was locked
+    // doesn't make it unusable.
+    fn map(&self) -> MutexGuard<HashMap<PathBuf, PathBuf>> {
+        self.map.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[unstable(feature = "fs_canonicalize_cached", reason = "recently added API",
+           issue = "0")]
+impl Default for PathCache {
+    fn default() -> PathCache {
+        PathCache::new()
+    }
+}
+
+fn default_hasher() -> Box<Hasher> {
+    Box::new(SipHasher::new())
+}
+
+/// Copies the contents of one file to another like `copy`, with the behavior
+/// adjusted by `options`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_copy_with_options)]
+/// use std::fs::{self, CopyOptions};
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::copy_with_options("foo.txt", "bar.txt",
+///                            CopyOptions::new().preserve_xattrs(true)));
+/// # Ok(()) }
+/// ```
+#[unstable(feature = "fs_copy_with_options", reason = "recently added API",
+           issue = "0")]
+pub fn copy_with_options<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q, options: &CopyOptions)
+                                                         -> io::Result<u64> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    let ret = match options.verify {
+        Some(new_hasher) => try!(copy_verified(from, to, new_hasher)),
+        None => try!(fs_imp::copy(from, to)),
+    };
+    if options.preserve_xattrs {
+        // On Unix, setting attributes takes write permission on the file,
+        // which the copy has just lost if the source is read-only, so its
+        // permissions are put back only once the attributes are there.
+        // `File::set_readonly` gives only the owner write access back, so
+        // nobody else can write to the copy in between. Windows has no
+        // attributes to copy.
+        let src = try!(File::open(from));
+        let dst = try!(File::open(to));
+        let perm = try!(src.metadata()).permissions();
+        let restore = cfg!(unix) && perm.readonly();
+        if restore {
+            try!(dst.set_readonly(false));
+        }
+        try!(fs_imp::copy_xattrs(src.as_inner(), dst.as_inner()));
+        if restore {
+            try!(set_permissions(to, perm));
+        }
+    }
+    Ok(ret)
+}
+
+/// Recursively copies the directory `from` and all of its contents to `to`,
+/// returning the total number of bytes copied.
+///
+/// `to` and any missing parents are created, and existing directories are
+/// merged into. Files are copied with `copy_with_options` and `options`, so
+/// they overwrite files of the same name in `to`. Directories get the
+/// permissions of their originals once everything in them has been copied.
+///
+/// Symbolic links are followed unless `CopyOptions::copy_symlinks_as_links`
+/// is set, in which case they are recreated as links. When following links,
+/// a link to one of the directories being copied is an error of kind
+/// `FilesystemLoop` rather than the start of an endless copy.
+///
+/// # Errors
+///
+/// This function will return an error if `from` isn't a directory, if `to`
+/// is `from` or lies within it, and in the situations `read_dir`,
+/// `create_dir_all` and `copy_with_options` do. The copy stops at the first
+/// error, leaving whatever was copied so far in place.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_copy_with_options)]
+/// use std::fs::{self, CopyOptions};
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let mut opts = CopyOptions::new();
+/// opts.copy_symlinks_as_links(true);
+/// let bytes = try!(fs::copy_dir_all("site", "backup/site", &opts));
+/// println!("copied {} bytes", bytes);
+/// # Ok(()) }
+/// ```
+#[unstable(feature = "fs_copy_with_options", reason = "recently added API",
+           issue = "0")]
+pub fn copy_dir_all<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q, options: &CopyOptions)
+                                                    -> io::Result<u64> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if !try!(metadata(from)).is_dir() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "the source path is not a directory"))
+    }
+    if try!(canonicalize_lenient(to)).starts_with(&try!(canonicalize(from))) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "the destination is inside the source directory"))
+    }
+    copy_dir_inner(from, to, options, &mut vec![try!(fs_imp::file_id(from))])
+}
+
+// `ancestors` identifies the directories being copied, to spot links back to
+// them.
+fn copy_dir_inner(from: &Path, to: &Path, options: &CopyOptions,
+                  ancestors: &mut Vec<(u64, u64)>) -> io::Result<u64> {
+    let perm = try!(metadata(from)).permissions();
+    try!(create_dir_all(to));
+    let mut total = 0;
+    for entry in try!(read_dir(from)) {
+        let entry = try!(entry);
+        let (src, dst) = (entry.path(), to.join(entry.file_name()));
+        let mut ty = try!(entry.file_type());
+        if ty.is_symlink() {
+            if options.copy_symlinks_as_links {
+                try!(fs_imp::copy_symlink(&src, &dst));
+                continue
+            }
+            ty = try!(metadata(&src)).file_type();
+        }
+        if !ty.is_dir() {
+            total += try!(copy_with_options(&src, &dst, options));
+            continue
+        }
+        let id = try!(fs_imp::file_id(&src));
+        if ancestors.contains(&id) {
+            return Err(filesystem_loop())
+        }
+        ancestors.push(id);
+        total += try!(copy_dir_inner(&src, &dst, options, ancestors));
+        ancestors.pop();
+    }
+    try!(set_permissions(to, perm));
+    Ok(total)
+}
+
+fn filesystem_loop() -> io::Error {
+    io::Error::new(io::ErrorKind::FilesystemLoop,
+                   "symbolic link to a directory containing it")
+}
+
+// Canonicalizes the longest prefix of `path` that exists and appends the
+// rest as is.
+fn canonicalize_lenient(path: &Path) -> io::Result<PathBuf> {
+    let path = try!(absolute_lexical(path));
+    let mut missing = Vec::new();
+    let mut existing = path.as_path();
+    loop {
+        match canonicalize(existing) {
+            Ok(mut ret) => {
+                for name in missing.iter().rev() {
+                    ret.push(name);
+                }
+                return Ok(ret)
+            }
+            Err(ref e) if fs_imp::is_not_found(e) && existing.file_name().is_some() => {
+                missing.push(existing.file_name().unwrap());
+                existing = existing.parent().unwrap();
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Like `fs_imp::copy`, but hashes the data on its way through and then
+// compares that to a hash of the destination. The data has to pass through
+// the hasher, so this can't hand the copy to the OS, e.g. to CopyFileEx.
+fn copy_verified(from: &Path, to: &Path, new_hasher: fn() -> Box<Hasher>) -> io::Result<u64> {
+    let mut reader = try!(File::open(from));
+    let metadata = try!(reader.metadata());
+    if !metadata.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "the source path is not an existing regular file"))
+    }
+    // Opening `to` truncates it, which would wipe out the source as well if
+    // both are the same file. Metadata queried through a handle identifies
+    // the file on all platforms.
+    if let Ok(dest) = File::open(to) {
+        if try!(dest.metadata()).identity() == metadata.identity() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "the source and destination are the same file"))
+        }
+    }
+
+    let mut writer = try!(File::create(to));
+    // Reserve the space up front as `copy` does, where the platform and the
+    // file system support it.
+    let preallocated = metadata.len() > 0 &&
+                       writer.as_inner().allocate(metadata.len()).is_ok();
+    let mut hasher = new_hasher();
+    let mut buf = [0; CHECKSUM_BLOCK];
+    let mut written = 0;
+    loop {
+        let len = try!(read_block(&mut reader, &mut buf));
+        if len == 0 {
+            break
+        }
+        hasher.write(&buf[..len]);
+        try!(writer.write_all(&buf[..len]));
+        written += len as u64;
+    }
+    if preallocated {
+        // The source may have shrunk since its size was queried.
+        try!(writer.set_len(written));
+    }
+    drop(writer);
+    try!(set_permissions(to, metadata.permissions()));
+
+    let mut reader = try!(File::open(to));
+    let mut check = new_hasher();
+    loop {
+        let len = try!(read_block(&mut reader, &mut buf));
+        if len == 0 {
+            break
+        }
+        check.write(&buf[..len]);
+    }
+    if check.finish() != hasher.finish() {
+        let _ = remove_file(to);
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  "the copy doesn't match the source"))
+    }
+    Ok(written)
+}
+
+// Verified copies are hashed in blocks of this size. `Hasher::write` isn't
+// required to be streaming, so how short reads happen to split the data
+// mustn't change the result.
+const CHECKSUM_BLOCK: usize = 64 * 1024;
+
+// Fills `buf` unless the end of the file comes first, returning how much
+// was read.
+fn read_block(reader: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+/// Creates a new hard link on the filesystem.
+///
+/// The `dst` path will be a link pointing to the `src` path. Note that systems
+/// often require these two paths to both be located on the same filesystem.
+///
+/// Relative paths are resolved against the current working directory, not
+/// against each other: `hard_link("a", "sub/b")` links `sub/b` to `./a`, not
+/// to `sub/a`. On Unix `os::unix::fs::Dir::link_at` resolves each path
+/// relative to a directory of the caller's choosing instead.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::hard_link("a.txt", "b.txt"));
+/// # Ok(())
+/// # }
+/// ```
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<()> {
+    fs_imp::link(src.as_ref(), dst.as_ref())
+}
+
+/// Creates a hard link at `dst` pointing to `src` like `hard_link`, falling
+/// back to copying `src` with `copy` where a link isn't possible.
+///
+/// A copy is made if the two paths are on different file systems, or if
+/// `src` already has as many links as its file system allows. Any other
+/// failure to link, such as `dst` already existing, is returned as is. Note
+/// that unlike `copy`, this never overwrites `dst`: the link is tried first
+/// and fails if it exists.
+///
+/// # Platform-specific behavior
+///
+/// This falls back on `EXDEV` and `EMLINK` on Unix, and on
+/// `ERROR_NOT_SAME_DEVICE` and `ERROR_TOO_MANY_LINKS` on Windows.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_hard_link_or_copy)]
+/// use std::fs::{self, LinkResult};
+///
+/// # fn foo() -> std::io::Result<()> {
+/// match try!(fs::hard_link_or_copy("store/ab/cdef", "checkout/a.txt")) {
+///     LinkResult::Linked => {}
+///     LinkResult::Copied(n) => println!("had to copy {} bytes", n),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_hard_link_or_copy", reason = "recently added API",
+           issue = "0")]
+pub fn hard_link_or_copy<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q)
+                                                         -> io::Result<LinkResult> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    match fs_imp::link(src, dst) {
+        Ok(()) => Ok(LinkResult::Linked),
+        Err(ref e) if fs_imp::is_link_impossible(e) => {
+            fs_imp::copy(src, dst).map(LinkResult::Copied)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates a new symbolic link on the filesystem.
+///
+/// The `dst` path will be a symbolic link pointing to the `src` path.
+/// On Windows, this will be a file symlink, not a directory symlink;
+/// for this reason, the platform-specific `std::os::unix::fs::symlink`
+/// and `std::os::windows::fs::{symlink_file, symlink_dir}` should be
+/// used instead to make the intent explicit.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::soft_link("a.txt", "b.txt"));
+/// # Ok(())
+/// # }
+/// ```
+#[deprecated(since = "1.1.0",
+             reason = "replaced with std::os::unix::fs::symlink and \
+                       std::os::windows::fs::{symlink_file, symlink_dir}")]
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn soft_link<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<()> {
+    fs_imp::symlink(src.as_ref(), dst.as_ref())
+}
+
+/// Creates a symbolic link at `link` pointing to `target`, storing the
+/// target relative to the directory containing `link`.
+///
+/// Both paths are interpreted relative to the current working directory as
+/// usual, and the relative target is then computed from them, so that the
+/// link stays valid if the tree containing both of them is moved or copied
+/// elsewhere. For example `symlink_relative("a/b/file", "a/c/link")` creates
+/// `a/c/link` pointing to `../b/file`.
+///
+/// The computation is purely lexical: symlinks along either path are not
+/// resolved, and `..` components are applied to the path preceding them.
+/// If there is no relative path between the two, e.g. because they are on
+/// different drives on Windows, the absolute target is stored instead.
+///
+/// # Platform-specific behavior
+///
+/// Windows distinguishes between file and directory symlinks. This creates
+/// a directory symlink if `target` currently is a directory and a file
+/// symlink otherwise, including when `target` doesn't exist.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_symlink_relative)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::symlink_relative("forest/pkgs/foo-1.0", "forest/current/foo"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_symlink_relative", reason = "recently added API",
+           issue = "0")]
+pub fn symlink_relative<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link: Q) -> io::Result<()> {
+    let target = try!(absolute_lexical(target.as_ref()));
+    let link = try!(absolute_lexical(link.as_ref()));
+    let rel = match link.parent() {
+        Some(parent) => relative_lexical(parent, &target),
+        None => None,
+    };
+    fs_imp::symlink_auto(rel.as_ref().unwrap_or(&target), &link)
+}
+
+/// Creates a symbolic link at `link` pointing to `target`, atomically
+/// replacing whatever file or symbolic link was at `link` before.
+///
+/// The new link is first created under a temporary name in the same
+/// directory and then renamed over `link`, so other processes see either
+/// the old link or the new one, but never nothing. This makes it suitable
+/// for switching something like a `current -> release-41` link over to
+/// `release-42`. `target` is stored as given, just like `symlink` does.
+///
+/// # Platform-specific behavior
+///
+/// Windows distinguishes between file and directory symlinks. This creates
+/// a directory symlink if `target` currently is a directory and a file
+/// symlink otherwise, with a relative `target` taken relative to `link`'s
+/// directory. Windows also can't rename over a directory symlink, so one is
+/// removed right before the rename, and the replacement isn't atomic then.
+///
+/// # Errors
+///
+/// Fails if `link` is an actual directory, and under the same conditions as
+/// creating a symlink or renaming. The temporary link is removed if the
+/// rename fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_symlink_replace)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::symlink_replace("releases/42", "current"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_symlink_replace", reason = "recently added API",
+           issue = "0")]
+pub fn symlink_replace<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link: Q) -> io::Result<()> {
+    use rand::{self, Rng};
+
+    let (target, link) = (target.as_ref(), link.as_ref());
+    let name = match link.file_name() {
+        Some(name) => name,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "link path has no file name")),
+    };
+    let mut rng = rand::thread_rng();
+    loop {
+        let mut tmp_name = OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(&format!(".{:08x}.tmp", rng.next_u32()));
+        let tmp = link.with_file_name(&tmp_name);
+        match fs_imp::symlink_auto(target, &tmp) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+        return fs_imp::rename_over_symlink(&tmp, link).map_err(|e| {
+            let _ = fs_imp::unlink(&tmp);
+            e
+        })
+    }
+}
+
+/// Makes `path` absolute and removes `.` and `..` components without
+/// touching the file system.
+fn absolute_lexical(path: &Path) -> io::Result<PathBuf> {
+    let mut ret = if path.is_absolute() {
+        PathBuf::new()
+    } else {
+        try!(env::current_dir())
+    };
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => { ret.pop(); }
+            c => ret.push(c.as_os_str()),
+        }
+    }
+    Ok(ret)
+}
+
+/// Returns the path which leads from the directory `from` to `to`, both of
+/// which must be absolute and free of `.` and `..` components.
+fn relative_lexical(from: &Path, to: &Path) -> Option<PathBuf> {
+    let from = from.components().collect::<Vec<_>>();
+    let to = to.components().collect::<Vec<_>>();
+    let common = from.iter().zip(&to).take_while(|&(a, b)| a == b).count();
+    if common == 0 {
+        return None
+    }
+    let mut ret = PathBuf::new();
+    for _ in common..from.len() {
+        ret.push("..");
+    }
+    for c in &to[common..] {
+        ret.push(c.as_os_str());
+    }
+    if ret == Path::new("") {
+        ret.push(".");
+    }
+    Some(ret)
+}
+
+/// Reads a symbolic link, returning the file that the link points to.
+///
+/// # Errors
+///
+/// This function will return an error on failure. Failure conditions include
+/// reading a file that does not exist or reading a file that is not a symbolic
+/// link.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let path = try!(fs::read_link("a.txt"));
+/// # Ok(())
+/// # }
+/// ```
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn read_link<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    fs_imp::readlink(path.as_ref())
+}
+
+/// Reads a symbolic link, returning its target along with the details needed
+/// to create an identical link elsewhere.
+///
+/// # Platform-specific behavior
+///
+/// On Windows, whether the link is relative is taken from the link's
+/// `SYMLINK_FLAG_RELATIVE` flag, and whether it's a directory link from its
+/// attributes. Unlike `read_link`, this also reads directory junctions, whose
+/// targets are always absolute.
+///
+/// On Unix, symbolic links are neither file nor directory links, so
+/// `SymlinkTarget::is_directory` returns `None`, and the link is relative if
+/// its target is.
+///
+/// # Errors
+///
+/// This function returns an error under the same conditions as `read_link`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_read_link_full)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let link = try!(fs::read_link_full("a.txt"));
+/// if link.is_relative() {
+///     println!("relative link to {}", link.target().display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_read_link_full", reason = "recently added API", issue = "0")]
+pub fn read_link_full<P: AsRef<Path>>(path: P) -> io::Result<SymlinkTarget> {
+    fs_imp::readlink_full(path.as_ref()).map(|(target, is_relative, is_directory)| {
+        SymlinkTarget {
+            target: target,
+            is_relative: is_relative,
+            is_directory: is_directory,
+        }
+    })
+}
+
+/// Reads the target of the symbolic link at `path` and returns it as an
+/// absolute path.
+///
+/// A relative target is joined onto the directory containing the link, which
+/// is where the OS resolves it from, and a relative `path` is first taken
+/// relative to the current directory. `.` and `..` components are then
+/// removed lexically, without looking at the file system. Unlike
+/// `canonicalize`, only this one link is read: symbolic links in the target,
+/// or in the directories leading up to the link, are left as they are, and
+/// the target doesn't have to exist.
+///
+/// Because `..` is taken to mean the lexical parent, the result differs from
+/// where the OS would end up if the component before a `..` is itself a
+/// symbolic link to a directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_read_link_absolute)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// // For /etc/localtime -> ../usr/share/zoneinfo/UTC this is
+/// // /usr/share/zoneinfo/UTC.
+/// let zone = try!(fs::read_link_absolute("/etc/localtime"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_read_link_absolute", reason = "recently added API",
+           issue = "0")]
+pub fn read_link_absolute<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+    let target = try!(fs_imp::readlink(path));
+    let link = try!(absolute_lexical(path));
+    // An absolute target replaces the directory in `join`.
+    match link.parent() {
+        Some(dir) => absolute_lexical(&dir.join(&target)),
+        None => absolute_lexical(&target),
+    }
+}
+
+impl SymlinkTarget {
+    /// Returns the target of the link, exactly as stored in it, which is
+    /// what `read_link` returns.
+    #[unstable(feature = "fs_read_link_full", reason = "recently added API", issue = "0")]
+    pub fn target(&self) -> &Path { &self.target }
+
+    /// Consumes this `SymlinkTarget`, returning the target of the link.
+    #[unstable(feature = "fs_read_link_full", reason = "recently added API", issue = "0")]
+    pub fn into_target(self) -> PathBuf { self.target }
+
+    /// Returns whether the target is interpreted relative to the directory
+    /// containing the link.
+    #[unstable(feature = "fs_read_link_full", reason = "recently added API", issue = "0")]
+    pub fn is_relative(&self) -> bool { self.is_relative }
+
+    /// Returns whether this is a directory link, or `None` on platforms that
+    /// don't make the distinction.
+    #[unstable(feature = "fs_read_link_full", reason = "recently added API", issue = "0")]
+    pub fn is_directory(&self) -> Option<bool> { self.is_directory }
+}
+
+/// Reads a symbolic link, returning the path it points to along with the
+/// type of the file at that path, or `None` if the link is dangling.
+///
+/// A relative link target is interpreted relative to the directory containing
+/// the link, not to the current directory, when looking up its type. The
+/// returned path is the target exactly as stored in the link. If the target is
+/// itself a symbolic link it is followed, so the file type is never that of a
+/// symlink.
+///
+/// # Errors
+///
+/// This function returns an error under the same conditions as `read_link`,
+/// and if looking up the target fails for any reason other than it not
+/// existing.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(fs_symlink_target_status)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let (target, ty) = try!(fs::symlink_target_status("a.txt"));
+/// if ty.is_none() {
+///     println!("dangling link to {}", target.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_symlink_target_status", reason = "recently added API",
+           issue = "0")]
+pub fn symlink_target_status<P: AsRef<Path>>(path: P)
+                                             -> io::Result<(PathBuf, Option<FileType>)> {
+    let path = path.as_ref();
+    let target = try!(read_link(path));
+    let resolved = match path.parent() {
+        Some(parent) => parent.join(&target),
+        None => target.clone(),
+    };
+    match metadata(&resolved) {
+        Ok(m) => Ok((target, Some(m.file_type()))),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok((target, None)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the length in bytes of the path the symbolic link at `path`
+/// points to.
+///
+/// This is what `symlink_metadata(path).len()` returns on Unix, but unlike
+/// that it also works on Windows, where the metadata of a symbolic link has
+/// a length of zero. The length is that of the target in the platform's
+/// native encoding: bytes on Unix, UTF-16 code units times two on Windows.
+/// It says nothing about the size of the file the link points to; use
+/// `metadata` for that.
+///
+/// # Errors
+///
+/// This function returns an error under the same conditions as `read_link`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_symlink_target_len)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let len = try!(fs::symlink_target_len("a.txt"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_symlink_target_len", reason = "recently added API",
+           issue = "0")]
+pub fn symlink_target_len<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    fs_imp::symlink_target_len(path.as_ref())
+}
+
+/// Returns the canonical form of a path with all intermediate components
+/// normalized and symbolic links resolved.
+///
+/// # Platform-specific behavior
+///
+/// On Windows the result uses backslashes throughout and an uppercase drive
+/// letter, whatever the input looked like. It is a plain `C:\...` or
+/// `\\server\share\...` path unless it can only be expressed in the
+/// verbatim `\\?\` form, which is kept for paths of `MAX_PATH` characters or
+/// more and for components Windows would otherwise reinterpret, such as
+/// names ending in a dot or reserved device names.
+#[unstable(feature = "fs_canonicalize", reason = "recently added API",
+           issue = "27706")]
+pub fn canonicalize<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    fs_imp::canonicalize(path.as_ref())
+}
+
+/// Same as `canonicalize`, but looks `path` up in `cache` first and only
+/// canonicalizes it if it isn't there yet.
+///
+/// Paths are looked up exactly as given, so `a/b` and `a/./b` are cached
+/// separately. Errors aren't cached. See `PathCache` for what the cache
+/// assumes about the file system.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_canonicalize_cached)]
+/// use std::fs::{self, PathCache};
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let cache = PathCache::new();
+/// for dir in &["include", "src/../include"] {
+///     let dir = try!(fs::canonicalize_cached(dir, &cache));
+///     println!("{}", dir.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_canonicalize_cached", reason = "recently added API",
+           issue = "0")]
+pub fn canonicalize_cached<P: AsRef<Path>>(path: P, cache: &PathCache) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+    if let Some(canonical) = cache.map().get(path) {
+        return Ok(canonical.clone())
+    }
+    // The lock isn't held while canonicalizing, so other threads can use the
+    // cache meanwhile; at worst the same path is canonicalized twice.
+    let canonical = try!(fs_imp::canonicalize(path));
+    cache.map().insert(path.to_path_buf(), canonical.clone());
+    Ok(canonical)
+}
+
+/// Same as `canonicalize`, but writes the result into `buf` instead of
+/// allocating a new `PathBuf`.
+///
+/// The previous contents of `buf` are discarded, but its allocation is
+/// reused, which makes this suitable for canonicalizing many paths in a
+/// loop. On error the contents of `buf` are unspecified.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_canonicalize_into)]
+/// use std::fs;
+/// use std::path::PathBuf;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let mut buf = PathBuf::new();
+/// for name in &["a.txt", "b.txt"] {
+///     try!(fs::canonicalize_into(name, &mut buf));
+///     println!("{}", buf.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_canonicalize_into", reason = "recently added API",
+           issue = "0")]
+pub fn canonicalize_into<P: AsRef<Path>>(path: P, buf: &mut PathBuf) -> io::Result<()> {
+    fs_imp::canonicalize_into(path.as_ref(), buf)
+}
+
+/// Returns the canonical form of a path along with the number of symbolic
+/// links that had to be followed to get there.
+///
+/// Unlike `canonicalize` the resolution is done component by component in
+/// Rust rather than by the operating system, with every symlink encountered
+/// along the way, including ones inside the targets of other symlinks,
+/// counted once per time it is followed.
+///
+/// # Errors
+///
+/// In addition to the errors `canonicalize` can return, this fails if more
+/// than 40 symbolic links have to be followed, which usually indicates a
+/// loop.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(fs_resolve_with_stats)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let (path, hops) = try!(fs::resolve_with_stats("a.txt"));
+/// if hops > 5 {
+///     println!("{} is {} symlinks deep", path.display(), hops);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_resolve_with_stats", reason = "recently added API",
+           issue = "0")]
+pub fn resolve_with_stats<P: AsRef<Path>>(path: P) -> io::Result<(PathBuf, usize)> {
+    resolve_symlinks(path.as_ref())
+}
+
+// Resolves `path` the same way the OS would, returning the result along with
+// the number of symlinks followed.
+fn resolve_symlinks(path: &Path) -> io::Result<(PathBuf, usize)> {
+    const MAX_HOPS: usize = 40;
+
+    let mut resolved = if path.is_absolute() {
+        PathBuf::new()
+    } else {
+        try!(env::current_dir())
+    };
+    // Components still to be resolved, in reverse order.
+    let mut pending: Vec<PathBuf> = path.components().rev()
+                                        .map(|c| PathBuf::from(c.as_os_str()))
+                                        .collect();
+    let mut hops = 0;
+    while let Some(next) = pending.pop() {
+        match next.components().next() {
+            Some(Component::CurDir) | None => {}
+            Some(Component::ParentDir) => { resolved.pop(); }
+            Some(Component::Prefix(..)) |
+            Some(Component::RootDir) => resolved.push(&next),
+            Some(Component::Normal(name)) => {
+                let candidate = resolved.join(name);
+                if !try!(symlink_metadata(&candidate)).file_type().is_symlink() {
+                    resolved = candidate;
+                    continue
+                }
+                hops += 1;
+                if hops > MAX_HOPS {
+                    return Err(io::Error::new(io::ErrorKind::Other,
+                                              "too many levels of symbolic links"))
+                }
+                let target = try!(read_link(&candidate));
+                pending.extend(target.components().rev()
+                                     .map(|c| PathBuf::from(c.as_os_str())));
+            }
+        }
+    }
+    Ok((resolved, hops))
+}
+
+/// Creates a new, empty directory at the provided path
+///
+/// # Errors
+///
+/// This function will return an error if the user lacks permissions to make a
+/// new directory at the provided `path`, or if the directory already exists.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::create_dir("/some/dir"));
+/// # Ok(())
+/// # }
+/// ```
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn create_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    DirBuilder::new().create(path.as_ref())
+}
+
+/// Recursively create a directory and all of its parent components if they
+/// are missing.
+///
+/// # Errors
+///
+/// This function will fail if any directory in the path specified by `path`
+/// does not already exist and it could not be created otherwise. The specific
+/// error conditions for when a directory is being created (after it is
+/// determined to not exist) are outlined by `fs::create_dir`.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::create_dir_all("/some/dir"));
+/// # Ok(())
+/// # }
+/// ```
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    DirBuilder::new().recursive(true).create(path.as_ref())
+}
+
+/// Recursively create a directory and all of its parent components if they
+/// are missing, returning the directories that were actually created.
+///
+/// The returned paths are in the order they were created, so the last one is
+/// the deepest. Directories which already existed, including ones created by
+/// another process while this function was running, are not included. This
+/// makes it possible to undo the operation by removing the returned
+/// directories in reverse order.
+///
+/// # Errors
+///
+/// This function fails under the same conditions as `create_dir_all`. Any
+/// directories created before the failure are left in place.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(create_dir_all_tracked)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let created = try!(fs::create_dir_all_tracked("/some/dir"));
+/// for dir in created.iter().rev() {
+///     try!(fs::remove_dir(dir));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "create_dir_all_tracked", reason = "recently added API",
+           issue = "0")]
+pub fn create_dir_all_tracked<P: AsRef<Path>>(path: P) -> io::Result<Vec<PathBuf>> {
+    let mut created = Vec::new();
+    try!(DirBuilder::new().create_dir_all_tracked(path.as_ref(), &mut created));
+    Ok(created)
+}
+
+/// Removes an existing, empty directory.
+///
+/// # Errors
+///
+/// This function will return an error if the user lacks permissions to remove
+/// the directory at the provided `path`, or if the directory isn't empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::remove_dir("/some/dir"));
+/// # Ok(())
+/// # }
+/// ```
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn remove_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    fs_imp::rmdir(path.as_ref())
+}
+
+/// Removes an empty directory if it exists, returning whether there was one
+/// to remove.
+///
+/// This is `remove_dir` with a missing directory not counting as an error,
+/// in the same way as for `remove_file_if_exists`. A directory that isn't
+/// empty is still an error, and so is a path which names something other
+/// than a directory.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(fs_remove_if_exists)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::remove_dir_if_exists("/some/dir"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_remove_if_exists", reason = "recently added API",
+           issue = "0")]
+pub fn remove_dir_if_exists<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let path = path.as_ref();
+    match fs_imp::rmdir(path) {
+        Ok(()) => Ok(true),
+        Err(ref e) if fs_imp::is_dir_not_found(e) => Ok(false),
+        // On Unix, `rmdir` fails the same way for a path through a file as
+        // for the file itself, and only the former is missing.
+        Err(e) => {
+            if fs_imp::is_not_found(&e) {
+                if let Err(ref e) = fs_imp::lstat(path) {
+                    if fs_imp::is_not_found(e) {
+                        return Ok(false)
+                    }
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Removes a directory at this path, after removing all its contents. Use
+/// carefully!
+///
+/// This function does **not** follow symbolic links and it will simply remove the
+/// symbolic link itself.
+///
+/// Read-only files and directories in the tree are removed as well. On
+/// Windows this means clearing their read-only attribute first, like
+/// `force_remove_file` does; on Unix only the permissions of the containing
+/// directories matter anyway.
+///
+/// # Errors
+///
+/// See `file::remove_file` and `fs::remove_dir`.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// try!(fs::remove_dir_all("/some/dir"));
+/// # Ok(())
+/// # }
+/// ```
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    _remove_dir_all(path.as_ref())
+}
+
+fn _remove_dir_all(path: &Path) -> io::Result<()> {
+    for child in try!(read_dir(path)) {
+        let child = try!(child).path();
+        let stat = try!(symlink_metadata(&*child));
+        if stat.is_dir() {
+            try!(remove_dir_all(&*child));
+        } else {
+            try!(fs_imp::unlink_force(&*child));
+        }
+    }
+    fs_imp::rmdir_force(path)
+}
+
+/// Returns an iterator over the entries within a directory.
+///
+/// The iterator will yield instances of `io::Result<DirEntry>`. New errors may
+/// be encountered after an iterator is initially constructed.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use std::fs::{self, DirEntry};
+/// use std::path::Path;
+///
+/// // one possible implementation of fs::walk_dir only visiting files
+/// fn visit_dirs(dir: &Path, cb: &Fn(&DirEntry)) -> io::Result<()> {
+///     if try!(fs::metadata(dir)).is_dir() {
+///         for entry in try!(fs::read_dir(dir)) {
+///             let entry = try!(entry);
+///             if try!(fs::metadata(entry.path())).is_dir() {
+///                 try!(visit_dirs(&entry.path(), cb));
+///             } else {
+///                 cb(&entry);
+///             }
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This function will return an error if the provided `path` doesn't exist, if
+/// the process lacks permissions to view the contents or if the `path` points
+/// at a non-directory file
+#[stable(feature = "rust1", since = "1.0.0")]
+pub fn read_dir<P: AsRef<Path>>(path: P) -> io::Result<ReadDir> {
+    fs_imp::readdir(path.as_ref()).map(ReadDir)
+}
+
+/// Returns a rough estimate of the number of entries in the directory at
+/// `path`, if one is available without reading the directory.
+///
+/// This is a hint, primarily meant for showing progress while walking a
+/// directory; it's neither exact nor guaranteed to be stable, and `None`
+/// means nothing is known. Use `read_dir` for the actual entries.
+///
+/// # Platform-specific behavior
+///
+/// On Unix this is the directory's link count minus two, which on many
+/// file systems is the number of subdirectories it contains, a lower bound
+/// on the number of entries. File systems that don't maintain that count,
+/// like btrfs, yield `None`. On Windows this is always `None`.
+///
+/// # Errors
+///
+/// Returns an error if `path` doesn't exist or isn't a directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_dir_entry_count_hint)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// if let Some(n) = try!(fs::dir_entry_count_hint("/usr")) {
+///     println!("at least {} entries to go", n);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_dir_entry_count_hint", reason = "recently added API",
+           issue = "0")]
+pub fn dir_entry_count_hint<P: AsRef<Path>>(path: P) -> io::Result<Option<u64>> {
+    fs_imp::dir_entry_count_hint(path.as_ref())
+}
+
+/// Returns an iterator over the entries within a directory whose file names
+/// are accepted by `predicate`.
+///
+/// The predicate is only handed the bare file name of each entry, and it is
+/// consulted before a `DirEntry` is built, so rejected entries cost nothing
+/// beyond the underlying `readdir` call. On Windows the name still has to be
+/// converted from UTF-16 before it can be given to the predicate.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(read_dir_filtered)]
+/// use std::fs;
+/// use std::path::Path;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let sources = try!(fs::read_dir_filtered("src", |name| {
+///     Path::new(name).extension().map_or(false, |ext| ext == "rs")
+/// }));
+/// for entry in sources {
+///     println!("{:?}", try!(entry).path());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This function will return an error in the same situations as `read_dir`.
+#[unstable(feature = "read_dir_filtered", reason = "recently added API",
+           issue = "0")]
+pub fn read_dir_filtered<P, F>(path: P, predicate: F) -> io::Result<ReadDirFiltered<F>>
+    where P: AsRef<Path>, F: FnMut(&OsStr) -> bool
+{
+    let inner = try!(fs_imp::readdir(path.as_ref()));
+    Ok(ReadDirFiltered { inner: inner, predicate: predicate })
+}
+
+/// Returns an iterator over the entries within a directory, each paired with
+/// its metadata.
+///
+/// The metadata is the same as what `DirEntry::metadata` returns, i.e.
+/// symbolic links are not followed, but it is fetched more cheaply: on Linux
+/// with `fstatat` relative to the directory being read instead of with
+/// `lstat` on the entry's full path, and on Windows straight from the data
+/// returned by the directory listing.
+///
+/// Failing to get the metadata of an entry, e.g. because it was removed
+/// after it was listed, doesn't end the iteration. The error is returned
+/// alongside the entry instead.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(read_dir_with_metadata)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// for entry in try!(fs::read_dir_with_metadata(".")) {
+///     let (entry, metadata) = try!(entry);
+///     match metadata {
+///         Ok(m) => println!("{:?}: {} bytes", entry.path(), m.len()),
+///         Err(e) => println!("{:?}: {}", entry.path(), e),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This function will return an error in the same situations as `read_dir`.
+#[unstable(feature = "read_dir_with_metadata", reason = "recently added API",
+           issue = "0")]
+pub fn read_dir_with_metadata<P: AsRef<Path>>(path: P) -> io::Result<ReadDirWithMetadata> {
+    fs_imp::readdir(path.as_ref()).map(ReadDirWithMetadata)
+}
+
+/// Creates a file for writing like `File::create`, wrapped so that writes
+/// fail once it would grow past `cap` bytes.
+///
+/// A write that only partly fits writes as much as fits and returns the
+/// number of bytes written; any further write returns an error of kind
+/// `WriteZero`. This guards against a runaway writer filling the disk, not
+/// against other processes: nothing is reserved up front and the limit is
+/// only enforced within this process, on writes through the returned
+/// `CappedFile`. Seeking back below the limit allows writing again.
+///
+/// An existing file at `path` is truncated, as with `File::create`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_create_capped)]
+/// use std::fs;
+/// use std::io::Write;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let mut log = try!(fs::create_capped("debug.log", 100 * 1024 * 1024));
+/// try!(log.write_all(b"starting up\n"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_create_capped", reason = "recently added API", issue = "0")]
+pub fn create_capped<P: AsRef<Path>>(path: P, cap: u64) -> io::Result<CappedFile> {
+    File::create(path).map(|inner| CappedFile { inner: inner, cap: cap, pos: 0 })
+}
+
+/// Collects the entries of the directory at `path` and of its subdirectories
+/// up to `max_depth` levels down.
+///
+/// A `max_depth` of 0 returns just the entries of `path` itself, like
+/// collecting `read_dir` does, 1 adds the entries of its subdirectories, and
+/// so on. Entries come in some top-down order: a directory always comes
+/// before its contents. Symbolic links to directories are returned but not
+/// descended into.
+///
+/// Unlike `walk_dir`, this reads everything up front. Errors below `path`,
+/// like a subdirectory that can't be read, don't stop the listing; they are
+/// collected into the second vector returned and the affected entries are
+/// left out.
+///
+/// # Errors
+///
+/// This function will return an error in the same situations as `read_dir`
+/// does for `path` itself.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_read_dir_recursive)]
+/// use std::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let (entries, errors) = try!(fs::read_dir_recursive("src", 1));
+/// for entry in entries {
+///     println!("{}", entry.path().display());
+/// }
+/// for e in errors {
+///     println!("skipped: {}", e);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_read_dir_recursive", reason = "recently added API",
+           issue = "0")]
+pub fn read_dir_recursive<P: AsRef<Path>>(path: P, max_depth: usize)
+                                          -> io::Result<(Vec<DirEntry>, Vec<io::Error>)> {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut first = Some(try!(read_dir(path)));
+    // Subdirectories are only opened once they're popped, so that a wide
+    // tree doesn't hold a descriptor open for every directory still to go.
+    let mut pending = Vec::new();
+    loop {
+        let (dir, depth) = match first.take() {
+            Some(dir) => (dir, 0),
+            None => match pending.pop() {
+                Some((path, depth)) => match read_dir(&path) {
+                    Ok(dir) => (dir, depth),
+                    Err(e) => { errors.push(e); continue }
+                },
+                None => break,
+            },
+        };
+        for entry in dir {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => { errors.push(e); continue }
+            };
+            if depth < max_depth {
+                match entry.file_type() {
+                    Ok(ref ty) if ty.is_dir() => pending.push((entry.path(), depth + 1)),
+                    Ok(_) => {}
+                    Err(e) => { errors.push(e); continue }
+                }
+            }
+            entries.push(entry);
+        }
+    }
+    Ok((entries, errors))
+}
+
+/// Returns an iterator that will recursively walk the directory structure
+/// rooted at `path`.
+///
+/// The path given will not be iterated over, and this will perform iteration in
+/// some top-down order.  The contents of unreadable subdirectories are ignored.
+///
+/// The iterator will yield instances of `io::Result<DirEntry>`. New errors may
+/// be encountered after an iterator is initially constructed.
+///
+/// Symbolic links to directories are followed. A link to a directory that
+/// contains it is yielded as usual, but not descended into; an error of kind
+/// `FilesystemLoop` is yielded in its place.
+#[unstable(feature = "fs_walk",
+           reason = "the precise semantics and defaults for a recursive walk \
+                     may change and this may end up accounting for files such \
+                     as symlinks differently",
+           issue = "27707")]
+pub fn walk_dir<P: AsRef<Path>>(path: P) -> io::Result<WalkDir> {
+    _walk_dir(path.as_ref())
+}
+
+fn _walk_dir(path: &Path) -> io::Result<WalkDir> {
+    let start = try!(read_dir(path));
+    let id = try!(fs_imp::file_id(path));
+    Ok(WalkDir { cur: Some((start, vec![id])), stack: Vec::new() })
+}
+
+// Opens the directory `path` below the directories identified by
+// `ancestors`, refusing to go around in circles.
+fn walk_descend(path: &Path, ancestors: &[(u64, u64)])
+                -> io::Result<(ReadDir, Vec<(u64, u64)>)> {
+    let id = try!(fs_imp::file_id(path));
+    if ancestors.contains(&id) {
+        return Err(filesystem_loop())
+    }
+    let dir = try!(read_dir(path));
+    let mut ancestors = ancestors.to_vec();
+    ancestors.push(id);
+    Ok((dir, ancestors))
+}
+
+#[unstable(feature = "fs_walk", issue = "27707")]
+impl Iterator for WalkDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+        loop {
+            if let Some((ref mut cur, ref ancestors)) = self.cur {
+                match cur.next() {
+                    Some(Err(e)) => return Some(Err(e)),
+                    Some(Ok(next)) => {
+                        let path = next.path();
+                        if path.is_dir() {
+                            self.stack.push(walk_descend(&path, ancestors));
+                        }
+                        return Some(Ok(next))
+                    }
+                    None => {}
+                }
+            }
+            self.cur = None;
+            match self.stack.pop() {
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(next)) => self.cur = Some(next),
+                None => return None,
+            }
+        }
+    }
+}
+
+/// Utility methods for paths.
+#[unstable(feature = "path_ext",
+           reason = "The precise set of methods exposed on this trait may \
+                     change and some methods may be removed.  For stable code, \
+                     see the std::fs::metadata function.",
+           issue = "27725")]
+pub trait PathExt {
+    /// Gets information on the file, directory, etc at this path.
+    ///
+    /// Consult the `fs::metadata` documentation for more info.
+    ///
+    /// This call preserves identical runtime/error semantics with
+    /// `fs::metadata`.
+    fn metadata(&self) -> io::Result<Metadata>;
+
+    /// Gets information on the file, directory, etc at this path.
+    ///
+    /// Consult the `fs::symlink_metadata` documentation for more info.
+    ///
+    /// This call preserves identical runtime/error semantics with
+    /// `fs::symlink_metadata`.
+    fn symlink_metadata(&self) -> io::Result<Metadata>;
+
+    /// Returns the canonical form of a path, normalizing all components and
+    /// eliminate all symlinks.
+    ///
+    /// This call preserves identical runtime/error semantics with
+    /// `fs::canonicalize`.
+    fn canonicalize(&self) -> io::Result<PathBuf>;
+
+    /// Reads the symlink at this path.
+    ///
+    /// For more information see `fs::read_link`.
+    fn read_link(&self) -> io::Result<PathBuf>;
+
+    /// Reads the directory at this path.
+    ///
+    /// For more information see `fs::read_dir`.
+    fn read_dir(&self) -> io::Result<ReadDir>;
+
+    /// Boolean value indicator whether the underlying file exists on the local
+    /// filesystem. Returns false in exactly the cases where `fs::stat` fails.
+    fn exists(&self) -> bool;
+
+    /// Whether the underlying implementation (be it a file path, or something
+    /// else) points at a "regular file" on the FS. Will return false for paths
+    /// to non-existent locations or directories or other non-regular files
+    /// (named pipes, etc). Follows links when making this determination.
+    fn is_file(&self) -> bool;
+
+    /// Whether the underlying implementation (be it a file path, or something
+    /// else) is pointing at a directory in the underlying FS. Will return
+    /// false for paths to non-existent locations or if the item is not a
+    /// directory (eg files, named pipes, etc). Follows links when making this
+    /// determination.
+    fn is_dir(&self) -> bool;
+}
+
+impl PathExt for Path {
+    fn metadata(&self) -> io::Result<Metadata> { metadata(self) }
+    fn symlink_metadata(&self) -> io::Result<Metadata> { symlink_metadata(self) }
+    fn canonicalize(&self) -> io::Result<PathBuf> { canonicalize(self) }
+    fn read_link(&self) -> io::Result<PathBuf> { read_link(self) }
+    fn read_dir(&self) -> io::Result<ReadDir> { read_dir(self) }
+    fn exists(&self) -> bool { metadata(self).is_ok() }
+
+    fn is_file(&self) -> bool {
+        metadata(self).map(|s| s.is_file()).unwrap_or(false)
+    }
+
+    fn is_dir(&self) -> bool {
+        metadata(self).map(|s| s.is_dir()).unwrap_or(false)
+    }
+}
+
+/// Changes the permissions found on a file or a directory.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> std::io::Result<()> {
+/// use std::fs;
+///
+/// let mut perms = try!(fs::metadata("foo.txt")).permissions();
+/// perms.set_readonly(true);
+/// try!(fs::set_permissions("foo.txt", perms));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This function will return an error if the provided `path` doesn't exist, if
+/// the process lacks permissions to change the attributes of the file, or if
+/// some other I/O error is encountered.
+#[stable(feature = "set_permissions", since = "1.1.0")]
+pub fn set_permissions<P: AsRef<Path>>(path: P, perm: Permissions)
+                                       -> io::Result<()> {
+    fs_imp::set_perm(path.as_ref(), perm.0)
+}
+
+/// Changes the permissions of a symbolic link itself rather than those of the
+/// file it points to.
+///
+/// If `path` is not a symbolic link this behaves like `set_permissions`.
+///
+/// # Platform behavior
+///
+/// * On OS X, iOS, FreeBSD, DragonFly and NetBSD this calls `lchmod`.
+/// * On OpenBSD and Bitrig this calls `fchmodat` with `AT_SYMLINK_NOFOLLOW`.
+/// * On Linux and Android the permissions of a symlink are always `0o777`
+///   and are never consulted, so this always returns an "operation not
+///   supported" error (`EOPNOTSUPP`), even for paths which aren't symlinks.
+/// * On Windows the attributes of the reparse point itself are changed by
+///   opening it with `FILE_FLAG_OPEN_REPARSE_POINT`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(set_symlink_permissions)]
+/// # fn foo() -> std::io::Result<()> {
+/// use std::fs;
+///
+/// let perms = try!(fs::symlink_metadata("link")).permissions();
+/// try!(fs::set_symlink_permissions("restored-link", perms));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This function will return an error in the same situations as
+/// `set_permissions`, and additionally on platforms where a symlink's own
+/// permissions can't be changed.
+#[unstable(feature = "set_symlink_permissions", reason = "recently added API",
+           issue = "0")]
+pub fn set_symlink_permissions<P: AsRef<Path>>(path: P, perm: Permissions)
+                                               -> io::Result<()> {
+    fs_imp::set_perm_nofollow(path.as_ref(), perm.0)
+}
+
+#[unstable(feature = "dir_builder", reason = "recently added API",
+           issue = "27710")]
+impl DirBuilder {
+    /// Creates a new set of options with default mode/security settings for all
+    /// platforms and also non-recursive.
+    pub fn new() -> DirBuilder {
+        DirBuilder {
+            inner: fs_imp::DirBuilder::new(),
+            recursive: false,
+        }
+    }
+
+    /// Indicate that directories create should be created recursively, creating
+    /// all parent directories if they do not exist with the same security and
+    /// permissions settings.
+    ///
+    /// Parent directories that already exist keep their permissions.
+    ///
+    /// This option defaults to `false`
+    pub fn recursive(&mut self, recursive: bool) -> &mut Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Create the specified directory with the options configured in this
+    /// builder.
+    pub fn create<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self._create(path.as_ref())
+    }
+
+    fn _create(&self, path: &Path) -> io::Result<()> {
+        if self.recursive {
+            self.create_dir_all(path)
+        } else {
+            self.inner.mkdir(path)
+        }
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        if path == Path::new("") || path.is_dir() { return Ok(()) }
+        if let Some(p) = path.parent() {
+            try!(self.create_dir_all(p))
+        }
+        self.inner.mkdir(path)
+    }
+
+    fn create_dir_all_tracked(&self, path: &Path, created: &mut Vec<PathBuf>)
+                              -> io::Result<()> {
+        if path == Path::new("") || path.is_dir() { return Ok(()) }
+        if let Some(p) = path.parent() {
+            try!(self.create_dir_all_tracked(p, created))
+        }
+        match self.inner.mkdir(path) {
+            Ok(()) => {
+                created.push(path.to_path_buf());
+                Ok(())
+            }
+            // Somebody else beat us to it, so it's not ours to report.
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists &&
+                          path.is_dir() => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl AsInnerMut<fs_imp::DirBuilder> for DirBuilder {
+    fn as_inner_mut(&mut self) -> &mut fs_imp::DirBuilder {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(deprecated)] //rand
+
+    use prelude::v1::*;
+    use io::prelude::*;
+
+    use env;
+    use fs::{self, File, OpenOptions};
+    use io::{ErrorKind, SeekFrom};
+    use path::PathBuf;
+    use path::Path as Path2;
+    use os;
+    use rand::{self, StdRng, Rng};
+    use str;
+
+    macro_rules! check { ($e:expr) => (
+        match $e {
+            Ok(t) => t,
+            Err(e) => panic!("{} failed with: {}", stringify!($e), e),
+        }
+    ) }
+
+    macro_rules! error { ($e:expr, $s:expr) => (
+        match $e {
+            Ok(_) => panic!("Unexpected success. Should've been: {:?}", $s),
+            Err(ref err) => assert!(err.to_string().contains($s),
+                                    format!("`{}` did not contain `{}`", err, $s))
+        }
+    ) }
+
+    pub struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn join(&self, path: &str) -> PathBuf {
+            let TempDir(ref p) = *self;
+            p.join(path)
+        }
+
+        fn path<'a>(&'a self) -> &'a Path2 {
+            let TempDir(ref p) = *self;
+            p
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            // Gee, seeing how we're testing the fs module I sure hope that we
+            // at least implement this correctly!
+            let TempDir(ref p) = *self;
+            check!(fs::remove_dir_all(p));
+        }
+    }
+
+    pub fn tmpdir() -> TempDir {
+        let p = env::temp_dir();
+        let mut r = rand::thread_rng();
+        let ret = p.join(&format!("rust-{}", r.next_u32()));
+        check!(fs::create_dir(&ret));
+        TempDir(ret)
+    }
+
+    // Creating symlinks on Windows requires a privilege the tests may not
+    // have, in which case the test calling this is skipped.
+    fn symlink_created(ret: ::io::Result<()>) -> bool {
+        match ret {
+            Ok(()) => true,
+            Err(_) if cfg!(windows) => false,
+            Err(e) => panic!("failed to create a symlink: {}", e),
+        }
+    }
+
+    #[test]
+    fn file_test_io_smoke_test() {
+        let message = "it's alright. have a good time";
+        let tmpdir = tmpdir();
+        let filename = &tmpdir.join("file_rt_io_file_test.txt");
+        {
+            let mut write_stream = check!(File::create(filename));
+            check!(write_stream.write(message.as_bytes()));
+        }
+        {
+            let mut read_stream = check!(File::open(filename));
+            let mut read_buf = [0; 1028];
+            let read_str = match check!(read_stream.read(&mut read_buf)) {
+                0 => panic!("shouldn't happen"),
+                n => str::from_utf8(&read_buf[..n]).unwrap().to_string()
+            };
+            assert_eq!(read_str, message);
+        }
+        check!(fs::remove_file(filename));
+    }
+
+    #[test]
+    fn invalid_path_raises() {
+        let tmpdir = tmpdir();
+        let filename = &tmpdir.join("file_that_does_not_exist.txt");
+        let result = File::open(filename);
+
+        if cfg!(unix) {
+            error!(result, "o such file or directory");
+        }
+        // error!(result, "couldn't open path as file");
+        // error!(result, format!("path={}; mode=open; access=read", filename.display()));
+    }
+
+    #[test]
+    fn file_test_iounlinking_invalid_path_should_raise_condition() {
+        let tmpdir = tmpdir();
+        let filename = &tmpdir.join("file_another_file_that_does_not_exist.txt");
+
+        let result = fs::remove_file(filename);
+
+        if cfg!(unix) {
+            error!(result, "o such file or directory");
+        }
+        // error!(result, "couldn't unlink path");
+        // error!(result, format!("path={}", filename.display()));
+    }
+
+    #[test]
+    fn file_test_io_non_positional_read() {
+        let message: &str = "ten-four";
+        let mut read_mem = [0; 8];
+        let tmpdir = tmpdir();
+        let filename = &tmpdir.join("file_rt_io_file_test_positional.txt");
+        {
+            let mut rw_stream = check!(File::create(filename));
+            check!(rw_stream.write(message.as_bytes()));
+        }
+        {
+            let mut read_stream = check!(File::open(filename));
+            {
+                let read_buf = &mut read_mem[0..4];
+                check!(read_stream.read(read_buf));
+            }
+            {
+                let read_buf = &mut read_mem[4..8];
+                check!(read_stream.read(read_buf));
+            }
+        }
+        check!(fs::remove_file(filename));
+        let read_str = str::from_utf8(&read_mem).unwrap();
+        assert_eq!(read_str, message);
+    }
+
+    #[test]
+    fn file_test_io_seek_and_tell_smoke_test() {
+        let message = "ten-four";
+        let mut read_mem = [0; 4];
+        let set_cursor = 4 as u64;
+        let mut tell_pos_pre_read;
+        let mut tell_pos_post_read;
+        let tmpdir = tmpdir();
+        let filename = &tmpdir.join("file_rt_io_file_test_seeking.txt");
+        {
+            let mut rw_stream = check!(File::create(filename));
+            check!(rw_stream.write(message.as_bytes()));
+        }
+        {
+            let mut read_stream = check!(File::open(filename));
+            check!(read_stream.seek(SeekFrom::Start(set_cursor)));
+            tell_pos_pre_read = check!(read_stream.seek(SeekFrom::Current(0)));
+            check!(read_stream.read(&mut read_mem));
+            tell_pos_post_read = check!(read_stream.seek(SeekFrom::Current(0)));
+        }
+        check!(fs::remove_file(filename));
+        let read_str = str::from_utf8(&read_mem).unwrap();
+        assert_eq!(read_str, &message[4..8]);
+        assert_eq!(tell_pos_pre_read, set_cursor);
+        assert_eq!(tell_pos_post_read, message.len() as u64);
+    }
+
+    #[test]
+    fn file_test_io_seek_and_write() {
+        let initial_msg =   "food-is-yummy";
+        let overwrite_msg =    "-the-bar!!";
+        let final_msg =     "foo-the-bar!!";
+        let seek_idx = 3;
+        let mut read_mem = [0; 13];
+        let tmpdir = tmpdir();
+        let filename = &tmpdir.join("file_rt_io_file_test_seek_and_write.txt");
+        {
+            let mut rw_stream = check!(File::create(filename));
+            check!(rw_stream.write(initial_msg.as_bytes()));
+            check!(rw_stream.seek(SeekFrom::Start(seek_idx)));
+            check!(rw_stream.write(overwrite_msg.as_bytes()));
+        }
+        {
+            let mut read_stream = check!(File::open(filename));
+            check!(read_stream.read(&mut read_mem));
+        }
+        check!(fs::remove_file(filename));
+        let read_str = str::from_utf8(&read_mem).unwrap();
+        assert!(read_str == final_msg);
+    }
+
+    #[test]
+    fn file_test_io_seek_shakedown() {
+        //                   01234567890123
+        let initial_msg =   "qwer-asdf-zxcv";
+        let chunk_one: &str = "qwer";
+        let chunk_two: &str = "asdf";
+        let chunk_three: &str = "zxcv";
+        let mut read_mem = [0; 4];
+        let tmpdir = tmpdir();
+        let filename = &tmpdir.join("file_rt_io_file_test_seek_shakedown.txt");
+        {
+            let mut rw_stream = check!(File::create(filename));
+            check!(rw_stream.write(initial_msg.as_bytes()));
+        }
+        {
+            let mut read_stream = check!(File::open(filename));
+
+            check!(read_stream.seek(SeekFrom::End(-4)));
+            check!(read_stream.read(&mut read_mem));
+            assert_eq!(str::from_utf8(&read_mem).unwrap(), chunk_three);
+
+            check!(read_stream.seek(SeekFrom::Current(-9)));
+            check!(read_stream.read(&mut read_mem));
+            assert_eq!(str::from_utf8(&read_mem).unwrap(), chunk_two);
+
+            check!(read_stream.seek(SeekFrom::Start(0)));
+            check!(read_stream.read(&mut read_mem));
+            assert_eq!(str::from_utf8(&read_mem).unwrap(), chunk_one);
+        }
+        check!(fs::remove_file(filename));
+    }
+
+    #[test]
+    fn file_test_stat_is_correct_on_is_file() {
+        let tmpdir = tmpdir();
+        let filename = &tmpdir.join("file_stat_correct_on_is_file.txt");
+        {
+            let mut opts = OpenOptions::new();
+            let mut fs = check!(opts.read(true).write(true)
+                                    .create(true).open(filename));
+            let msg = "hw";
+            fs.write(msg.as_bytes()).unwrap();
+
+            let fstat_res = check!(fs.metadata());
+            assert!(fstat_res.is_file());
+        }
+        let stat_res_fn = check!(fs::metadata(filename));
+        assert!(stat_res_fn.is_file());
+        let stat_res_meth = check!(filename.metadata());
+        assert!(stat_res_meth.is_file());
+        check!(fs::remove_file(filename));
+    }
+
+    #[test]
+    fn file_test_stat_is_correct_on_is_dir() {
+        let tmpdir = tmpdir();
+        let filename = &tmpdir.join("file_stat_correct_on_is_dir");
+        check!(fs::create_dir(filename));
+        let stat_res_fn = check!(fs::metadata(filename));
+        assert!(stat_res_fn.is_dir());
+        let stat_res_meth = check!(filename.metadata());
+        assert!(stat_res_meth.is_dir());
+        check!(fs::remove_dir(filename));
+    }
+
+    #[test]
+    fn file_test_fileinfo_false_when_checking_is_file_on_a_directory() {
+        let tmpdir = tmpdir();
+        let dir = &tmpdir.join("fileinfo_false_on_dir");
+        check!(fs::create_dir(dir));
+        assert!(dir.is_file() == false);
+        check!(fs::remove_dir(dir));
+    }
+
+    #[test]
+    fn file_test_fileinfo_check_exists_before_and_after_file_creation() {
+        let tmpdir = tmpdir();
+        let file = &tmpdir.join("fileinfo_check_exists_b_and_a.txt");
+        check!(check!(File::create(file)).write(b"foo"));
+        assert!(file.exists());
+        check!(fs::remove_file(file));
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn file_test_directoryinfo_check_exists_before_and_after_mkdir() {
+        let tmpdir = tmpdir();
+        let dir = &tmpdir.join("before_and_after_dir");
+        assert!(!dir.exists());
+        check!(fs::create_dir(dir));
+        assert!(dir.exists());
+        assert!(dir.is_dir());
+        check!(fs::remove_dir(dir));
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn file_test_directoryinfo_readdir() {
+        let tmpdir = tmpdir();
+        let dir = &tmpdir.join("di_readdir");
+        check!(fs::create_dir(dir));
+        let prefix = "foo";
+        for n in 0..3 {
+            let f = dir.join(&format!("{}.txt", n));
+            let mut w = check!(File::create(&f));
+            let msg_str = format!("{}{}", prefix, n.to_string());
+            let msg = msg_str.as_bytes();
+            check!(w.write(msg));
+        }
+        let files = check!(fs::read_dir(dir));
+        let mut mem = [0; 4];
+        for f in files {
+            let f = f.unwrap().path();
+            {
+                let n = f.file_stem().unwrap();
+                check!(check!(File::open(&f)).read(&mut mem));
+                let read_str = str::from_utf8(&mem).unwrap();
+                let expected = format!("{}{}", prefix, n.to_str().unwrap());
+                assert_eq!(expected, read_str);
+            }
+            check!(fs::remove_file(&f));
+        }
+        check!(fs::remove_dir(dir));
+    }
+
+    #[test]
+    fn file_test_walk_dir() {
+        let tmpdir = tmpdir();
+        let dir = &tmpdir.join("walk_dir");
+        check!(fs::create_dir(dir));
+
+        let dir1 = &dir.join("01/02/03");
+        check!(fs::create_dir_all(dir1));
+        check!(File::create(&dir1.join("04")));
+
+        let dir2 = &dir.join("11/12/13");
+        check!(fs::create_dir_all(dir2));
+        check!(File::create(&dir2.join("14")));
+
+        let files = check!(fs::walk_dir(dir));
+        let mut cur = [0; 2];
+        for f in files {
+            let f = f.unwrap().path();
+            let stem = f.file_stem().unwrap().to_str().unwrap();
+            let root = stem.as_bytes()[0] - b'0';
+            let name = stem.as_bytes()[1] - b'0';
+            assert!(cur[root as usize] < name);
+            cur[root as usize] = name;
+        }
+
+        check!(fs::remove_dir_all(dir));
+    }
+
+    #[test]
+    fn mkdir_path_already_exists_error() {
+        let tmpdir = tmpdir();
+        let dir = &tmpdir.join("mkdir_error_twice");
+        check!(fs::create_dir(dir));
+        let e = fs::create_dir(dir).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn recursive_mkdir() {
+        let tmpdir = tmpdir();
+        let dir = tmpdir.join("d1/d2");
+        check!(fs::create_dir_all(&dir));
+        assert!(dir.is_dir())
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn metadata_by_handle_fields() {
+        use os::windows::fs::MetadataExt;
+
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let f = check!(File::create(&path));
+        check!(fs::hard_link(&path, &tmpdir.join("link")));
+
+        let m = check!(f.metadata());
+        assert_eq!(m.number_of_links(), Some(2));
+        assert!(m.volume_serial_number().is_some());
+        assert!(m.file_index().is_some());
+        let other = check!(check!(File::open(&tmpdir.join("link"))).metadata());
+        assert_eq!(m.file_index(), other.file_index());
+
+        assert_eq!(check!(fs::symlink_metadata(&path)).file_index(), None);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn recursive_mkdir_security_attributes() {
+        use libc;
+        use mem;
+        use os::windows::fs::DirBuilderExt;
+
+        let tmpdir = tmpdir();
+        let dir = tmpdir.join("d1/d2");
+        let mut attrs = libc::SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<libc::SECURITY_ATTRIBUTES>() as libc::DWORD,
+            lpSecurityDescriptor: 0 as *mut _,
+            bInheritHandle: libc::TRUE,
+        };
+        let mut builder = fs::DirBuilder::new();
+        // `attrs` outlives the builder.
+        unsafe {
+            builder.security_attributes(&mut attrs as *mut _ as *mut _);
+        }
+        check!(builder.recursive(true).create(&dir));
+        assert!(dir.is_dir());
+    }
+
+    #[test]
+    fn recursive_mkdir_tracked() {
+        let tmpdir = tmpdir();
+        check!(fs::create_dir(&tmpdir.join("d1")));
+        let dir = tmpdir.join("d1/d2/d3");
+
+        let created = check!(fs::create_dir_all_tracked(&dir));
+        assert_eq!(created, [tmpdir.join("d1/d2"), dir.clone()]);
+        assert!(dir.is_dir());
+
+        assert!(check!(fs::create_dir_all_tracked(&dir)).is_empty());
+    }
+
+    #[test]
+    fn recursive_mkdir_failure() {
+        let tmpdir = tmpdir();
+        let dir = tmpdir.join("d1");
+        let file = dir.join("f1");
+
+        check!(fs::create_dir_all(&dir));
+        check!(File::create(&file));
+
+        let result = fs::create_dir_all(&file);
+
+        assert!(result.is_err());
+        // error!(result, "couldn't recursively mkdir");
+        // error!(result, "couldn't create directory");
+        // error!(result, "mode=0700");
+        // error!(result, format!("path={}", file.display()));
+    }
+
+    #[test]
+    fn recursive_mkdir_slash() {
+        check!(fs::create_dir_all(&Path2::new("/")));
+    }
+
+    // FIXME(#12795) depends on lstat to work on windows
+    #[cfg(not(windows))]
+    #[test]
+    fn recursive_rmdir() {
+        let tmpdir = tmpdir();
+        let d1 = tmpdir.join("d1");
+        let dt = d1.join("t");
+        let dtt = dt.join("t");
+        let d2 = tmpdir.join("d2");
+        let canary = d2.join("do_not_delete");
+        check!(fs::create_dir_all(&dtt));
+        check!(fs::create_dir_all(&d2));
+        check!(check!(File::create(&canary)).write(b"foo"));
+        check!(fs::soft_link(&d2, &dt.join("d2")));
+        check!(fs::remove_dir_all(&d1));
+
+        assert!(!d1.is_dir());
+        assert!(canary.exists());
+    }
+
+    #[test]
+    fn unicode_path_is_dir() {
+        assert!(Path2::new(".").is_dir());
+        assert!(!Path2::new("test/stdtest/fs.rs").is_dir());
+
+        let tmpdir = tmpdir();
+
+        let mut dirpath = tmpdir.path().to_path_buf();
+        dirpath.push(&format!("test-가一ー你好"));
+        check!(fs::create_dir(&dirpath));
+        assert!(dirpath.is_dir());
+
+        let mut filepath = dirpath;
+        filepath.push("unicode-file-\u{ac00}\u{4e00}\u{30fc}\u{4f60}\u{597d}.rs");
+        check!(File::create(&filepath)); // ignore return; touch only
+        assert!(!filepath.is_dir());
+        assert!(filepath.exists());
+    }
+
+    #[test]
+    fn unicode_path_exists() {
+        assert!(Path2::new(".").exists());
+        assert!(!Path2::new("test/nonexistent-bogus-path").exists());
+
+        let tmpdir = tmpdir();
+        let unicode = tmpdir.path();
+        let unicode = unicode.join(&format!("test-각丁ー再见"));
+        check!(fs::create_dir(&unicode));
+        assert!(unicode.exists());
+        assert!(!Path2::new("test/unicode-bogus-path-각丁ー再见").exists());
+    }
+
+    #[test]
+    fn copy_file_does_not_exist() {
+        let from = Path2::new("test/nonexistent-bogus-path");
+        let to = Path2::new("test/other-bogus-path");
+
+        match fs::copy(&from, &to) {
+            Ok(..) => panic!(),
+            Err(..) => {
+                assert!(!from.exists());
+                assert!(!to.exists());
+            }
+        }
+    }
+
+    #[test]
+    fn copy_src_does_not_exist() {
+        let tmpdir = tmpdir();
+        let from = Path2::new("test/nonexistent-bogus-path");
+        let to = tmpdir.join("out.txt");
+        check!(check!(File::create(&to)).write(b"hello"));
+        assert!(fs::copy(&from, &to).is_err());
+        assert!(!from.exists());
+        let mut v = Vec::new();
+        check!(check!(File::open(&to)).read_to_end(&mut v));
+        assert_eq!(v, b"hello");
+    }
+
+    #[test]
+    fn copy_file_ok() {
+        let tmpdir = tmpdir();
+        let input = tmpdir.join("in.txt");
+        let out = tmpdir.join("out.txt");
+
+        check!(check!(File::create(&input)).write(b"hello"));
+        check!(fs::copy(&input, &out));
+        let mut v = Vec::new();
+        check!(check!(File::open(&out)).read_to_end(&mut v));
+        assert_eq!(v, b"hello");
+
+        assert_eq!(check!(input.metadata()).permissions(),
+                   check!(out.metadata()).permissions());
+    }
+
+    #[test]
+    fn copy_file_dst_dir() {
+        let tmpdir = tmpdir();
+        let out = tmpdir.join("out");
+
+        check!(File::create(&out));
+        match fs::copy(&*out, tmpdir.path()) {
+            Ok(..) => panic!(), Err(..) => {}
+        }
+    }
+
+    #[test]
+    fn copy_file_dst_exists() {
+        let tmpdir = tmpdir();
+        let input = tmpdir.join("in");
+        let output = tmpdir.join("out");
+
+        check!(check!(File::create(&input)).write("foo".as_bytes()));
+        check!(check!(File::create(&output)).write("bar".as_bytes()));
+        check!(fs::copy(&input, &output));
+
+        let mut v = Vec::new();
+        check!(check!(File::open(&output)).read_to_end(&mut v));
+        assert_eq!(v, b"foo".to_vec());
+    }
+
+    #[test]
+    fn copy_file_src_dir() {
+        let tmpdir = tmpdir();
+        let out = tmpdir.join("out");
+
+        match fs::copy(tmpdir.path(), &out) {
+            Ok(..) => panic!(), Err(..) => {}
+        }
+        assert!(!out.exists());
+    }
+
+    #[test]
+    fn copy_file_preserves_perm_bits() {
+        let tmpdir = tmpdir();
+        let input = tmpdir.join("in.txt");
+        let out = tmpdir.join("out.txt");
+
+        let attr = check!(check!(File::create(&input)).metadata());
+        let mut p = attr.permissions();
+        p.set_readonly(true);
+        check!(fs::set_permissions(&input, p));
+        check!(fs::copy(&input, &out));
+        assert!(check!(out.metadata()).permissions().readonly());
+        check!(fs::set_permissions(&input, attr.permissions()));
+        check!(fs::set_permissions(&out, attr.permissions()));
+    }
+
+    #[test]
+    fn copy_file_large_exact_len() {
+        let tmpdir = tmpdir();
+        let input = tmpdir.join("in");
+        let output = tmpdir.join("out");
+
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| i as u8).collect();
+        check!(check!(File::create(&input)).write_all(&data));
+        assert_eq!(check!(fs::copy(&input, &output)), data.len() as u64);
+        assert_eq!(check!(output.metadata()).len(), data.len() as u64);
+
+        let mut v = Vec::new();
+        check!(check!(File::open(&output)).read_to_end(&mut v));
+        assert!(v == data);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn copy_file_preserves_streams() {
+        let tmp = tmpdir();
+        check!(check!(File::create(tmp.join("in.txt:bunny"))).write("carrot".as_bytes()));
+        assert_eq!(check!(fs::copy(tmp.join("in.txt"), tmp.join("out.txt"))), 6);
+        assert_eq!(check!(tmp.join("out.txt").metadata()).len(), 0);
+        let mut v = Vec::new();
+        check!(check!(File::open(tmp.join("out.txt:bunny"))).read_to_end(&mut v));
+        assert_eq!(v, b"carrot".to_vec());
+    }
+
+    #[cfg(not(windows))] // FIXME(#10264) operation not permitted?
+    #[test]
+    fn symlinks_work() {
+        let tmpdir = tmpdir();
+        let input = tmpdir.join("in.txt");
+        let out = tmpdir.join("out.txt");
+
+        check!(check!(File::create(&input)).write("foobar".as_bytes()));
+        check!(fs::soft_link(&input, &out));
+        // if cfg!(not(windows)) {
+        //     assert_eq!(check!(lstat(&out)).kind, FileType::Symlink);
+        //     assert_eq!(check!(out.lstat()).kind, FileType::Symlink);
+        // }
+        assert_eq!(check!(fs::metadata(&out)).len(),
+                   check!(fs::metadata(&input)).len());
+        let mut v = Vec::new();
+        check!(check!(File::open(&out)).read_to_end(&mut v));
+        assert_eq!(v, b"foobar".to_vec());
+    }
+
+    #[cfg(not(windows))] // apparently windows doesn't like symlinks
+    #[test]
+    fn symlink_noexist() {
+        let tmpdir = tmpdir();
+        // symlinks can point to things that don't exist
+        check!(fs::soft_link(&tmpdir.join("foo"), &tmpdir.join("bar")));
+        assert_eq!(check!(fs::read_link(&tmpdir.join("bar"))),
+                   tmpdir.join("foo"));
+    }
+
+    #[test]
+    fn readlink_not_symlink() {
+        let tmpdir = tmpdir();
+        match fs::read_link(tmpdir.path()) {
+            Ok(..) => panic!("wanted a failure"),
+            Err(..) => {}
+        }
+    }
+
+    #[test]
+    fn links_work() {
+        let tmpdir = tmpdir();
+        let input = tmpdir.join("in.txt");
+        let out = tmpdir.join("out.txt");
+
+        check!(check!(File::create(&input)).write("foobar".as_bytes()));
+        check!(fs::hard_link(&input, &out));
+        assert_eq!(check!(fs::metadata(&out)).len(),
+                   check!(fs::metadata(&input)).len());
+        assert_eq!(check!(fs::metadata(&out)).len(),
+                   check!(input.metadata()).len());
+        let mut v = Vec::new();
+        check!(check!(File::open(&out)).read_to_end(&mut v));
+        assert_eq!(v, b"foobar".to_vec());
+
+        // can't link to yourself
+        match fs::hard_link(&input, &input) {
+            Ok(..) => panic!("wanted a failure"),
+            Err(..) => {}
+        }
+        // can't link to something that doesn't exist
+        match fs::hard_link(&tmpdir.join("foo"), &tmpdir.join("bar")) {
+            Ok(..) => panic!("wanted a failure"),
+            Err(..) => {}
+        }
+    }
+
+    #[test]
+    fn chmod_works() {
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("in.txt");
+
+        check!(File::create(&file));
+        let attr = check!(fs::metadata(&file));
+        assert!(!attr.permissions().readonly());
+        let mut p = attr.permissions();
+        p.set_readonly(true);
+        check!(fs::set_permissions(&file, p.clone()));
+        let attr = check!(fs::metadata(&file));
+        assert!(attr.permissions().readonly());
+
+        match fs::set_permissions(&tmpdir.join("foo"), p.clone()) {
+            Ok(..) => panic!("wanted an error"),
+            Err(..) => {}
+        }
+
+        p.set_readonly(false);
+        check!(fs::set_permissions(&file, p));
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_symlink_permissions_unsupported() {
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("in.txt");
+        check!(File::create(&file));
+        let p = check!(fs::metadata(&file)).permissions();
+        let err = fs::set_symlink_permissions(&file, p).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(::libc::EOPNOTSUPP));
+    }
+
+    #[test]
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    fn set_symlink_permissions_leaves_target_alone() {
+        use os::unix::fs::{symlink, PermissionsExt};
+
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("in.txt");
+        let link = tmpdir.join("link");
+        check!(File::create(&file));
+        check!(symlink(&file, &link));
+        check!(fs::set_permissions(&file, fs::Permissions::from_mode(0o644)));
+        check!(fs::set_symlink_permissions(&link, fs::Permissions::from_mode(0o700)));
+
+        assert_eq!(check!(fs::metadata(&file)).permissions().mode() & 0o777, 0o644);
+        assert_eq!(check!(fs::symlink_metadata(&link)).permissions().mode() & 0o777,
+                   0o700);
+    }
+
+    #[test]
+    fn sync_doesnt_kill_anything() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("in.txt");
+
+        let mut file = check!(File::create(&path));
+        check!(file.sync_all());
+        check!(file.sync_data());
+        check!(file.write(b"foo"));
+        check!(file.sync_all());
+        check!(file.sync_data());
+    }
+
+    #[test]
+    fn truncate_works() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("in.txt");
+
+        let mut file = check!(File::create(&path));
+        check!(file.write(b"foo"));
+        check!(file.sync_all());
+
+        // Do some simple things with truncation
+        assert_eq!(check!(file.metadata()).len(), 3);
+        check!(file.set_len(10));
+        assert_eq!(check!(file.metadata()).len(), 10);
+        check!(file.write(b"bar"));
+        check!(file.sync_all());
+        assert_eq!(check!(file.metadata()).len(), 10);
+
+        let mut v = Vec::new();
+        check!(check!(File::open(&path)).read_to_end(&mut v));
+        assert_eq!(v, b"foobar\0\0\0\0".to_vec());
+
+        // Truncate to a smaller length, don't seek, and then write something.
+        // Ensure that the intermediate zeroes are all filled in (we have `seek`ed
+        // past the end of the file).
+        check!(file.set_len(2));
+        assert_eq!(check!(file.metadata()).len(), 2);
+        check!(file.write(b"wut"));
+        check!(file.sync_all());
+        assert_eq!(check!(file.metadata()).len(), 9);
+        let mut v = Vec::new();
+        check!(check!(File::open(&path)).read_to_end(&mut v));
+        assert_eq!(v, b"fo\0\0\0\0wut".to_vec());
+    }
+
+    #[test]
+    fn open_flavors() {
+        use fs::OpenOptions as OO;
+        fn c<T: Clone>(t: &T) -> T { t.clone() }
+
+        let tmpdir = tmpdir();
+
+        let mut r = OO::new(); r.read(true);
+        let mut w = OO::new(); w.write(true);
+        let mut rw = OO::new(); rw.write(true).read(true);
+
+        match r.open(&tmpdir.join("a")) {
+            Ok(..) => panic!(), Err(..) => {}
+        }
+
+        // Perform each one twice to make sure that it succeeds the second time
+        // (where the file exists)
+        check!(c(&w).create(true).open(&tmpdir.join("b")));
+        assert!(tmpdir.join("b").exists());
+        check!(c(&w).create(true).open(&tmpdir.join("b")));
+        check!(w.open(&tmpdir.join("b")));
+
+        check!(c(&rw).create(true).open(&tmpdir.join("c")));
+        assert!(tmpdir.join("c").exists());
+        check!(c(&rw).create(true).open(&tmpdir.join("c")));
+        check!(rw.open(&tmpdir.join("c")));
+
+        check!(c(&w).append(true).create(true).open(&tmpdir.join("d")));
+        assert!(tmpdir.join("d").exists());
+        check!(c(&w).append(true).create(true).open(&tmpdir.join("d")));
+        check!(c(&w).append(true).open(&tmpdir.join("d")));
+
+        check!(c(&rw).append(true).create(true).open(&tmpdir.join("e")));
+        assert!(tmpdir.join("e").exists());
+        check!(c(&rw).append(true).create(true).open(&tmpdir.join("e")));
+        check!(c(&rw).append(true).open(&tmpdir.join("e")));
+
+        check!(c(&w).truncate(true).create(true).open(&tmpdir.join("f")));
+        assert!(tmpdir.join("f").exists());
+        check!(c(&w).truncate(true).create(true).open(&tmpdir.join("f")));
+        check!(c(&w).truncate(true).open(&tmpdir.join("f")));
+
+        check!(c(&rw).truncate(true).create(true).open(&tmpdir.join("g")));
+        assert!(tmpdir.join("g").exists());
+        check!(c(&rw).truncate(true).create(true).open(&tmpdir.join("g")));
+        check!(c(&rw).truncate(true).open(&tmpdir.join("g")));
+
+        check!(check!(File::create(&tmpdir.join("h"))).write("foo".as_bytes()));
+        check!(r.open(&tmpdir.join("h")));
+        {
+            let mut f = check!(r.open(&tmpdir.join("h")));
+            assert!(f.write("wut".as_bytes()).is_err());
+        }
+        assert_eq!(check!(fs::metadata(&tmpdir.join("h"))).len(), 3);
+        {
+            let mut f = check!(c(&w).append(true).open(&tmpdir.join("h")));
+            check!(f.write("bar".as_bytes()));
+        }
+        assert_eq!(check!(fs::metadata(&tmpdir.join("h"))).len(), 6);
+        {
+            let mut f = check!(c(&w).truncate(true).open(&tmpdir.join("h")));
+            check!(f.write("bar".as_bytes()));
+        }
+        assert_eq!(check!(fs::metadata(&tmpdir.join("h"))).len(), 3);
+    }
+
+    #[test]
+    fn binary_file() {
+        let mut bytes = [0; 1024];
+        StdRng::new().unwrap().fill_bytes(&mut bytes);
+
+        let tmpdir = tmpdir();
+
+        check!(check!(File::create(&tmpdir.join("test"))).write(&bytes));
+        let mut v = Vec::new();
+        check!(check!(File::open(&tmpdir.join("test"))).read_to_end(&mut v));
+        assert!(v == &bytes[..]);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn unlink_readonly() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        check!(File::create(&path));
+        let mut perm = check!(fs::metadata(&path)).permissions();
+        perm.set_readonly(true);
+        check!(fs::set_permissions(&path, perm));
+        check!(fs::remove_file(&path));
+    }
+
+    #[test]
+    fn mkdir_trailing_slash() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        check!(fs::create_dir_all(&path.join("a/")));
+    }
+
+    #[test]
+    fn canonicalize_works_simple() {
+        let tmpdir = tmpdir();
+        let tmpdir = fs::canonicalize(tmpdir.path()).unwrap();
+        let file = tmpdir.join("test");
+        File::create(&file).unwrap();
+        assert_eq!(fs::canonicalize(&file).unwrap(), file);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn metadata_is_symlink() {
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("file");
+        let link = tmpdir.join("link");
+        check!(File::create(&file));
+        check!(fs::soft_link(&file, &link));
+
+        assert!(!check!(fs::metadata(&file)).is_symlink());
+        assert!(!check!(fs::metadata(&link)).is_symlink());
+        assert!(check!(fs::metadata(&link)).is_file());
+        assert!(check!(fs::symlink_metadata(&link)).is_symlink());
+        assert!(!check!(fs::symlink_metadata(&link)).is_file());
+    }
+
+    #[test]
+    fn read_to_string_strips_bom() {
+        let tmpdir = tmpdir();
+        let plain = tmpdir.join("plain");
+        let bom = tmpdir.join("bom");
+        let bad = tmpdir.join("bad");
+        check!(check!(File::create(&plain)).write(b"hello"));
+        check!(check!(File::create(&bom)).write(b"\xEF\xBB\xBFhello"));
+        check!(check!(File::create(&bad)).write(b"\xEF\xBBhello"));
+
+        assert_eq!(check!(fs::read_to_string(&plain)), "hello");
+        assert_eq!(check!(fs::read_to_string(&bom)), "hello");
+        assert_eq!(check!(fs::read_to_string_opts(&bom, false)), "\u{feff}hello");
+        match fs::read_to_string(&bad) {
+            Ok(..) => panic!("invalid UTF-8 was accepted"),
+            Err(e) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+        }
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn canonicalize_normalizes_separators_and_drive() {
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("test");
+        check!(File::create(&file));
+
+        let canonical = check!(fs::canonicalize(&file));
+        let s = canonical.to_str().unwrap();
+        assert!(!s.starts_with(r"\\?\"), "{}", s);
+        assert!(!s.contains('/'), "{}", s);
+        let drive = s.as_bytes()[0];
+        assert!(drive >= b'A' && drive <= b'Z' && s.as_bytes()[1] == b':', "{}", s);
+
+        let mut mangled = canonical.to_str().unwrap().replace('\\', "/");
+        mangled = mangled[..1].to_lowercase() + &mangled[1..];
+        assert_eq!(check!(fs::canonicalize(&mangled)), canonical);
+        let verbatim = format!(r"\\?\{}", s);
+        assert_eq!(check!(fs::canonicalize(&verbatim)), canonical);
+    }
+
+    #[test]
+    fn canonicalize_into_reuses_buf() {
+        let tmpdir = tmpdir();
+        let tmpdir = fs::canonicalize(tmpdir.path()).unwrap();
+        let a = tmpdir.join("a");
+        let b = tmpdir.join("a-much-longer-file-name");
+        File::create(&a).unwrap();
+        File::create(&b).unwrap();
+
+        let mut buf = PathBuf::from("garbage");
+        check!(fs::canonicalize_into(&b, &mut buf));
+        assert_eq!(buf, fs::canonicalize(&b).unwrap());
+        check!(fs::canonicalize_into(&a, &mut buf));
+        assert_eq!(buf, fs::canonicalize(&a).unwrap());
+        assert!(fs::canonicalize_into(&tmpdir.join("missing"), &mut buf).is_err());
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn realpath_works() {
+        let tmpdir = tmpdir();
+        let tmpdir = fs::canonicalize(tmpdir.path()).unwrap();
+        let file = tmpdir.join("test");
+        let dir = tmpdir.join("test2");
+        let link = dir.join("link");
+        let linkdir = tmpdir.join("test3");
+
+        File::create(&file).unwrap();
+        fs::create_dir(&dir).unwrap();
+        fs::soft_link(&file, &link).unwrap();
+        fs::soft_link(&dir, &linkdir).unwrap();
+
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+
+        assert_eq!(fs::canonicalize(&tmpdir).unwrap(), tmpdir);
+        assert_eq!(fs::canonicalize(&file).unwrap(), file);
+        assert_eq!(fs::canonicalize(&link).unwrap(), file);
+        assert_eq!(fs::canonicalize(&linkdir).unwrap(), dir);
+        assert_eq!(fs::canonicalize(&linkdir.join("link")).unwrap(), file);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn realpath_works_tricky() {
+        let tmpdir = tmpdir();
+        let tmpdir = fs::canonicalize(tmpdir.path()).unwrap();
+
+        let a = tmpdir.join("a");
+        let b = a.join("b");
+        let c = b.join("c");
+        let d = a.join("d");
+        let e = d.join("e");
+        let f = a.join("f");
+
+        fs::create_dir_all(&b).unwrap();
+        fs::create_dir_all(&d).unwrap();
+        File::create(&f).unwrap();
+        fs::soft_link("../d/e", &c).unwrap();
+        fs::soft_link("../f", &e).unwrap();
+
+        assert_eq!(fs::canonicalize(&c).unwrap(), f);
+        assert_eq!(fs::canonicalize(&e).unwrap(), f);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn canonicalize_by_fd_works() {
+        use os::unix::fs::{canonicalize_by_fd, symlink};
+
+        let tmpdir = tmpdir();
+        let tmpdir = fs::canonicalize(tmpdir.path()).unwrap();
+        let file = tmpdir.join("test");
+        let dir = tmpdir.join("test2");
+        let link = dir.join("link");
+
+        File::create(&file).unwrap();
+        fs::create_dir(&dir).unwrap();
+        symlink(&file, &link).unwrap();
+
+        assert_eq!(canonicalize_by_fd(&tmpdir).unwrap(), tmpdir);
+        assert_eq!(canonicalize_by_fd(&link).unwrap(), file);
+        assert_eq!(canonicalize_by_fd(&dir.join("../test2/link")).unwrap(), file);
+        assert!(canonicalize_by_fd(&tmpdir.join("missing")).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn dir_resolve_beneath_stays_inside() {
+        use io::ErrorKind;
+        use os::unix::fs::{Dir, symlink};
+
+        let tmpdir = tmpdir();
+        let root = tmpdir.join("root");
+        check!(fs::create_dir_all(&root.join("a/b")));
+        check!(check!(File::create(&root.join("a/b/c"))).write(b"inside"));
+        check!(File::create(&tmpdir.join("outside")));
+        check!(symlink(&tmpdir.join("outside"), &root.join("a/link")));
+        check!(symlink(&tmpdir.join("root/a"), &root.join("dirlink")));
+
+        let dir = check!(Dir::open_root(&root));
+        let mut opts = OpenOptions::new();
+        opts.read(true);
+
+        let mut s = String::new();
+        check!(check!(dir.resolve_beneath("a/./b/c", &opts)).read_to_string(&mut s));
+        assert_eq!(s, "inside");
+        check!(dir.resolve_dir_beneath("a/b"));
+
+        let e = dir.resolve_beneath("a/../a/b/c", &opts).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+        let e = dir.resolve_beneath(&tmpdir.join("outside"), &opts).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+        assert!(dir.resolve_beneath("a/link", &opts).is_err());
+        assert!(dir.resolve_beneath("dirlink/b/c", &opts).is_err());
+        assert!(Dir::open_root(&root.join("dirlink")).is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn dir_openat2_beneath() {
+        use libc;
+        use os::linux::fs::{DirExt, RESOLVE_BENEATH};
+        use os::unix::fs::Dir;
+
+        let tmpdir = tmpdir();
+        check!(fs::create_dir(&tmpdir.join("sub")));
+        check!(File::create(&tmpdir.join("sub/file")));
+        check!(File::create(&tmpdir.join("outside")));
+
+        let dir = check!(Dir::open(&tmpdir.join("sub")));
+        let mut opts = OpenOptions::new();
+        opts.read(true);
+        match dir.openat2("file", &opts, RESOLVE_BENEATH) {
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOSYS) => return,
+            r => { check!(r); }
+        }
+        let e = dir.openat2("../outside", &opts, RESOLVE_BENEATH).unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(libc::EXDEV));
+
+        let mut opts = OpenOptions::new();
+        opts.write(true).create(true);
+        check!(dir.openat2("new", &opts, RESOLVE_BENEATH));
+        assert!(tmpdir.join("sub/new").exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn resolve_with_stats_counts_hops() {
+        use os::unix::fs::symlink;
+
+        let tmpdir = tmpdir();
+        let tmpdir = fs::canonicalize(tmpdir.path()).unwrap();
+        let file = tmpdir.join("file");
+        check!(File::create(&file));
+        check!(fs::create_dir(&tmpdir.join("dir")));
+        check!(symlink(&file, &tmpdir.join("one")));
+        check!(symlink("one", &tmpdir.join("two")));
+        check!(symlink("../two", &tmpdir.join("dir/three")));
+        check!(symlink("loop", &tmpdir.join("loop")));
+
+        assert_eq!(check!(fs::resolve_with_stats(&file)), (file.clone(), 0));
+        assert_eq!(check!(fs::resolve_with_stats(&tmpdir.join("one"))),
+                   (file.clone(), 1));
+        assert_eq!(check!(fs::resolve_with_stats(&tmpdir.join("dir/three"))),
+                   (file.clone(), 3));
+        assert_eq!(check!(fs::resolve_with_stats(&tmpdir.join("dir/../two"))),
+                   (file.clone(), 2));
+        assert!(fs::resolve_with_stats(&tmpdir.join("loop")).is_err());
+        assert!(fs::resolve_with_stats(&tmpdir.join("missing")).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn raw_fd_round_trip() {
+        use os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
 
-/// Returns the canonical form of a path with all intermediate components
-/// normalized and symbolic links resolved.
-#[unstable(feature = "fs_canonicalize", reason = "recently added API",
-           issue = "27706")]
-pub fn canonicalize<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
-    fs_imp::canonicalize(path.as_ref())
-}
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        check!(check!(File::create(&path)).write(b"hello"));
+
+        let f = check!(File::open(&path));
+        let fd = f.as_raw_fd();
+        assert_eq!(f.into_raw_fd(), fd);
+        let mut f = unsafe { File::from_raw_fd(fd) };
+        let mut s = String::new();
+        check!(f.read_to_string(&mut s));
+        assert_eq!(s, "hello");
+    }
 
-/// Creates a new, empty directory at the provided path
-///
-/// # Errors
-///
-/// This function will return an error if the user lacks permissions to make a
-/// new directory at the provided `path`, or if the directory already exists.
-///
-/// # Examples
-///
-/// ```
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// try!(fs::create_dir("/some/dir"));
-/// # Ok(())
-/// # }
-/// ```
-#[stable(feature = "rust1", since = "1.0.0")]
-pub fn create_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
-    DirBuilder::new().create(path.as_ref())
-}
+    #[test]
+    #[cfg(windows)]
+    fn raw_handle_round_trip() {
+        use os::windows::io::{AsRawHandle, FromRawHandle, IntoRawHandle};
 
-/// Recursively create a directory and all of its parent components if they
-/// are missing.
-///
-/// # Errors
-///
-/// This function will fail if any directory in the path specified by `path`
-/// does not already exist and it could not be created otherwise. The specific
-/// error conditions for when a directory is being created (after it is
-/// determined to not exist) are outlined by `fs::create_dir`.
-///
-/// # Examples
-///
-/// ```
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// try!(fs::create_dir_all("/some/dir"));
-/// # Ok(())
-/// # }
-/// ```
-#[stable(feature = "rust1", since = "1.0.0")]
-pub fn create_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
-    DirBuilder::new().recursive(true).create(path.as_ref())
-}
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        check!(check!(File::create(&path)).write(b"hello"));
+
+        let f = check!(File::open(&path));
+        let handle = f.as_raw_handle();
+        assert_eq!(f.into_raw_handle(), handle);
+        let mut f = unsafe { File::from_raw_handle(handle) };
+        let mut s = String::new();
+        check!(f.read_to_string(&mut s));
+        assert_eq!(s, "hello");
+    }
 
-/// Removes an existing, empty directory.
-///
-/// # Errors
-///
-/// This function will return an error if the user lacks permissions to remove
-/// the directory at the provided `path`, or if the directory isn't empty.
-///
-/// # Examples
-///
-/// ```
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// try!(fs::remove_dir("/some/dir"));
-/// # Ok(())
-/// # }
-/// ```
-#[stable(feature = "rust1", since = "1.0.0")]
-pub fn remove_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
-    fs_imp::rmdir(path.as_ref())
-}
+    #[test]
+    fn set_len_sync_shrinks() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("log");
+        let mut f = check!(File::create(&path));
+        check!(f.write(b"some log lines"));
+        check!(f.set_len_sync(4));
+        assert_eq!(check!(fs::metadata(&path)).len(), 4);
+        check!(f.set_len_sync(0));
+        assert_eq!(check!(f.metadata()).len(), 0);
+    }
 
-/// Removes a directory at this path, after removing all its contents. Use
-/// carefully!
-///
-/// This function does **not** follow symbolic links and it will simply remove the
-/// symbolic link itself.
-///
-/// # Errors
-///
-/// See `file::remove_file` and `fs::remove_dir`.
-///
-/// # Examples
-///
-/// ```
-/// use std::fs;
-///
-/// # fn foo() -> std::io::Result<()> {
-/// try!(fs::remove_dir_all("/some/dir"));
-/// # Ok(())
-/// # }
-/// ```
-#[stable(feature = "rust1", since = "1.0.0")]
-pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
-    _remove_dir_all(path.as_ref())
-}
+    #[test]
+    fn set_len_sync_order() {
+        use cell::RefCell;
+        use super::truncate_then_sync;
+
+        let calls = RefCell::new(Vec::new());
+        let step = |name: &'static str, ok: bool| {
+            calls.borrow_mut().push(name);
+            if ok { Ok(()) } else { Err(::io::Error::new(ErrorKind::Other, name)) }
+        };
+        check!(truncate_then_sync(|| step("truncate", true),
+                                  || step("sync", true),
+                                  || step("sync_dir", true)));
+        assert_eq!(*calls.borrow(), ["truncate", "sync", "sync_dir"]);
+
+        calls.borrow_mut().clear();
+        assert!(truncate_then_sync(|| step("truncate", true),
+                                   || step("sync", false),
+                                   || step("sync_dir", true)).is_err());
+        assert_eq!(*calls.borrow(), ["truncate", "sync"]);
+    }
 
-fn _remove_dir_all(path: &Path) -> io::Result<()> {
-    for child in try!(read_dir(path)) {
-        let child = try!(child).path();
-        let stat = try!(symlink_metadata(&*child));
-        if stat.is_dir() {
-            try!(remove_dir_all(&*child));
-        } else {
-            try!(remove_file(&*child));
-        }
+    #[test]
+    fn file_set_readonly() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let f = check!(File::create(&path));
+
+        check!(f.set_readonly(true));
+        assert!(check!(fs::metadata(&path)).permissions().readonly());
+        check!(f.set_readonly(false));
+        assert!(!check!(fs::metadata(&path)).permissions().readonly());
     }
-    remove_dir(path)
-}
 
-/// Returns an iterator over the entries within a directory.
-///
-/// The iterator will yield instances of `io::Result<DirEntry>`. New errors may
-/// be encountered after an iterator is initially constructed.
-///
-/// # Examples
-///
-/// ```
-/// use std::io;
-/// use std::fs::{self, DirEntry};
-/// use std::path::Path;
-///
-/// // one possible implementation of fs::walk_dir only visiting files
-/// fn visit_dirs(dir: &Path, cb: &Fn(&DirEntry)) -> io::Result<()> {
-///     if try!(fs::metadata(dir)).is_dir() {
-///         for entry in try!(fs::read_dir(dir)) {
-///             let entry = try!(entry);
-///             if try!(fs::metadata(entry.path())).is_dir() {
-///                 try!(visit_dirs(&entry.path(), cb));
-///             } else {
-///                 cb(&entry);
-///             }
-///         }
-///     }
-///     Ok(())
-/// }
-/// ```
-///
-/// # Errors
-///
-/// This function will return an error if the provided `path` doesn't exist, if
-/// the process lacks permissions to view the contents or if the `path` points
-/// at a non-directory file
-#[stable(feature = "rust1", since = "1.0.0")]
-pub fn read_dir<P: AsRef<Path>>(path: P) -> io::Result<ReadDir> {
-    fs_imp::readdir(path.as_ref()).map(ReadDir)
-}
+    #[test]
+    #[cfg(windows)]
+    fn file_set_readonly_keeps_attributes() {
+        use os::windows::fs::{MetadataExt, OpenOptionsExt};
 
-/// Returns an iterator that will recursively walk the directory structure
-/// rooted at `path`.
-///
-/// The path given will not be iterated over, and this will perform iteration in
-/// some top-down order.  The contents of unreadable subdirectories are ignored.
-///
-/// The iterator will yield instances of `io::Result<DirEntry>`. New errors may
-/// be encountered after an iterator is initially constructed.
-#[unstable(feature = "fs_walk",
-           reason = "the precise semantics and defaults for a recursive walk \
-                     may change and this may end up accounting for files such \
-                     as symlinks differently",
-           issue = "27707")]
-pub fn walk_dir<P: AsRef<Path>>(path: P) -> io::Result<WalkDir> {
-    _walk_dir(path.as_ref())
-}
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
 
-fn _walk_dir(path: &Path) -> io::Result<WalkDir> {
-    let start = try!(read_dir(path));
-    Ok(WalkDir { cur: Some(start), stack: Vec::new() })
-}
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let f = check!(OpenOptions::new().write(true).create(true)
+                                         .flags_and_attributes(FILE_ATTRIBUTE_HIDDEN)
+                                         .open(&path));
+        let attrs = check!(f.metadata()).file_attributes();
+        assert!(attrs & FILE_ATTRIBUTE_HIDDEN != 0);
+
+        check!(f.set_readonly(true));
+        let attrs = check!(fs::metadata(&path)).file_attributes();
+        assert!(attrs & FILE_ATTRIBUTE_HIDDEN != 0);
+        assert!(attrs & FILE_ATTRIBUTE_READONLY != 0);
+
+        check!(f.set_readonly(false));
+        let attrs = check!(fs::metadata(&path)).file_attributes();
+        assert!(attrs & FILE_ATTRIBUTE_HIDDEN != 0);
+        assert!(attrs & FILE_ATTRIBUTE_READONLY == 0);
+    }
 
-#[unstable(feature = "fs_walk", issue = "27707")]
-impl Iterator for WalkDir {
-    type Item = io::Result<DirEntry>;
+    #[test]
+    #[cfg(unix)]
+    fn file_set_readonly_keeps_mode_bits() {
+        use os::unix::fs::{MetadataExt, PermissionsExt};
 
-    fn next(&mut self) -> Option<io::Result<DirEntry>> {
-        loop {
-            if let Some(ref mut cur) = self.cur {
-                match cur.next() {
-                    Some(Err(e)) => return Some(Err(e)),
-                    Some(Ok(next)) => {
-                        let path = next.path();
-                        if path.is_dir() {
-                            self.stack.push(read_dir(&*path));
-                        }
-                        return Some(Ok(next))
-                    }
-                    None => {}
-                }
-            }
-            self.cur = None;
-            match self.stack.pop() {
-                Some(Err(e)) => return Some(Err(e)),
-                Some(Ok(next)) => self.cur = Some(next),
-                None => return None,
-            }
-        }
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let f = check!(File::create(&path));
+        check!(fs::set_permissions(&path, fs::Permissions::from_mode(0o1754)));
+
+        check!(f.set_readonly(true));
+        assert_eq!(check!(fs::metadata(&path)).mode() & 0o7777, 0o1554);
+        check!(f.set_readonly(false));
+        assert_eq!(check!(fs::metadata(&path)).mode() & 0o7777, 0o1754);
     }
-}
 
-/// Utility methods for paths.
-#[unstable(feature = "path_ext",
-           reason = "The precise set of methods exposed on this trait may \
-                     change and some methods may be removed.  For stable code, \
-                     see the std::fs::metadata function.",
-           issue = "27725")]
-pub trait PathExt {
-    /// Gets information on the file, directory, etc at this path.
-    ///
-    /// Consult the `fs::metadata` documentation for more info.
-    ///
-    /// This call preserves identical runtime/error semantics with
-    /// `fs::metadata`.
-    fn metadata(&self) -> io::Result<Metadata>;
+    #[test]
+    fn file_stream_len_keeps_position() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let mut f = check!(OpenOptions::new().read(true).write(true)
+                                             .create(true).open(&path));
+        check!(f.write(b"hello world"));
+        check!(f.seek(SeekFrom::Start(3)));
+
+        assert_eq!(check!(f.stream_len()), 11);
+        assert_eq!(check!(f.stream_position()), 3);
+        let mut buf = [0; 2];
+        check!(f.read(&mut buf));
+        assert_eq!(&buf, b"lo");
+        assert_eq!(check!(f.stream_position()), 5);
+    }
+
+    #[test]
+    fn open_no_follow_refuses_symlink() {
+        #[cfg(unix)] use os::unix::fs::{OpenOptionsExt, symlink};
+        #[cfg(windows)] use os::windows::fs::{OpenOptionsExt, symlink_file as symlink};
+
+        let tmpdir = tmpdir();
+        let target = tmpdir.join("target");
+        let link = tmpdir.join("link");
+        check!(check!(File::create(&target)).write(b"secret"));
+        if !symlink_created(symlink(&target, &link)) {
+            return
+        }
 
-    /// Gets information on the file, directory, etc at this path.
-    ///
-    /// Consult the `fs::symlink_metadata` documentation for more info.
-    ///
-    /// This call preserves identical runtime/error semantics with
-    /// `fs::symlink_metadata`.
-    fn symlink_metadata(&self) -> io::Result<Metadata>;
+        check!(OpenOptions::new().read(true).no_follow(true).open(&target));
+        let e = OpenOptions::new().write(true).append(true).no_follow(true)
+                                  .open(&link).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::SymlinkNotFollowed);
+        assert_eq!(check!(fs::metadata(&target)).len(), 6);
+        check!(OpenOptions::new().read(true).no_follow(false).open(&link));
+    }
 
-    /// Returns the canonical form of a path, normalizing all components and
-    /// eliminate all symlinks.
-    ///
-    /// This call preserves identical runtime/error semantics with
-    /// `fs::canonicalize`.
-    fn canonicalize(&self) -> io::Result<PathBuf>;
+    #[test]
+    #[cfg(unix)]
+    fn open_no_follow_reports_loops_in_parents() {
+        use os::unix::fs::{OpenOptionsExt, symlink};
 
-    /// Reads the symlink at this path.
-    ///
-    /// For more information see `fs::read_link`.
-    fn read_link(&self) -> io::Result<PathBuf>;
+        let tmpdir = tmpdir();
+        check!(symlink("loop", &tmpdir.join("loop")));
+        let e = OpenOptions::new().read(true).no_follow(true)
+                                  .open(&tmpdir.join("loop/file")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::FilesystemLoop);
+    }
 
-    /// Reads the directory at this path.
-    ///
-    /// For more information see `fs::read_dir`.
-    fn read_dir(&self) -> io::Result<ReadDir>;
+    #[test]
+    #[cfg(unix)]
+    fn symlink_target_status_relative_and_dangling() {
+        use os::unix::fs::symlink;
 
-    /// Boolean value indicator whether the underlying file exists on the local
-    /// filesystem. Returns false in exactly the cases where `fs::stat` fails.
-    fn exists(&self) -> bool;
+        let tmpdir = tmpdir();
+        check!(fs::create_dir(&tmpdir.join("dir")));
+        check!(File::create(&tmpdir.join("dir/file")));
+        check!(symlink("file", &tmpdir.join("dir/good")));
+        check!(symlink("missing", &tmpdir.join("dir/dangling")));
 
-    /// Whether the underlying implementation (be it a file path, or something
-    /// else) points at a "regular file" on the FS. Will return false for paths
-    /// to non-existent locations or directories or other non-regular files
-    /// (named pipes, etc). Follows links when making this determination.
-    fn is_file(&self) -> bool;
+        let (target, ty) = check!(fs::symlink_target_status(&tmpdir.join("dir/good")));
+        assert_eq!(target, Path2::new("file"));
+        assert!(ty.unwrap().is_file());
 
-    /// Whether the underlying implementation (be it a file path, or something
-    /// else) is pointing at a directory in the underlying FS. Will return
-    /// false for paths to non-existent locations or if the item is not a
-    /// directory (eg files, named pipes, etc). Follows links when making this
-    /// determination.
-    fn is_dir(&self) -> bool;
-}
+        let (target, ty) = check!(fs::symlink_target_status(&tmpdir.join("dir/dangling")));
+        assert_eq!(target, Path2::new("missing"));
+        assert!(ty.is_none());
 
-impl PathExt for Path {
-    fn metadata(&self) -> io::Result<Metadata> { metadata(self) }
-    fn symlink_metadata(&self) -> io::Result<Metadata> { symlink_metadata(self) }
-    fn canonicalize(&self) -> io::Result<PathBuf> { canonicalize(self) }
-    fn read_link(&self) -> io::Result<PathBuf> { read_link(self) }
-    fn read_dir(&self) -> io::Result<ReadDir> { read_dir(self) }
-    fn exists(&self) -> bool { metadata(self).is_ok() }
+        assert!(fs::symlink_target_status(&tmpdir.join("dir/file")).is_err());
+    }
 
-    fn is_file(&self) -> bool {
-        metadata(self).map(|s| s.is_file()).unwrap_or(false)
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn file_read_lease() {
+        use os::linux::fs::{FileExt, LeaseKind};
+
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        check!(File::create(&path));
+
+        let f = check!(File::open(&path));
+        assert_eq!(check!(f.get_lease()), LeaseKind::Unlock);
+        check!(f.set_lease(LeaseKind::Read));
+        assert_eq!(check!(f.get_lease()), LeaseKind::Read);
+        check!(f.set_lease(LeaseKind::Unlock));
+        assert_eq!(check!(f.get_lease()), LeaseKind::Unlock);
     }
 
-    fn is_dir(&self) -> bool {
-        metadata(self).map(|s| s.is_dir()).unwrap_or(false)
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn file_readahead() {
+        use os::linux::fs::FileExt;
+
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        check!(check!(File::create(&path)).write(&[1; 8192]));
+
+        let f = check!(File::open(&path));
+        check!(f.readahead(0, 8192));
+        // Ranges past the end of the file are simply not read.
+        check!(f.readahead(4096, u64::max_value()));
+        assert!(f.readahead(u64::max_value(), 1).is_err());
     }
-}
 
-/// Changes the permissions found on a file or a directory.
-///
-/// # Examples
-///
-/// ```
-/// # fn foo() -> std::io::Result<()> {
-/// use std::fs;
-///
-/// let mut perms = try!(fs::metadata("foo.txt")).permissions();
-/// perms.set_readonly(true);
-/// try!(fs::set_permissions("foo.txt", perms));
-/// # Ok(())
-/// # }
-/// ```
-///
-/// # Errors
-///
-/// This function will return an error if the provided `path` doesn't exist, if
-/// the process lacks permissions to change the attributes of the file, or if
-/// some other I/O error is encountered.
-#[stable(feature = "set_permissions", since = "1.1.0")]
-pub fn set_permissions<P: AsRef<Path>>(path: P, perm: Permissions)
-                                       -> io::Result<()> {
-    fs_imp::set_perm(path.as_ref(), perm.0)
-}
+    #[test]
+    fn metadata_batch_keeps_order() {
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("file");
+        check!(check!(File::create(&file)).write(b"abc"));
+        let missing = tmpdir.join("missing");
+
+        let paths = [file.as_path(), missing.as_path(), tmpdir.path()];
+        let results = fs::metadata_batch(&paths);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().len(), 3);
+        match results[1] {
+            Ok(..) => panic!("metadata of a missing file"),
+            Err(ref e) => assert_eq!(e.kind(), ErrorKind::NotFound),
+        }
+        assert!(results[2].as_ref().unwrap().is_dir());
+    }
 
-#[unstable(feature = "dir_builder", reason = "recently added API",
-           issue = "27710")]
-impl DirBuilder {
-    /// Creates a new set of options with default mode/security settings for all
-    /// platforms and also non-recursive.
-    pub fn new() -> DirBuilder {
-        DirBuilder {
-            inner: fs_imp::DirBuilder::new(),
-            recursive: false,
+    #[test]
+    fn metadata_batch_many() {
+        // Enough paths that Linux goes through io_uring where it can.
+        let tmpdir = tmpdir();
+        let mut paths = Vec::new();
+        for i in 0..40 {
+            let path = tmpdir.join(&format!("file{}", i));
+            if i % 3 != 0 {
+                check!(check!(File::create(&path)).write(&vec![0; i]));
+            }
+            paths.push(path);
+        }
+        if cfg!(unix) {
+            paths.push(PathBuf::from("nul\0byte"));
+        }
+
+        let results = fs::metadata_batch(&paths);
+        assert_eq!(results.len(), paths.len());
+        for (i, result) in results[..40].iter().enumerate() {
+            match *result {
+                Ok(ref meta) => {
+                    assert!(i % 3 != 0);
+                    assert_eq!(meta.len(), i as u64);
+                }
+                Err(ref e) => {
+                    assert!(i % 3 == 0);
+                    assert_eq!(e.kind(), ErrorKind::NotFound);
+                }
+            }
+        }
+        if cfg!(unix) {
+            match results[40] {
+                Ok(..) => panic!("metadata of a path with a nul byte"),
+                Err(ref e) => assert_eq!(e.kind(), ErrorKind::InvalidInput),
+            }
         }
     }
 
-    /// Indicate that directories create should be created recursively, creating
-    /// all parent directories if they do not exist with the same security and
-    /// permissions settings.
-    ///
-    /// This option defaults to `false`
-    pub fn recursive(&mut self, recursive: bool) -> &mut Self {
-        self.recursive = recursive;
-        self
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn read_prefixes_truncates() {
+        use os::linux::fs::read_prefixes;
+
+        let tmpdir = tmpdir();
+        let long = tmpdir.join("long");
+        let short = tmpdir.join("short");
+        check!(check!(File::create(&long)).write(b"0123456789"));
+        check!(check!(File::create(&short)).write(b"012"));
+
+        let missing = tmpdir.join("missing");
+        let paths = [long.as_path(), missing.as_path(), short.as_path()];
+        let results = read_prefixes(&paths, 4);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), b"0123");
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), b"012");
     }
 
-    /// Create the specified directory with the options configured in this
-    /// builder.
-    pub fn create<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
-        self._create(path.as_ref())
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn read_prefixes_many() {
+        use os::linux::fs::read_prefixes;
+
+        // More than one batch's worth, for kernels with io_uring.
+        let tmpdir = tmpdir();
+        let mut paths = Vec::new();
+        for i in 0..100 {
+            let path = tmpdir.join(&format!("file{}", i));
+            if i % 7 != 0 {
+                check!(check!(File::create(&path)).write(&vec![i as u8; i % 10]));
+            }
+            paths.push(path);
+        }
+        paths.push(tmpdir.path().to_path_buf());
+
+        let results = read_prefixes(&paths, 5);
+        assert_eq!(results.len(), paths.len());
+        for (i, result) in results[..100].iter().enumerate() {
+            match *result {
+                Ok(ref prefix) => {
+                    assert!(i % 7 != 0);
+                    assert_eq!(*prefix, vec![i as u8; ::cmp::min(i % 10, 5)]);
+                }
+                Err(ref e) => {
+                    assert!(i % 7 == 0);
+                    assert_eq!(e.kind(), ErrorKind::NotFound);
+                }
+            }
+        }
+        // Opening a directory works, but reading from it doesn't.
+        assert!(results[100].is_err());
+
+        // Buffers grow with the data rather than being allocated up front.
+        let results = read_prefixes(&paths[..20], usize::max_value());
+        match results[19] {
+            Ok(ref prefix) => assert_eq!(*prefix, vec![19; 9]),
+            Err(ref e) => panic!("{}", e),
+        }
     }
 
-    fn _create(&self, path: &Path) -> io::Result<()> {
-        if self.recursive {
-            self.create_dir_all(path)
-        } else {
-            self.inner.mkdir(path)
+    #[test]
+    fn file_exact_at() {
+        #[cfg(unix)] use os::unix::fs::FileExt;
+        #[cfg(windows)] use os::windows::fs::FileExt;
+
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let f = check!(OpenOptions::new().read(true).write(true)
+                                         .create(true).open(&path));
+        check!(f.write_all_at(b"hello world", 0));
+        check!(f.write_all_at(b"W", 6));
+
+        let mut buf = [0; 5];
+        check!(f.read_exact_at(&mut buf, 6));
+        assert_eq!(&buf, b"World");
+        let e = f.read_exact_at(&mut buf, 8).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEOF);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn file_zero_range_releases_space() {
+        use os::windows::fs::FileExt;
+        use sys::c;
+        use sys::fs::to_utf16;
+
+        fn on_disk_size(p: &Path2) -> u64 {
+            let p = to_utf16(p);
+            let mut high = 0;
+            let low = unsafe { c::GetCompressedFileSizeW(p.as_ptr(), &mut high) };
+            ((high as u64) << 32) | low as u64
         }
+
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let f = check!(OpenOptions::new().read(true).write(true)
+                                         .create(true).open(&path));
+        let data = vec![0xaa; 4 << 20];
+        check!(f.write_all_at(&data, 0));
+        check!(f.sync_all());
+        let before = on_disk_size(&path);
+
+        check!(f.zero_range(1 << 20, 2 << 20));
+        // Zeroing again must not fail even though the file is sparse now.
+        check!(f.zero_range(1 << 20, 2 << 20));
+        check!(f.sync_all());
+
+        assert_eq!(check!(f.metadata()).len(), 4 << 20);
+        assert!(on_disk_size(&path) < before);
+        let mut buf = [0xff; 16];
+        check!(f.read_exact_at(&mut buf, 2 << 20));
+        assert_eq!(buf, [0; 16]);
+        check!(f.read_exact_at(&mut buf, 3 << 20));
+        assert_eq!(buf, [0xaa; 16]);
     }
 
-    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
-        if path == Path::new("") || path.is_dir() { return Ok(()) }
-        if let Some(p) = path.parent() {
-            try!(self.create_dir_all(p))
+    #[test]
+    fn file_allocated_ranges() {
+        let tmpdir = tmpdir();
+        let dense = tmpdir.join("dense");
+        let sparse = tmpdir.join("sparse");
+
+        let mut f = check!(File::create(&dense));
+        check!(f.write(&[1; 8192]));
+        assert_eq!(check!(f.allocated_ranges()), [0..8192]);
+        assert_eq!(check!(f.stream_position()), 8192);
+
+        let mut f = check!(OpenOptions::new().read(true).write(true)
+                                             .create(true).open(&sparse));
+        check!(f.seek(SeekFrom::Start(16 << 20)));
+        check!(f.write(b"tail"));
+        check!(f.seek(SeekFrom::Start(1)));
+        let ranges = check!(f.allocated_ranges());
+        assert_eq!(check!(f.stream_position()), 1);
+
+        let len = (16 << 20) + 4;
+        assert!(!ranges.is_empty());
+        assert_eq!(ranges[ranges.len() - 1].end, len);
+        for pair in ranges.windows(2) {
+            assert!(pair[0].end <= pair[1].start);
+        }
+        for range in &ranges {
+            assert!(range.start < range.end);
         }
-        self.inner.mkdir(path)
     }
-}
 
-impl AsInnerMut<fs_imp::DirBuilder> for DirBuilder {
-    fn as_inner_mut(&mut self) -> &mut fs_imp::DirBuilder {
-        &mut self.inner
+    #[test]
+    #[cfg(unix)]
+    fn file_type_raw_mode() {
+        use os::unix::fs::FileTypeExt;
+        use os::unix::raw::mode_t;
+        use libc;
+
+        let tmpdir = tmpdir();
+        let ty = check!(fs::metadata(tmpdir.path())).file_type();
+        assert_eq!(ty.raw_mode(), libc::S_IFDIR as mode_t);
+
+        let ty = fs::FileType::from_raw_mode(libc::S_IFREG as mode_t | 0o644);
+        assert!(ty.is_file());
+        assert_eq!(ty.raw_mode(), libc::S_IFREG as mode_t);
+        assert!(fs::FileType::from_raw_mode(libc::S_IFIFO as mode_t).is_fifo());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #![allow(deprecated)] //rand
+    #[test]
+    #[cfg(unix)]
+    fn permissions_apply_mode_bits_from() {
+        use os::unix::fs::{PermissionsExt, MetadataExt};
 
-    use prelude::v1::*;
-    use io::prelude::*;
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        check!(File::create(&path));
+        check!(fs::set_permissions(&path, fs::Permissions::from_mode(0o600)));
 
-    use env;
-    use fs::{self, File, OpenOptions};
-    use io::{ErrorKind, SeekFrom};
-    use path::PathBuf;
-    use path::Path as Path2;
-    use os;
-    use rand::{self, StdRng, Rng};
-    use str;
+        let source = fs::Permissions::from_mode(0o4755);
+        let mut perm = check!(fs::metadata(&path)).permissions();
+        perm.apply_mode_bits_from(&source, 0o777);
+        assert_eq!(perm.mode(), 0o755);
+        perm.apply_mode_bits_from(&source, 0o7777);
+        assert_eq!(perm.mode(), 0o4755);
 
-    macro_rules! check { ($e:expr) => (
-        match $e {
-            Ok(t) => t,
-            Err(e) => panic!("{} failed with: {}", stringify!($e), e),
-        }
-    ) }
+        check!(fs::set_permissions(&path, perm));
+        assert_eq!(check!(fs::metadata(&path)).mode() & 0o7777, 0o4755);
+    }
 
-    macro_rules! error { ($e:expr, $s:expr) => (
-        match $e {
-            Ok(_) => panic!("Unexpected success. Should've been: {:?}", $s),
-            Err(ref err) => assert!(err.to_string().contains($s),
-                                    format!("`{}` did not contain `{}`", err, $s))
-        }
-    ) }
+    #[test]
+    #[cfg(unix)]
+    fn permissions_special_bits() {
+        use os::unix::fs::PermissionsExt;
 
-    pub struct TempDir(PathBuf);
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        check!(File::create(&path));
 
-    impl TempDir {
-        fn join(&self, path: &str) -> PathBuf {
-            let TempDir(ref p) = *self;
-            p.join(path)
+        let mut perm = fs::Permissions::from_mode(0o755);
+        assert!(!perm.is_setuid() && !perm.is_setgid() && !perm.is_sticky());
+        perm.set_setgid(true);
+        perm.set_sticky(true);
+        assert_eq!(perm.mode(), 0o3755);
+        perm.set_sticky(false);
+        assert!(perm.is_setgid() && !perm.is_sticky());
+
+        // Unprivileged processes may not be allowed to keep the setgid bit,
+        // so only round-trip setuid through the file system.
+        perm.set_setgid(false);
+        perm.set_setuid(true);
+        check!(fs::set_permissions(&path, perm));
+        let perm = check!(fs::metadata(&path)).permissions();
+        assert!(perm.is_setuid());
+        assert_eq!(perm.mode(), 0o4755);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn dir_entry_metadata_opt_dangling() {
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("file");
+        check!(File::create(&file));
+        check!(fs::soft_link(&file, &tmpdir.join("good")));
+        check!(fs::soft_link(&tmpdir.join("missing"), &tmpdir.join("broken")));
+        check!(fs::soft_link(&file.join("child"), &tmpdir.join("notdir")));
+
+        for entry in check!(fs::read_dir(tmpdir.path())) {
+            let entry = check!(entry);
+            let target = check!(entry.metadata_opt());
+            match entry.file_name().to_str().unwrap() {
+                "file" | "good" => assert!(target.unwrap().is_file()),
+                "broken" | "notdir" => assert!(target.is_none()),
+                name => panic!("unexpected entry {}", name),
+            }
         }
+    }
 
-        fn path<'a>(&'a self) -> &'a Path2 {
-            let TempDir(ref p) = *self;
-            p
+    #[test]
+    fn rename_with_options() {
+        use fs::RenameOptions;
+
+        let tmpdir = tmpdir();
+        let from = tmpdir.join("from");
+        let to = tmpdir.join("a").join("b").join("to");
+        check!(check!(File::create(&from)).write(b"from"));
+
+        assert!(fs::rename_with(&from, &to, &RenameOptions::new()).is_err());
+        check!(fs::rename_with(&from, &to, RenameOptions::new().create_parents(true)));
+        assert!(!from.exists());
+        assert!(to.is_file());
+
+        check!(check!(File::create(&from)).write(b"other"));
+        let mut opts = RenameOptions::new();
+        opts.overwrite(false);
+        let e = fs::rename_with(&from, &to, &opts).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AlreadyExists);
+        assert!(from.exists());
+        let mut contents = String::new();
+        check!(check!(File::open(&to)).read_to_string(&mut contents));
+        assert_eq!(contents, "from");
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+    fn file_clone_independent() {
+        use os::unix::fs::FileExt;
+
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let mut f = check!(OpenOptions::new().read(true).write(true)
+                                             .create(true).open(&path));
+        check!(f.write(b"hello"));
+
+        let mut clone = check!(f.clone_independent());
+        check!(clone.write(b"J"));
+        assert_eq!(check!(f.stream_position()), 5);
+        assert_eq!(check!(clone.stream_position()), 1);
+        check!(f.write(b" world"));
+
+        let mut contents = String::new();
+        check!(clone.read_to_string(&mut contents));
+        assert_eq!(contents, "ello world");
+    }
+
+    #[test]
+    fn copy_file_onto_itself() {
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("file");
+        let link = tmpdir.join("link");
+        check!(check!(File::create(&file)).write(b"data"));
+        check!(fs::hard_link(&file, &link));
+
+        for dest in &[&file, &link] {
+            let e = fs::copy(&file, dest).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
         }
+        assert_eq!(check!(fs::metadata(&file)).len(), 4);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn dir_link_at() {
+        use os::unix::fs::{Dir, MetadataExt};
+
+        let tmpdir = tmpdir();
+        check!(fs::create_dir(&tmpdir.join("src")));
+        check!(fs::create_dir(&tmpdir.join("dst")));
+        check!(File::create(&tmpdir.join("src").join("file")));
+
+        let src = check!(Dir::open(&tmpdir.join("src")));
+        let dst = check!(Dir::open(&tmpdir.join("dst")));
+        check!(src.link_at("file", &dst, "link", false));
+
+        let a = check!(fs::metadata(&tmpdir.join("src").join("file")));
+        let b = check!(fs::metadata(&tmpdir.join("dst").join("link")));
+        assert_eq!((a.dev(), a.ino()), (b.dev(), b.ino()));
+        assert_eq!(b.nlink(), 2);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn dir_fd_relative_ops() {
+        use os::unix::fs::{self as unix_fs, Dir};
+
+        let tmpdir = tmpdir();
+        let dir = check!(Dir::open(tmpdir.path()));
+        check!(dir.create_dir("sub"));
+        assert!(tmpdir.join("sub").is_dir());
+        check!(dir.open_file("sub/file", OpenOptions::new().write(true).create(true)));
+
+        let sub = check!(dir.open_dir("sub"));
+        check!(sub.rename("file", &dir, "moved"));
+        assert!(tmpdir.join("moved").exists());
+        assert!(sub.remove_dir(".").is_err());
+        check!(dir.remove_file("moved"));
+        check!(dir.remove_dir("sub"));
+        assert!(!tmpdir.join("sub").exists());
+        assert!(dir.remove_file("moved").is_err());
+
+        // Switching to the directory the process is already in keeps this
+        // from disturbing tests running concurrently.
+        let before = check!(env::current_dir());
+        let here = check!(Dir::current());
+        let inside = check!(unix_fs::with_cwd(&here, || env::current_dir()));
+        assert_eq!(check!(inside), before);
+        assert_eq!(check!(env::current_dir()), before);
     }
 
-    impl Drop for TempDir {
-        fn drop(&mut self) {
-            // Gee, seeing how we're testing the fs module I sure hope that we
-            // at least implement this correctly!
-            let TempDir(ref p) = *self;
-            check!(fs::remove_dir_all(p));
-        }
+    #[test]
+    #[cfg(not(windows))]
+    fn symlink_relative_survives_move() {
+        let tmpdir = tmpdir();
+        let a = tmpdir.join("a");
+        check!(fs::create_dir_all(&a.join("b")));
+        check!(fs::create_dir_all(&a.join("c")));
+        check!(check!(File::create(&a.join("b").join("file"))).write(b"data"));
+
+        let link = a.join("c").join("link");
+        check!(fs::symlink_relative(&a.join("b").join("file"), &link));
+        assert_eq!(check!(fs::read_link(&link)), Path2::new("../b/file"));
+        check!(fs::symlink_relative(&a.join("c"), &a.join("c").join("self")));
+        assert_eq!(check!(fs::read_link(&a.join("c").join("self"))), Path2::new("."));
+
+        let moved = tmpdir.join("moved");
+        check!(fs::rename(&a, &moved));
+        let mut contents = String::new();
+        check!(check!(File::open(&moved.join("c").join("link")))
+                   .read_to_string(&mut contents));
+        assert_eq!(contents, "data");
     }
 
-    pub fn tmpdir() -> TempDir {
-        let p = env::temp_dir();
-        let mut r = rand::thread_rng();
-        let ret = p.join(&format!("rust-{}", r.next_u32()));
-        check!(fs::create_dir(&ret));
-        TempDir(ret)
+    #[test]
+    #[cfg(unix)]
+    fn copy_any_drains_fifo() {
+        use thread;
+
+        let tmpdir = tmpdir();
+        let fifo = tmpdir.join("fifo");
+        let out = tmpdir.join("out");
+        let c_path = ::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { ::libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        assert!(fs::copy(&fifo, &out).is_err());
+
+        let writer_path = fifo.clone();
+        let t = thread::spawn(move || {
+            let mut f = check!(OpenOptions::new().write(true).open(&writer_path));
+            check!(f.write_all(b"hello "));
+            check!(f.write_all(b"pipe"));
+        });
+        assert_eq!(check!(fs::copy_any(&fifo, &out)), 10);
+        t.join().unwrap();
+        assert_eq!(check!(fs::read(&out)), b"hello pipe");
+
+        let e = fs::copy_any(tmpdir.path(), &out).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
     }
 
     #[test]
-    fn file_test_io_smoke_test() {
-        let message = "it's alright. have a good time";
+    #[cfg(target_os = "linux")]
+    fn metadata_rdev_major_minor() {
+        use os::unix::fs::MetadataExt;
+
+        let null = check!(fs::metadata("/dev/null"));
+        assert_eq!((null.rdev_major(), null.rdev_minor()), (1, 3));
         let tmpdir = tmpdir();
-        let filename = &tmpdir.join("file_rt_io_file_test.txt");
-        {
-            let mut write_stream = check!(File::create(filename));
-            check!(write_stream.write(message.as_bytes()));
-        }
-        {
-            let mut read_stream = check!(File::open(filename));
-            let mut read_buf = [0; 1028];
-            let read_str = match check!(read_stream.read(&mut read_buf)) {
-                0 => panic!("shouldn't happen"),
-                n => str::from_utf8(&read_buf[..n]).unwrap().to_string()
-            };
-            assert_eq!(read_str, message);
-        }
-        check!(fs::remove_file(filename));
+        let m = check!(fs::metadata(tmpdir.path()));
+        assert_eq!((m.rdev_major(), m.rdev_minor()), (0, 0));
+        assert!(m.dev_major() != 0 || m.dev_minor() != 0);
     }
 
     #[test]
-    fn invalid_path_raises() {
+    #[cfg(unix)]
+    fn file_wait_readable_times_out() {
+        use os::unix::fs::FileExt;
+        use time::Duration;
+
+        // A pipe opened for both reading and writing never blocks on open
+        // and stays empty until something is written to it.
         let tmpdir = tmpdir();
-        let filename = &tmpdir.join("file_that_does_not_exist.txt");
-        let result = File::open(filename);
+        let path = tmpdir.join("fifo");
+        let c_path = ::ffi::CString::new(path.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { ::libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let mut fifo = check!(OpenOptions::new().read(true).write(true).open(&path));
+
+        let e = fifo.wait_readable(Some(Duration::from_millis(10))).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+        check!(fifo.wait_writable(Some(Duration::new(0, 0))));
+
+        check!(fifo.write(b"x"));
+        check!(fifo.wait_readable(None));
+        let mut buf = [0];
+        assert_eq!(check!(fifo.read(&mut buf)), 1);
+    }
 
-        if cfg!(unix) {
-            error!(result, "o such file or directory");
-        }
-        // error!(result, "couldn't open path as file");
-        // error!(result, format!("path={}; mode=open; access=read", filename.display()));
+    #[test]
+    #[cfg(unix)]
+    fn file_lock_flock_and_fcntl() {
+        use os::unix::fs::FileExt;
+        use os::unix::io::AsRawFd;
+
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let a = check!(OpenOptions::new().read(true).write(true).create(true).open(&path));
+        let b = check!(OpenOptions::new().read(true).write(true).open(&path));
+        let try_exclusive = |f: &File| unsafe {
+            ::libc::flock(f.as_raw_fd(), ::libc::LOCK_EX | ::libc::LOCK_NB) == 0
+        };
+
+        // flock locks belong to the open file, so two independent opens
+        // conflict even within one process.
+        check!(a.lock_shared());
+        check!(b.lock_shared());
+        assert!(!try_exclusive(&b));
+        check!(a.unlock());
+        assert!(try_exclusive(&b));
+        check!(b.unlock());
+        check!(a.lock_exclusive());
+        assert!(!try_exclusive(&b));
+        check!(a.unlock());
+
+        check!(a.lock_exclusive_fcntl());
+        check!(a.unlock_fcntl());
+        check!(a.lock_shared_fcntl());
+        check!(a.unlock_fcntl());
     }
 
     #[test]
-    fn file_test_iounlinking_invalid_path_should_raise_condition() {
+    #[cfg(unix)]
+    fn file_lock_exclusive_interprocess() {
+        use os::unix::fs::FileExt;
+        use os::unix::io::AsRawFd;
+
         let tmpdir = tmpdir();
-        let filename = &tmpdir.join("file_another_file_that_does_not_exist.txt");
+        let path = tmpdir.join("file");
+        let a = check!(OpenOptions::new().write(true).create(true).open(&path));
+        let b = check!(OpenOptions::new().write(true).open(&path));
+        let try_exclusive = |f: &File| unsafe {
+            ::libc::flock(f.as_raw_fd(), ::libc::LOCK_EX | ::libc::LOCK_NB) == 0
+        };
+
+        let guard = check!(a.lock_exclusive_interprocess());
+        assert!(!try_exclusive(&b));
+        // The same open file doesn't exclude itself.
+        assert!(try_exclusive(&a));
+        drop(guard);
+        assert!(try_exclusive(&b));
+        check!(b.unlock());
+
+        let guard = check!(a.lock_exclusive_interprocess());
+        check!(guard.unlock());
+        assert!(try_exclusive(&b));
+    }
 
-        let result = fs::remove_file(filename);
+    #[test]
+    fn metadata_preferred_io_block_size() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let f = check!(File::create(&path));
 
-        if cfg!(unix) {
-            error!(result, "o such file or directory");
+        for size in vec![check!(fs::metadata(&path)).preferred_io_block_size(),
+                         check!(f.metadata()).preferred_io_block_size()] {
+            assert!(size >= 512 && size.is_power_of_two(), "{}", size);
         }
-        // error!(result, "couldn't unlink path");
-        // error!(result, format!("path={}", filename.display()));
     }
 
     #[test]
-    fn file_test_io_non_positional_read() {
-        let message: &str = "ten-four";
-        let mut read_mem = [0; 8];
+    #[cfg(windows)]
+    fn metadata_preferred_io_block_size_is_cluster_size() {
+        use os::windows::ffi::OsStrExt;
+        use sys::c;
+
         let tmpdir = tmpdir();
-        let filename = &tmpdir.join("file_rt_io_file_test_positional.txt");
-        {
-            let mut rw_stream = check!(File::create(filename));
-            check!(rw_stream.write(message.as_bytes()));
-        }
-        {
-            let mut read_stream = check!(File::open(filename));
-            {
-                let read_buf = &mut read_mem[0..4];
-                check!(read_stream.read(read_buf));
-            }
-            {
-                let read_buf = &mut read_mem[4..8];
-                check!(read_stream.read(read_buf));
-            }
+        let path = tmpdir.join("file");
+        let f = check!(File::create(&path));
+
+        let wide = tmpdir.path().as_os_str().encode_wide().chain(Some(0)).collect::<Vec<_>>();
+        let mut root = vec![0u16; wide.len() + 1];
+        let (mut sectors_per_cluster, mut bytes_per_sector) = (0, 0);
+        unsafe {
+            assert!(c::GetVolumePathNameW(wide.as_ptr(), root.as_mut_ptr(),
+                                          root.len() as ::libc::DWORD) != 0);
+            assert!(c::GetDiskFreeSpaceW(root.as_ptr(), &mut sectors_per_cluster,
+                                         &mut bytes_per_sector, 0 as *mut _,
+                                         0 as *mut _) != 0);
         }
-        check!(fs::remove_file(filename));
-        let read_str = str::from_utf8(&read_mem).unwrap();
-        assert_eq!(read_str, message);
+        // Both have to find the real cluster size rather than fall back to
+        // the default.
+        let expected = sectors_per_cluster as u64 * bytes_per_sector as u64;
+        assert_eq!(check!(fs::metadata(&path)).preferred_io_block_size(), expected);
+        assert_eq!(check!(f.metadata()).preferred_io_block_size(), expected);
     }
 
     #[test]
-    fn file_test_io_seek_and_tell_smoke_test() {
-        let message = "ten-four";
-        let mut read_mem = [0; 4];
-        let set_cursor = 4 as u64;
-        let mut tell_pos_pre_read;
-        let mut tell_pos_post_read;
+    fn file_memory_map() {
         let tmpdir = tmpdir();
-        let filename = &tmpdir.join("file_rt_io_file_test_seeking.txt");
-        {
-            let mut rw_stream = check!(File::create(filename));
-            check!(rw_stream.write(message.as_bytes()));
-        }
-        {
-            let mut read_stream = check!(File::open(filename));
-            check!(read_stream.seek(SeekFrom::Start(set_cursor)));
-            tell_pos_pre_read = check!(read_stream.seek(SeekFrom::Current(0)));
-            check!(read_stream.read(&mut read_mem));
-            tell_pos_post_read = check!(read_stream.seek(SeekFrom::Current(0)));
-        }
-        check!(fs::remove_file(filename));
-        let read_str = str::from_utf8(&read_mem).unwrap();
-        assert_eq!(read_str, &message[4..8]);
-        assert_eq!(tell_pos_pre_read, set_cursor);
-        assert_eq!(tell_pos_post_read, message.len() as u64);
+        let path = tmpdir.join("file");
+        let data = (0..200_000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+        check!(check!(File::create(&path)).write_all(&data));
+        let f = check!(File::open(&path));
+
+        // Neither page nor allocation granularity aligned.
+        let map = check!(unsafe { f.memory_map(70_001, Some(1000)) });
+        assert_eq!(&map[..], &data[70_001..71_001]);
+        let map = check!(unsafe { f.memory_map(123, None) });
+        assert_eq!(&map[..], &data[123..]);
+        drop(f);
+        assert_eq!(map[0], data[123]);
+
+        let f = check!(File::open(&path));
+        assert!(check!(unsafe { f.memory_map(200_000, None) }).is_empty());
+        let e = unsafe { f.memory_map(199_999, Some(2)) }.unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert!(unsafe { f.memory_map(200_001, None) }.is_err());
     }
 
     #[test]
-    fn file_test_io_seek_and_write() {
-        let initial_msg =   "food-is-yummy";
-        let overwrite_msg =    "-the-bar!!";
-        let final_msg =     "foo-the-bar!!";
-        let seek_idx = 3;
-        let mut read_mem = [0; 13];
+    fn file_memory_map_mut_flush() {
         let tmpdir = tmpdir();
-        let filename = &tmpdir.join("file_rt_io_file_test_seek_and_write.txt");
-        {
-            let mut rw_stream = check!(File::create(filename));
-            check!(rw_stream.write(initial_msg.as_bytes()));
-            check!(rw_stream.seek(SeekFrom::Start(seek_idx)));
-            check!(rw_stream.write(overwrite_msg.as_bytes()));
-        }
-        {
-            let mut read_stream = check!(File::open(filename));
-            check!(read_stream.read(&mut read_mem));
-        }
-        check!(fs::remove_file(filename));
-        let read_str = str::from_utf8(&read_mem).unwrap();
-        assert!(read_str == final_msg);
+        let path = tmpdir.join("file");
+        let f = check!(OpenOptions::new().read(true).write(true).create(true).open(&path));
+        check!(f.set_len(100_000));
+
+        let mut map = check!(unsafe { f.memory_map_mut(70_001, Some(10)) });
+        check!((&mut map[..]).write_all(b"0123456789"));
+        check!(map.flush(0..10));
+        drop(map);
+        drop(f);
+
+        let contents = check!(fs::read(&path));
+        assert_eq!(contents.len(), 100_000);
+        assert_eq!(&contents[70_001..70_011], b"0123456789");
+        assert!(contents[..70_001].iter().all(|&b| b == 0));
+
+        let f = check!(File::open(&path));
+        assert!(unsafe { f.memory_map_mut(0, None) }.is_err());
+        let f = check!(OpenOptions::new().read(true).write(true).open(&path));
+        let map = check!(unsafe { f.memory_map_mut(0, Some(10)) });
+        let e = map.flush(5..11).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
     }
 
     #[test]
-    fn file_test_io_seek_shakedown() {
-        //                   01234567890123
-        let initial_msg =   "qwer-asdf-zxcv";
-        let chunk_one: &str = "qwer";
-        let chunk_two: &str = "asdf";
-        let chunk_three: &str = "zxcv";
-        let mut read_mem = [0; 4];
+    fn symlink_target_len() {
+        #[cfg(unix)] use os::unix::fs::symlink;
+        #[cfg(windows)] use os::windows::fs::symlink_file as symlink;
+
         let tmpdir = tmpdir();
-        let filename = &tmpdir.join("file_rt_io_file_test_seek_shakedown.txt");
-        {
-            let mut rw_stream = check!(File::create(filename));
-            check!(rw_stream.write(initial_msg.as_bytes()));
+        let target = tmpdir.join("target");
+        let link = tmpdir.join("link");
+        check!(check!(File::create(&target)).write_all(&[0; 100]));
+        if !symlink_created(symlink(Path2::new("target"), &link)) {
+            return
         }
-        {
-            let mut read_stream = check!(File::open(filename));
-
-            check!(read_stream.seek(SeekFrom::End(-4)));
-            check!(read_stream.read(&mut read_mem));
-            assert_eq!(str::from_utf8(&read_mem).unwrap(), chunk_three);
 
-            check!(read_stream.seek(SeekFrom::Current(-9)));
-            check!(read_stream.read(&mut read_mem));
-            assert_eq!(str::from_utf8(&read_mem).unwrap(), chunk_two);
+        let expected = if cfg!(windows) { 12 } else { 6 };
+        assert_eq!(check!(fs::symlink_target_len(&link)), expected);
+        let link_len = check!(fs::symlink_metadata(&link)).len();
+        assert_eq!(link_len, if cfg!(windows) { 0 } else { 6 });
+        assert_eq!(check!(fs::metadata(&link)).len(), 100);
+        assert!(fs::symlink_target_len(&target).is_err());
+    }
 
-            check!(read_stream.seek(SeekFrom::Start(0)));
-            check!(read_stream.read(&mut read_mem));
-            assert_eq!(str::from_utf8(&read_mem).unwrap(), chunk_one);
+    #[test]
+    fn dir_entry_count_hint() {
+        let tmpdir = tmpdir();
+        let dir = tmpdir.join("dir");
+        check!(fs::create_dir(&dir));
+        check!(fs::create_dir(&dir.join("a")));
+        check!(fs::create_dir(&dir.join("b")));
+        check!(File::create(&dir.join("file")));
+
+        if let Some(n) = check!(fs::dir_entry_count_hint(&dir)) {
+            assert!(n <= 3, "{}", n);
         }
-        check!(fs::remove_file(filename));
+        let e = fs::dir_entry_count_hint(&dir.join("file")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert!(fs::dir_entry_count_hint(&dir.join("missing")).is_err());
     }
 
     #[test]
-    fn file_test_stat_is_correct_on_is_file() {
+    fn open_path_handle() {
         let tmpdir = tmpdir();
-        let filename = &tmpdir.join("file_stat_correct_on_is_file.txt");
-        {
-            let mut opts = OpenOptions::new();
-            let mut fs = check!(opts.read(true).write(true)
-                                    .create(true).open(filename));
-            let msg = "hw";
-            fs.write(msg.as_bytes()).unwrap();
+        let path = tmpdir.join("file");
+        check!(check!(File::create(&path)).write_all(b"hello"));
 
-            let fstat_res = check!(fs.metadata());
-            assert!(fstat_res.is_file());
-        }
-        let stat_res_fn = check!(fs::metadata(filename));
-        assert!(stat_res_fn.is_file());
-        let stat_res_meth = check!(filename.metadata());
-        assert!(stat_res_meth.is_file());
-        check!(fs::remove_file(filename));
+        let handle = check!(fs::open_path_handle(&path, false));
+        assert_eq!(check!(handle.metadata()).len(), 5);
+        let handle = check!(fs::open_path_handle(tmpdir.path(), false));
+        assert!(check!(handle.metadata()).is_dir());
+        assert!(fs::open_path_handle(&tmpdir.join("missing"), false).is_err());
     }
 
     #[test]
-    fn file_test_stat_is_correct_on_is_dir() {
+    #[cfg(target_os = "linux")]
+    fn open_path_handle_o_path() {
+        use os::unix::fs::{symlink, PermissionsExt};
+
         let tmpdir = tmpdir();
-        let filename = &tmpdir.join("file_stat_correct_on_is_dir");
-        check!(fs::create_dir(filename));
-        let stat_res_fn = check!(fs::metadata(filename));
-        assert!(stat_res_fn.is_dir());
-        let stat_res_meth = check!(filename.metadata());
-        assert!(stat_res_meth.is_dir());
-        check!(fs::remove_dir(filename));
+        let path = tmpdir.join("file");
+        let link = tmpdir.join("link");
+        check!(check!(File::create(&path)).write_all(b"hello"));
+        check!(symlink(&path, &link));
+        check!(fs::set_permissions(&path, PermissionsExt::from_mode(0o000)));
+
+        let mut handle = check!(fs::open_path_handle(&path, false));
+        assert!(handle.read(&mut [0; 5]).is_err());
+        let handle = check!(fs::open_path_handle(&link, true));
+        assert!(check!(handle.metadata()).file_type().is_symlink());
+        let handle = check!(fs::open_path_handle(&link, false));
+        assert_eq!(check!(handle.metadata()).len(), 5);
     }
 
     #[test]
-    fn file_test_fileinfo_false_when_checking_is_file_on_a_directory() {
+    #[cfg(target_os = "linux")]
+    fn splice_and_copy_file_range() {
+        use os::linux::fs::{splice, copy_file_range};
+        use os::unix::io::FromRawFd;
+
         let tmpdir = tmpdir();
-        let dir = &tmpdir.join("fileinfo_false_on_dir");
-        check!(fs::create_dir(dir));
-        assert!(dir.is_file() == false);
-        check!(fs::remove_dir(dir));
+        let src_path = tmpdir.join("src");
+        let dst_path = tmpdir.join("dst");
+        check!(check!(File::create(&src_path)).write_all(b"hello world"));
+        let src = check!(File::open(&src_path));
+        let mut dst = check!(File::create(&dst_path));
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { ::libc::pipe(fds.as_mut_ptr()) }, 0);
+        let (reader, writer) = unsafe {
+            (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1]))
+        };
+        assert_eq!(check!(splice(&src, Some(6), &writer, None, 100)), 5);
+        assert_eq!(check!(splice(&reader, None, &dst, None, 100)), 5);
+        assert_eq!(check!(src.seek(SeekFrom::Current(0))), 0);
+
+        let e = splice(&src, None, &dst, None, 5).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+        match copy_file_range(&src, Some(0), &dst, Some(5), 5) {
+            Ok(n) => {
+                assert_eq!(n, 5);
+                check!(dst.flush());
+                assert_eq!(check!(fs::read(&dst_path)), b"worldhello");
+            }
+            // Kernels before 4.5 don't have it.
+            Err(ref e) if e.raw_os_error() == Some(::libc::ENOSYS) => {}
+            Err(e) => panic!("{}", e),
+        }
     }
 
     #[test]
-    fn file_test_fileinfo_check_exists_before_and_after_file_creation() {
+    fn metadata_detailed_missing_kind() {
+        use fs::MissingKind;
+
         let tmpdir = tmpdir();
-        let file = &tmpdir.join("fileinfo_check_exists_b_and_a.txt");
-        check!(check!(File::create(file)).write(b"foo"));
-        assert!(file.exists());
-        check!(fs::remove_file(file));
-        assert!(!file.exists());
+        let file = tmpdir.join("file");
+        check!(check!(File::create(&file)).write_all(b"abc"));
+
+        assert_eq!(check!(fs::metadata_detailed(&file)).len(), 3);
+        match fs::metadata_detailed(&tmpdir.join("missing")) {
+            Err(MissingKind::Final) => {}
+            r => panic!("{:?}", r.map(|_| ())),
+        }
+        match fs::metadata_detailed(&tmpdir.join("a/b/c")) {
+            Err(MissingKind::Parent) => {}
+            r => panic!("{:?}", r.map(|_| ())),
+        }
+        match fs::metadata_detailed(&file.join("c")) {
+            Err(MissingKind::Parent) => {}
+            r => panic!("{:?}", r.map(|_| ())),
+        }
+        let e = fs::metadata_detailed(&tmpdir.join("missing")).map_err(::io::Error::from);
+        assert_eq!(e.map(|_| ()).unwrap_err().kind(), ErrorKind::NotFound);
     }
 
     #[test]
-    fn file_test_directoryinfo_check_exists_before_and_after_mkdir() {
+    fn symlink_replace_repoints_link() {
         let tmpdir = tmpdir();
-        let dir = &tmpdir.join("before_and_after_dir");
-        assert!(!dir.exists());
-        check!(fs::create_dir(dir));
-        assert!(dir.exists());
-        assert!(dir.is_dir());
-        check!(fs::remove_dir(dir));
-        assert!(!dir.exists());
+        let a = tmpdir.join("a");
+        let b = tmpdir.join("b");
+        let link = tmpdir.join("current");
+        check!(check!(File::create(&a)).write_all(b"a"));
+        check!(check!(File::create(&b)).write_all(b"bb"));
+        if !symlink_created(fs::symlink_replace(&a, &link)) {
+            return
+        }
+        assert_eq!(check!(fs::metadata(&link)).len(), 1);
+
+        check!(fs::symlink_replace(&b, &link));
+        assert_eq!(check!(fs::read_link(&link)), b);
+        assert_eq!(check!(fs::metadata(&link)).len(), 2);
+        // Replacing a regular file works too, and nothing is left behind.
+        check!(fs::symlink_replace(&b, &a));
+        assert!(check!(fs::symlink_metadata(&a)).file_type().is_symlink());
+        assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 3);
+
+        let dir = tmpdir.join("dir");
+        check!(fs::create_dir(&dir));
+        assert!(fs::symlink_replace(&b, &dir).is_err());
+        assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 4);
     }
 
     #[test]
-    fn file_test_directoryinfo_readdir() {
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+              target_os = "macos", target_os = "ios"))]
+    fn file_no_cache() {
+        use os::unix::fs::FileExt;
+
         let tmpdir = tmpdir();
-        let dir = &tmpdir.join("di_readdir");
-        check!(fs::create_dir(dir));
-        let prefix = "foo";
-        for n in 0..3 {
-            let f = dir.join(&format!("{}.txt", n));
-            let mut w = check!(File::create(&f));
-            let msg_str = format!("{}{}", prefix, n.to_string());
-            let msg = msg_str.as_bytes();
-            check!(w.write(msg));
-        }
-        let files = check!(fs::read_dir(dir));
-        let mut mem = [0; 4];
-        for f in files {
-            let f = f.unwrap().path();
-            {
-                let n = f.file_stem().unwrap();
-                check!(check!(File::open(&f)).read(&mut mem));
-                let read_str = str::from_utf8(&mem).unwrap();
-                let expected = format!("{}{}", prefix, n.to_str().unwrap());
-                assert_eq!(expected, read_str);
-            }
-            check!(fs::remove_file(&f));
+        let path = tmpdir.join("file");
+        let mut f = check!(File::create(&path));
+        check!(f.set_no_cache(true));
+        check!(f.write_all(&[1; 8192]));
+        check!(f.drop_cache_range(0, 4096));
+        check!(f.write_all(&[2; 4096]));
+        check!(f.drop_cache_range(4096, 0));
+        check!(f.set_no_cache(false));
+        assert!(f.drop_cache_range(u64::max_value(), 1).is_err());
+        drop(f);
+
+        let mut v = Vec::new();
+        check!(check!(File::open(&path)).read_to_end(&mut v));
+        assert_eq!(v.len(), 12288);
+        assert!(v[..8192].iter().all(|&b| b == 1));
+        assert!(v[8192..].iter().all(|&b| b == 2));
+    }
+
+    #[test]
+    fn metadata_identity() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let mut f = check!(File::create(&path));
+        check!(f.write_all(b"abc"));
+        let first = check!(fs::metadata(&path)).identity();
+        assert_eq!(check!(fs::metadata(&path)).identity(), first);
+
+        check!(f.write_all(b"d"));
+        let second = check!(fs::metadata(&path)).identity();
+        assert!(second != first);
+
+        let mut set = ::collections::HashSet::new();
+        set.insert(first);
+        set.insert(second);
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn read_link_full() {
+        #[cfg(unix)] use os::unix::fs::{symlink as symlink_file, symlink as symlink_dir};
+        #[cfg(windows)] use os::windows::fs::{symlink_file, symlink_dir};
+
+        let tmpdir = tmpdir();
+        let dir = tmpdir.join("dir");
+        check!(fs::create_dir(&dir));
+        let relative = tmpdir.join("relative");
+        let absolute = tmpdir.join("absolute");
+        if !symlink_created(symlink_file("dir", &relative)) {
+            return
         }
-        check!(fs::remove_dir(dir));
+        check!(symlink_dir(&dir, &absolute));
+
+        let link = check!(fs::read_link_full(&relative));
+        assert_eq!(link.target(), Path2::new("dir"));
+        assert!(link.is_relative());
+        let link = check!(fs::read_link_full(&absolute));
+        assert!(!link.is_relative());
+        assert_eq!(link.into_target(), check!(fs::read_link(&absolute)));
+
+        if cfg!(windows) {
+            let link = check!(fs::read_link_full(&relative));
+            assert_eq!(link.is_directory(), Some(false));
+            let link = check!(fs::read_link_full(&absolute));
+            assert_eq!(link.is_directory(), Some(true));
+        } else {
+            assert_eq!(check!(fs::read_link_full(&absolute)).is_directory(), None);
+        }
+        assert!(fs::read_link_full(&dir).is_err());
     }
 
     #[test]
-    fn file_test_walk_dir() {
+    fn copy_with_options_verify() {
+        use fs::CopyOptions;
+        use hash::{Hasher, SipHasher};
+
+        fn keyed() -> Box<Hasher> { Box::new(SipHasher::new_with_keys(1, 2)) }
+
         let tmpdir = tmpdir();
-        let dir = &tmpdir.join("walk_dir");
-        check!(fs::create_dir(dir));
+        let from = tmpdir.join("from");
+        let to = tmpdir.join("to");
+        let data: Vec<u8> = (0..200000).map(|i| i as u8).collect();
+        check!(check!(File::create(&from)).write_all(&data));
 
-        let dir1 = &dir.join("01/02/03");
-        check!(fs::create_dir_all(dir1));
-        check!(File::create(&dir1.join("04")));
+        let n = check!(fs::copy_with_options(&from, &to, CopyOptions::new().verify(true)));
+        assert_eq!(n, data.len() as u64);
+        let mut v = Vec::new();
+        check!(check!(File::open(&to)).read_to_end(&mut v));
+        assert!(v == data);
 
-        let dir2 = &dir.join("11/12/13");
-        check!(fs::create_dir_all(dir2));
-        check!(File::create(&dir2.join("14")));
+        check!(fs::copy_with_options(&from, &to, CopyOptions::new().verify_with(keyed)));
+        assert_eq!(check!(fs::metadata(&to)).len(), data.len() as u64);
 
-        let files = check!(fs::walk_dir(dir));
-        let mut cur = [0; 2];
-        for f in files {
-            let f = f.unwrap().path();
-            let stem = f.file_stem().unwrap().to_str().unwrap();
-            let root = stem.as_bytes()[0] - b'0';
-            let name = stem.as_bytes()[1] - b'0';
-            assert!(cur[root as usize] < name);
-            cur[root as usize] = name;
-        }
+        let err = fs::copy_with_options(&from, &from, CopyOptions::new().verify(true));
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(check!(fs::metadata(&from)).len(), data.len() as u64);
+    }
 
-        check!(fs::remove_dir_all(dir));
+    #[test]
+    fn read_dir_recursive_depth() {
+        let tmpdir = tmpdir();
+        check!(fs::create_dir_all(&tmpdir.join("a/b/c")));
+        check!(File::create(&tmpdir.join("f")));
+        check!(File::create(&tmpdir.join("a/b/g")));
+
+        let names = |max_depth| {
+            let (entries, errors) = check!(fs::read_dir_recursive(tmpdir.path(), max_depth));
+            assert!(errors.is_empty());
+            let mut names: Vec<_> = entries.iter().map(|e| {
+                e.path().relative_from(tmpdir.path()).unwrap().to_path_buf()
+            }).collect();
+            names.sort();
+            names
+        };
+        let p = |s: &str| Path2::new(s).to_path_buf();
+        assert_eq!(names(0), [p("a"), p("f")]);
+        assert_eq!(names(1), [p("a"), p("a/b"), p("f")]);
+        assert_eq!(names(2), [p("a"), p("a/b"), p("a/b/c"), p("a/b/g"), p("f")]);
+        assert_eq!(names(10), names(2));
+
+        assert!(fs::read_dir_recursive(&tmpdir.join("missing"), 1).is_err());
     }
 
     #[test]
-    fn mkdir_path_already_exists_error() {
+    fn create_capped_limits_writes() {
         let tmpdir = tmpdir();
-        let dir = &tmpdir.join("mkdir_error_twice");
-        check!(fs::create_dir(dir));
-        let e = fs::create_dir(dir).err().unwrap();
-        assert_eq!(e.kind(), ErrorKind::AlreadyExists);
+        let path = tmpdir.join("capped");
+        let mut f = check!(fs::create_capped(&path, 10));
+        check!(f.write_all(b"0123456"));
+        assert_eq!(f.remaining(), 3);
+        assert_eq!(check!(f.write(b"789abc")), 3);
+        assert_eq!(f.remaining(), 0);
+        let err = f.write(b"x").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WriteZero);
+        assert_eq!(check!(f.write(b"")), 0);
+        assert_eq!(f.write_all(b"x").unwrap_err().kind(), ErrorKind::WriteZero);
+
+        check!(f.seek(SeekFrom::Start(8)));
+        assert_eq!(f.write_all(b"XYZ").unwrap_err().kind(), ErrorKind::WriteZero);
+        drop(f);
+
+        let mut v = Vec::new();
+        check!(check!(File::open(&path)).read_to_end(&mut v));
+        assert_eq!(v, b"01234567XY");
     }
 
     #[test]
-    fn recursive_mkdir() {
+    fn hard_link_or_copy() {
+        use fs::LinkResult;
+
         let tmpdir = tmpdir();
-        let dir = tmpdir.join("d1/d2");
-        check!(fs::create_dir_all(&dir));
-        assert!(dir.is_dir())
+        let src = tmpdir.join("src");
+        let dst = tmpdir.join("dst");
+        check!(check!(File::create(&src)).write_all(b"hello"));
+
+        assert_eq!(check!(fs::hard_link_or_copy(&src, &dst)), LinkResult::Linked);
+        let err = fs::hard_link_or_copy(&src, &dst).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert!(fs::hard_link_or_copy(&tmpdir.join("missing"), &tmpdir.join("x")).is_err());
+
+        // /dev/shm is usually on a different file system than the temp dir,
+        // which forces a copy.
+        if cfg!(target_os = "linux") && Path2::new("/dev/shm").is_dir() {
+            let copy = PathBuf::from(format!("/dev/shm/rust-fs-test-{}",
+                                             rand::thread_rng().next_u64()));
+            match check!(fs::hard_link_or_copy(&src, &copy)) {
+                LinkResult::Linked => {}
+                LinkResult::Copied(n) => assert_eq!(n, 5),
+            }
+            check!(fs::remove_file(&copy));
+        }
     }
 
     #[test]
-    fn recursive_mkdir_failure() {
+    #[cfg(unix)]
+    fn dir_builder_mode_exact() {
+        use os::unix::fs::{DirBuilderExt, PermissionsExt};
+
         let tmpdir = tmpdir();
-        let dir = tmpdir.join("d1");
-        let file = dir.join("f1");
+        let existing = tmpdir.join("existing");
+        check!(fs::create_dir(&existing));
+        check!(fs::set_permissions(&existing, fs::Permissions::from_mode(0o755)));
+        let plain = existing.join("a/b");
+        let exact = existing.join("c/d");
+
+        // The umask is process wide and other tests run concurrently, so it
+        // can't be changed here, only read. Linux 4.7 and up report it in
+        // `/proc/self/status`. Without a umask that masks something the test
+        // can't tell `mode_exact` from `mode`, so it's skipped then.
+        let status = fs::read_to_string("/proc/self/status").unwrap_or(String::new());
+        let umask = status.lines().filter_map(|line| {
+            if line.starts_with("Umask:") {
+                u32::from_str_radix(line["Umask:".len()..].trim(), 8).ok()
+            } else {
+                None
+            }
+        }).next();
+        let umask = match umask {
+            Some(umask) if umask & 0o777 != 0 => umask & 0o777,
+            _ => return,
+        };
+        check!(fs::DirBuilder::new().recursive(true).mode(0o777).create(&plain));
+        check!(fs::DirBuilder::new().recursive(true).mode_exact(0o777).create(&exact));
+
+        let mode = |p: &Path2| check!(fs::metadata(p)).permissions().mode() as u32 & 0o777;
+        assert_eq!(mode(plain.as_path()), 0o777 & !umask);
+        assert_eq!(mode(plain.parent().unwrap()), 0o777 & !umask);
+        assert_eq!(mode(exact.as_path()), 0o777);
+        assert_eq!(mode(exact.parent().unwrap()), 0o777);
+        assert_eq!(mode(existing.as_path()), 0o755);
+    }
 
-        check!(fs::create_dir_all(&dir));
+    #[test]
+    fn remove_if_exists() {
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("file");
+        let dir = tmpdir.join("dir");
         check!(File::create(&file));
+        check!(fs::create_dir(&dir));
 
-        let result = fs::create_dir_all(&file);
+        assert!(check!(fs::remove_file_if_exists(&file)));
+        assert!(!check!(fs::remove_file_if_exists(&file)));
+        assert!(!check!(fs::remove_file_if_exists(&tmpdir.join("missing/file"))));
 
-        assert!(result.is_err());
-        // error!(result, "couldn't recursively mkdir");
-        // error!(result, "couldn't create directory");
-        // error!(result, "mode=0700");
-        // error!(result, format!("path={}", file.display()));
+        check!(File::create(&dir.join("inner")));
+        assert!(fs::remove_dir_if_exists(&dir).is_err());
+        check!(fs::remove_file(&dir.join("inner")));
+        assert!(check!(fs::remove_dir_if_exists(&dir)));
+        assert!(!check!(fs::remove_dir_if_exists(&dir)));
+
+        // A file isn't a missing directory, and is left alone, but a path
+        // through it is missing like it is for `remove_file_if_exists`.
+        check!(File::create(&file));
+        assert!(fs::remove_dir_if_exists(&file).is_err());
+        assert!(file.exists());
+        assert!(!check!(fs::remove_dir_if_exists(&file.join("child"))));
+        assert!(!check!(fs::remove_file_if_exists(&file.join("child"))));
     }
 
     #[test]
-    fn recursive_mkdir_slash() {
-        check!(fs::create_dir_all(&Path2::new("/")));
+    fn copy_dir_all() {
+        use fs::CopyOptions;
+
+        let tmpdir = tmpdir();
+        let from = tmpdir.join("from");
+        check!(fs::create_dir_all(&from.join("a/b")));
+        check!(fs::create_dir(&from.join("empty")));
+        check!(check!(File::create(&from.join("f"))).write_all(b"hello"));
+        check!(check!(File::create(&from.join("a/b/g"))).write_all(b"world!"));
+
+        let to = tmpdir.join("to/nested");
+        assert_eq!(check!(fs::copy_dir_all(&from, &to, &CopyOptions::new())), 11);
+        assert!(to.join("empty").is_dir());
+        assert_eq!(check!(fs::metadata(&to.join("a/b/g"))).len(), 6);
+        assert_eq!(check!(fs::metadata(&to.join("f"))).len(), 5);
+
+        let err = fs::copy_dir_all(&from, &from.join("a/copy"), &CopyOptions::new());
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(!from.join("a/copy").exists());
+        assert!(fs::copy_dir_all(&from.join("f"), &tmpdir.join("x"), &CopyOptions::new())
+                   .is_err());
     }
 
-    // FIXME(#12795) depends on lstat to work on windows
-    #[cfg(not(windows))]
     #[test]
-    fn recursive_rmdir() {
-        let tmpdir = tmpdir();
-        let d1 = tmpdir.join("d1");
-        let dt = d1.join("t");
-        let dtt = dt.join("t");
-        let d2 = tmpdir.join("d2");
-        let canary = d2.join("do_not_delete");
-        check!(fs::create_dir_all(&dtt));
-        check!(fs::create_dir_all(&d2));
-        check!(check!(File::create(&canary)).write(b"foo"));
-        check!(fs::soft_link(&d2, &dt.join("d2")));
-        check!(fs::remove_dir_all(&d1));
+    #[cfg(unix)]
+    fn copy_dir_all_symlinks() {
+        use fs::CopyOptions;
+        use os::unix::fs::symlink;
 
-        assert!(!d1.is_dir());
-        assert!(canary.exists());
+        let tmpdir = tmpdir();
+        let from = tmpdir.join("from");
+        check!(fs::create_dir_all(&from.join("dir")));
+        check!(check!(File::create(&from.join("dir/f"))).write_all(b"abc"));
+        check!(symlink("dir/f", &from.join("link")));
+
+        let to = tmpdir.join("links");
+        let mut opts = CopyOptions::new();
+        opts.copy_symlinks_as_links(true);
+        assert_eq!(check!(fs::copy_dir_all(&from, &to, &opts)), 3);
+        assert_eq!(check!(fs::read_link(&to.join("link"))), Path2::new("dir/f"));
+
+        let to = tmpdir.join("followed");
+        assert_eq!(check!(fs::copy_dir_all(&from, &to, &CopyOptions::new())), 6);
+        assert!(check!(fs::symlink_metadata(&to.join("link"))).is_file());
+
+        check!(symlink("..", &from.join("dir/up")));
+        let err = fs::copy_dir_all(&from, &tmpdir.join("loop"), &CopyOptions::new());
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::FilesystemLoop);
+        check!(fs::copy_dir_all(&from, &tmpdir.join("noloop"), &opts));
     }
 
     #[test]
-    fn unicode_path_is_dir() {
-        assert!(Path2::new(".").is_dir());
-        assert!(!Path2::new("test/stdtest/fs.rs").is_dir());
+    fn walk_dir_symlink_loop() {
+        #[cfg(unix)] use os::unix::fs::symlink as symlink_dir;
+        #[cfg(windows)] use os::windows::fs::symlink_dir;
 
         let tmpdir = tmpdir();
+        let a = tmpdir.join("a");
+        check!(fs::create_dir(&a));
+        check!(File::create(&a.join("f")));
+        if !symlink_created(symlink_dir(&a, &a.join("b"))) {
+            return
+        }
 
-        let mut dirpath = tmpdir.path().to_path_buf();
-        dirpath.push(&format!("test-가一ー你好"));
-        check!(fs::create_dir(&dirpath));
-        assert!(dirpath.is_dir());
+        let (mut entries, mut loops) = (0, 0);
+        for entry in check!(fs::walk_dir(&a)) {
+            match entry {
+                Ok(_) => entries += 1,
+                Err(ref e) if e.kind() == ErrorKind::FilesystemLoop => loops += 1,
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert_eq!((entries, loops), (2, 1));
 
-        let mut filepath = dirpath;
-        filepath.push("unicode-file-\u{ac00}\u{4e00}\u{30fc}\u{4f60}\u{597d}.rs");
-        check!(File::create(&filepath)); // ignore return; touch only
-        assert!(!filepath.is_dir());
-        assert!(filepath.exists());
+        let err = fs::copy_dir_all(&a, &tmpdir.join("copy"), &fs::CopyOptions::new());
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::FilesystemLoop);
     }
 
     #[test]
-    fn unicode_path_exists() {
-        assert!(Path2::new(".").exists());
-        assert!(!Path2::new("test/nonexistent-bogus-path").exists());
+    #[cfg(target_os = "linux")]
+    fn file_manipulate_range() {
+        use os::linux::fs::{FallocMode, FileExt};
+
+        // Not every file system the temporary directory may be on supports
+        // every mode.
+        fn supported(r: ::io::Result<()>) -> bool {
+            match r {
+                Ok(()) => true,
+                Err(ref e) if e.raw_os_error() == Some(::libc::EOPNOTSUPP) => false,
+                Err(e) => panic!("{}", e),
+            }
+        }
+        fn contents(f: &mut File) -> Vec<u8> {
+            let mut v = Vec::new();
+            check!(f.seek(SeekFrom::Start(0)));
+            check!(f.read_to_end(&mut v));
+            v
+        }
 
+        const BLOCK: usize = 64 * 1024;
         let tmpdir = tmpdir();
-        let unicode = tmpdir.path();
-        let unicode = unicode.join(&format!("test-각丁ー再见"));
-        check!(fs::create_dir(&unicode));
-        assert!(unicode.exists());
-        assert!(!Path2::new("test/unicode-bogus-path-각丁ー再见").exists());
+        let path = tmpdir.join("file");
+        let mut f = check!(OpenOptions::new().read(true).write(true).create(true).open(&path));
+        let mut data = vec![1; BLOCK];
+        data.extend(vec![2; BLOCK]);
+        data.extend(vec![3; BLOCK]);
+        check!(f.write_all(&data));
+
+        if supported(f.manipulate_range(FallocMode::Allocate, 0, 4 * BLOCK as u64)) {
+            assert_eq!(check!(f.metadata()).len(), 4 * BLOCK as u64);
+            check!(f.set_len(3 * BLOCK as u64));
+        }
+        if supported(f.manipulate_range(FallocMode::PunchHole, 0, BLOCK as u64)) {
+            let v = contents(&mut f);
+            assert_eq!(v.len(), 3 * BLOCK);
+            assert!(v[..BLOCK].iter().all(|&b| b == 0));
+            assert!(v[BLOCK..] == data[BLOCK..]);
+        }
+        if supported(f.manipulate_range(FallocMode::CollapseRange, BLOCK as u64,
+                                        BLOCK as u64)) {
+            let v = contents(&mut f);
+            assert_eq!(v.len(), 2 * BLOCK);
+            assert!(v[BLOCK..].iter().all(|&b| b == 3));
+        }
+        let err = f.manipulate_range(FallocMode::PunchHole, u64::max_value(), 1).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
     }
 
     #[test]
-    fn copy_file_does_not_exist() {
-        let from = Path2::new("test/nonexistent-bogus-path");
-        let to = Path2::new("test/other-bogus-path");
+    fn canonicalize_cached() {
+        let tmpdir = tmpdir();
+        let dir = tmpdir.join("dir");
+        check!(fs::create_dir(&dir));
+        let cache = fs::PathCache::new();
+
+        let first = check!(fs::canonicalize_cached(&dir, &cache));
+        assert_eq!(first, check!(fs::canonicalize(&dir)));
+        assert_eq!(cache.len(), 1);
+
+        // The second lookup is served from the cache, even though the
+        // directory is gone by now.
+        check!(fs::remove_dir(&dir));
+        assert_eq!(check!(fs::canonicalize_cached(&dir, &cache)), first);
+
+        assert!(cache.remove(&dir));
+        assert!(!cache.remove(&dir));
+        assert!(fs::canonicalize_cached(&dir, &cache).is_err());
+        assert_eq!(cache.len(), 0);
+
+        check!(fs::canonicalize_cached(tmpdir.path(), &cache));
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+    }
 
-        match fs::copy(&from, &to) {
-            Ok(..) => panic!(),
-            Err(..) => {
-                assert!(!from.exists());
-                assert!(!to.exists());
-            }
+    #[test]
+    fn write_all_vectored() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let f = check!(File::create(&path));
+
+        let n = check!(f.write_vectored(&[&b"ab"[..], &b""[..], &b"cde"[..]]));
+        assert!(n <= 5);
+        check!(f.set_len(0));
+        check!((&f).seek(SeekFrom::Start(0)));
+
+        let big = vec![7; 100000];
+        let mut bufs = vec![&b""[..], &b"head"[..], &big[..], &b""[..], &b"tail"[..]];
+        // More slices than a single writev accepts.
+        for _ in 0..2000 {
+            bufs.push(b"x");
         }
+        check!(f.write_all_vectored(&bufs));
+        check!(f.write_all_vectored(&[]));
+        check!(f.write_all_vectored(&[&b""[..], &b""[..]]));
+
+        let mut expected = b"head".to_vec();
+        expected.push_all(&big);
+        expected.push_all(b"tail");
+        expected.extend(vec![b'x'; 2000]);
+        let mut v = Vec::new();
+        check!(check!(File::open(&path)).read_to_end(&mut v));
+        assert!(v == expected);
     }
 
     #[test]
-    fn copy_src_does_not_exist() {
+    fn read_vectored() {
         let tmpdir = tmpdir();
-        let from = Path2::new("test/nonexistent-bogus-path");
-        let to = tmpdir.join("out.txt");
-        check!(check!(File::create(&to)).write(b"hello"));
-        assert!(fs::copy(&from, &to).is_err());
-        assert!(!from.exists());
-        let mut v = Vec::new();
-        check!(check!(File::open(&to)).read_to_end(&mut v));
-        assert_eq!(v, b"hello");
+        let path = tmpdir.join("file");
+        check!(check!(File::create(&path)).write_all(b"headerbody"));
+
+        let f = check!(File::open(&path));
+        let mut header = [0; 6];
+        let mut empty = [0; 0];
+        let mut body = [0; 10];
+        let n = check!(f.read_vectored(&mut [&mut header[..], &mut empty[..], &mut body[..]]));
+        assert_eq!(n, 10);
+        assert_eq!(&header, b"header");
+        assert_eq!(&body[..4], b"body");
+        assert_eq!(check!((&f).seek(SeekFrom::Current(0))), 10);
+        assert_eq!(check!(f.read_vectored(&mut [&mut header[..], &mut body[..]])), 0);
+
+        check!((&f).seek(SeekFrom::Start(2)));
+        let mut a = [0; 2];
+        let mut b = [0; 2];
+        assert_eq!(check!(f.read_vectored(&mut [&mut a[..], &mut b[..]])), 4);
+        assert_eq!((&a, &b), (b"ad", b"er"));
+        assert_eq!(check!((&f).seek(SeekFrom::Current(0))), 6);
     }
 
     #[test]
-    fn copy_file_ok() {
+    fn advance_slices() {
+        let mut bufs = vec![&b"abc"[..], &b""[..], &b"de"[..], &b"f"[..]];
+        let mut start = 0;
+        super::advance_slices(&mut bufs, &mut start, 2);
+        assert_eq!((start, bufs[start]), (0, &b"c"[..]));
+        super::advance_slices(&mut bufs, &mut start, 1);
+        assert_eq!((start, bufs[start]), (2, &b"de"[..]));
+        super::advance_slices(&mut bufs, &mut start, 3);
+        assert_eq!(start, 4);
+    }
+
+    #[test]
+    fn symlink_exists() {
+        #[cfg(unix)] use os::unix::fs::symlink;
+        #[cfg(windows)] use os::windows::fs::symlink_file as symlink;
+
         let tmpdir = tmpdir();
-        let input = tmpdir.join("in.txt");
-        let out = tmpdir.join("out.txt");
+        let file = tmpdir.join("file");
+        let link = tmpdir.join("link");
+        assert!(!fs::symlink_exists(&file));
+        check!(File::create(&file));
+        assert!(fs::symlink_exists(&file));
 
-        check!(check!(File::create(&input)).write(b"hello"));
-        check!(fs::copy(&input, &out));
-        let mut v = Vec::new();
-        check!(check!(File::open(&out)).read_to_end(&mut v));
-        assert_eq!(v, b"hello");
+        if !symlink_created(symlink(&file, &link)) {
+            return
+        }
+        check!(fs::remove_file(&file));
+        assert!(!link.exists());
+        assert!(fs::symlink_exists(&link));
+    }
 
-        assert_eq!(check!(input.metadata()).permissions(),
-                   check!(out.metadata()).permissions());
+    #[test]
+    fn subdir_count() {
+        let tmpdir = tmpdir();
+        let dir = tmpdir.join("dir");
+        check!(fs::create_dir(&dir));
+        check!(fs::create_dir(&dir.join("a")));
+        check!(fs::create_dir(&dir.join("b")));
+        check!(File::create(&dir.join("file")));
+
+        match check!(fs::subdir_count(&dir)) {
+            Some(n) => assert_eq!(n, 2),
+            None => {}
+        }
+        assert_eq!(check!(fs::subdir_count(&dir.join("file"))), None);
+        assert!(fs::subdir_count(&dir.join("missing")).is_err());
     }
 
     #[test]
-    fn copy_file_dst_dir() {
+    fn file_rewind_and_seek_relative() {
         let tmpdir = tmpdir();
-        let out = tmpdir.join("out");
+        let path = tmpdir.join("file");
+        let mut f = check!(OpenOptions::new().read(true).write(true).create(true).open(&path));
+        check!(f.write_all(b"0123456789"));
+
+        check!(f.rewind());
+        assert_eq!(check!(f.stream_position()), 0);
+        check!(f.seek_relative(4));
+        check!(f.seek_relative(-1));
+        assert_eq!(check!(f.stream_position()), 3);
+        let mut buf = [0; 2];
+        check!(f.read_exact(&mut buf));
+        assert_eq!(&buf, b"34");
+        assert!(f.seek_relative(-10).is_err());
+    }
 
-        check!(File::create(&out));
-        match fs::copy(&*out, tmpdir.path()) {
-            Ok(..) => panic!(), Err(..) => {}
+    #[test]
+    #[cfg(any(windows, target_os = "linux", target_os = "android", target_os = "macos",
+              target_os = "ios", target_os = "freebsd"))]
+    fn filesystem_type() {
+        let tmpdir = tmpdir();
+        let fs_type = check!(fs::filesystem_type(tmpdir.path()));
+        assert!(!fs_type.name().is_empty());
+        assert_eq!(fs_type.magic().is_some(), cfg!(any(target_os = "linux",
+                                                       target_os = "android")));
+        if cfg!(windows) {
+            assert!(fs_type.kind() != fs::FsKind::Other);
         }
+        let e = fs::filesystem_type(tmpdir.join("missing")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::NotFound);
     }
 
     #[test]
-    fn copy_file_dst_exists() {
+    fn read_into_appends() {
         let tmpdir = tmpdir();
-        let input = tmpdir.join("in");
-        let output = tmpdir.join("out");
+        let path = tmpdir.join("file");
+        check!(check!(File::create(&path)).write_all(b"hello"));
 
-        check!(check!(File::create(&input)).write("foo".as_bytes()));
-        check!(check!(File::create(&output)).write("bar".as_bytes()));
-        check!(fs::copy(&input, &output));
+        let mut buf = b"say ".to_vec();
+        assert_eq!(check!(fs::read_into(&path, &mut buf)), 5);
+        assert_eq!(buf, b"say hello");
 
-        let mut v = Vec::new();
-        check!(check!(File::open(&output)).read_to_end(&mut v));
-        assert_eq!(v, b"foo".to_vec());
+        check!(File::create(&path));
+        assert_eq!(check!(fs::read_into(&path, &mut buf)), 0);
+        assert_eq!(buf, b"say hello");
+        assert!(fs::read_into(&tmpdir.join("missing"), &mut buf).is_err());
     }
 
     #[test]
-    fn copy_file_src_dir() {
+    fn append_from_many_handles_does_not_tear() {
+        use sync::Arc;
+        use thread;
+
+        const THREADS: usize = 8;
+        const RECORDS: usize = 500;
+        const LEN: usize = 256;
+
         let tmpdir = tmpdir();
-        let out = tmpdir.join("out");
+        let path = Arc::new(tmpdir.join("log"));
+        check!(File::create(&*path));
+
+        let threads = (0..THREADS).map(|i| {
+            let path = path.clone();
+            thread::spawn(move || {
+                // Every thread has its own handle, just like separate
+                // processes would.
+                let mut f = check!(OpenOptions::new().write(true).append(true).open(&*path));
+                let mut record = vec![b'a' + i as u8; LEN];
+                record[LEN - 1] = b'\n';
+                for _ in 0..RECORDS {
+                    assert_eq!(check!(f.write(&record)), LEN);
+                }
+            })
+        }).collect::<Vec<_>>();
+        for t in threads {
+            t.join().unwrap();
+        }
 
-        match fs::copy(tmpdir.path(), &out) {
-            Ok(..) => panic!(), Err(..) => {}
+        let data = check!(fs::read(&*path));
+        assert_eq!(data.len(), THREADS * RECORDS * LEN);
+        let mut counts = [0; THREADS];
+        for record in data.chunks(LEN) {
+            let c = record[0];
+            assert!(record[..LEN - 1].iter().all(|&b| b == c), "torn record");
+            assert_eq!(record[LEN - 1], b'\n');
+            counts[(c - b'a') as usize] += 1;
         }
-        assert!(!out.exists());
+        assert!(counts.iter().all(|&n| n == RECORDS));
     }
 
     #[test]
-    fn copy_file_preserves_perm_bits() {
+    fn read_link_absolute() {
+        #[cfg(unix)] use os::unix::fs::symlink as symlink_file;
+        #[cfg(windows)] use os::windows::fs::symlink_file;
+
         let tmpdir = tmpdir();
-        let input = tmpdir.join("in.txt");
-        let out = tmpdir.join("out.txt");
+        let dir = tmpdir.join("dir");
+        check!(fs::create_dir(&dir));
+        let link = dir.join("link");
+        let target = Path2::new("..").join(".").join("other").join("..").join("file");
+        if !symlink_created(symlink_file(&target, &link)) {
+            return
+        }
+        // The target doesn't exist, and doesn't have to.
+        assert_eq!(check!(fs::read_link_absolute(&link)), tmpdir.join("file"));
 
-        let attr = check!(check!(File::create(&input)).metadata());
-        let mut p = attr.permissions();
-        p.set_readonly(true);
-        check!(fs::set_permissions(&input, p));
-        check!(fs::copy(&input, &out));
-        assert!(check!(out.metadata()).permissions().readonly());
-        check!(fs::set_permissions(&input, attr.permissions()));
-        check!(fs::set_permissions(&out, attr.permissions()));
+        let abs = tmpdir.join("abs");
+        check!(symlink_file(&tmpdir.join("x").join("..").join("y"), &abs));
+        assert_eq!(check!(fs::read_link_absolute(&abs)), tmpdir.join("y"));
+        assert!(fs::read_link_absolute(&dir).is_err());
     }
 
-    #[cfg(windows)]
     #[test]
-    fn copy_file_preserves_streams() {
-        let tmp = tmpdir();
-        check!(check!(File::create(tmp.join("in.txt:bunny"))).write("carrot".as_bytes()));
-        assert_eq!(check!(fs::copy(tmp.join("in.txt"), tmp.join("out.txt"))), 6);
-        assert_eq!(check!(tmp.join("out.txt").metadata()).len(), 0);
-        let mut v = Vec::new();
-        check!(check!(File::open(tmp.join("out.txt:bunny"))).read_to_end(&mut v));
-        assert_eq!(v, b"carrot".to_vec());
+    fn file_clear() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let mut f = check!(OpenOptions::new().read(true).write(true).create(true).open(&path));
+        check!(f.write_all(b"old contents"));
+
+        check!(f.clear());
+        assert_eq!(check!(f.stream_position()), 0);
+        assert_eq!(check!(f.metadata()).len(), 0);
+        check!(f.write_all(b"new"));
+        assert_eq!(check!(fs::read(&path)), b"new");
+
+        let ro = check!(File::open(&path));
+        assert!(ro.clear().is_err());
     }
 
-    #[cfg(not(windows))] // FIXME(#10264) operation not permitted?
     #[test]
-    fn symlinks_work() {
+    fn metadata_trailing_separator_requires_dir() {
         let tmpdir = tmpdir();
-        let input = tmpdir.join("in.txt");
-        let out = tmpdir.join("out.txt");
+        let file = tmpdir.join("file.txt");
+        let dir = tmpdir.join("dir");
+        check!(File::create(&file));
+        check!(fs::create_dir(&dir));
 
-        check!(check!(File::create(&input)).write("foobar".as_bytes()));
-        check!(fs::soft_link(&input, &out));
-        // if cfg!(not(windows)) {
-        //     assert_eq!(check!(lstat(&out)).kind, FileType::Symlink);
-        //     assert_eq!(check!(out.lstat()).kind, FileType::Symlink);
-        // }
-        assert_eq!(check!(fs::metadata(&out)).len(),
-                   check!(fs::metadata(&input)).len());
-        let mut v = Vec::new();
-        check!(check!(File::open(&out)).read_to_end(&mut v));
-        assert_eq!(v, b"foobar".to_vec());
+        let sep = if cfg!(windows) { "\\" } else { "/" };
+        let file_sep = format!("{}{}", file.display(), sep);
+        let dir_sep = format!("{}{}", dir.display(), sep);
+
+        let e = fs::metadata(&file_sep).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::NotADirectory);
+        let e = File::open(&file_sep).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::NotADirectory);
+        assert!(check!(fs::metadata(&dir_sep)).is_dir());
+    }
+
+    #[test]
+    fn sync_barrier() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("journal");
+        let mut f = check!(File::create(&path));
+        check!(f.write_all(b"entry"));
+        check!(f.sync_barrier());
+        check!(f.write_all(b" applied"));
+        check!(f.sync_barrier());
+
+        let mut s = String::new();
+        check!(check!(File::open(&path)).read_to_string(&mut s));
+        assert_eq!(s, "entry applied");
+    }
+
+    #[test]
+    fn dir_entry_borrowed_name() {
+        #[cfg(unix)] use os::unix::fs::DirEntryExt;
+        #[cfg(windows)] use os::windows::fs::DirEntryExt;
+
+        let tmpdir = tmpdir();
+        check!(File::create(&tmpdir.join("match.txt")));
+        check!(File::create(&tmpdir.join("other.rs")));
+
+        let mut found = Vec::new();
+        for entry in check!(fs::read_dir(tmpdir.path())) {
+            let entry = check!(entry);
+            #[cfg(unix)]
+            fn matches(entry: &fs::DirEntry) -> bool {
+                entry.file_name_bytes().ends_with(b".txt")
+            }
+            #[cfg(windows)]
+            fn matches(entry: &fs::DirEntry) -> bool {
+                let suffix = ".txt".utf16_units().collect::<Vec<u16>>();
+                entry.file_name_wide().ends_with(&suffix)
+            }
+            if matches(&entry) {
+                found.push(entry.file_name());
+            }
+        }
+        assert_eq!(found, vec![::ffi::OsString::from("match.txt")]);
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", windows))]
+    fn open_running_executable_for_write() {
+        let exe = check!(env::current_exe());
+        let e = OpenOptions::new().write(true).open(&exe).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ExecutableBusy);
+        check!(File::open(&exe));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn open_locked_file_for_write_keeps_os_error() {
+        use os::windows::fs::OpenOptionsExt;
+
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("locked.txt");
+        check!(check!(File::create(&path)).write_all(b"not an executable"));
+        let _locked = check!(OpenOptions::new().read(true).share_mode(0).open(&path));
+        let e = OpenOptions::new().write(true).open(&path).unwrap_err();
+        // ERROR_SHARING_VIOLATION
+        assert_eq!(e.raw_os_error(), Some(32));
     }
 
-    #[cfg(not(windows))] // apparently windows doesn't like symlinks
     #[test]
-    fn symlink_noexist() {
+    #[cfg(unix)]
+    fn dup_to() {
+        use os::unix::fs::FileExt;
+        use os::unix::io::{AsRawFd, IntoRawFd};
+
         let tmpdir = tmpdir();
-        // symlinks can point to things that don't exist
-        check!(fs::soft_link(&tmpdir.join("foo"), &tmpdir.join("bar")));
-        assert_eq!(check!(fs::read_link(&tmpdir.join("bar"))),
-                   tmpdir.join("foo"));
+        let path = tmpdir.join("dup");
+        let mut f = check!(File::create(&path));
+        // Take the target number from a file of our own, so that no other
+        // test's descriptor gets clobbered.
+        let target = check!(File::create(&tmpdir.join("placeholder"))).into_raw_fd();
+
+        // `target` was given up by its file above, so it's ours to reuse.
+        let mut dup = check!(unsafe { f.dup_to(target, false) }).unwrap();
+        assert_eq!(dup.as_raw_fd(), target);
+        let flags = unsafe { ::libc::fcntl(target, ::libc::F_GETFD) };
+        assert_eq!(flags & 1, 0);
+        check!(dup.write_all(b"hello"));
+        // The original is still open.
+        check!(f.write_all(b" world"));
+        assert_eq!(check!(fs::read_to_string(&path)), "hello world");
+
+        assert!(check!(unsafe { dup.dup_to(target, true) }).is_none());
+        let flags = unsafe { ::libc::fcntl(target, ::libc::F_GETFD) };
+        assert_eq!(flags & 1, 1);
+
+        // A failed duplication leaves the file alone.
+        assert!(unsafe { f.dup_to(-1, false) }.is_err());
+        check!(f.write_all(b"!"));
     }
 
     #[test]
-    fn readlink_not_symlink() {
+    #[cfg(target_os = "linux")]
+    fn symlink_metadata_no_automount() {
+        use os::linux::fs::symlink_metadata_no_automount;
+
         let tmpdir = tmpdir();
-        match fs::read_link(tmpdir.path()) {
-            Ok(..) => panic!("wanted a failure"),
-            Err(..) => {}
-        }
+        let file = tmpdir.join("file");
+        let link = tmpdir.join("link");
+        check!(check!(File::create(&file)).write_all(b"four"));
+        check!(fs::soft_link(&file, &link));
+
+        let m = check!(symlink_metadata_no_automount(&file));
+        assert!(m.is_file());
+        assert_eq!(m.len(), 4);
+        let m = check!(symlink_metadata_no_automount(&link));
+        assert!(m.file_type().is_symlink());
+        assert!(check!(symlink_metadata_no_automount(tmpdir.path())).is_dir());
+
+        let e = symlink_metadata_no_automount(tmpdir.join("missing")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::NotFound);
     }
 
     #[test]
-    fn links_work() {
+    #[cfg(unix)]
+    fn file_lock_exclusive_timeout() {
+        use os::unix::fs::FileExt;
+        use sync::Arc;
+        use thread;
+        use time::Duration;
+
         let tmpdir = tmpdir();
-        let input = tmpdir.join("in.txt");
-        let out = tmpdir.join("out.txt");
+        let path = tmpdir.join("file");
+        let a = Arc::new(check!(OpenOptions::new().write(true).create(true).open(&path)));
+        let b = check!(OpenOptions::new().write(true).open(&path));
+
+        check!(a.lock_exclusive());
+        assert!(!check!(b.try_lock_exclusive()));
+        assert!(!check!(b.lock_exclusive_timeout(Duration::from_millis(0))));
+        let elapsed = Duration::span(|| {
+            assert!(!check!(b.lock_exclusive_timeout(Duration::from_millis(50))));
+        });
+        assert!(elapsed >= Duration::from_millis(50));
+
+        let a2 = a.clone();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            check!(a2.unlock());
+        });
+        assert!(check!(b.lock_exclusive_timeout(Duration::from_secs(60))));
+        t.join().unwrap();
+        assert!(!check!(a.try_lock_exclusive()));
+        check!(b.unlock());
+        assert!(check!(a.try_lock_exclusive()));
+        check!(a.unlock());
+    }
 
-        check!(check!(File::create(&input)).write("foobar".as_bytes()));
-        check!(fs::hard_link(&input, &out));
-        assert_eq!(check!(fs::metadata(&out)).len(),
-                   check!(fs::metadata(&input)).len());
-        assert_eq!(check!(fs::metadata(&out)).len(),
-                   check!(input.metadata()).len());
-        let mut v = Vec::new();
-        check!(check!(File::open(&out)).read_to_end(&mut v));
-        assert_eq!(v, b"foobar".to_vec());
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn file_lock_ofd() {
+        use os::linux::fs::FileExt;
+        use os::unix::io::AsRawFd;
 
-        // can't link to yourself
-        match fs::hard_link(&input, &input) {
-            Ok(..) => panic!("wanted a failure"),
-            Err(..) => {}
-        }
-        // can't link to something that doesn't exist
-        match fs::hard_link(&tmpdir.join("foo"), &tmpdir.join("bar")) {
-            Ok(..) => panic!("wanted a failure"),
-            Err(..) => {}
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        let a = check!(OpenOptions::new().read(true).write(true).create(true).open(&path));
+        let b = check!(OpenOptions::new().read(true).write(true).open(&path));
+        // Takes an exclusive OFD lock if that's possible without waiting.
+        let try_exclusive = |f: &File| unsafe {
+            let mut lock: ::sys::c::flock = ::mem::zeroed();
+            lock.l_type = ::sys::c::F_WRLCK as ::libc::c_short;
+            ::libc::fcntl(f.as_raw_fd(), ::sys::c::F_OFD_SETLK, &lock as *const _) == 0
+        };
+
+        match a.lock_exclusive_ofd() {
+            Ok(()) => {}
+            // Kernels before 3.15.
+            Err(ref e) if e.kind() == ErrorKind::Other => return,
+            Err(e) => panic!("lock_exclusive_ofd failed with: {}", e),
         }
+        assert!(!try_exclusive(&b));
+        // Unlike a process-wide fcntl lock, closing an unrelated descriptor
+        // for the same file leaves the lock alone.
+        drop(check!(File::open(&path)));
+        assert!(!try_exclusive(&b));
+        check!(a.unlock_ofd());
+
+        check!(a.lock_shared_ofd());
+        check!(b.lock_shared_ofd());
+        assert!(!try_exclusive(&b));
+        check!(a.unlock_ofd());
+        assert!(try_exclusive(&b));
+        check!(b.unlock_ofd());
     }
 
     #[test]
-    fn chmod_works() {
-        let tmpdir = tmpdir();
-        let file = tmpdir.join("in.txt");
-
-        check!(File::create(&file));
-        let attr = check!(fs::metadata(&file));
-        assert!(!attr.permissions().readonly());
-        let mut p = attr.permissions();
-        p.set_readonly(true);
-        check!(fs::set_permissions(&file, p.clone()));
-        let attr = check!(fs::metadata(&file));
-        assert!(attr.permissions().readonly());
+    #[cfg(all(target_os = "linux",
+              any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm",
+                  target_arch = "aarch64", target_arch = "powerpc")))]
+    fn metadata_times_after_2038() {
+        use os::unix::fs::MetadataExt;
+
+        // `utimensat` takes 64-bit times on 64-bit targets; 32-bit ones need
+        // `utimensat_time64` (Linux 5.1 and later) instead.
+        #[cfg(target_arch = "x86_64")]
+        const NR_UTIMENSAT: ::libc::c_long = 280;
+        #[cfg(target_arch = "aarch64")]
+        const NR_UTIMENSAT: ::libc::c_long = 88;
+        #[cfg(any(target_arch = "x86", target_arch = "arm", target_arch = "powerpc"))]
+        const NR_UTIMENSAT: ::libc::c_long = 412;
 
-        match fs::set_permissions(&tmpdir.join("foo"), p.clone()) {
-            Ok(..) => panic!("wanted an error"),
-            Err(..) => {}
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("future");
+        check!(File::create(&path));
+        // 2100-01-01T00:00:00Z
+        let secs: i64 = 4102444800;
+        let times: [[i64; 2]; 2] = [[secs, 0], [secs, 0]];
+        let c_path = ::ffi::CString::new(path.to_str().unwrap()).unwrap();
+        let ret = unsafe {
+            ::sys::c::syscall(NR_UTIMENSAT, ::sys::c::AT_FDCWD, c_path.as_ptr(),
+                              times.as_ptr(), 0)
+        };
+        if ret != 0 {
+            // Either the kernel or the file system can't store such a time.
+            return
         }
 
-        p.set_readonly(false);
-        check!(fs::set_permissions(&file, p));
+        let meta = check!(fs::metadata(&path));
+        assert_eq!(meta.atime64(), secs);
+        assert_eq!(meta.mtime64(), secs);
+        let meta = check!(check!(File::open(&path)).metadata());
+        assert_eq!(meta.mtime64(), secs);
+        let meta = check!(fs::symlink_metadata(&path));
+        assert_eq!(meta.mtime64(), secs);
     }
 
     #[test]
-    fn sync_doesnt_kill_anything() {
+    fn read_ignores_size_hint() {
         let tmpdir = tmpdir();
-        let path = tmpdir.join("in.txt");
+        let path = tmpdir.join("file");
+        let data = (0..100_000).map(|i| i as u8).collect::<Vec<u8>>();
+        check!(check!(File::create(&path)).write_all(&data));
+        assert_eq!(check!(fs::read(&path)), data);
+        check!(File::create(&tmpdir.join("empty")));
+        assert!(check!(fs::read(&tmpdir.join("empty"))).is_empty());
+    }
 
-        let mut file = check!(File::create(&path));
-        check!(file.sync_all());
-        check!(file.sync_data());
-        check!(file.write(b"foo"));
-        check!(file.sync_all());
-        check!(file.sync_data());
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn read_proc_file_with_zero_size() {
+        let path = Path2::new("/proc/self/status");
+        assert_eq!(check!(fs::metadata(path)).len(), 0);
+        let status = check!(fs::read_to_string(path));
+        assert!(status.contains("Pid:"));
     }
 
     #[test]
-    fn truncate_works() {
+    fn metadata_of_open_file() {
         let tmpdir = tmpdir();
-        let path = tmpdir.join("in.txt");
-
-        let mut file = check!(File::create(&path));
-        check!(file.write(b"foo"));
-        check!(file.sync_all());
-
-        // Do some simple things with truncation
-        assert_eq!(check!(file.metadata()).len(), 3);
-        check!(file.set_len(10));
-        assert_eq!(check!(file.metadata()).len(), 10);
-        check!(file.write(b"bar"));
-        check!(file.sync_all());
-        assert_eq!(check!(file.metadata()).len(), 10);
-
-        let mut v = Vec::new();
-        check!(check!(File::open(&path)).read_to_end(&mut v));
-        assert_eq!(v, b"foobar\0\0\0\0".to_vec());
-
-        // Truncate to a smaller length, don't seek, and then write something.
-        // Ensure that the intermediate zeroes are all filled in (we have `seek`ed
-        // past the end of the file).
-        check!(file.set_len(2));
-        assert_eq!(check!(file.metadata()).len(), 2);
-        check!(file.write(b"wut"));
-        check!(file.sync_all());
-        assert_eq!(check!(file.metadata()).len(), 9);
-        let mut v = Vec::new();
-        check!(check!(File::open(&path)).read_to_end(&mut v));
-        assert_eq!(v, b"fo\0\0\0\0wut".to_vec());
+        let path = tmpdir.join("file");
+        let mut f = check!(File::create(&path));
+        check!(f.write(b"four"));
+        check!(fs::rename(&path, &tmpdir.join("renamed")));
+        assert_eq!(check!(fs::metadata_of(&f)).len(), 4);
     }
 
     #[test]
-    fn open_flavors() {
-        use fs::OpenOptions as OO;
-        fn c<T: Clone>(t: &T) -> T { t.clone() }
+    #[cfg(target_os = "linux")]
+    fn copy_preserves_user_xattr() {
+        use fs::CopyOptions;
+        use libc;
+        use os::unix::prelude::*;
+        use sys::c;
 
         let tmpdir = tmpdir();
+        let from = tmpdir.join("from");
+        let to = tmpdir.join("to");
+        let f = check!(File::create(&from));
+        let name = b"user.rust-test\0";
+        let ret = unsafe {
+            c::fsetxattr(f.as_raw_fd(), name.as_ptr() as *const _,
+                         b"value".as_ptr() as *const _, 5, 0)
+        };
+        if ret != 0 {
+            // The file system of the temporary directory may not support
+            // user attributes.
+            return
+        }
+        // The copy only gets the source's permissions after its attributes.
+        check!(fs::set_permissions(&from, fs::Permissions::from_mode(0o444)));
+
+        check!(fs::copy_with_options(&from, &to, CopyOptions::new().preserve_xattrs(true)));
+        assert_eq!(check!(fs::metadata(&to)).permissions().mode() & 0o777, 0o444);
+        let dst = check!(File::open(&to));
+        let mut buf = [0u8; 16];
+        let n = unsafe {
+            c::fgetxattr(dst.as_raw_fd(), name.as_ptr() as *const _,
+                         buf.as_mut_ptr() as *mut _, buf.len() as libc::size_t)
+        };
+        assert_eq!(n, 5);
+        assert_eq!(&buf[..5], b"value");
+    }
 
-        let mut r = OO::new(); r.read(true);
-        let mut w = OO::new(); w.write(true);
-        let mut rw = OO::new(); rw.write(true).read(true);
+    #[test]
+    fn file_type_describe() {
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("file");
+        check!(File::create(&file));
+        assert_eq!(check!(fs::metadata(tmpdir.path())).file_type().describe(), "directory");
+        assert_eq!(check!(fs::metadata(&file)).file_type().describe(), "file");
+    }
 
-        match r.open(&tmpdir.join("a")) {
-            Ok(..) => panic!(), Err(..) => {}
+    #[test]
+    fn read_dir_with_metadata_matches_metadata() {
+        let tmpdir = tmpdir();
+        check!(fs::create_dir(&tmpdir.join("dir")));
+        check!(check!(File::create(&tmpdir.join("file"))).write(b"abc"));
+
+        let mut seen = 0;
+        for entry in check!(fs::read_dir_with_metadata(tmpdir.path())) {
+            let (entry, metadata) = check!(entry);
+            let metadata = check!(metadata);
+            let expected = check!(entry.metadata());
+            assert_eq!(metadata.is_dir(), expected.is_dir());
+            assert_eq!(metadata.len(), expected.len());
+            if metadata.is_file() {
+                assert_eq!(metadata.len(), 3);
+            }
+            seen += 1;
         }
+        assert_eq!(seen, 2);
+    }
 
-        // Perform each one twice to make sure that it succeeds the second time
-        // (where the file exists)
-        check!(c(&w).create(true).open(&tmpdir.join("b")));
-        assert!(tmpdir.join("b").exists());
-        check!(c(&w).create(true).open(&tmpdir.join("b")));
-        check!(w.open(&tmpdir.join("b")));
-
-        check!(c(&rw).create(true).open(&tmpdir.join("c")));
-        assert!(tmpdir.join("c").exists());
-        check!(c(&rw).create(true).open(&tmpdir.join("c")));
-        check!(rw.open(&tmpdir.join("c")));
+    #[test]
+    fn create_new_refuses_existing() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("file");
+        check!(OpenOptions::new().write(true).create_new(true).open(&path));
+        let e = OpenOptions::new().write(true).create_new(true).open(&path).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AlreadyExists);
+    }
 
-        check!(c(&w).append(true).create(true).open(&tmpdir.join("d")));
-        assert!(tmpdir.join("d").exists());
-        check!(c(&w).append(true).create(true).open(&tmpdir.join("d")));
-        check!(c(&w).append(true).open(&tmpdir.join("d")));
+    #[test]
+    fn create_new_refuses_dangling_symlink() {
+        #[cfg(unix)] use os::unix::fs::symlink;
+        #[cfg(windows)] use os::windows::fs::symlink_file as symlink;
 
-        check!(c(&rw).append(true).create(true).open(&tmpdir.join("e")));
-        assert!(tmpdir.join("e").exists());
-        check!(c(&rw).append(true).create(true).open(&tmpdir.join("e")));
-        check!(c(&rw).append(true).open(&tmpdir.join("e")));
+        let tmpdir = tmpdir();
+        let target = tmpdir.join("target");
+        let link = tmpdir.join("link");
+        if !symlink_created(symlink(&target, &link)) {
+            return
+        }
 
-        check!(c(&w).truncate(true).create(true).open(&tmpdir.join("f")));
-        assert!(tmpdir.join("f").exists());
-        check!(c(&w).truncate(true).create(true).open(&tmpdir.join("f")));
-        check!(c(&w).truncate(true).open(&tmpdir.join("f")));
+        let e = OpenOptions::new().write(true).create_new(true).open(&link).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AlreadyExists);
+        assert!(!target.exists());
+    }
 
-        check!(c(&rw).truncate(true).create(true).open(&tmpdir.join("g")));
-        assert!(tmpdir.join("g").exists());
-        check!(c(&rw).truncate(true).create(true).open(&tmpdir.join("g")));
-        check!(c(&rw).truncate(true).open(&tmpdir.join("g")));
+    #[test]
+    fn create_new_refuses_symlink_to_existing_file() {
+        #[cfg(unix)] use os::unix::fs::symlink;
+        #[cfg(windows)] use os::windows::fs::symlink_file as symlink;
 
-        check!(check!(File::create(&tmpdir.join("h"))).write("foo".as_bytes()));
-        check!(r.open(&tmpdir.join("h")));
-        {
-            let mut f = check!(r.open(&tmpdir.join("h")));
-            assert!(f.write("wut".as_bytes()).is_err());
-        }
-        assert_eq!(check!(fs::metadata(&tmpdir.join("h"))).len(), 3);
-        {
-            let mut f = check!(c(&w).append(true).open(&tmpdir.join("h")));
-            check!(f.write("bar".as_bytes()));
-        }
-        assert_eq!(check!(fs::metadata(&tmpdir.join("h"))).len(), 6);
-        {
-            let mut f = check!(c(&w).truncate(true).open(&tmpdir.join("h")));
-            check!(f.write("bar".as_bytes()));
+        let tmpdir = tmpdir();
+        let target = tmpdir.join("target");
+        let link = tmpdir.join("link");
+        check!(check!(File::create(&target)).write(b"secret"));
+        if !symlink_created(symlink(&target, &link)) {
+            return
         }
-        assert_eq!(check!(fs::metadata(&tmpdir.join("h"))).len(), 3);
+
+        let e = OpenOptions::new().write(true).truncate(true).create_new(true)
+                                  .open(&link).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(check!(fs::metadata(&target)).len(), 6);
+        assert!(check!(fs::symlink_metadata(&link)).file_type().is_symlink());
     }
 
     #[test]
-    fn binary_file() {
-        let mut bytes = [0; 1024];
-        StdRng::new().unwrap().fill_bytes(&mut bytes);
-
+    fn remove_dir_all_readonly_contents() {
         let tmpdir = tmpdir();
+        let dir = tmpdir.join("dir");
+        let sub = dir.join("sub");
+        check!(fs::create_dir_all(&sub));
+        check!(check!(File::create(&dir.join("file"))).set_readonly(true));
+        check!(check!(File::create(&sub.join("file"))).set_readonly(true));
+        // Unix directories need their write bit to have entries removed, so
+        // only the Windows attribute can be tested on a subdirectory.
+        if cfg!(windows) {
+            let mut perm = check!(fs::metadata(&sub)).permissions();
+            perm.set_readonly(true);
+            check!(fs::set_permissions(&sub, perm));
+        }
 
-        check!(check!(File::create(&tmpdir.join("test"))).write(&bytes));
-        let mut v = Vec::new();
-        check!(check!(File::open(&tmpdir.join("test"))).read_to_end(&mut v));
-        assert!(v == &bytes[..]);
+        check!(fs::remove_dir_all(&dir));
+        assert!(!dir.exists());
     }
 
     #[test]
-    #[cfg(not(windows))]
-    fn unlink_readonly() {
+    fn force_remove_readonly_file() {
         let tmpdir = tmpdir();
         let path = tmpdir.join("file");
-        check!(File::create(&path));
-        let mut perm = check!(fs::metadata(&path)).permissions();
-        perm.set_readonly(true);
-        check!(fs::set_permissions(&path, perm));
-        check!(fs::remove_file(&path));
+        check!(check!(File::create(&path)).set_readonly(true));
+
+        check!(fs::force_remove_file(&path));
+        assert!(!path.exists());
+        assert!(fs::force_remove_file(&path).is_err());
     }
 
     #[test]
-    fn mkdir_trailing_slash() {
+    fn remove_file_retry_readonly() {
+        use time::Duration;
+
         let tmpdir = tmpdir();
         let path = tmpdir.join("file");
-        check!(fs::create_dir_all(&path.join("a/")));
+        let f = check!(File::create(&path));
+        check!(f.set_readonly(true));
+        drop(f);
+
+        check!(fs::remove_file_retry(&path, 3, Duration::from_millis(10)));
+        assert!(!path.exists());
+        assert!(fs::remove_file_retry(&path, 3, Duration::from_millis(10)).is_err());
     }
 
     #[test]
-    fn canonicalize_works_simple() {
+    #[cfg(windows)]
+    fn remove_file_retry_waits_for_handle() {
+        use os::windows::fs::OpenOptionsExt;
+        use thread;
+        use time::Duration;
+
         let tmpdir = tmpdir();
-        let tmpdir = fs::canonicalize(tmpdir.path()).unwrap();
-        let file = tmpdir.join("test");
-        File::create(&file).unwrap();
-        assert_eq!(fs::canonicalize(&file).unwrap(), file);
+        let path = tmpdir.join("file");
+        let f = check!(OpenOptions::new().write(true).create(true)
+                                         .share_mode(0).open(&path));
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(f);
+        });
+        check!(fs::remove_file_retry(&path, 100, Duration::from_millis(10)));
+        t.join().unwrap();
+        assert!(!path.exists());
     }
 
     #[test]
-    #[cfg(not(windows))]
-    fn realpath_works() {
-        let tmpdir = tmpdir();
-        let tmpdir = fs::canonicalize(tmpdir.path()).unwrap();
-        let file = tmpdir.join("test");
-        let dir = tmpdir.join("test2");
-        let link = dir.join("link");
-        let linkdir = tmpdir.join("test3");
+    fn rename_retry() {
+        use time::Duration;
 
-        File::create(&file).unwrap();
-        fs::create_dir(&dir).unwrap();
-        fs::soft_link(&file, &link).unwrap();
-        fs::soft_link(&dir, &linkdir).unwrap();
+        let tmpdir = tmpdir();
+        let from = tmpdir.join("from");
+        let to = tmpdir.join("to");
+        check!(fs::create_dir(&from));
+
+        check!(fs::rename_retry(&from, &to, 3, Duration::from_millis(10)));
+        assert!(to.is_dir());
+        // A missing source isn't worth retrying, so this returns at once.
+        let err = fs::rename_retry(&from, &to, 1000, Duration::from_secs(1)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
 
-        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+    #[test]
+    #[cfg(windows)]
+    fn rename_retry_waits_for_handle() {
+        use os::windows::fs::OpenOptionsExt;
+        use thread;
+        use time::Duration;
 
-        assert_eq!(fs::canonicalize(&tmpdir).unwrap(), tmpdir);
-        assert_eq!(fs::canonicalize(&file).unwrap(), file);
-        assert_eq!(fs::canonicalize(&link).unwrap(), file);
-        assert_eq!(fs::canonicalize(&linkdir).unwrap(), dir);
-        assert_eq!(fs::canonicalize(&linkdir.join("link")).unwrap(), file);
+        let tmpdir = tmpdir();
+        let from = tmpdir.join("from");
+        let to = tmpdir.join("to");
+        check!(fs::create_dir(&from));
+        let f = check!(OpenOptions::new().write(true).create(true)
+                                         .share_mode(0).open(&from.join("file")));
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(f);
+        });
+        check!(fs::rename_retry(&from, &to, 100, Duration::from_millis(10)));
+        t.join().unwrap();
+        assert!(to.join("file").exists());
     }
 
     #[test]
-    #[cfg(not(windows))]
-    fn realpath_works_tricky() {
+    fn dir_entry_into_path() {
         let tmpdir = tmpdir();
-        let tmpdir = fs::canonicalize(tmpdir.path()).unwrap();
-
-        let a = tmpdir.join("a");
-        let b = a.join("b");
-        let c = b.join("c");
-        let d = a.join("d");
-        let e = d.join("e");
-        let f = a.join("f");
+        check!(File::create(&tmpdir.join("a")));
+        check!(File::create(&tmpdir.join("b")));
+
+        let entries = check!(fs::read_dir(tmpdir.path()))
+                          .map(|e| check!(e)).collect::<Vec<_>>();
+        let mut paths = entries.into_iter().map(|e| {
+            let path = e.path();
+            assert_eq!(e.into_path(), path);
+            path
+        }).collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(paths, [tmpdir.join("a"), tmpdir.join("b")]);
+    }
 
-        fs::create_dir_all(&b).unwrap();
-        fs::create_dir_all(&d).unwrap();
-        File::create(&f).unwrap();
-        fs::soft_link("../d/e", &c).unwrap();
-        fs::soft_link("../f", &e).unwrap();
+    #[test]
+    fn file_read_buf_appends() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("in.txt");
+        check!(check!(File::create(&path)).write(b"hello world"));
+
+        let f = check!(File::open(&path));
+        let mut buf = b"> ".to_vec();
+        buf.reserve(5);
+        let cap = buf.capacity();
+        let n = check!(f.read_buf(&mut buf));
+        assert!(n > 0 && n <= cap - 2);
+        assert_eq!(&buf[..], &b"> hello world"[..n + 2]);
+
+        let mut rest = Vec::new();
+        check!((&f).read_to_end(&mut rest));
+        assert_eq!(&rest[..], &b"hello world"[n..]);
+    }
 
-        assert_eq!(fs::canonicalize(&c).unwrap(), f);
-        assert_eq!(fs::canonicalize(&e).unwrap(), f);
+    #[test]
+    fn read_dir_filtered_skips_rejected_names() {
+        let tmpdir = tmpdir();
+        check!(File::create(&tmpdir.join("a.rs")));
+        check!(File::create(&tmpdir.join("b.txt")));
+        check!(fs::create_dir(&tmpdir.join("c.rs")));
+
+        let mut names = check!(fs::read_dir_filtered(tmpdir.path(), |name| {
+            name.to_str().unwrap().ends_with(".rs")
+        })).map(|e| check!(e).file_name().into_string().unwrap())
+           .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["a.rs", "c.rs"]);
     }
 
     #[test]
@@ -159,6 +159,33 @@ pub enum ErrorKind {
     #[unstable(feature = "read_exact", reason = "recently added", issue = "27585")]
     UnexpectedEOF,
 
+    /// A loop was found in the file system, such as a symbolic link that
+    /// points back to a directory containing it, or a chain of symbolic
+    /// links too long to resolve.
+    #[unstable(feature = "io_error_filesystem_loop", reason = "recently added",
+               issue = "0")]
+    FilesystemLoop,
+
+    /// A path named something other than a directory where a directory was
+    /// required, for example a regular file used as a path prefix or a path
+    /// ending with a separator.
+    #[unstable(feature = "io_error_not_a_directory", reason = "recently added",
+               issue = "0")]
+    NotADirectory,
+
+    /// An executable file couldn't be opened for writing because it is
+    /// currently running. Replacing such a file usually works by renaming a
+    /// new copy over it instead.
+    #[unstable(feature = "io_error_executable_busy", reason = "recently added",
+               issue = "0")]
+    ExecutableBusy,
+
+    /// A path was opened without following symbolic links, and its last
+    /// component is one.
+    #[unstable(feature = "io_error_symlink_not_followed", reason = "recently added",
+               issue = "0")]
+    SymlinkNotFollowed,
+
     /// Any I/O error not part of this list.
     #[unstable(feature = "io_error_internals",
                reason = "better expressed through extensible enums that this \
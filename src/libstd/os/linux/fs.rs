@@ -0,0 +1,403 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Linux-specific extensions to primitives in the `std::fs` module.
+
+#![stable(feature = "raw_ext", since = "1.1.0")]
+
+#[stable(feature = "raw_ext", since = "1.1.0")]
+pub use sys::fs::MetadataExt;
+
+use fs::{File, Metadata, OpenOptions};
+use io;
+use os::raw::c_int;
+use os::unix::fs::Dir;
+use path::Path;
+use sys::{self, c};
+use sys_common::{AsInner, FromInner};
+use vec::Vec;
+
+/// Don't cross a mount point while resolving the path.
+#[unstable(feature = "openat2", reason = "recently added API", issue = "0")]
+pub const RESOLVE_NO_XDEV: u64 = c::RESOLVE_NO_XDEV;
+/// Don't follow "magic" links such as the ones in `/proc/self/fd`.
+#[unstable(feature = "openat2", reason = "recently added API", issue = "0")]
+pub const RESOLVE_NO_MAGICLINKS: u64 = c::RESOLVE_NO_MAGICLINKS;
+/// Don't follow any symlinks while resolving the path.
+#[unstable(feature = "openat2", reason = "recently added API", issue = "0")]
+pub const RESOLVE_NO_SYMLINKS: u64 = c::RESOLVE_NO_SYMLINKS;
+/// Fail if resolving the path would leave the starting directory.
+#[unstable(feature = "openat2", reason = "recently added API", issue = "0")]
+pub const RESOLVE_BENEATH: u64 = c::RESOLVE_BENEATH;
+/// Resolve the path as if the starting directory was the root directory.
+#[unstable(feature = "openat2", reason = "recently added API", issue = "0")]
+pub const RESOLVE_IN_ROOT: u64 = c::RESOLVE_IN_ROOT;
+
+/// Linux-specific extensions to `os::unix::fs::Dir`.
+#[unstable(feature = "openat2", reason = "recently added API", issue = "0")]
+pub trait DirExt {
+    /// Opens a file relative to this directory using `openat2(2)`, with
+    /// `resolve` being a combination of the `RESOLVE_*` flags controlling
+    /// how the path is looked up.
+    ///
+    /// # Errors
+    ///
+    /// `openat2` was added in Linux 5.6; on older kernels this returns an
+    /// error whose `raw_os_error` is `ENOSYS`, in which case callers should
+    /// fall back to other means of opening the file.
+    fn openat2<P: AsRef<Path>>(&self, path: P, opts: &OpenOptions, resolve: u64)
+                               -> io::Result<File>;
+
+    /// Opens a directory relative to this directory using `openat2(2)`.
+    ///
+    /// The same caveats as for `openat2` apply.
+    fn openat2_dir<P: AsRef<Path>>(&self, path: P, resolve: u64)
+                                   -> io::Result<Dir>;
+}
+
+#[unstable(feature = "openat2", reason = "recently added API", issue = "0")]
+impl DirExt for Dir {
+    fn openat2<P: AsRef<Path>>(&self, path: P, opts: &OpenOptions, resolve: u64)
+                               -> io::Result<File> {
+        self.as_inner().openat2(path.as_ref(), opts.as_inner(), resolve)
+            .map(File::from_inner)
+    }
+
+    fn openat2_dir<P: AsRef<Path>>(&self, path: P, resolve: u64)
+                                   -> io::Result<Dir> {
+        self.as_inner().openat2_dir(path.as_ref(), resolve).map(Dir::from_inner)
+    }
+}
+
+/// The kinds of lease which can be placed on a file with
+/// `FileExt::set_lease`.
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LeaseKind {
+    /// Be notified when another process opens the file for writing or
+    /// truncates it. The file must be open read-only.
+    Read,
+    /// Be notified when another process opens the file at all. The file must
+    /// be open for writing and not be open anywhere else.
+    Write,
+    /// No lease, or remove the lease currently held.
+    Unlock,
+}
+
+/// What `FileExt::manipulate_range` does to a range of a file.
+#[unstable(feature = "file_manipulate_range", reason = "recently added API", issue = "0")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FallocMode {
+    /// Allocates disk space for the range, so that later writes to it can't
+    /// fail for lack of space. The file grows if the range extends past its
+    /// end; data already in the range is kept.
+    Allocate,
+    /// Deallocates the range, which then reads back as zeros. The file size
+    /// doesn't change.
+    PunchHole,
+    /// Zeroes the range, allocating it as with `Allocate` but without
+    /// writing any data. Usually much faster than writing zeros.
+    ZeroRange,
+    /// Removes the range from the file, moving everything after it down and
+    /// shrinking the file by `len`.
+    CollapseRange,
+    /// Inserts a hole of `len` bytes at `offset`, moving everything from
+    /// there up and growing the file by `len`.
+    InsertRange,
+}
+
+/// A file in the directory was accessed, for use with `FileExt::notify`.
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+pub const DN_ACCESS: u32 = 0x00000001;
+/// A file in the directory was modified.
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+pub const DN_MODIFY: u32 = 0x00000002;
+/// A file was created in the directory.
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+pub const DN_CREATE: u32 = 0x00000004;
+/// A file was removed from the directory.
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+pub const DN_DELETE: u32 = 0x00000008;
+/// A file in the directory was renamed.
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+pub const DN_RENAME: u32 = 0x00000010;
+/// The attributes of a file in the directory changed.
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+pub const DN_ATTRIB: u32 = 0x00000020;
+/// Keep the notification in place after the first event instead of having
+/// to request it again.
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+pub const DN_MULTISHOT: u32 = 0x80000000;
+
+/// Linux-specific extensions to `fs::File`.
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+pub trait FileExt {
+    /// Places or removes a lease on this file with `fcntl(F_SETLEASE)`.
+    ///
+    /// When another process performs an operation conflicting with the
+    /// lease, the kernel blocks that process and sends this one a signal,
+    /// `SIGIO` unless changed with `set_signal`. The lease holder then has
+    /// `/proc/sys/fs/lease-break-time` seconds to clean up and call
+    /// `set_lease(LeaseKind::Unlock)` before the lease is broken forcibly.
+    ///
+    /// # Errors
+    ///
+    /// Only regular files can have leases, and the process must own the
+    /// file or have the `CAP_LEASE` capability.
+    fn set_lease(&self, lease: LeaseKind) -> io::Result<()>;
+
+    /// Returns the lease currently held on this file with
+    /// `fcntl(F_GETLEASE)`, `LeaseKind::Unlock` meaning none.
+    fn get_lease(&self) -> io::Result<LeaseKind>;
+
+    /// Changes the signal sent when a lease is broken or a directory
+    /// notification fires, with `fcntl(F_SETSIG)`.
+    ///
+    /// Passing 0 restores the default of `SIGIO`.
+    fn set_signal(&self, signal: c_int) -> io::Result<()>;
+
+    /// Requests a signal when the directory this file refers to changes,
+    /// with `fcntl(F_NOTIFY)`. `events` is a combination of the `DN_*`
+    /// constants in this module.
+    ///
+    /// This file must have been opened on a directory.
+    fn notify(&self, events: u32) -> io::Result<()>;
+
+    /// Reads `len` bytes of this file starting at `offset` into the page
+    /// cache with `readahead(2)`, e.g. to warm it up before mapping the file
+    /// into memory.
+    ///
+    /// Unlike `posix_fadvise(POSIX_FADV_WILLNEED)`, which is merely a hint
+    /// the kernel may act on whenever it likes, this initiates the reads
+    /// before returning. It doesn't wait for them to finish, though, and
+    /// may read less than asked for, e.g. under memory pressure. Nothing is
+    /// read past the end of the file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file isn't open for reading or doesn't support
+    /// readahead, such as pipes and sockets.
+    #[unstable(feature = "file_readahead", reason = "recently added API", issue = "0")]
+    fn readahead(&self, offset: u64, len: u64) -> io::Result<()>;
+
+    /// Allocates, deallocates, zeroes, removes or inserts `len` bytes of this
+    /// file starting at `offset` with `fallocate(2)`, as chosen by `mode`.
+    ///
+    /// `CollapseRange` in particular cuts a piece out of the middle of a
+    /// file without rewriting everything after it, which only takes
+    /// updating the file's extents.
+    ///
+    /// # Errors
+    ///
+    /// The file has to be open for writing. Which modes are available
+    /// depends on the file system and kernel version:
+    ///
+    /// * `PunchHole` needs Linux 2.6.38 and works on most local file
+    ///   systems, including tmpfs.
+    /// * `ZeroRange` needs Linux 3.15 and ext4, XFS or a few others.
+    /// * `CollapseRange` needs Linux 3.15 and `InsertRange` Linux 4.1, and
+    ///   both only work on ext4 and XFS. `offset` and `len` have to be
+    ///   multiples of the file system's block size, and the range must end
+    ///   before the end of the file, or at it for `InsertRange`. Otherwise
+    ///   they fail with `InvalidInput`.
+    ///
+    /// Where a mode isn't supported, this returns an error whose
+    /// `raw_os_error` is `EOPNOTSUPP`, and the file is left unchanged.
+    #[unstable(feature = "file_manipulate_range", reason = "recently added API",
+               issue = "0")]
+    fn manipulate_range(&self, mode: FallocMode, offset: u64, len: u64) -> io::Result<()>;
+
+    /// Takes an exclusive open file description (OFD) lock on the whole
+    /// file, waiting for conflicting locks to be released.
+    ///
+    /// OFD locks are record locks like the ones of `lock_exclusive_fcntl`,
+    /// and conflict with those, but they belong to the open file rather
+    /// than the process, much like `flock` locks. They are shared with
+    /// descriptors duplicated from this one and only released by
+    /// `unlock_ofd` or once all of them are closed, not when the process
+    /// happens to close some other descriptor for the same file. Two
+    /// independent opens of a file conflict with each other, even within
+    /// one process.
+    ///
+    /// # Errors
+    ///
+    /// OFD locks were added in Linux 3.15. Older kernels reject them, in
+    /// which case this returns an error of kind `Other` saying that they
+    /// are not supported.
+    #[unstable(feature = "file_lock_ofd", reason = "recently added API", issue = "0")]
+    fn lock_exclusive_ofd(&self) -> io::Result<()>;
+
+    /// Takes a shared OFD lock on the whole file, waiting for any exclusive
+    /// lock to be released first.
+    ///
+    /// See `lock_exclusive_ofd` for details.
+    #[unstable(feature = "file_lock_ofd", reason = "recently added API", issue = "0")]
+    fn lock_shared_ofd(&self) -> io::Result<()>;
+
+    /// Releases a lock taken with `lock_exclusive_ofd` or `lock_shared_ofd`.
+    #[unstable(feature = "file_lock_ofd", reason = "recently added API", issue = "0")]
+    fn unlock_ofd(&self) -> io::Result<()>;
+}
+
+#[unstable(feature = "file_lease", reason = "recently added API", issue = "0")]
+impl FileExt for File {
+    fn set_lease(&self, lease: LeaseKind) -> io::Result<()> {
+        let arg = match lease {
+            LeaseKind::Read => c::F_RDLCK,
+            LeaseKind::Write => c::F_WRLCK,
+            LeaseKind::Unlock => c::F_UNLCK,
+        };
+        self.as_inner().fcntl(c::F_SETLEASE, arg).map(|_| ())
+    }
+
+    fn get_lease(&self) -> io::Result<LeaseKind> {
+        match try!(self.as_inner().fcntl(c::F_GETLEASE, 0)) {
+            c::F_RDLCK => Ok(LeaseKind::Read),
+            c::F_WRLCK => Ok(LeaseKind::Write),
+            _ => Ok(LeaseKind::Unlock),
+        }
+    }
+
+    fn set_signal(&self, signal: c_int) -> io::Result<()> {
+        self.as_inner().fcntl(c::F_SETSIG, signal).map(|_| ())
+    }
+
+    fn notify(&self, events: u32) -> io::Result<()> {
+        self.as_inner().fcntl(c::F_NOTIFY, events as c_int).map(|_| ())
+    }
+
+    fn readahead(&self, offset: u64, len: u64) -> io::Result<()> {
+        self.as_inner().readahead(offset, len)
+    }
+
+    fn manipulate_range(&self, mode: FallocMode, offset: u64, len: u64) -> io::Result<()> {
+        let mode = match mode {
+            FallocMode::Allocate => 0,
+            FallocMode::PunchHole => c::FALLOC_FL_PUNCH_HOLE | c::FALLOC_FL_KEEP_SIZE,
+            FallocMode::ZeroRange => c::FALLOC_FL_ZERO_RANGE,
+            FallocMode::CollapseRange => c::FALLOC_FL_COLLAPSE_RANGE,
+            FallocMode::InsertRange => c::FALLOC_FL_INSERT_RANGE,
+        };
+        self.as_inner().fallocate(mode, offset, len)
+    }
+
+    fn lock_exclusive_ofd(&self) -> io::Result<()> {
+        self.as_inner().ofd_lock(c::F_WRLCK)
+    }
+
+    fn lock_shared_ofd(&self) -> io::Result<()> {
+        self.as_inner().ofd_lock(c::F_RDLCK)
+    }
+
+    fn unlock_ofd(&self) -> io::Result<()> {
+        self.as_inner().ofd_lock(c::F_UNLCK)
+    }
+}
+
+/// Reads up to the first `len` bytes of each file in `paths`.
+///
+/// The returned vector has one entry per element of `paths`, in the same
+/// order. A file shorter than `len` bytes yields all of its contents.
+///
+/// On Linux 5.6 and later the files are opened, read and closed through
+/// `io_uring` a batch at a time, which takes a few system calls per batch
+/// instead of several per file. Older kernels, and kernels which lack any of
+/// the `io_uring` operations needed, get the same results by reading the
+/// files one after the other.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(fs_read_prefixes)]
+/// use std::os::linux::fs;
+/// use std::path::Path;
+///
+/// let paths = [Path::new("a.png"), Path::new("b.png")];
+/// for header in fs::read_prefixes(&paths, 8) {
+///     if let Ok(header) = header {
+///         println!("{:?}", header);
+///     }
+/// }
+/// ```
+#[unstable(feature = "fs_read_prefixes", reason = "recently added API",
+           issue = "0")]
+pub fn read_prefixes<P: AsRef<Path>>(paths: &[P], len: usize)
+                                     -> Vec<io::Result<Vec<u8>>> {
+    let paths: Vec<&Path> = paths.iter().map(|p| p.as_ref()).collect();
+    sys::fs::read_prefixes(&paths, len)
+}
+
+/// Moves up to `len` bytes from `src` to `dst` with `splice(2)`, returning
+/// the number of bytes moved.
+///
+/// The data is moved inside the kernel without being copied to userspace,
+/// but at least one of `src` and `dst` has to be a pipe; for copying between
+/// two regular files use `copy_file_range` instead. A common pattern is to
+/// splice from a file into a pipe and from that pipe into a socket.
+///
+/// An offset of `Some` reads from or writes to that position without
+/// touching the file's cursor, while `None` uses and advances the cursor.
+/// Pipes have no position, so their offset has to be `None`.
+///
+/// Fewer bytes than asked for may be moved, and 0 means `src` is at its end
+/// (or is a pipe without writers), so callers should loop until they've
+/// moved as much as they need.
+///
+/// # Errors
+///
+/// Returns an error of kind `InvalidInput` if neither end is a pipe.
+#[unstable(feature = "fs_splice", reason = "recently added API", issue = "0")]
+pub fn splice(src: &File, src_off: Option<u64>, dst: &File, dst_off: Option<u64>,
+              len: usize) -> io::Result<usize> {
+    sys::fs::splice(src.as_inner(), src_off, dst.as_inner(), dst_off, len)
+}
+
+/// Copies up to `len` bytes from `src` to `dst` with `copy_file_range(2)`,
+/// returning the number of bytes copied.
+///
+/// Unlike `splice` this works between two regular files, and lets the file
+/// system share the data between both files (reflinks) or copy it on the
+/// server side for network file systems. Offsets behave as with `splice`.
+///
+/// Fewer bytes than asked for may be copied, and 0 means `src` has no more
+/// data at the offset, so callers should loop.
+///
+/// # Errors
+///
+/// The system call was added in Linux 4.5, and before 5.3 it only works
+/// within a single file system; otherwise the `raw_os_error` of the result
+/// is `ENOSYS` or `EXDEV`, respectively, and callers should fall back to
+/// copying the data themselves.
+#[unstable(feature = "fs_splice", reason = "recently added API", issue = "0")]
+pub fn copy_file_range(src: &File, src_off: Option<u64>, dst: &File, dst_off: Option<u64>,
+                       len: usize) -> io::Result<usize> {
+    sys::fs::copy_file_range(src.as_inner(), src_off, dst.as_inner(), dst_off, len)
+}
+
+/// Queries the metadata of a file without following symlinks, like
+/// `fs::symlink_metadata`, and without triggering an automount of the file
+/// itself.
+///
+/// Querying an autofs mount point, such as a host's directory below `/net`,
+/// can mount the file system there as a side effect. This passes
+/// `AT_NO_AUTOMOUNT | AT_SYMLINK_NOFOLLOW` to `fstatat` instead, so that if
+/// the last component of `path` is an automount point which isn't mounted
+/// yet, it's described as the directory it is. That lets backup and
+/// indexing tools look at such directories without changing what is
+/// mounted, as long as they don't descend into them.
+///
+/// The flag only covers the last component. Automount points in the
+/// directories leading up to it are mounted as usual, since the path can't
+/// be looked up through them otherwise. Paths that are already mounted are
+/// looked up as usual too.
+#[unstable(feature = "fs_no_automount", reason = "recently added API", issue = "0")]
+pub fn symlink_metadata_no_automount<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    sys::fs::lstat_no_automount(path.as_ref()).map(FromInner::from_inner)
+}
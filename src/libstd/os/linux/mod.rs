@@ -13,8 +13,4 @@
 #![stable(feature = "raw_ext", since = "1.1.0")]
 
 pub mod raw;
-
-pub mod fs {
-    #![stable(feature = "raw_ext", since = "1.1.0")]
-    pub use sys::fs::MetadataExt;
-}
+pub mod fs;
@@ -17,4 +17,26 @@ pub mod raw;
 pub mod fs {
     #![stable(feature = "raw_ext", since = "1.1.0")]
     pub use sys::fs::MetadataExt;
+
+    use fs::File;
+    use io;
+    use sys_common::AsInner;
+
+    /// OS X-specific extensions to `fs::File`.
+    #[unstable(feature = "file_full_sync", reason = "recently added API", issue = "0")]
+    pub trait FileExt {
+        /// Flushes this file's data all the way to permanent storage with
+        /// `fcntl(F_FULLFSYNC)`, including the drive's own write cache.
+        ///
+        /// `File::sync_all` only calls `fsync`, which lets the drive delay
+        /// and reorder writes. This is the same as `File::sync_barrier`.
+        fn full_sync(&self) -> io::Result<()>;
+    }
+
+    #[unstable(feature = "file_full_sync", reason = "recently added API", issue = "0")]
+    impl FileExt for File {
+        fn full_sync(&self) -> io::Result<()> {
+            self.as_inner().full_fsync()
+        }
+    }
 }
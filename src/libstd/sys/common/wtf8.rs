@@ -184,19 +184,26 @@ impl Wtf8Buf {
     /// will always return the original code units.
     pub fn from_wide(v: &[u16]) -> Wtf8Buf {
         let mut string = Wtf8Buf::with_capacity(v.len());
+        string.assign_wide(v);
+        string
+    }
+
+    /// Replaces the contents of this string with the result of `from_wide`,
+    /// reusing the existing allocation.
+    pub fn assign_wide(&mut self, v: &[u16]) {
+        self.bytes.clear();
         for item in char::decode_utf16(v.iter().cloned()) {
             match item {
-                Ok(ch) => string.push_char(ch),
+                Ok(ch) => self.push_char(ch),
                 Err(surrogate) => {
                     // Surrogates are known to be in the code point range.
                     let code_point = unsafe { CodePoint::from_u32_unchecked(surrogate as u32) };
                     // Skip the WTF-8 concatenation check,
                     // surrogate pairs are already decoded by decode_utf16
-                    string.push_code_point_unchecked(code_point)
+                    self.push_code_point_unchecked(code_point)
                 }
             }
         }
-        string
     }
 
     /// Copied from String::push
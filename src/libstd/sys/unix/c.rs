@@ -54,6 +54,515 @@ pub const FIOCLEX: libc::c_ulong = 0x6601;
 
 pub const WNOHANG: libc::c_int = 1;
 
+pub const FD_CLOEXEC: libc::c_int = 1;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const O_PATH: libc::c_int = 0o10000000;
+// O_TMPFILE includes O_DIRECTORY, so it has to be tested for as a whole.
+#[cfg(target_os = "linux")]
+pub const O_TMPFILE: libc::c_int = 0o20000000 | O_DIRECTORY;
+
+#[cfg(all(any(target_os = "linux", target_os = "android", target_os = "nacl"),
+          any(target_arch = "x86",
+              target_arch = "x86_64",
+              target_arch = "mips",
+              target_arch = "mipsel",
+              target_arch = "le32")))]
+pub const O_DIRECTORY: libc::c_int = 0o200000;
+#[cfg(all(any(target_os = "linux", target_os = "android", target_os = "nacl"),
+          any(target_arch = "x86",
+              target_arch = "x86_64",
+              target_arch = "mips",
+              target_arch = "mipsel",
+              target_arch = "le32")))]
+pub const O_NOFOLLOW: libc::c_int = 0o400000;
+#[cfg(all(any(target_os = "linux", target_os = "android"),
+          any(target_arch = "arm",
+              target_arch = "aarch64",
+              target_arch = "powerpc")))]
+pub const O_DIRECTORY: libc::c_int = 0o40000;
+#[cfg(all(any(target_os = "linux", target_os = "android"),
+          any(target_arch = "arm",
+              target_arch = "aarch64",
+              target_arch = "powerpc")))]
+pub const O_NOFOLLOW: libc::c_int = 0o100000;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub const O_DIRECTORY: libc::c_int = 0x100000;
+#[cfg(any(target_os = "freebsd", target_os = "bitrig", target_os = "openbsd"))]
+pub const O_DIRECTORY: libc::c_int = 0x20000;
+#[cfg(target_os = "netbsd")]
+pub const O_DIRECTORY: libc::c_int = 0x200000;
+#[cfg(target_os = "dragonfly")]
+pub const O_DIRECTORY: libc::c_int = 0x8000000;
+#[cfg(any(target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          target_os = "bitrig",
+          target_os = "netbsd",
+          target_os = "openbsd"))]
+pub const O_NOFOLLOW: libc::c_int = 0x100;
+
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "dragonfly")))]
+pub const AT_FDCWD: libc::c_int = -100;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub const AT_FDCWD: libc::c_int = -2;
+#[cfg(target_os = "dragonfly")]
+pub const AT_FDCWD: libc::c_int = -328243;
+#[cfg(any(target_os = "bitrig", target_os = "openbsd"))]
+pub const AT_SYMLINK_NOFOLLOW: libc::c_int = 0x02;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const AT_SYMLINK_NOFOLLOW: libc::c_int = 0x100;
+#[cfg(target_os = "linux")]
+pub const AT_NO_AUTOMOUNT: libc::c_int = 0x800;
+#[cfg(target_os = "linux")]
+pub const AT_EMPTY_PATH: libc::c_int = 0x1000;
+
+#[cfg(any(target_os = "linux",
+          target_os = "android",
+          target_os = "freebsd",
+          target_os = "netbsd"))]
+pub const AT_SYMLINK_FOLLOW: libc::c_int = 0x400;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub const AT_SYMLINK_FOLLOW: libc::c_int = 0x40;
+#[cfg(any(target_os = "bitrig", target_os = "openbsd"))]
+pub const AT_SYMLINK_FOLLOW: libc::c_int = 0x04;
+#[cfg(target_os = "dragonfly")]
+pub const AT_SYMLINK_FOLLOW: libc::c_int = 0x08;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const AT_REMOVEDIR: libc::c_int = 0x200;
+#[cfg(any(target_os = "freebsd", target_os = "netbsd"))]
+pub const AT_REMOVEDIR: libc::c_int = 0x800;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub const AT_REMOVEDIR: libc::c_int = 0x80;
+#[cfg(any(target_os = "bitrig", target_os = "openbsd"))]
+pub const AT_REMOVEDIR: libc::c_int = 0x08;
+#[cfg(target_os = "dragonfly")]
+pub const AT_REMOVEDIR: libc::c_int = 0x02;
+
+#[cfg(all(target_os = "linux",
+          any(target_arch = "x86",
+              target_arch = "x86_64",
+              target_arch = "arm",
+              target_arch = "aarch64",
+              target_arch = "powerpc")))]
+pub const NR_OPENAT2: libc::c_long = 437;
+#[cfg(all(target_os = "linux",
+          any(target_arch = "mips", target_arch = "mipsel")))]
+pub const NR_OPENAT2: libc::c_long = 4437;
+
+#[cfg(all(target_os = "linux", target_arch = "x86"))]
+pub const NR_RENAMEAT2: libc::c_long = 353;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub const NR_RENAMEAT2: libc::c_long = 316;
+#[cfg(all(target_os = "linux", target_arch = "arm"))]
+pub const NR_RENAMEAT2: libc::c_long = 382;
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+pub const NR_RENAMEAT2: libc::c_long = 276;
+#[cfg(all(target_os = "linux", target_arch = "powerpc"))]
+pub const NR_RENAMEAT2: libc::c_long = 357;
+#[cfg(all(target_os = "linux",
+          any(target_arch = "mips", target_arch = "mipsel")))]
+pub const NR_RENAMEAT2: libc::c_long = 4351;
+
+#[cfg(all(target_os = "linux", target_arch = "x86"))]
+pub const NR_COPY_FILE_RANGE: libc::c_long = 377;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub const NR_COPY_FILE_RANGE: libc::c_long = 326;
+#[cfg(all(target_os = "linux", target_arch = "arm"))]
+pub const NR_COPY_FILE_RANGE: libc::c_long = 391;
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+pub const NR_COPY_FILE_RANGE: libc::c_long = 285;
+#[cfg(all(target_os = "linux", target_arch = "powerpc"))]
+pub const NR_COPY_FILE_RANGE: libc::c_long = 379;
+#[cfg(all(target_os = "linux",
+          any(target_arch = "mips", target_arch = "mipsel")))]
+pub const NR_COPY_FILE_RANGE: libc::c_long = 4360;
+
+#[cfg(all(target_os = "linux", target_arch = "x86"))]
+pub const NR_STATX: libc::c_long = 383;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub const NR_STATX: libc::c_long = 332;
+#[cfg(all(target_os = "linux", target_arch = "arm"))]
+pub const NR_STATX: libc::c_long = 397;
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+pub const NR_STATX: libc::c_long = 291;
+#[cfg(all(target_os = "linux", target_arch = "powerpc"))]
+pub const NR_STATX: libc::c_long = 383;
+#[cfg(all(target_os = "linux",
+          any(target_arch = "mips", target_arch = "mipsel")))]
+pub const NR_STATX: libc::c_long = 4366;
+
+#[cfg(all(target_os = "linux",
+          any(target_arch = "x86",
+              target_arch = "x86_64",
+              target_arch = "arm",
+              target_arch = "aarch64",
+              target_arch = "powerpc")))]
+pub const NR_IO_URING_SETUP: libc::c_long = 425;
+#[cfg(all(target_os = "linux",
+          any(target_arch = "mips", target_arch = "mipsel")))]
+pub const NR_IO_URING_SETUP: libc::c_long = 4425;
+#[cfg(target_os = "linux")]
+pub const NR_IO_URING_ENTER: libc::c_long = NR_IO_URING_SETUP + 1;
+#[cfg(target_os = "linux")]
+pub const NR_IO_URING_REGISTER: libc::c_long = NR_IO_URING_SETUP + 2;
+
+#[cfg(target_os = "linux")]
+pub const STATX_BASIC_STATS: libc::c_uint = 0x7ff;
+
+#[cfg(target_os = "linux")]
+pub const RENAME_NOREPLACE: libc::c_uint = 1;
+
+#[cfg(target_os = "linux")]
+pub const RESOLVE_NO_XDEV: u64 = 0x01;
+#[cfg(target_os = "linux")]
+pub const RESOLVE_NO_MAGICLINKS: u64 = 0x02;
+#[cfg(target_os = "linux")]
+pub const RESOLVE_NO_SYMLINKS: u64 = 0x04;
+#[cfg(target_os = "linux")]
+pub const RESOLVE_BENEATH: u64 = 0x08;
+#[cfg(target_os = "linux")]
+pub const RESOLVE_IN_ROOT: u64 = 0x10;
+
+#[cfg(target_os = "linux")]
+pub const F_SETSIG: libc::c_int = 10;
+#[cfg(target_os = "linux")]
+pub const F_GETSIG: libc::c_int = 11;
+#[cfg(target_os = "linux")]
+pub const F_SETLEASE: libc::c_int = 1024;
+#[cfg(target_os = "linux")]
+pub const F_GETLEASE: libc::c_int = 1025;
+#[cfg(target_os = "linux")]
+pub const F_NOTIFY: libc::c_int = 1026;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const F_RDLCK: libc::c_int = 0;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const F_WRLCK: libc::c_int = 1;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const F_UNLCK: libc::c_int = 2;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub const F_RDLCK: libc::c_int = 1;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub const F_UNLCK: libc::c_int = 2;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub const F_WRLCK: libc::c_int = 3;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const SEEK_DATA: libc::c_int = 3;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const SEEK_HOLE: libc::c_int = 4;
+
+pub const POLLIN: libc::c_short = 0x1;
+pub const POLLOUT: libc::c_short = 0x4;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub type nfds_t = libc::c_ulong;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub type nfds_t = libc::c_uint;
+
+#[repr(C)]
+pub struct pollfd {
+    pub fd: libc::c_int,
+    pub events: libc::c_short,
+    pub revents: libc::c_short,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[repr(C)]
+pub struct statfs {
+    pub f_type: libc::c_long,
+    // The rest of the layout varies between architectures; this is more
+    // than enough room for any of them.
+    pub __f_rest: [libc::c_ulong; 31],
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+#[repr(C)]
+pub struct statfs {
+    pub f_bsize: u32,
+    pub f_iosize: i32,
+    pub f_blocks: u64,
+    pub f_bfree: u64,
+    pub f_bavail: u64,
+    pub f_files: u64,
+    pub f_ffree: u64,
+    pub f_fsid: [i32; 2],
+    pub f_owner: libc::uid_t,
+    pub f_type: u32,
+    pub f_flags: u32,
+    pub f_fssubtype: u32,
+    pub f_fstypename: [libc::c_char; 16],
+    pub f_mntonname: [libc::c_char; 1024],
+    pub f_mntfromname: [libc::c_char; 1024],
+    pub f_reserved: [u32; 8],
+}
+
+#[cfg(target_os = "freebsd")]
+#[repr(C)]
+pub struct statfs {
+    pub f_version: u32,
+    pub f_type: u32,
+    pub f_flags: u64,
+    pub f_bsize: u64,
+    pub f_iosize: u64,
+    pub f_blocks: u64,
+    pub f_bfree: u64,
+    pub f_bavail: i64,
+    pub f_files: u64,
+    pub f_ffree: i64,
+    pub f_syncwrites: u64,
+    pub f_asyncwrites: u64,
+    pub f_syncreads: u64,
+    pub f_asyncreads: u64,
+    pub f_spare: [u64; 10],
+    pub f_namemax: u32,
+    pub f_owner: libc::uid_t,
+    pub f_fsid: [i32; 2],
+    pub f_charspare: [libc::c_char; 80],
+    pub f_fstypename: [libc::c_char; 16],
+    pub f_mntfromname: [libc::c_char; 88],
+    pub f_mntonname: [libc::c_char; 88],
+}
+
+#[repr(C)]
+pub struct iovec {
+    pub iov_base: *mut libc::c_void,
+    pub iov_len: libc::size_t,
+}
+
+// The smallest IOV_MAX of the supported platforms; Linux, the BSDs and OS X
+// all allow this many.
+pub const IOV_MAX: usize = 1024;
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub const F_NOCACHE: libc::c_int = 48;
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub const POSIX_FADV_NORMAL: libc::c_int = 0;
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub const POSIX_FADV_DONTNEED: libc::c_int = 4;
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub const POSIX_FADV_NOREUSE: libc::c_int = 5;
+
+#[cfg(target_os = "linux")]
+pub const FALLOC_FL_KEEP_SIZE: libc::c_int = 0x01;
+#[cfg(target_os = "linux")]
+pub const FALLOC_FL_PUNCH_HOLE: libc::c_int = 0x02;
+#[cfg(target_os = "linux")]
+pub const FALLOC_FL_COLLAPSE_RANGE: libc::c_int = 0x08;
+#[cfg(target_os = "linux")]
+pub const FALLOC_FL_ZERO_RANGE: libc::c_int = 0x10;
+#[cfg(target_os = "linux")]
+pub const FALLOC_FL_INSERT_RANGE: libc::c_int = 0x20;
+
+#[cfg(target_os = "linux")]
+pub const SYNC_FILE_RANGE_WAIT_BEFORE: libc::c_uint = 1;
+#[cfg(target_os = "linux")]
+pub const SYNC_FILE_RANGE_WRITE: libc::c_uint = 2;
+#[cfg(target_os = "linux")]
+pub const SYNC_FILE_RANGE_WAIT_AFTER: libc::c_uint = 4;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const F_SETLKW: libc::c_int = 7;
+#[cfg(target_os = "freebsd")]
+pub const F_SETLKW: libc::c_int = 13;
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub const F_SETLKW: libc::c_int = 9;
+#[cfg(target_os = "linux")]
+pub const F_OFD_SETLK: libc::c_int = 37;
+#[cfg(target_os = "linux")]
+pub const F_OFD_SETLKW: libc::c_int = 38;
+
+#[repr(C)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub struct flock {
+    pub l_type: libc::c_short,
+    pub l_whence: libc::c_short,
+    pub l_start: libc::off_t,
+    pub l_len: libc::off_t,
+    pub l_pid: libc::pid_t,
+}
+
+#[repr(C)]
+#[cfg(target_os = "freebsd")]
+pub struct flock {
+    pub l_start: libc::off_t,
+    pub l_len: libc::off_t,
+    pub l_pid: libc::pid_t,
+    pub l_type: libc::c_short,
+    pub l_whence: libc::c_short,
+    pub l_sysid: libc::c_int,
+}
+
+#[repr(C)]
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub struct flock {
+    pub l_start: libc::off_t,
+    pub l_len: libc::off_t,
+    pub l_pid: libc::pid_t,
+    pub l_type: libc::c_short,
+    pub l_whence: libc::c_short,
+}
+
+#[repr(C)]
+#[cfg(target_os = "linux")]
+pub struct open_how {
+    pub flags: u64,
+    pub mode: u64,
+    pub resolve: u64,
+}
+
+#[repr(C)]
+#[cfg(target_os = "linux")]
+pub struct statx_timestamp {
+    pub tv_sec: i64,
+    pub tv_nsec: u32,
+    pub __reserved: i32,
+}
+
+#[repr(C)]
+#[cfg(target_os = "linux")]
+pub struct statx {
+    pub stx_mask: u32,
+    pub stx_blksize: u32,
+    pub stx_attributes: u64,
+    pub stx_nlink: u32,
+    pub stx_uid: u32,
+    pub stx_gid: u32,
+    pub stx_mode: u16,
+    pub __spare0: u16,
+    pub stx_ino: u64,
+    pub stx_size: u64,
+    pub stx_blocks: u64,
+    pub stx_attributes_mask: u64,
+    pub stx_atime: statx_timestamp,
+    pub stx_btime: statx_timestamp,
+    pub stx_ctime: statx_timestamp,
+    pub stx_mtime: statx_timestamp,
+    pub stx_rdev_major: u32,
+    pub stx_rdev_minor: u32,
+    pub stx_dev_major: u32,
+    pub stx_dev_minor: u32,
+    pub __spare2: [u64; 14],
+}
+
+#[repr(C)]
+#[cfg(target_os = "linux")]
+pub struct io_sqring_offsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub flags: u32,
+    pub dropped: u32,
+    pub array: u32,
+    pub resv1: u32,
+    pub resv2: u64,
+}
+
+#[repr(C)]
+#[cfg(target_os = "linux")]
+pub struct io_cqring_offsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub overflow: u32,
+    pub cqes: u32,
+    pub flags: u32,
+    pub resv1: u32,
+    pub resv2: u64,
+}
+
+#[repr(C)]
+#[cfg(target_os = "linux")]
+pub struct io_uring_params {
+    pub sq_entries: u32,
+    pub cq_entries: u32,
+    pub flags: u32,
+    pub sq_thread_cpu: u32,
+    pub sq_thread_idle: u32,
+    pub features: u32,
+    pub wq_fd: u32,
+    pub resv: [u32; 3],
+    pub sq_off: io_sqring_offsets,
+    pub cq_off: io_cqring_offsets,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg(target_os = "linux")]
+pub struct io_uring_sqe {
+    pub opcode: u8,
+    pub flags: u8,
+    pub ioprio: u16,
+    pub fd: i32,
+    pub off: u64,
+    pub addr: u64,
+    pub len: u32,
+    // `rw_flags`, `open_flags`, `statx_flags` and so on, depending on the
+    // opcode.
+    pub op_flags: u32,
+    pub user_data: u64,
+    pub buf_index: u16,
+    pub personality: u16,
+    pub splice_fd_in: i32,
+    pub __pad2: [u64; 2],
+}
+
+#[repr(C)]
+#[cfg(target_os = "linux")]
+pub struct io_uring_cqe {
+    pub user_data: u64,
+    pub res: i32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+#[cfg(target_os = "linux")]
+pub struct io_uring_probe_op {
+    pub op: u8,
+    pub resv: u8,
+    pub flags: u16,
+    pub resv2: u32,
+}
+
+#[repr(C)]
+#[cfg(target_os = "linux")]
+pub struct io_uring_probe {
+    pub last_op: u8,
+    pub ops_len: u8,
+    pub resv: u16,
+    pub resv2: [u32; 3],
+    pub ops: [io_uring_probe_op; 64],
+}
+
+#[cfg(target_os = "linux")]
+pub const IORING_OFF_SQ_RING: libc::off_t = 0;
+#[cfg(target_os = "linux")]
+pub const IORING_OFF_CQ_RING: libc::off_t = 0x8000000;
+#[cfg(target_os = "linux")]
+pub const IORING_OFF_SQES: libc::off_t = 0x10000000;
+#[cfg(target_os = "linux")]
+pub const IORING_ENTER_GETEVENTS: libc::c_uint = 1;
+#[cfg(target_os = "linux")]
+pub const IORING_REGISTER_PROBE: libc::c_uint = 8;
+#[cfg(target_os = "linux")]
+pub const IO_URING_OP_SUPPORTED: u16 = 1;
+#[cfg(target_os = "linux")]
+pub const IORING_FEAT_RW_CUR_POS: u32 = 1 << 3;
+#[cfg(target_os = "linux")]
+pub const IORING_OP_OPENAT: u8 = 18;
+#[cfg(target_os = "linux")]
+pub const IORING_OP_CLOSE: u8 = 19;
+#[cfg(target_os = "linux")]
+pub const IORING_OP_STATX: u8 = 21;
+#[cfg(target_os = "linux")]
+pub const IORING_OP_READ: u8 = 22;
+
 #[cfg(target_os = "linux")]
 pub const _SC_GETPW_R_SIZE_MAX: libc::c_int = 70;
 #[cfg(any(target_os = "macos",
@@ -160,10 +669,91 @@ extern {
     pub fn utimes(filename: *const libc::c_char,
                   times: *const libc::timeval) -> libc::c_int;
     pub fn gai_strerror(errcode: libc::c_int) -> *const libc::c_char;
+    pub fn readv(fd: libc::c_int, iov: *const iovec,
+                 iovcnt: libc::c_int) -> libc::ssize_t;
+    pub fn writev(fd: libc::c_int, iov: *const iovec,
+                  iovcnt: libc::c_int) -> libc::ssize_t;
     pub fn setgroups(ngroups: libc::c_int,
                      ptr: *const libc::c_void) -> libc::c_int;
     pub fn realpath(pathname: *const libc::c_char, resolved: *mut libc::c_char)
                     -> *mut libc::c_char;
+
+    pub fn openat(dirfd: libc::c_int, path: *const libc::c_char,
+                  flags: libc::c_int, ...) -> libc::c_int;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn dirfd(dirp: *mut libc::DIR) -> libc::c_int;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn fstatat(dirfd: libc::c_int, pathname: *const libc::c_char,
+                   buf: *mut libc::stat, flags: libc::c_int) -> libc::c_int;
+    pub fn poll(fds: *mut pollfd, nfds: nfds_t, timeout: libc::c_int) -> libc::c_int;
+    #[cfg(target_os = "linux")]
+    pub fn splice(fd_in: libc::c_int, off_in: *mut i64, fd_out: libc::c_int,
+                  off_out: *mut i64, len: libc::size_t,
+                  flags: libc::c_uint) -> libc::ssize_t;
+    #[cfg(target_os = "linux")]
+    pub fn readahead(fd: libc::c_int, offset: i64, count: libc::size_t) -> libc::ssize_t;
+    #[cfg(target_os = "linux")]
+    pub fn sync_file_range(fd: libc::c_int, offset: i64, nbytes: i64,
+                           flags: libc::c_uint) -> libc::c_int;
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    pub fn posix_fadvise(fd: libc::c_int, offset: libc::off_t, len: libc::off_t,
+                         advice: libc::c_int) -> libc::c_int;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn flistxattr(fd: libc::c_int, list: *mut libc::c_char,
+                      size: libc::size_t) -> libc::ssize_t;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn fgetxattr(fd: libc::c_int, name: *const libc::c_char,
+                     value: *mut libc::c_void, size: libc::size_t) -> libc::ssize_t;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn fsetxattr(fd: libc::c_int, name: *const libc::c_char,
+                     value: *const libc::c_void, size: libc::size_t,
+                     flags: libc::c_int) -> libc::c_int;
+    #[cfg(target_os = "macos")]
+    pub fn flistxattr(fd: libc::c_int, namebuf: *mut libc::c_char,
+                      size: libc::size_t, options: libc::c_int) -> libc::ssize_t;
+    #[cfg(target_os = "macos")]
+    pub fn fgetxattr(fd: libc::c_int, name: *const libc::c_char,
+                     value: *mut libc::c_void, size: libc::size_t,
+                     position: u32, options: libc::c_int) -> libc::ssize_t;
+    #[cfg(target_os = "macos")]
+    pub fn fsetxattr(fd: libc::c_int, name: *const libc::c_char,
+                     value: *const libc::c_void, size: libc::size_t,
+                     position: u32, options: libc::c_int) -> libc::c_int;
+    pub fn linkat(olddirfd: libc::c_int, oldpath: *const libc::c_char,
+                  newdirfd: libc::c_int, newpath: *const libc::c_char,
+                  flags: libc::c_int) -> libc::c_int;
+    pub fn mkdirat(dirfd: libc::c_int, path: *const libc::c_char,
+                   mode: libc::mode_t) -> libc::c_int;
+    pub fn unlinkat(dirfd: libc::c_int, path: *const libc::c_char,
+                    flags: libc::c_int) -> libc::c_int;
+    pub fn renameat(olddirfd: libc::c_int, oldpath: *const libc::c_char,
+                    newdirfd: libc::c_int, newpath: *const libc::c_char) -> libc::c_int;
+    pub fn fchdir(fd: libc::c_int) -> libc::c_int;
+    #[cfg(target_os = "linux")]
+    pub fn dup3(oldfd: libc::c_int, newfd: libc::c_int, flags: libc::c_int) -> libc::c_int;
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos",
+              target_os = "ios", target_os = "freebsd"))]
+    #[cfg_attr(target_os = "macos", link_name = "statfs$INODE64")]
+    pub fn statfs(path: *const libc::c_char, buf: *mut statfs) -> libc::c_int;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn fallocate(fd: libc::c_int, mode: libc::c_int,
+                     offset: libc::off_t, len: libc::off_t) -> libc::c_int;
+    #[cfg(target_os = "freebsd")]
+    pub fn posix_fallocate(fd: libc::c_int, offset: libc::off_t,
+                           len: libc::off_t) -> libc::c_int;
+    #[cfg(target_os = "linux")]
+    pub fn syscall(number: libc::c_long, ...) -> libc::c_long;
+
+    #[cfg(any(target_os = "macos",
+              target_os = "ios",
+              target_os = "freebsd",
+              target_os = "dragonfly",
+              target_os = "netbsd"))]
+    pub fn lchmod(path: *const libc::c_char, mode: libc::mode_t) -> libc::c_int;
+    #[cfg(any(target_os = "bitrig", target_os = "openbsd"))]
+    pub fn fchmodat(dirfd: libc::c_int, path: *const libc::c_char,
+                    mode: libc::mode_t, flags: libc::c_int) -> libc::c_int;
 }
 
 // Ugh. This is only available as an inline until Android API 21.
@@ -12,15 +12,21 @@
 
 #![stable(feature = "rust1", since = "1.0.0")]
 
+use cmp;
 use fs::{self, Permissions, OpenOptions};
 use io;
 use libc;
+use mem;
 use os::raw::c_long;
+use os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use os::unix::raw;
-use path::Path;
+use path::{Path, PathBuf};
 use sys::fs::MetadataExt as UnixMetadataExt;
 use sys;
+use sys::time::SteadyTime;
 use sys_common::{FromInner, AsInner, AsInnerMut};
+use thread;
+use time::Duration;
 
 #[unstable(feature = "fs_mode", reason = "recently added API", issue = "27712")]
 pub const USER_READ: raw::mode_t = 0o400;
@@ -77,6 +83,51 @@ pub trait PermissionsExt {
     /// permission bits.
     #[stable(feature = "fs_ext", since = "1.1.0")]
     fn from_mode(mode: raw::mode_t) -> Self;
+
+    /// Replaces the bits selected by `mask` with the corresponding bits of
+    /// `other`, leaving every other bit of this set of permissions as it is.
+    ///
+    /// This is useful when copying permissions between files, where a mask
+    /// of `0o777` carries over the access bits without also copying the
+    /// setuid, setgid and sticky bits, and `0o7777` copies all of them.
+    #[unstable(feature = "permissions_apply_bits", reason = "recently added API",
+               issue = "0")]
+    fn apply_mode_bits_from(&mut self, other: &Permissions, mask: raw::mode_t);
+
+    /// Returns whether the set-user-ID bit (`SETUID`) is set.
+    #[unstable(feature = "permissions_special_bits", reason = "recently added API",
+               issue = "0")]
+    fn is_setuid(&self) -> bool { self.mode() & SETUID != 0 }
+
+    /// Returns whether the set-group-ID bit (`SETGID`) is set.
+    #[unstable(feature = "permissions_special_bits", reason = "recently added API",
+               issue = "0")]
+    fn is_setgid(&self) -> bool { self.mode() & SETGID != 0 }
+
+    /// Returns whether the sticky bit (`STICKY_BIT`) is set.
+    #[unstable(feature = "permissions_special_bits", reason = "recently added API",
+               issue = "0")]
+    fn is_sticky(&self) -> bool { self.mode() & STICKY_BIT != 0 }
+
+    /// Sets or clears the set-user-ID bit.
+    #[unstable(feature = "permissions_special_bits", reason = "recently added API",
+               issue = "0")]
+    fn set_setuid(&mut self, on: bool) { set_mode_bit(self, SETUID, on) }
+
+    /// Sets or clears the set-group-ID bit.
+    #[unstable(feature = "permissions_special_bits", reason = "recently added API",
+               issue = "0")]
+    fn set_setgid(&mut self, on: bool) { set_mode_bit(self, SETGID, on) }
+
+    /// Sets or clears the sticky bit.
+    #[unstable(feature = "permissions_special_bits", reason = "recently added API",
+               issue = "0")]
+    fn set_sticky(&mut self, on: bool) { set_mode_bit(self, STICKY_BIT, on) }
+}
+
+fn set_mode_bit<P: PermissionsExt + ?Sized>(perm: &mut P, bit: raw::mode_t, on: bool) {
+    let mode = perm.mode();
+    perm.set_mode(if on { mode | bit } else { mode & !bit });
 }
 
 #[stable(feature = "fs_ext", since = "1.1.0")]
@@ -90,6 +141,382 @@ impl PermissionsExt for Permissions {
     fn from_mode(mode: raw::mode_t) -> Permissions {
         FromInner::from_inner(FromInner::from_inner(mode))
     }
+
+    fn apply_mode_bits_from(&mut self, other: &Permissions, mask: raw::mode_t) {
+        self.as_inner_mut().apply_bits_from(other.as_inner(), mask)
+    }
+}
+
+/// Unix-specific extensions to `fs::File`
+#[unstable(feature = "file_offset", reason = "recently added API", issue = "0")]
+pub trait FileExt {
+    /// Reads a number of bytes starting from a given offset, returning the
+    /// number of bytes read.
+    ///
+    /// The offset is relative to the start of the file and thus independent
+    /// from the current cursor, which is left untouched. This is done with
+    /// `pread`.
+    ///
+    /// Note that similar to `Read::read`, it is not an error to return with
+    /// a short read.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    /// Writes a number of bytes starting from a given offset, returning the
+    /// number of bytes written.
+    ///
+    /// The offset is relative to the start of the file and thus independent
+    /// from the current cursor, which is left untouched. This is done with
+    /// `pwrite`.
+    ///
+    /// Note that on Linux a file opened in append mode ignores the offset and
+    /// always appends, and that similar to `Write::write`, it is not an error
+    /// to return a short write.
+    fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize>;
+
+    /// Reads the exact number of bytes required to fill `buf` from the given
+    /// offset, calling `read_at` as many times as necessary.
+    ///
+    /// This is the positional counterpart of `Read::read_exact`.
+    ///
+    /// # Errors
+    ///
+    /// If end of file is reached before `buf` is filled, an error of kind
+    /// `ErrorKind::UnexpectedEOF` is returned and the contents of `buf` are
+    /// unspecified.
+    fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                    offset += n as u64;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if !buf.is_empty() {
+            Err(io::Error::new(io::ErrorKind::UnexpectedEOF,
+                               "failed to fill whole buffer"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Writes all of `buf` starting at the given offset, calling `write_at` as
+    /// many times as necessary.
+    ///
+    /// This is the positional counterpart of `Write::write_all`.
+    fn write_all_at(&self, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write_at(buf, offset) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                   "failed to write whole buffer")),
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the same file again, returning a handle with its own cursor.
+    ///
+    /// Duplicating a file descriptor with `dup`, as `FromRawFd` on a copied
+    /// descriptor would, yields a second descriptor for the same *open file
+    /// description*: both share one cursor and one set of status flags, so
+    /// reading or writing through either moves the position of the other.
+    /// This function instead opens the file anew, so the returned `File`
+    /// starts at offset 0 and moving its cursor doesn't affect this one.
+    ///
+    /// The new handle is opened with the same access mode and `O_APPEND`
+    /// flag as this one. It refers to the same file even if that file has
+    /// since been renamed.
+    ///
+    /// # Platform-specific behavior
+    ///
+    /// This goes through `/proc/self/fd` on Linux and Android and uses
+    /// `F_GETPATH` on OS X, where the file must still be reachable under
+    /// its current path. Other platforms return an error.
+    #[unstable(feature = "file_clone_independent", reason = "recently added API",
+               issue = "0")]
+    fn clone_independent(&self) -> io::Result<fs::File>;
+
+    /// Waits until data can be read from this file without blocking, or
+    /// until `timeout` has passed.
+    ///
+    /// This is intended for character devices such as serial ports and for
+    /// FIFOs, ideally opened with `O_NONBLOCK` through
+    /// `OpenOptionsExt::custom_flags`, where a read may otherwise hang
+    /// indefinitely. Reads on regular files never block in this sense, so
+    /// they are always reported as ready, and there is no way to bound the
+    /// time spent in a read on a hung network file system.
+    ///
+    /// A timeout of `None` waits forever and a zero timeout only checks
+    /// whether the file is ready right now.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `TimedOut` if the file didn't become readable
+    /// in time. An error or hangup condition on the file counts as readable;
+    /// the subsequent read will report it.
+    #[unstable(feature = "file_poll", reason = "recently added API", issue = "0")]
+    fn wait_readable(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Waits until data can be written to this file without blocking, or
+    /// until `timeout` has passed.
+    ///
+    /// This behaves like `wait_readable`, except that it waits for the file
+    /// to accept writes.
+    #[unstable(feature = "file_poll", reason = "recently added API", issue = "0")]
+    fn wait_writable(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Takes an exclusive advisory lock on the whole file with `flock`,
+    /// waiting for any conflicting lock to be released first.
+    ///
+    /// Unix has two independent kinds of advisory locks, and which one is
+    /// used matters:
+    ///
+    /// * `flock` locks, taken by this method, `lock_shared` and released by
+    ///   `unlock`, belong to the open file description. Descriptors created
+    ///   from it with `dup` or inherited over `fork` share the lock, which
+    ///   is released once all of them are closed or one of them unlocks.
+    ///   Opening the same file a second time gives a description that
+    ///   conflicts with the first one, even within a process. On many
+    ///   systems these locks are either not propagated over NFS or emulated
+    ///   with `fcntl` locks there.
+    /// * `fcntl` (POSIX record) locks, taken by `lock_exclusive_fcntl` and
+    ///   `lock_shared_fcntl`, belong to the process and the file, not the
+    ///   descriptor. They are not inherited by child processes, never
+    ///   conflict with other locks of the same process, and are released as
+    ///   soon as the process closes *any* descriptor for the file, even one
+    ///   opened independently by an unrelated library. They do work over
+    ///   NFS.
+    ///
+    /// The two kinds don't interact on most systems, so all parties sharing
+    /// a file need to agree on one. Neither stops processes that don't ask
+    /// for a lock from reading or writing the file.
+    #[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+    fn lock_exclusive(&self) -> io::Result<()>;
+
+    /// Takes a shared advisory lock on the whole file with `flock`, waiting
+    /// for any exclusive lock to be released first.
+    ///
+    /// See `lock_exclusive` for how this differs from `lock_shared_fcntl`.
+    #[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+    fn lock_shared(&self) -> io::Result<()>;
+
+    /// Releases a lock taken with `lock_exclusive` or `lock_shared`.
+    #[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+    fn unlock(&self) -> io::Result<()>;
+
+    /// Tries to take the lock `lock_exclusive` takes without waiting,
+    /// returning `Ok(false)` if another open file holds a conflicting lock.
+    #[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+    fn try_lock_exclusive(&self) -> io::Result<bool>;
+
+    /// Like `lock_exclusive`, but gives up after waiting for `timeout`,
+    /// returning `Ok(false)` in that case and `Ok(true)` once the lock is
+    /// taken.
+    ///
+    /// `flock` can't wait for a limited time, so this polls with
+    /// `try_lock_exclusive`, sleeping between attempts for 1ms at first and
+    /// twice as long after each failed one, up to 100ms. The lock may
+    /// therefore be taken somewhat later than it became free, and a steady
+    /// stream of other processes taking it can starve this one. A `timeout`
+    /// of zero makes a single attempt.
+    #[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+    fn lock_exclusive_timeout(&self, timeout: Duration) -> io::Result<bool>;
+
+    /// Takes an exclusive lock on the whole file that keeps other processes
+    /// out, returning a guard which releases it when dropped.
+    ///
+    /// This is a `flock` lock, as taken by `lock_exclusive`, because unlike
+    /// an `fcntl` lock it isn't silently dropped when some other part of the
+    /// program closes an unrelated descriptor for the same file.
+    ///
+    /// The lock is about processes, not threads. Every thread using this
+    /// `File`, or a handle cloned from it with `try_clone`, is considered
+    /// to hold the lock along with the thread that took it, and can take it
+    /// again without waiting. A thread which opens the file independently
+    /// and locks it does wait, including the thread already holding the
+    /// lock through another handle, which then deadlocks. To also keep
+    /// threads of this process apart, share a single `File` between them
+    /// behind a `Mutex` and only lock the file while holding the mutex.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(file_lock)]
+    /// use std::fs::OpenOptions;
+    /// use std::os::unix::fs::FileExt;
+    ///
+    /// # fn foo() -> std::io::Result<()> {
+    /// let f = try!(OpenOptions::new().write(true).create(true).open("db.lock"));
+    /// let guard = try!(f.lock_exclusive_interprocess());
+    /// // No other process holds the lock until `guard` goes away.
+    /// try!(guard.unlock());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+    fn lock_exclusive_interprocess(&self) -> io::Result<InterprocessLock>;
+
+    /// Takes an exclusive POSIX record lock on the whole file with `fcntl`,
+    /// waiting for conflicting locks of other processes to be released.
+    ///
+    /// Use this over `lock_exclusive` when the file may live on NFS or has
+    /// to cooperate with other programs using `fcntl` or `lockf`, but see
+    /// `lock_exclusive` for the surprising ways these locks get released.
+    /// The file has to be open for writing.
+    #[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+    fn lock_exclusive_fcntl(&self) -> io::Result<()>;
+
+    /// Takes a shared POSIX record lock on the whole file with `fcntl`.
+    ///
+    /// The file has to be open for reading. See `lock_exclusive_fcntl`.
+    #[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+    fn lock_shared_fcntl(&self) -> io::Result<()>;
+
+    /// Releases a lock taken with `lock_exclusive_fcntl` or
+    /// `lock_shared_fcntl`.
+    #[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+    fn unlock_fcntl(&self) -> io::Result<()>;
+
+    /// Asks the system not to keep this file's data in the page cache, so
+    /// that bulk reads or writes don't push everything else out of it.
+    /// Passing `false` restores normal caching.
+    ///
+    /// # Platform-specific behavior
+    ///
+    /// On OS X and iOS this sets `F_NOCACHE`, and from then on the file's
+    /// data bypasses the cache where possible.
+    ///
+    /// Linux and FreeBSD can't turn the cache off for a descriptor short of
+    /// opening it with `O_DIRECT`, so this is only a best-effort stand-in:
+    /// it drops the pages cached so far, as `drop_cache_range(0, 0)` does,
+    /// and hints with `posix_fadvise(POSIX_FADV_NOREUSE)` that the data
+    /// won't be needed again, which current Linux kernels ignore. Later
+    /// writes are cached like before, so a bulk writer has to call
+    /// `drop_cache_range` on what it has written every so often, e.g. after
+    /// each few megabytes, to keep the cache from filling up.
+    ///
+    /// Other systems return an error.
+    #[unstable(feature = "file_no_cache", reason = "recently added API", issue = "0")]
+    fn set_no_cache(&self, no_cache: bool) -> io::Result<()>;
+
+    /// Removes `len` bytes of this file starting at `offset` from the page
+    /// cache, writing them back to the disk first if needed. A `len` of 0
+    /// covers everything from `offset` to the end of the file.
+    ///
+    /// This waits for the write back, which makes it expensive to call for
+    /// small ranges. On Linux, Android and FreeBSD the pages are then
+    /// dropped with `posix_fadvise(POSIX_FADV_DONTNEED)`, although the
+    /// kernel may keep pages that are mapped or in use elsewhere. Other
+    /// systems have no way to drop pages on request; there this only writes
+    /// them back, so the kernel can reclaim them cheaply.
+    #[unstable(feature = "file_no_cache", reason = "recently added API", issue = "0")]
+    fn drop_cache_range(&self, offset: u64, len: u64) -> io::Result<()>;
+
+    /// Duplicates this file onto the descriptor number `target_fd`, e.g. to
+    /// hand it to a child process as fd 3, and returns the file at the new
+    /// number.
+    ///
+    /// Whatever `target_fd` referred to before is closed, atomically with
+    /// the file taking its place, as `dup2` does. The close-on-exec flag of
+    /// the new descriptor is set if `cloexec` is `true` and cleared
+    /// otherwise. On Linux this is done by `dup3` in the same step; other
+    /// systems set the flag with `fcntl` right after `dup2`.
+    ///
+    /// This file stays open at its own number, and on errors it's left as it
+    /// was. If it already is `target_fd` nothing but the close-on-exec flag
+    /// changes, and `None` is returned since there's no new descriptor.
+    ///
+    /// # Safety
+    ///
+    /// The returned file takes ownership of `target_fd`, as if it had been
+    /// passed to `FromRawFd::from_raw_fd`. If `target_fd` was already open,
+    /// the caller must own it: any other object still wrapping it would go
+    /// on to use, and eventually close, a descriptor that now refers to this
+    /// file.
+    #[unstable(feature = "file_dup_to", reason = "recently added API", issue = "0")]
+    unsafe fn dup_to(&self, target_fd: RawFd, cloexec: bool) -> io::Result<Option<fs::File>>;
+}
+
+#[unstable(feature = "file_offset", reason = "recently added API", issue = "0")]
+impl FileExt for fs::File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.as_inner().read_at(buf, offset)
+    }
+    fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        self.as_inner().write_at(buf, offset)
+    }
+    fn clone_independent(&self) -> io::Result<fs::File> {
+        self.as_inner().reopen().map(FromInner::from_inner)
+    }
+    fn wait_readable(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.as_inner().poll(sys::c::POLLIN, timeout)
+    }
+    fn wait_writable(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.as_inner().poll(sys::c::POLLOUT, timeout)
+    }
+    fn lock_exclusive(&self) -> io::Result<()> {
+        self.as_inner().flock(libc::LOCK_EX)
+    }
+    fn lock_shared(&self) -> io::Result<()> {
+        self.as_inner().flock(libc::LOCK_SH)
+    }
+    fn unlock(&self) -> io::Result<()> {
+        self.as_inner().flock(libc::LOCK_UN)
+    }
+    fn try_lock_exclusive(&self) -> io::Result<bool> {
+        self.as_inner().try_flock(libc::LOCK_EX)
+    }
+    fn lock_exclusive_timeout(&self, timeout: Duration) -> io::Result<bool> {
+        let start = SteadyTime::now();
+        let mut delay = Duration::from_millis(1);
+        loop {
+            if try!(self.try_lock_exclusive()) {
+                return Ok(true)
+            }
+            let elapsed = &SteadyTime::now() - &start;
+            if elapsed >= timeout {
+                return Ok(false)
+            }
+            thread::sleep(cmp::min(delay, timeout - elapsed));
+            delay = cmp::min(delay * 2, Duration::from_millis(100));
+        }
+    }
+    fn lock_exclusive_interprocess(&self) -> io::Result<InterprocessLock> {
+        try!(self.lock_exclusive());
+        Ok(InterprocessLock { file: self })
+    }
+    fn lock_exclusive_fcntl(&self) -> io::Result<()> {
+        self.as_inner().fcntl_lock(sys::c::F_WRLCK)
+    }
+    fn lock_shared_fcntl(&self) -> io::Result<()> {
+        self.as_inner().fcntl_lock(sys::c::F_RDLCK)
+    }
+    fn unlock_fcntl(&self) -> io::Result<()> {
+        self.as_inner().fcntl_lock(sys::c::F_UNLCK)
+    }
+    fn set_no_cache(&self, no_cache: bool) -> io::Result<()> {
+        self.as_inner().set_no_cache(no_cache)
+    }
+    fn drop_cache_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        self.as_inner().drop_cache_range(offset, len)
+    }
+    unsafe fn dup_to(&self, target_fd: RawFd, cloexec: bool) -> io::Result<Option<fs::File>> {
+        try!(self.as_inner().dup_to(target_fd, cloexec));
+        if self.as_raw_fd() == target_fd {
+            return Ok(None)
+        }
+        Ok(Some(fs::File::from_raw_fd(target_fd)))
+    }
 }
 
 /// Unix-specific extensions to `OpenOptions`
@@ -101,6 +528,17 @@ pub trait OpenOptionsExt {
     /// specified `mode` will be used as the permission bits for the new file.
     #[stable(feature = "fs_ext", since = "1.1.0")]
     fn mode(&mut self, mode: raw::mode_t) -> &mut Self;
+
+    /// Refuses to open the path if its last component is a symbolic link,
+    /// by passing `O_NOFOLLOW` to `open`.
+    ///
+    /// When this happens the open fails with an error of kind
+    /// `SymlinkNotFollowed`, the same as on Windows. Symbolic links in the
+    /// preceding components of the path are still followed, and a loop
+    /// among those is reported as `FilesystemLoop` as usual.
+    #[unstable(feature = "open_options_no_follow", reason = "recently added API",
+               issue = "0")]
+    fn no_follow(&mut self, no_follow: bool) -> &mut Self;
 }
 
 #[stable(feature = "fs_ext", since = "1.1.0")]
@@ -108,6 +546,10 @@ impl OpenOptionsExt for OpenOptions {
     fn mode(&mut self, mode: raw::mode_t) -> &mut OpenOptions {
         self.as_inner_mut().mode(mode); self
     }
+
+    fn no_follow(&mut self, no_follow: bool) -> &mut OpenOptions {
+        self.as_inner_mut().no_follow(no_follow); self
+    }
 }
 
 // Hm, why are there casts here to the returned type, shouldn't the types always
@@ -151,6 +593,52 @@ pub trait MetadataExt {
     fn blksize(&self) -> raw::blksize_t;
     #[stable(feature = "metadata_ext", since = "1.1.0")]
     fn blocks(&self) -> raw::blkcnt_t;
+
+    /// Returns the major number of the device containing the file, taken
+    /// from `dev` as the platform's `major()` macro would.
+    #[unstable(feature = "metadata_dev_major_minor", reason = "recently added API",
+               issue = "0")]
+    fn dev_major(&self) -> u32;
+    /// Returns the minor number of the device containing the file, taken
+    /// from `dev` as the platform's `minor()` macro would.
+    #[unstable(feature = "metadata_dev_major_minor", reason = "recently added API",
+               issue = "0")]
+    fn dev_minor(&self) -> u32;
+    /// Returns the major number of the device this file represents, if it
+    /// is a block or character device, taken from `rdev`.
+    ///
+    /// How major and minor numbers are packed into a `dev_t` differs between
+    /// Linux, OS X and each of the BSDs; this knows about all of them. For
+    /// files which aren't devices `rdev` is normally 0, and so is this.
+    #[unstable(feature = "metadata_dev_major_minor", reason = "recently added API",
+               issue = "0")]
+    fn rdev_major(&self) -> u32;
+    /// Returns the minor number of the device this file represents, taken
+    /// from `rdev`. See `rdev_major`.
+    #[unstable(feature = "metadata_dev_major_minor", reason = "recently added API",
+               issue = "0")]
+    fn rdev_minor(&self) -> u32;
+
+    /// Returns the seconds of the last access time as a 64-bit number.
+    ///
+    /// `atime` is only as wide as the platform's `time_t`, which on 32-bit
+    /// Linux can't hold times past January 2038. There the metadata comes
+    /// from `statx` when the kernel supports it (Linux 4.11 and later), and
+    /// this returns its full 64-bit value; everywhere else it's the same as
+    /// `atime`.
+    #[unstable(feature = "metadata_time64", reason = "recently added API",
+               issue = "0")]
+    fn atime64(&self) -> i64;
+    /// Returns the seconds of the last modification time as a 64-bit
+    /// number. See `atime64`.
+    #[unstable(feature = "metadata_time64", reason = "recently added API",
+               issue = "0")]
+    fn mtime64(&self) -> i64;
+    /// Returns the seconds of the last status change time as a 64-bit
+    /// number. See `atime64`.
+    #[unstable(feature = "metadata_time64", reason = "recently added API",
+               issue = "0")]
+    fn ctime64(&self) -> i64;
 }
 
 impl MetadataExt for fs::Metadata {
@@ -175,6 +663,15 @@ impl MetadataExt for fs::Metadata {
     fn blocks(&self) -> raw::blkcnt_t {
         self.as_raw_stat().st_blocks as raw::blkcnt_t
     }
+
+    fn dev_major(&self) -> u32 { sys::fs::dev_major(self.as_raw_stat().st_dev as u64) }
+    fn dev_minor(&self) -> u32 { sys::fs::dev_minor(self.as_raw_stat().st_dev as u64) }
+    fn rdev_major(&self) -> u32 { sys::fs::dev_major(self.as_raw_stat().st_rdev as u64) }
+    fn rdev_minor(&self) -> u32 { sys::fs::dev_minor(self.as_raw_stat().st_rdev as u64) }
+
+    fn atime64(&self) -> i64 { self.as_inner().atime64() }
+    fn mtime64(&self) -> i64 { self.as_inner().mtime64() }
+    fn ctime64(&self) -> i64 { self.as_inner().ctime64() }
 }
 
 /// Add special unix types (block/char device, fifo and socket)
@@ -189,6 +686,21 @@ pub trait FileTypeExt {
     fn is_fifo(&self) -> bool;
     /// Returns whether this file type is a socket.
     fn is_socket(&self) -> bool;
+
+    /// Returns the `S_IFMT` bits of the underlying `st_mode`, that is one of
+    /// the `S_IF*` constants.
+    #[unstable(feature = "file_type_raw_mode", reason = "recently added API",
+               issue = "0")]
+    fn raw_mode(&self) -> raw::mode_t;
+
+    /// Creates a file type from a raw Unix mode, such as one read from an
+    /// archive header, without touching the file system.
+    ///
+    /// Only the `S_IFMT` bits of `mode` are kept; permission bits are
+    /// ignored.
+    #[unstable(feature = "file_type_raw_mode", reason = "recently added API",
+               issue = "0")]
+    fn from_raw_mode(mode: raw::mode_t) -> Self;
 }
 
 #[unstable(feature = "file_type_ext", reason = "recently added API",
@@ -198,6 +710,10 @@ impl FileTypeExt for fs::FileType {
     fn is_char_device(&self) -> bool { self.as_inner().is(libc::S_IFCHR) }
     fn is_fifo(&self) -> bool { self.as_inner().is(libc::S_IFIFO) }
     fn is_socket(&self) -> bool { self.as_inner().is(libc::S_IFSOCK) }
+    fn raw_mode(&self) -> raw::mode_t { self.as_inner().raw_mode() }
+    fn from_raw_mode(mode: raw::mode_t) -> fs::FileType {
+        FromInner::from_inner(FromInner::from_inner(mode))
+    }
 }
 
 /// Unix-specific extension methods for `fs::DirEntry`
@@ -207,10 +723,21 @@ pub trait DirEntryExt {
     /// structure.
     #[stable(feature = "dir_entry_ext", since = "1.1.0")]
     fn ino(&self) -> raw::ino_t;
+
+    /// Returns the bytes of this entry's file name, borrowed from the entry
+    /// itself.
+    ///
+    /// Unlike `file_name` this doesn't allocate, which makes it cheap to
+    /// filter entries by name and only allocate for the ones kept. The
+    /// returned slice lives only as long as the `DirEntry`.
+    #[unstable(feature = "dir_entry_name_bytes", reason = "recently added API",
+               issue = "0")]
+    fn file_name_bytes(&self) -> &[u8];
 }
 
 impl DirEntryExt for fs::DirEntry {
     fn ino(&self) -> raw::ino_t { self.as_inner().ino() }
+    fn file_name_bytes(&self) -> &[u8] { self.as_inner().name_bytes() }
 }
 
 /// Creates a new symbolic link on the filesystem.
@@ -242,13 +769,293 @@ pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<()>
     sys::fs::symlink(src.as_ref(), dst.as_ref())
 }
 
+/// Returns the canonical form of a path without going through `realpath`.
+///
+/// On Linux the path is opened with `O_PATH` and the canonical name is read
+/// back from `/proc/self/fd`, which also works for special files and for
+/// paths that `realpath` can't fully `stat`. On other platforms, or when
+/// `/proc` isn't mounted, this is equivalent to `fs::canonicalize`.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(fs_canonicalize_by_fd)]
+/// use std::os::unix::fs;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let path = try!(fs::canonicalize_by_fd("../a/../foo.txt"));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "fs_canonicalize_by_fd", reason = "recently added API",
+           issue = "0")]
+pub fn canonicalize_by_fd<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    sys::fs::canonicalize_by_fd(path.as_ref())
+}
+
+/// An exclusive lock on a file held by this process, as returned by
+/// `FileExt::lock_exclusive_interprocess`. The lock is released when this is
+/// dropped.
+///
+/// This only excludes other processes and other open file descriptions of
+/// the file. It does *not* stop other threads using the same `File` from
+/// reading or writing it, or from locking it themselves.
+#[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+#[must_use]
+pub struct InterprocessLock<'a> {
+    file: &'a fs::File,
+}
+
+#[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+impl<'a> InterprocessLock<'a> {
+    /// Releases the lock, returning any error from doing so, which dropping
+    /// the guard would ignore.
+    pub fn unlock(self) -> io::Result<()> {
+        let ret = self.file.unlock();
+        mem::forget(self);
+        ret
+    }
+}
+
+#[unstable(feature = "file_lock", reason = "recently added API", issue = "0")]
+impl<'a> Drop for InterprocessLock<'a> {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// A handle to an open directory which other files can be opened relative to.
+///
+/// Paths given to the methods of a `Dir` are looked up starting from the
+/// directory itself rather than from the current working directory, so they
+/// keep referring to the same place even if the directory is renamed or the
+/// working directory changes in the meantime.
+///
+/// The working directory is shared by all threads of a process, so changing
+/// it with `env::set_current_dir` races with every relative path used
+/// elsewhere. A `Dir` for each directory a program works in, with the
+/// operations below, makes changing it unnecessary.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(dir_fd)]
+/// use std::fs::OpenOptions;
+/// use std::os::unix::fs::Dir;
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let root = try!(Dir::open_root("/srv/uploads"));
+/// let f = try!(root.resolve_beneath("user/avatar.png",
+///                                   OpenOptions::new().read(true)));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "dir_fd", reason = "recently added API", issue = "0")]
+pub struct Dir(sys::fs::DirFd);
+
+#[unstable(feature = "dir_fd", reason = "recently added API", issue = "0")]
+impl Dir {
+    /// Opens the directory at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Dir> {
+        sys::fs::DirFd::open(path.as_ref(), false).map(Dir)
+    }
+
+    /// Opens the directory at `path` to be used as the root of a confined
+    /// traversal with `resolve_beneath`.
+    ///
+    /// This is like `open` except that it fails if the last component of
+    /// `path` is a symlink, so the root is always the directory that was
+    /// actually named.
+    pub fn open_root<P: AsRef<Path>>(path: P) -> io::Result<Dir> {
+        sys::fs::DirFd::open(path.as_ref(), true).map(Dir)
+    }
+
+    /// Opens the current working directory.
+    ///
+    /// The returned `Dir` keeps referring to the same directory after the
+    /// working directory is changed.
+    #[unstable(feature = "dir_fd_current", reason = "recently added API", issue = "0")]
+    pub fn current() -> io::Result<Dir> {
+        sys::fs::DirFd::current().map(Dir)
+    }
+
+    /// Opens a file relative to this directory with the options specified by
+    /// `opts`.
+    ///
+    /// An absolute `path` ignores this directory entirely, just like
+    /// `openat(2)` does.
+    pub fn open_file<P: AsRef<Path>>(&self, path: P, opts: &OpenOptions)
+                                     -> io::Result<fs::File> {
+        self.0.open_file(path.as_ref(), opts.as_inner()).map(fs::File::from_inner)
+    }
+
+    /// Opens a directory relative to this directory.
+    pub fn open_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<Dir> {
+        self.0.open_dir(path.as_ref()).map(Dir)
+    }
+
+    /// Creates a new, empty directory relative to this directory, like
+    /// `fs::create_dir`.
+    #[unstable(feature = "dir_fd_current", reason = "recently added API", issue = "0")]
+    pub fn create_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.0.create_dir(path.as_ref())
+    }
+
+    /// Removes a file relative to this directory, like `fs::remove_file`.
+    #[unstable(feature = "dir_fd_current", reason = "recently added API", issue = "0")]
+    pub fn remove_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.0.remove_file(path.as_ref())
+    }
+
+    /// Removes an empty directory relative to this directory, like
+    /// `fs::remove_dir`.
+    #[unstable(feature = "dir_fd_current", reason = "recently added API", issue = "0")]
+    pub fn remove_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.0.remove_dir(path.as_ref())
+    }
+
+    /// Renames `from`, relative to this directory, to `to`, relative to
+    /// `to_dir`, like `fs::rename`.
+    #[unstable(feature = "dir_fd_current", reason = "recently added API", issue = "0")]
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to_dir: &Dir, to: Q)
+                                                  -> io::Result<()> {
+        self.0.rename(from.as_ref(), &to_dir.0, to.as_ref())
+    }
+
+    /// Creates a hard link named `dst`, relative to `dst_dir`, to the file
+    /// named `src`, relative to this directory.
+    ///
+    /// Unlike `fs::hard_link`, whose relative paths are both resolved against
+    /// the current working directory, each name here is resolved against its
+    /// own directory. Absolute paths ignore the directory, as with
+    /// `linkat(2)`.
+    ///
+    /// If `src` is a symlink, the new link points at the symlink itself
+    /// unless `follow_symlinks` is `true`, in which case it points at the
+    /// symlink's target.
+    pub fn link_at<P: AsRef<Path>, Q: AsRef<Path>>(&self, src: P, dst_dir: &Dir, dst: Q,
+                                                   follow_symlinks: bool) -> io::Result<()> {
+        self.0.link_at(src.as_ref(), &dst_dir.0, dst.as_ref(), follow_symlinks)
+    }
+
+    /// Opens a file beneath this directory, guaranteeing that it can't be
+    /// escaped while resolving `path`.
+    ///
+    /// `path` is walked one component at a time without following symlinks.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `PermissionDenied` if `path` is absolute or contains a `..`
+    /// component, and with the error of the underlying `openat` call if any
+    /// component of `path` is a symlink.
+    pub fn resolve_beneath<P: AsRef<Path>>(&self, path: P, opts: &OpenOptions)
+                                           -> io::Result<fs::File> {
+        self.0.resolve_beneath(path.as_ref(), opts.as_inner())
+            .map(fs::File::from_inner)
+    }
+
+    /// Opens a directory beneath this directory, with the same restrictions
+    /// as `resolve_beneath`.
+    pub fn resolve_dir_beneath<P: AsRef<Path>>(&self, path: P) -> io::Result<Dir> {
+        self.0.resolve_dir_beneath(path.as_ref()).map(Dir)
+    }
+}
+
+/// Runs `f` with `dir` as the current working directory, changing back to
+/// the previous one afterwards, even if `f` panics.
+///
+/// This is meant for calling code which only takes relative paths and can't
+/// be given a `Dir`. The working directory is shared by the whole process,
+/// so other threads see the change while `f` runs, and anything resolving
+/// relative paths concurrently, including another `with_cwd`, may end up in
+/// the wrong place. Prefer the methods of `Dir` wherever possible.
+///
+/// # Errors
+///
+/// Fails if the current directory can't be opened or either change of the
+/// working directory fails. If changing back fails, `f` has already run.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(dir_fd, dir_fd_current)]
+/// use std::fs::File;
+/// use std::os::unix::fs::{self, Dir};
+///
+/// # fn foo() -> std::io::Result<()> {
+/// let build = try!(Dir::open("build"));
+/// let f = try!(try!(fs::with_cwd(&build, || File::create("out.log"))));
+/// # Ok(())
+/// # }
+/// ```
+#[unstable(feature = "dir_fd_current", reason = "recently added API", issue = "0")]
+pub fn with_cwd<R, F: FnOnce() -> R>(dir: &Dir, f: F) -> io::Result<R> {
+    // Changes back if `f` panics.
+    struct Restore(Option<sys::fs::DirFd>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(ref prev) = self.0 {
+                let _ = prev.set_current();
+            }
+        }
+    }
+
+    let mut restore = Restore(Some(try!(sys::fs::DirFd::current())));
+    try!(dir.0.set_current());
+    let ret = f();
+    let prev = restore.0.take().unwrap();
+    try!(prev.set_current());
+    Ok(ret)
+}
+
+impl AsInner<sys::fs::DirFd> for Dir {
+    fn as_inner(&self) -> &sys::fs::DirFd { &self.0 }
+}
+
+impl FromInner<sys::fs::DirFd> for Dir {
+    fn from_inner(dir: sys::fs::DirFd) -> Dir { Dir(dir) }
+}
+
+#[unstable(feature = "dir_fd", reason = "recently added API", issue = "0")]
+impl AsRawFd for Dir {
+    fn as_raw_fd(&self) -> RawFd { self.0.fd().raw() }
+}
+
+#[unstable(feature = "dir_fd", reason = "recently added API", issue = "0")]
+impl FromRawFd for Dir {
+    unsafe fn from_raw_fd(fd: RawFd) -> Dir {
+        Dir(sys::fs::DirFd::from_inner(fd))
+    }
+}
+
+#[unstable(feature = "dir_fd", reason = "recently added API", issue = "0")]
+impl IntoRawFd for Dir {
+    fn into_raw_fd(self) -> RawFd { self.0.into_fd().into_raw() }
+}
+
 #[unstable(feature = "dir_builder", reason = "recently added API",
            issue = "27710")]
 /// An extension trait for `fs::DirBuilder` for unix-specific options.
 pub trait DirBuilderExt {
     /// Sets the mode to create new directories with. This option defaults to
     /// 0o777.
+    ///
+    /// When creating recursively the mode applies to every directory that
+    /// gets created, including the intermediate ones, while directories
+    /// that already exist are left alone. As with `mkdir`, the bits set in
+    /// the process's umask are cleared from the mode; see `mode_exact`.
     fn mode(&mut self, mode: raw::mode_t) -> &mut Self;
+
+    /// Sets the mode to create new directories with, disregarding the umask.
+    ///
+    /// Each new directory is created with `mkdir`, which applies the umask,
+    /// and then given exactly `mode` with `chmod`. Until then it has the
+    /// more restrictive mode, never a more permissive one. Overrides any
+    /// earlier call to `mode`, and vice versa.
+    #[unstable(feature = "dir_builder_mode_exact", reason = "recently added API",
+               issue = "0")]
+    fn mode_exact(&mut self, mode: raw::mode_t) -> &mut Self;
 }
 
 impl DirBuilderExt for fs::DirBuilder {
@@ -256,5 +1063,10 @@ impl DirBuilderExt for fs::DirBuilder {
         self.as_inner_mut().set_mode(mode);
         self
     }
+
+    fn mode_exact(&mut self, mode: raw::mode_t) -> &mut fs::DirBuilder {
+        self.as_inner_mut().set_mode_exact(mode);
+        self
+    }
 }
 
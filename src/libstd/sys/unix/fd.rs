@@ -14,6 +14,7 @@ use mem;
 use sys::c;
 use sys::cvt;
 use sys_common::AsInner;
+use vec::Vec;
 
 pub struct FileDesc {
     fd: c_int,
@@ -42,6 +43,22 @@ impl FileDesc {
         Ok(ret as usize)
     }
 
+    /// Reads into the spare capacity of `buf`, extending its length by the
+    /// number of bytes read.
+    ///
+    /// The uninitialized region is handed straight to `read`, so it never has
+    /// to be zeroed first.
+    pub fn read_to_spare(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let len = buf.len();
+        let ret = try!(cvt(unsafe {
+            libc::read(self.fd,
+                       buf.as_mut_ptr().offset(len as isize) as *mut c_void,
+                       (buf.capacity() - len) as size_t)
+        }));
+        unsafe { buf.set_len(len + ret as usize); }
+        Ok(ret as usize)
+    }
+
     pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
         let ret = try!(cvt(unsafe {
             libc::write(self.fd,
@@ -11,29 +11,51 @@
 use io::prelude::*;
 use os::unix::prelude::*;
 
+use cmp;
 use ffi::{CString, CStr, OsString, OsStr};
 use fmt;
 use io::{self, Error, ErrorKind, SeekFrom};
 use libc::{self, c_int, size_t, off_t, c_char, mode_t};
 use mem;
-use path::{Path, PathBuf};
+use ops::Range;
+use path::{Component, Path, PathBuf};
 use ptr;
+use slice;
+use string::String;
 use sync::Arc;
 use sys::fd::FileDesc;
 use sys::platform::raw;
 use sys::{c, cvt, cvt_r};
 use sys_common::{AsInner, FromInner};
+use time::Duration;
 use vec::Vec;
 
 pub struct File(FileDesc);
 
+pub struct Mmap {
+    // Start of the mapping, which is page aligned and thus may lie `delta`
+    // bytes before the data that was asked for. Null for empty maps, as
+    // mmap refuses to create those.
+    ptr: *mut libc::c_void,
+    delta: usize,
+    len: usize,
+}
+
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
 pub struct FileAttr {
     stat: raw::stat,
+    // The seconds of the access, modification and change times, when they
+    // came from `statx` and so may not fit in the `time_t` of `stat`.
+    secs64: Option<[i64; 3]>,
 }
 
 pub struct ReadDir {
     dirp: Dir,
     root: Arc<PathBuf>,
+    // Set once the stream has ended or failed in a way it can't go on from.
+    done: bool,
 }
 
 struct Dir(*mut libc::DIR);
@@ -41,6 +63,8 @@ struct Dir(*mut libc::DIR);
 unsafe impl Send for Dir {}
 unsafe impl Sync for Dir {}
 
+pub struct DirFd(FileDesc);
+
 pub struct DirEntry {
     buf: Vec<u8>, // actually *mut libc::dirent_t
     root: Arc<PathBuf>,
@@ -60,17 +84,104 @@ pub struct FilePermissions { mode: mode_t }
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct FileType { mode: mode_t }
 
-pub struct DirBuilder { mode: mode_t }
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct FileIdentity {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    mtime: (i64, i64),
+    ctime: (i64, i64),
+}
+
+pub struct DirBuilder { mode: mode_t, exact: bool }
 
 impl FileAttr {
+    fn from_stat(stat: raw::stat) -> FileAttr {
+        FileAttr { stat: stat, secs64: None }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn from_statx(stx: &c::statx) -> FileAttr {
+        let mut stat: raw::stat = unsafe { mem::zeroed() };
+        stat.st_dev = makedev(stx.stx_dev_major, stx.stx_dev_minor) as _;
+        stat.st_ino = stx.stx_ino as _;
+        stat.st_mode = stx.stx_mode as _;
+        stat.st_nlink = stx.stx_nlink as _;
+        stat.st_uid = stx.stx_uid as _;
+        stat.st_gid = stx.stx_gid as _;
+        stat.st_rdev = makedev(stx.stx_rdev_major, stx.stx_rdev_minor) as _;
+        stat.st_size = stx.stx_size as _;
+        stat.st_blksize = stx.stx_blksize as _;
+        stat.st_blocks = stx.stx_blocks as _;
+        stat.st_atime = stx.stx_atime.tv_sec as _;
+        stat.st_atime_nsec = stx.stx_atime.tv_nsec as _;
+        stat.st_mtime = stx.stx_mtime.tv_sec as _;
+        stat.st_mtime_nsec = stx.stx_mtime.tv_nsec as _;
+        stat.st_ctime = stx.stx_ctime.tv_sec as _;
+        stat.st_ctime_nsec = stx.stx_ctime.tv_nsec as _;
+        FileAttr {
+            stat: stat,
+            secs64: Some([stx.stx_atime.tv_sec, stx.stx_mtime.tv_sec,
+                          stx.stx_ctime.tv_sec]),
+        }
+    }
+
+    pub fn atime64(&self) -> i64 {
+        self.secs64.map_or(self.stat.st_atime as i64, |s| s[0])
+    }
+    pub fn mtime64(&self) -> i64 {
+        self.secs64.map_or(self.stat.st_mtime as i64, |s| s[1])
+    }
+    pub fn ctime64(&self) -> i64 {
+        self.secs64.map_or(self.stat.st_ctime as i64, |s| s[2])
+    }
+
     pub fn size(&self) -> u64 { self.stat.st_size as u64 }
     pub fn perm(&self) -> FilePermissions {
-        FilePermissions { mode: (self.stat.st_mode as mode_t) & 0o777 }
+        FilePermissions { mode: (self.stat.st_mode as mode_t) & 0o7777 }
     }
 
     pub fn file_type(&self) -> FileType {
         FileType { mode: self.stat.st_mode as mode_t }
     }
+
+    pub fn identity(&self) -> FileIdentity {
+        FileIdentity {
+            dev: self.stat.st_dev as u64,
+            ino: self.stat.st_ino as u64,
+            size: self.stat.st_size as u64,
+            mtime: (self.mtime64(), self.stat.st_mtime_nsec as i64),
+            ctime: (self.ctime64(), self.stat.st_ctime_nsec as i64),
+        }
+    }
+
+    pub fn preferred_io_block_size(&self) -> Option<u64> {
+        if self.stat.st_blksize > 0 {
+            Some(self.stat.st_blksize as u64)
+        } else {
+            None
+        }
+    }
+
+    // A directory is linked to from its parent, from its own `.` and from the
+    // `..` of each subdirectory. File systems which don't keep up that count,
+    // such as btrfs, report a link count of 1 for every directory.
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    pub fn subdir_count(&self) -> Option<u64> {
+        let nlink = self.stat.st_nlink as u64;
+        if self.file_type().is_dir() && nlink > 1 {
+            Some(nlink - 2)
+        } else {
+            None
+        }
+    }
+
+    // HFS+ and APFS count every entry of a directory in its link count, not
+    // just its subdirectories.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn subdir_count(&self) -> Option<u64> {
+        None
+    }
 }
 
 impl AsInner<raw::stat> for FileAttr {
@@ -105,6 +216,9 @@ impl FilePermissions {
         }
     }
     pub fn mode(&self) -> raw::mode_t { self.mode }
+    pub fn apply_bits_from(&mut self, other: &FilePermissions, mask: raw::mode_t) {
+        self.mode = (self.mode & !mask) | (other.mode & mask);
+    }
 }
 
 impl FileType {
@@ -113,6 +227,27 @@ impl FileType {
     pub fn is_symlink(&self) -> bool { self.is(libc::S_IFLNK) }
 
     pub fn is(&self, mode: mode_t) -> bool { self.mode & libc::S_IFMT == mode }
+
+    pub fn raw_mode(&self) -> raw::mode_t { (self.mode & libc::S_IFMT) as raw::mode_t }
+
+    pub fn describe(&self) -> &'static str {
+        match self.mode & libc::S_IFMT {
+            libc::S_IFDIR => "directory",
+            libc::S_IFREG => "file",
+            libc::S_IFLNK => "symlink",
+            libc::S_IFIFO => "fifo",
+            libc::S_IFSOCK => "socket",
+            libc::S_IFBLK => "block device",
+            libc::S_IFCHR => "char device",
+            _ => "unknown",
+        }
+    }
+}
+
+impl FromInner<raw::mode_t> for FileType {
+    fn from_inner(mode: raw::mode_t) -> FileType {
+        FileType { mode: mode as mode_t & libc::S_IFMT }
+    }
 }
 
 impl FromInner<raw::mode_t> for FilePermissions {
@@ -121,10 +256,10 @@ impl FromInner<raw::mode_t> for FilePermissions {
     }
 }
 
-impl Iterator for ReadDir {
-    type Item = io::Result<DirEntry>;
-
-    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+impl ReadDir {
+    pub fn next_matching<F>(&mut self, mut f: F) -> Option<io::Result<DirEntry>>
+        where F: FnMut(&OsStr) -> bool
+    {
         extern {
             fn rust_dirent_t_size() -> c_int;
         }
@@ -136,26 +271,79 @@ impl Iterator for ReadDir {
 
         let mut entry_ptr = ptr::null_mut();
         loop {
-            if unsafe { libc::readdir_r(self.dirp.0, ptr, &mut entry_ptr) != 0 } {
-                return Some(Err(Error::last_os_error()))
+            if self.done {
+                return None
+            }
+            // readdir_r returns the error rather than setting errno.
+            let err = unsafe { libc::readdir_r(self.dirp.0, ptr, &mut entry_ptr) };
+            if err != 0 {
+                let err = Error::from_raw_os_error(err);
+                self.record_error(&err);
+                return Some(Err(err))
             }
             if entry_ptr.is_null() {
+                self.done = true;
                 return None
             }
 
-            let entry = DirEntry {
+            // Look at the name while it's still in the raw buffer so entries
+            // which are skipped never have to be turned into a `DirEntry`.
+            let name = unsafe { dirent_name(ptr) };
+            if name == b"." || name == b".." || !f(OsStr::from_bytes(name)) {
+                continue
+            }
+            return Some(Ok(DirEntry {
                 buf: buf,
                 root: self.root.clone()
-            };
-            if entry.name_bytes() == b"." || entry.name_bytes() == b".." {
-                buf = entry.buf;
-            } else {
-                return Some(Ok(entry))
-            }
+            }))
         }
     }
 }
 
+impl ReadDir {
+    // The only error which is tied to a single entry is ENAMETOOLONG, for a
+    // name which doesn't fit into the dirent buffer; readdir_r has already
+    // moved past that entry, so the rest can still be read. Any other error
+    // comes from the directory stream itself, whose position is unknown
+    // afterwards, so iteration stops there.
+    fn record_error(&mut self, err: &Error) {
+        self.done = err.raw_os_error() != Some(libc::ENAMETOOLONG);
+    }
+
+    // Looking the entry up relative to the open directory saves building its
+    // full path and walking all of its parents again.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn entry_metadata(&self, entry: &DirEntry) -> io::Result<FileAttr> {
+        extern {
+            fn rust_list_dir_val(ptr: *mut libc::dirent_t) -> *const c_char;
+        }
+        let dirfd = unsafe { c::dirfd(self.dirp.0) };
+        let name = unsafe { CStr::from_ptr(rust_list_dir_val(entry.dirent())) };
+        if let Some(ret) = try_statx(dirfd, name, STATX_NOFOLLOW) {
+            return ret
+        }
+        let mut stat: raw::stat = unsafe { mem::zeroed() };
+        try!(cvt(unsafe {
+            c::fstatat(dirfd, name.as_ptr(), &mut stat as *mut _ as *mut _,
+                       c::AT_SYMLINK_NOFOLLOW)
+        }));
+        Ok(FileAttr::from_stat(stat))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn entry_metadata(&self, entry: &DirEntry) -> io::Result<FileAttr> {
+        entry.metadata()
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+        self.next_matching(|_| true)
+    }
+}
+
 impl Drop for Dir {
     fn drop(&mut self) {
         let r = unsafe { libc::closedir(self.0) };
@@ -168,6 +356,13 @@ impl DirEntry {
         self.root.join(<OsStr as OsStrExt>::from_bytes(self.name_bytes()))
     }
 
+    pub fn into_path(self) -> PathBuf {
+        let DirEntry { buf, root } = self;
+        let mut path = Arc::try_unwrap(root).unwrap_or_else(|root| (*root).clone());
+        path.push(OsStr::from_bytes(unsafe { dirent_name(buf.as_ptr() as *mut _) }));
+        path
+    }
+
     pub fn file_name(&self) -> OsString {
         OsStr::from_bytes(self.name_bytes()).to_os_string()
     }
@@ -195,13 +390,8 @@ impl DirEntry {
         unsafe { rust_dir_get_ino(self.dirent()) }
     }
 
-    fn name_bytes(&self) -> &[u8] {
-        extern {
-            fn rust_list_dir_val(ptr: *mut libc::dirent_t) -> *const c_char;
-        }
-        unsafe {
-            CStr::from_ptr(rust_list_dir_val(self.dirent())).to_bytes()
-        }
+    pub fn name_bytes(&self) -> &[u8] {
+        unsafe { dirent_name(self.dirent()) }
     }
 
     fn dirent(&self) -> *mut libc::dirent_t {
@@ -209,6 +399,13 @@ impl DirEntry {
     }
 }
 
+unsafe fn dirent_name<'a>(ptr: *mut libc::dirent_t) -> &'a [u8] {
+    extern {
+        fn rust_list_dir_val(ptr: *mut libc::dirent_t) -> *const c_char;
+    }
+    CStr::from_ptr(rust_list_dir_val(ptr)).to_bytes()
+}
+
 impl OpenOptions {
     pub fn new() -> OpenOptions {
         OpenOptions {
@@ -219,28 +416,44 @@ impl OpenOptions {
         }
     }
 
-    pub fn read(&mut self, read: bool) {
+    pub fn read(&mut self, read: bool) -> &mut OpenOptions {
         self.read = read;
+        self
     }
 
-    pub fn write(&mut self, write: bool) {
+    pub fn write(&mut self, write: bool) -> &mut OpenOptions {
         self.write = write;
+        self
     }
 
-    pub fn append(&mut self, append: bool) {
+    pub fn append(&mut self, append: bool) -> &mut OpenOptions {
         self.flag(libc::O_APPEND, append);
+        self
     }
 
-    pub fn truncate(&mut self, truncate: bool) {
+    pub fn truncate(&mut self, truncate: bool) -> &mut OpenOptions {
         self.flag(libc::O_TRUNC, truncate);
+        self
     }
 
-    pub fn create(&mut self, create: bool) {
+    pub fn create(&mut self, create: bool) -> &mut OpenOptions {
         self.flag(libc::O_CREAT, create);
+        self
+    }
+
+    pub fn create_new(&mut self, create_new: bool) -> &mut OpenOptions {
+        self.flag(libc::O_EXCL, create_new);
+        self
     }
 
-    pub fn mode(&mut self, mode: raw::mode_t) {
+    pub fn mode(&mut self, mode: raw::mode_t) -> &mut OpenOptions {
         self.mode = mode as mode_t;
+        self
+    }
+
+    pub fn no_follow(&mut self, no_follow: bool) -> &mut OpenOptions {
+        self.flag(c::O_NOFOLLOW, no_follow);
+        self
     }
 
     fn flag(&mut self, bit: c_int, on: bool) {
@@ -250,6 +463,23 @@ impl OpenOptions {
             self.flags &= !bit;
         }
     }
+
+    fn get_flags(&self) -> c_int {
+        // O_EXCL only means something together with O_CREAT, and then makes
+        // open fail if the path exists in any form, including as a symlink,
+        // without ever following it.
+        let flags = if self.flags & libc::O_EXCL != 0 {
+            self.flags | libc::O_CREAT
+        } else {
+            self.flags
+        };
+        flags | match (self.read, self.write) {
+            (true, true) => libc::O_RDWR,
+            (false, true) => libc::O_WRONLY,
+            (true, false) |
+            (false, false) => libc::O_RDONLY,
+        }
+    }
 }
 
 impl File {
@@ -259,15 +489,24 @@ impl File {
     }
 
     pub fn open_c(path: &CStr, opts: &OpenOptions) -> io::Result<File> {
-        let flags = opts.flags | match (opts.read, opts.write) {
-            (true, true) => libc::O_RDWR,
-            (false, true) => libc::O_WRONLY,
-            (true, false) |
-            (false, false) => libc::O_RDONLY,
-        };
-        let fd = try!(cvt_r(|| unsafe {
+        let flags = opts.get_flags();
+        let fd = match cvt_r(|| unsafe {
             libc::open(path.as_ptr(), flags, opts.mode)
-        }));
+        }) {
+            Ok(fd) => fd,
+            // The same error also comes from a symlink loop anywhere in the
+            // path, so it only means the path is a symlink if lstat agrees.
+            Err(e) => {
+                if flags & c::O_NOFOLLOW != 0 && is_nofollow_error(&e) {
+                    let path = Path::new(OsStr::from_bytes(path.to_bytes()));
+                    if let Ok(true) = lstat(path).map(|a| a.file_type().is_symlink()) {
+                        return Err(Error::new(ErrorKind::SymlinkNotFollowed,
+                                              "path is a symbolic link"))
+                    }
+                }
+                return Err(e)
+            }
+        };
         let fd = FileDesc::new(fd);
         // Even though we open with the O_CLOEXEC flag, still set CLOEXEC here,
         // in case the open flag is not supported (it's just ignored by the OS
@@ -276,12 +515,74 @@ impl File {
         Ok(File(fd))
     }
 
+    pub fn open_path(p: &Path, no_follow: bool) -> io::Result<File> {
+        // Without O_PATH the best that can be done is a read-only descriptor,
+        // which needs read permission and can't refer to a symlink itself.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        const ACCESS: c_int = c::O_PATH;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        const ACCESS: c_int = libc::O_RDONLY;
+
+        let p = try!(cstr(p));
+        let mut flags = ACCESS | libc::O_CLOEXEC;
+        if no_follow {
+            flags |= c::O_NOFOLLOW;
+        }
+        let fd = try!(cvt_r(|| unsafe { libc::open(p.as_ptr(), flags, 0) }));
+        let fd = FileDesc::new(fd);
+        fd.set_cloexec();
+        Ok(File(fd))
+    }
+
+    pub fn reopen(&self) -> io::Result<File> {
+        let fd = self.0.raw();
+        let flags = try!(cvt(unsafe { libc::fcntl(fd, libc::F_GETFL) }));
+        let flags = flags & (libc::O_ACCMODE | libc::O_APPEND) | libc::O_CLOEXEC;
+        let path = try!(reopen_path(fd));
+        let fd = try!(cvt_r(|| unsafe { libc::open(path.as_ptr(), flags, 0) }));
+        let fd = FileDesc::new(fd);
+        fd.set_cloexec();
+        return Ok(File(fd));
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        fn reopen_path(fd: c_int) -> io::Result<CString> {
+            // Opening the magic link creates a new open file description
+            // for whatever the descriptor refers to, even if it was renamed
+            // or unlinked in the meantime.
+            Ok(CString::new(format!("/proc/self/fd/{}", fd)).unwrap())
+        }
+
+        #[cfg(target_os = "macos")]
+        fn reopen_path(fd: c_int) -> io::Result<CString> {
+            let mut buf = vec![0; libc::PATH_MAX as usize];
+            try!(cvt(unsafe { libc::fcntl(fd, libc::F_GETPATH, buf.as_ptr()) }));
+            let l = buf.iter().position(|&c| c == 0).unwrap();
+            buf.truncate(l);
+            Ok(CString::new(buf).unwrap())
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android",
+                      target_os = "macos")))]
+        fn reopen_path(_fd: c_int) -> io::Result<CString> {
+            Err(Error::new(ErrorKind::Other,
+                           "reopening a file is not supported on this platform"))
+        }
+    }
+
     pub fn file_attr(&self) -> io::Result<FileAttr> {
+        let empty = unsafe { CStr::from_ptr(b"\0".as_ptr() as *const c_char) };
+        if let Some(ret) = try_statx(self.0.raw(), empty, STATX_EMPTY_PATH) {
+            return ret
+        }
         let mut stat: raw::stat = unsafe { mem::zeroed() };
         try!(cvt(unsafe {
             libc::fstat(self.0.raw(), &mut stat as *mut _ as *mut _)
         }));
-        Ok(FileAttr { stat: stat })
+        Ok(FileAttr::from_stat(stat))
+    }
+
+    pub fn size(&self) -> io::Result<u64> {
+        self.file_attr().map(|attr| attr.size())
     }
 
     pub fn fsync(&self) -> io::Result<()> {
@@ -305,6 +606,34 @@ impl File {
         unsafe fn os_datasync(fd: c_int) -> c_int { libc::fsync(fd) }
     }
 
+    /// Syncs the directory containing this file. Where the file's path
+    /// can't be found out, or the file has no parent, this does nothing.
+    pub fn sync_parent_dir(&self) -> io::Result<()> {
+        let path = match fd_path(self.0.raw()) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let dir = match path.parent() {
+            Some(dir) => try!(File::open(dir, OpenOptions::new().read(true))),
+            None => return Ok(()),
+        };
+        dir.fsync()
+    }
+
+    pub fn full_fsync(&self) -> io::Result<()> {
+        try!(cvt_r(|| unsafe { os_full_fsync(self.0.raw()) }));
+        return Ok(());
+
+        // On OS X `fsync` only hands the data to the drive, which is free to
+        // reorder it from its own cache; F_FULLFSYNC flushes that cache too.
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        unsafe fn os_full_fsync(fd: c_int) -> c_int {
+            libc::fcntl(fd, libc::F_FULLFSYNC)
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+        unsafe fn os_full_fsync(fd: c_int) -> c_int { libc::fsync(fd) }
+    }
+
     pub fn truncate(&self, size: u64) -> io::Result<()> {
         try!(cvt_r(|| unsafe {
             libc::ftruncate(self.0.raw(), size as libc::off_t)
@@ -312,14 +641,116 @@ impl File {
         Ok(())
     }
 
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn allocate(&self, size: u64) -> io::Result<()> {
+        try!(cvt_r(|| unsafe {
+            c::fallocate(self.0.raw(), 0, 0, size as off_t)
+        }));
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn fallocate(&self, mode: c_int, offset: u64, len: u64) -> io::Result<()> {
+        if offset > off_t::max_value() as u64 || len > off_t::max_value() as u64 {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "range is too large"))
+        }
+        try!(cvt_r(|| unsafe {
+            c::fallocate(self.0.raw(), mode, offset as off_t, len as off_t)
+        }));
+        Ok(())
+    }
+
+    #[cfg(target_os = "freebsd")]
+    pub fn allocate(&self, size: u64) -> io::Result<()> {
+        // posix_fallocate returns the error instead of setting errno
+        match unsafe { c::posix_fallocate(self.0.raw(), 0, size as off_t) } {
+            0 => Ok(()),
+            e => Err(Error::from_raw_os_error(e)),
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux",
+                  target_os = "android",
+                  target_os = "freebsd")))]
+    pub fn allocate(&self, _size: u64) -> io::Result<()> {
+        Err(Error::from_raw_os_error(libc::EOPNOTSUPP))
+    }
+
+    pub fn set_perm(&self, perm: FilePermissions) -> io::Result<()> {
+        try!(cvt_r(|| unsafe { libc::fchmod(self.0.raw(), perm.mode) }));
+        Ok(())
+    }
+
+    pub fn set_readonly(&self, readonly: bool) -> io::Result<()> {
+        // `FileAttr::perm` only keeps the rwx bits, so go through the raw
+        // mode to not drop setuid/setgid/sticky.
+        let attr = try!(self.file_attr());
+        let mut perm = FilePermissions {
+            mode: (attr.stat.st_mode as mode_t) & 0o7777,
+        };
+        if readonly {
+            perm.set_readonly(true);
+        } else {
+            // Only the owner gets write access back, rather than everyone
+            // as with `Permissions::set_readonly(false)`.
+            perm.mode |= 0o200;
+        }
+        self.set_perm(perm)
+    }
+
     pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
         self.0.read(buf)
     }
 
+    pub fn read_vectored(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+        let iov = bufs.iter_mut().take(c::IOV_MAX).map(|buf| c::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len() as size_t,
+        }).collect::<Vec<_>>();
+        let ret = try!(cvt(unsafe {
+            c::readv(self.0.raw(), iov.as_ptr(), iov.len() as c_int)
+        }));
+        Ok(ret as usize)
+    }
+
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let ret = try!(cvt(unsafe {
+            libc::pread(self.0.raw(), buf.as_mut_ptr() as *mut libc::c_void,
+                        buf.len() as size_t, offset as off_t)
+        }));
+        Ok(ret as usize)
+    }
+
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        let ret = try!(cvt(unsafe {
+            libc::pwrite(self.0.raw(), buf.as_ptr() as *const libc::c_void,
+                         buf.len() as size_t, offset as off_t)
+        }));
+        Ok(ret as usize)
+    }
+
+    pub fn read_to_spare(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        self.0.read_to_spare(buf)
+    }
+
     pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
         self.0.write(buf)
     }
 
+    pub fn write_vectored(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+        // Slices past IOV_MAX are left for the caller's next call, just like
+        // the rest of a short write.
+        let iov = bufs.iter().take(c::IOV_MAX).map(|buf| c::iovec {
+            iov_base: buf.as_ptr() as *mut libc::c_void,
+            iov_len: buf.len() as size_t,
+        }).collect::<Vec<_>>();
+        let ret = try!(cvt(unsafe {
+            c::writev(self.0.raw(), iov.as_ptr(), iov.len() as c_int)
+        }));
+        Ok(ret as usize)
+    }
+
     pub fn flush(&self) -> io::Result<()> { Ok(()) }
 
     pub fn seek(&self, pos: SeekFrom) -> io::Result<u64> {
@@ -332,97 +763,677 @@ impl File {
         Ok(n as u64)
     }
 
-    pub fn fd(&self) -> &FileDesc { &self.0 }
+    // lseek returns the new offset either way.
+    pub fn seek_discard(&self, pos: SeekFrom) -> io::Result<()> {
+        self.seek(pos).map(|_| ())
+    }
 
-    pub fn into_fd(self) -> FileDesc { self.0 }
-}
+    pub fn allocated_ranges(&self) -> io::Result<Vec<Range<u64>>> {
+        let len = try!(self.size());
+        return allocated_ranges(self.0.raw(), len);
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        fn allocated_ranges(fd: c_int, len: u64) -> io::Result<Vec<Range<u64>>> {
+            // SEEK_DATA and SEEK_HOLE move the cursor, so put it back once
+            // we're done.
+            let cursor = try!(seek(fd, 0, libc::SEEK_CUR));
+            let ret = walk(fd, len);
+            try!(seek(fd, cursor, libc::SEEK_SET));
+            ret
+        }
 
-impl DirBuilder {
-    pub fn new() -> DirBuilder {
-        DirBuilder { mode: 0o777 }
-    }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        fn walk(fd: c_int, len: u64) -> io::Result<Vec<Range<u64>>> {
+            let mut ranges = Vec::new();
+            let mut pos = 0;
+            while pos < len {
+                let start = match seek(fd, pos, c::SEEK_DATA) {
+                    Ok(start) => start,
+                    // No more data past `pos`.
+                    Err(ref e) if e.raw_os_error() == Some(libc::ENXIO) => break,
+                    // The file system doesn't know about holes, so the whole
+                    // file is data.
+                    Err(ref e) if pos == 0 &&
+                                  e.raw_os_error() == Some(libc::EINVAL) => {
+                        ranges.push(0..len);
+                        break
+                    }
+                    Err(e) => return Err(e),
+                };
+                let end = try!(seek(fd, start, c::SEEK_HOLE));
+                ranges.push(start..end);
+                pos = end;
+            }
+            Ok(ranges)
+        }
 
-    pub fn mkdir(&self, p: &Path) -> io::Result<()> {
-        let p = try!(cstr(p));
-        try!(cvt(unsafe { libc::mkdir(p.as_ptr(), self.mode) }));
-        Ok(())
-    }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        fn seek(fd: c_int, pos: u64, whence: c_int) -> io::Result<u64> {
+            let n = try!(cvt(unsafe { libc::lseek(fd, pos as off_t, whence) }));
+            Ok(n as u64)
+        }
 
-    pub fn set_mode(&mut self, mode: mode_t) {
-        self.mode = mode;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        fn allocated_ranges(_fd: c_int, len: u64) -> io::Result<Vec<Range<u64>>> {
+            Ok(if len == 0 { Vec::new() } else { vec![0..len] })
+        }
     }
-}
 
-fn cstr(path: &Path) -> io::Result<CString> {
-    path.as_os_str().to_cstring().ok_or(
-        io::Error::new(io::ErrorKind::InvalidInput, "path contained a null"))
-}
+    pub fn poll(&self, events: libc::c_short, timeout: Option<Duration>)
+                -> io::Result<()> {
+        let timeout = match timeout {
+            // Round up so that a non-zero timeout never turns into a zero
+            // one, which wouldn't wait at all.
+            Some(dur) => {
+                let ms = dur.as_secs().checked_mul(1000).and_then(|ms| {
+                    ms.checked_add((dur.subsec_nanos() as u64 + 999_999) / 1_000_000)
+                }).unwrap_or(u64::max_value());
+                cmp::min(ms, c_int::max_value() as u64) as c_int
+            }
+            None => -1,
+        };
+        let mut fd = c::pollfd { fd: self.0.raw(), events: events, revents: 0 };
+        // FIXME: an interrupted poll is restarted with the full timeout.
+        let n = try!(cvt_r(|| unsafe { c::poll(&mut fd, 1, timeout) }));
+        if n == 0 {
+            Err(Error::new(ErrorKind::TimedOut, "timed out waiting for the file"))
+        } else {
+            // Errors and hangups are reported as ready as well, the read or
+            // write will then return them.
+            Ok(())
+        }
+    }
 
-impl FromInner<c_int> for File {
-    fn from_inner(fd: c_int) -> File {
-        File(FileDesc::new(fd))
+    pub fn flock(&self, operation: c_int) -> io::Result<()> {
+        try!(cvt_r(|| unsafe { libc::flock(self.0.raw(), operation) }));
+        Ok(())
     }
-}
 
-impl fmt::Debug for File {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        #[cfg(target_os = "linux")]
-        fn get_path(fd: c_int) -> Option<PathBuf> {
-            use string::ToString;
-            let mut p = PathBuf::from("/proc/self/fd");
-            p.push(&fd.to_string());
-            readlink(&p).ok()
+    /// Like `flock`, but returns `Ok(false)` instead of waiting when a
+    /// conflicting lock is held.
+    pub fn try_flock(&self, operation: c_int) -> io::Result<bool> {
+        match cvt_r(|| unsafe { libc::flock(self.0.raw(), operation | libc::LOCK_NB) }) {
+            Ok(_) => Ok(true),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
         }
+    }
 
-        #[cfg(target_os = "macos")]
-        fn get_path(fd: c_int) -> Option<PathBuf> {
-            // FIXME: The use of PATH_MAX is generally not encouraged, but it
-            // is inevitable in this case because OS X defines `fcntl` with
-            // `F_GETPATH` in terms of `MAXPATHLEN`, and there are no
-            // alternatives. If a better method is invented, it should be used
-            // instead.
-            let mut buf = vec![0;libc::PATH_MAX as usize];
-            let n = unsafe { libc::fcntl(fd, libc::F_GETPATH, buf.as_ptr()) };
-            if n == -1 {
-                return None;
+    /// Takes or releases a POSIX record lock of type `ty` covering the whole
+    /// file, including anything appended later, waiting for conflicting
+    /// locks held by other processes to go away.
+    pub fn fcntl_lock(&self, ty: c_int) -> io::Result<()> {
+        self.whole_file_lock(c::F_SETLKW, ty)
+    }
+
+    /// Like `fcntl_lock`, but takes an open file description lock, which
+    /// belongs to this descriptor and its duplicates rather than to the
+    /// process. Releasing a lock never waits.
+    #[cfg(target_os = "linux")]
+    pub fn ofd_lock(&self, ty: c_int) -> io::Result<()> {
+        let cmd = if ty == c::F_UNLCK { c::F_OFD_SETLK } else { c::F_OFD_SETLKW };
+        match self.whole_file_lock(cmd, ty) {
+            // Kernels before 3.15 don't know the command at all.
+            Err(ref e) if e.raw_os_error() == Some(libc::EINVAL) => {
+                Err(Error::new(ErrorKind::Other,
+                               "open file description locks are not supported \
+                                by this kernel"))
             }
-            let l = buf.iter().position(|&c| c == 0).unwrap();
-            buf.truncate(l as usize);
-            buf.shrink_to_fit();
-            Some(PathBuf::from(OsString::from_vec(buf)))
+            ret => ret,
         }
+    }
 
-        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
-        fn get_path(_fd: c_int) -> Option<PathBuf> {
-            // FIXME(#24570): implement this for other Unix platforms
-            None
-        }
+    fn whole_file_lock(&self, cmd: c_int, ty: c_int) -> io::Result<()> {
+        // OFD locks additionally require `l_pid` to be zero.
+        let mut lock: c::flock = unsafe { mem::zeroed() };
+        lock.l_type = ty as libc::c_short;
+        lock.l_whence = libc::SEEK_SET as libc::c_short;
+        // A start and length of zero mean the whole file.
+        try!(cvt_r(|| unsafe {
+            libc::fcntl(self.0.raw(), cmd, &lock as *const c::flock)
+        }));
+        Ok(())
+    }
 
-        #[cfg(any(target_os = "linux", target_os = "macos"))]
-        fn get_mode(fd: c_int) -> Option<(bool, bool)> {
-            let mode = unsafe { libc::fcntl(fd, libc::F_GETFL) };
-            if mode == -1 {
-                return None;
-            }
-            match mode & libc::O_ACCMODE {
-                libc::O_RDONLY => Some((true, false)),
-                libc::O_RDWR => Some((true, true)),
-                libc::O_WRONLY => Some((false, true)),
-                _ => None
-            }
+    pub fn memory_map(&self, offset: u64, len: usize, writable: bool) -> io::Result<Mmap> {
+        if len == 0 {
+            return Ok(Mmap { ptr: ptr::null_mut(), delta: 0, len: 0 })
+        }
+        let page = super::os::page_size() as u64;
+        let aligned = offset - offset % page;
+        let delta = (offset - aligned) as usize;
+        let map_len = match delta.checked_add(len) {
+            Some(map_len) if aligned <= off_t::max_value() as u64 => map_len,
+            _ => return Err(Error::new(ErrorKind::InvalidInput,
+                                       "range to map is too large")),
+        };
+        let prot = if writable {
+            libc::PROT_READ | libc::PROT_WRITE
+        } else {
+            libc::PROT_READ
+        };
+        let ptr = unsafe {
+            libc::mmap(ptr::null_mut(), map_len as size_t, prot,
+                       libc::MAP_SHARED, self.0.raw(), aligned as off_t)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(Error::last_os_error())
         }
+        Ok(Mmap { ptr: ptr, delta: delta, len: len })
+    }
 
-        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
-        fn get_mode(_fd: c_int) -> Option<(bool, bool)> {
-            // FIXME(#24570): implement this for other Unix platforms
-            None
+    pub fn fcntl(&self, cmd: c_int, arg: c_int) -> io::Result<c_int> {
+        cvt_r(|| unsafe { libc::fcntl(self.0.raw(), cmd, arg) })
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn readahead(&self, offset: u64, len: u64) -> io::Result<()> {
+        if offset > i64::max_value() as u64 {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "offset is too large"))
         }
+        let len = cmp::min(len, usize::max_value() as u64) as size_t;
+        try!(cvt(unsafe { c::readahead(self.0.raw(), offset as i64, len) }));
+        Ok(())
+    }
 
+    /// Makes `target` refer to this file, closing whatever it referred to
+    /// before, and sets or clears its close-on-exec flag.
+    pub fn dup_to(&self, target: c_int, cloexec: bool) -> io::Result<()> {
         let fd = self.0.raw();
-        let mut b = f.debug_struct("File");
-        b.field("fd", &fd);
-        if let Some(path) = get_path(fd) {
-            b.field("path", &path);
+        if fd == target {
+            return set_cloexec_flag(fd, cloexec)
+        }
+        return dup(fd, target, cloexec);
+
+        #[cfg(target_os = "linux")]
+        fn dup(fd: c_int, target: c_int, cloexec: bool) -> io::Result<()> {
+            let flags = if cloexec { libc::O_CLOEXEC } else { 0 };
+            try!(cvt_r(|| unsafe { c::dup3(fd, target, flags) }));
+            Ok(())
+        }
+        #[cfg(not(target_os = "linux"))]
+        fn dup(fd: c_int, target: c_int, cloexec: bool) -> io::Result<()> {
+            try!(cvt_r(|| unsafe { libc::dup2(fd, target) }));
+            set_cloexec_flag(target, cloexec)
+        }
+
+        fn set_cloexec_flag(fd: c_int, cloexec: bool) -> io::Result<()> {
+            let flags = try!(cvt(unsafe { libc::fcntl(fd, libc::F_GETFD) }));
+            let flags = if cloexec {
+                flags | c::FD_CLOEXEC
+            } else {
+                flags & !c::FD_CLOEXEC
+            };
+            try!(cvt(unsafe { libc::fcntl(fd, libc::F_SETFD, flags) }));
+            Ok(())
+        }
+    }
+
+    pub fn set_no_cache(&self, no_cache: bool) -> io::Result<()> {
+        return imp(self, no_cache);
+
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        fn imp(f: &File, no_cache: bool) -> io::Result<()> {
+            f.fcntl(c::F_NOCACHE, no_cache as c_int).map(|_| ())
+        }
+
+        // There's no way to turn the cache off for a descriptor short of
+        // O_DIRECT, so make do with dropping what has been cached so far.
+        #[cfg(any(target_os = "linux", target_os = "android",
+                  target_os = "freebsd"))]
+        fn imp(f: &File, no_cache: bool) -> io::Result<()> {
+            if no_cache {
+                try!(f.fadvise(0, 0, c::POSIX_FADV_NOREUSE));
+                f.drop_cache_range(0, 0)
+            } else {
+                f.fadvise(0, 0, c::POSIX_FADV_NORMAL)
+            }
+        }
+
+        #[cfg(not(any(target_os = "macos", target_os = "ios",
+                      target_os = "linux", target_os = "android",
+                      target_os = "freebsd")))]
+        fn imp(_f: &File, _no_cache: bool) -> io::Result<()> {
+            Err(Error::new(ErrorKind::Other,
+                           "disabling the page cache is not supported on this platform"))
+        }
+    }
+
+    /// Writes back the given range of the file and then asks the kernel to
+    /// drop it from the page cache. A `len` of 0 extends the range to the
+    /// end of the file.
+    pub fn drop_cache_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        if offset > i64::max_value() as u64 || len > i64::max_value() as u64 {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "range is too large"))
+        }
+        try!(self.write_back(offset, len));
+        return evict(self, offset, len);
+
+        #[cfg(any(target_os = "linux", target_os = "android",
+                  target_os = "freebsd"))]
+        fn evict(f: &File, offset: u64, len: u64) -> io::Result<()> {
+            f.fadvise(offset, len, c::POSIX_FADV_DONTNEED)
+        }
+
+        // Without posix_fadvise the best we can do is to make the pages
+        // clean, so that the kernel can drop them cheaply.
+        #[cfg(not(any(target_os = "linux", target_os = "android",
+                      target_os = "freebsd")))]
+        fn evict(_f: &File, _offset: u64, _len: u64) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Dirty pages are ignored by POSIX_FADV_DONTNEED, so they have to be
+    // written out first. Linux can do that for just the range in question.
+    #[cfg(target_os = "linux")]
+    fn write_back(&self, offset: u64, len: u64) -> io::Result<()> {
+        let flags = c::SYNC_FILE_RANGE_WAIT_BEFORE | c::SYNC_FILE_RANGE_WRITE |
+                    c::SYNC_FILE_RANGE_WAIT_AFTER;
+        try!(cvt_r(|| unsafe {
+            c::sync_file_range(self.0.raw(), offset as i64, len as i64, flags)
+        }));
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn write_back(&self, _offset: u64, _len: u64) -> io::Result<()> {
+        self.datasync()
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    fn fadvise(&self, offset: u64, len: u64, advice: c_int) -> io::Result<()> {
+        if offset > off_t::max_value() as u64 || len > off_t::max_value() as u64 {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "range is too large"))
+        }
+        // posix_fadvise returns the error instead of setting errno.
+        match unsafe {
+            c::posix_fadvise(self.0.raw(), offset as off_t, len as off_t, advice)
+        } {
+            0 => Ok(()),
+            err => Err(Error::from_raw_os_error(err)),
+        }
+    }
+
+    /// Borrows the descriptor of this file, e.g. for code elsewhere in std
+    /// reaching it through `AsInner`. The descriptor stays owned by this
+    /// `File`: callers must not close it, nor keep its raw value around for
+    /// longer than the borrow, as the number may be reused once it's closed.
+    pub fn fd(&self) -> &FileDesc { &self.0 }
+
+    /// Gives up ownership of the descriptor, which is then closed when the
+    /// returned `FileDesc` is dropped.
+    pub fn into_fd(self) -> FileDesc { self.0 }
+}
+
+impl Mmap {
+    pub fn as_slice(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            return &[]
+        }
+        unsafe {
+            slice::from_raw_parts((self.ptr as *const u8).offset(self.delta as isize),
+                                  self.len)
+        }
+    }
+
+    // Only called on maps created as writable.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.ptr.is_null() {
+            return &mut []
+        }
+        unsafe {
+            slice::from_raw_parts_mut((self.ptr as *mut u8).offset(self.delta as isize),
+                                      self.len)
+        }
+    }
+
+    pub fn flush(&self, offset: usize, len: usize) -> io::Result<()> {
+        if len == 0 {
+            return Ok(())
+        }
+        // msync wants a page aligned address.
+        let page = super::os::page_size();
+        let start = self.delta + offset;
+        let aligned = start - start % page;
+        try!(cvt(unsafe {
+            libc::msync((self.ptr as *mut u8).offset(aligned as isize) as *mut libc::c_void,
+                        (start + len - aligned) as size_t, libc::MS_SYNC)
+        }));
+        Ok(())
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                let _ = libc::munmap(self.ptr, (self.delta + self.len) as size_t);
+            }
+        }
+    }
+}
+
+impl DirBuilder {
+    pub fn new() -> DirBuilder {
+        DirBuilder { mode: 0o777, exact: false }
+    }
+
+    pub fn mkdir(&self, p: &Path) -> io::Result<()> {
+        let p = try!(cstr(p));
+        try!(cvt(unsafe { libc::mkdir(p.as_ptr(), self.mode) }));
+        if self.exact {
+            // mkdir applies the umask, chmod doesn't.
+            try!(cvt_r(|| unsafe { libc::chmod(p.as_ptr(), self.mode) }));
+        }
+        Ok(())
+    }
+
+    pub fn set_mode(&mut self, mode: mode_t) {
+        self.mode = mode;
+        self.exact = false;
+    }
+
+    pub fn set_mode_exact(&mut self, mode: mode_t) {
+        self.mode = mode;
+        self.exact = true;
+    }
+}
+
+impl DirFd {
+    pub fn open(p: &Path, nofollow: bool) -> io::Result<DirFd> {
+        let p = try!(cstr(p));
+        let mut flags = libc::O_RDONLY | libc::O_CLOEXEC | c::O_DIRECTORY;
+        if nofollow {
+            flags |= c::O_NOFOLLOW;
+        }
+        let fd = try!(cvt_r(|| unsafe { libc::open(p.as_ptr(), flags, 0) }));
+        let fd = FileDesc::new(fd);
+        fd.set_cloexec();
+        Ok(DirFd(fd))
+    }
+
+    pub fn current() -> io::Result<DirFd> {
+        DirFd::open(Path::new("."), false)
+    }
+
+    pub fn open_file(&self, p: &Path, opts: &OpenOptions) -> io::Result<File> {
+        let p = try!(cstr(p));
+        self.openat(&p, opts.get_flags(), opts.mode).map(File)
+    }
+
+    pub fn open_dir(&self, p: &Path) -> io::Result<DirFd> {
+        let p = try!(cstr(p));
+        self.openat(&p, libc::O_RDONLY | c::O_DIRECTORY, 0).map(DirFd)
+    }
+
+    pub fn link_at(&self, src: &Path, dst_dir: &DirFd, dst: &Path,
+                   follow_symlinks: bool) -> io::Result<()> {
+        let src = try!(cstr(src));
+        let dst = try!(cstr(dst));
+        let flags = if follow_symlinks { c::AT_SYMLINK_FOLLOW } else { 0 };
+        try!(cvt(unsafe {
+            c::linkat(self.0.raw(), src.as_ptr(), dst_dir.0.raw(), dst.as_ptr(), flags)
+        }));
+        Ok(())
+    }
+
+    pub fn create_dir(&self, p: &Path) -> io::Result<()> {
+        let p = try!(cstr(p));
+        try!(cvt(unsafe { c::mkdirat(self.0.raw(), p.as_ptr(), 0o777) }));
+        Ok(())
+    }
+
+    pub fn remove_file(&self, p: &Path) -> io::Result<()> {
+        let p = try!(cstr(p));
+        try!(cvt(unsafe { c::unlinkat(self.0.raw(), p.as_ptr(), 0) }));
+        Ok(())
+    }
+
+    pub fn remove_dir(&self, p: &Path) -> io::Result<()> {
+        let p = try!(cstr(p));
+        try!(cvt(unsafe { c::unlinkat(self.0.raw(), p.as_ptr(), c::AT_REMOVEDIR) }));
+        Ok(())
+    }
+
+    pub fn rename(&self, from: &Path, to_dir: &DirFd, to: &Path) -> io::Result<()> {
+        let from = try!(cstr(from));
+        let to = try!(cstr(to));
+        try!(cvt(unsafe {
+            c::renameat(self.0.raw(), from.as_ptr(), to_dir.0.raw(), to.as_ptr())
+        }));
+        Ok(())
+    }
+
+    pub fn set_current(&self) -> io::Result<()> {
+        try!(cvt(unsafe { c::fchdir(self.0.raw()) }));
+        Ok(())
+    }
+
+    pub fn resolve_beneath(&self, p: &Path, opts: &OpenOptions)
+                           -> io::Result<File> {
+        self.resolve(p, opts.get_flags(), opts.mode).map(File)
+    }
+
+    pub fn resolve_dir_beneath(&self, p: &Path) -> io::Result<DirFd> {
+        self.resolve(p, libc::O_RDONLY | c::O_DIRECTORY, 0).map(DirFd)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn openat2(&self, p: &Path, opts: &OpenOptions, resolve: u64)
+                   -> io::Result<File> {
+        let p = try!(cstr(p));
+        self.openat2_raw(&p, opts.get_flags(), opts.mode, resolve).map(File)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn openat2_dir(&self, p: &Path, resolve: u64) -> io::Result<DirFd> {
+        let p = try!(cstr(p));
+        let flags = libc::O_RDONLY | c::O_DIRECTORY;
+        self.openat2_raw(&p, flags, 0, resolve).map(DirFd)
+    }
+
+    pub fn fd(&self) -> &FileDesc { &self.0 }
+
+    pub fn into_fd(self) -> FileDesc { self.0 }
+
+    // `..` and absolute paths are rejected up front as they're the only ways
+    // to leave the directory other than symlinks, which are never followed.
+    fn resolve(&self, p: &Path, flags: c_int, mode: mode_t)
+               -> io::Result<FileDesc> {
+        let mut names = Vec::new();
+        for component in p.components() {
+            match component {
+                Component::CurDir => {}
+                Component::Normal(name) => names.push(name),
+                Component::ParentDir |
+                Component::RootDir |
+                Component::Prefix(..) => return Err(escapes_root()),
+            }
+        }
+        let last = match names.pop() {
+            Some(name) => name,
+            None => return Err(Error::new(ErrorKind::InvalidInput,
+                                          "path has no final component")),
+        };
+
+        if let Some(ret) = self.resolve_fast(p, flags, mode) {
+            return ret
+        }
+
+        // Open every directory leading up to the final component one at a
+        // time so a symlink anywhere along the way causes an error.
+        let mut cur: Option<DirFd> = None;
+        for name in names {
+            let name = try!(cstr(Path::new(name)));
+            let flags = libc::O_RDONLY | c::O_DIRECTORY | c::O_NOFOLLOW;
+            let next = try!(cur.as_ref().unwrap_or(self).openat(&name, flags, 0));
+            cur = Some(DirFd(next));
+        }
+        let last = try!(cstr(Path::new(last)));
+        cur.as_ref().unwrap_or(self).openat(&last, flags | c::O_NOFOLLOW, mode)
+    }
+
+    // Lets the kernel do the whole walk in one go when `openat2` is
+    // available, returning `None` if the manual walk is needed instead.
+    #[cfg(target_os = "linux")]
+    fn resolve_fast(&self, p: &Path, flags: c_int, mode: mode_t)
+                    -> Option<io::Result<FileDesc>> {
+        use sync::atomic::{AtomicBool, Ordering};
+
+        static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+        if UNAVAILABLE.load(Ordering::Relaxed) {
+            return None
+        }
+        let p = match cstr(p) {
+            Ok(p) => p,
+            Err(e) => return Some(Err(e)),
+        };
+        let resolve = c::RESOLVE_BENEATH | c::RESOLVE_NO_SYMLINKS;
+        match self.openat2_raw(&p, flags, mode, resolve) {
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOSYS) => {
+                UNAVAILABLE.store(true, Ordering::Relaxed);
+                None
+            }
+            Err(ref e) if e.raw_os_error() == Some(libc::EXDEV) => {
+                Some(Err(escapes_root()))
+            }
+            ret => Some(ret),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn resolve_fast(&self, _p: &Path, _flags: c_int, _mode: mode_t)
+                    -> Option<io::Result<FileDesc>> {
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    fn openat2_raw(&self, p: &CStr, flags: c_int, mode: mode_t, resolve: u64)
+                   -> io::Result<FileDesc> {
+        // Unlike openat, openat2 rejects a mode with EINVAL unless a file
+        // may actually be created.
+        let creates = flags & libc::O_CREAT != 0 || flags & c::O_TMPFILE == c::O_TMPFILE;
+        let how = c::open_how {
+            flags: (flags | libc::O_CLOEXEC) as u64,
+            mode: if creates { mode as u64 } else { 0 },
+            resolve: resolve,
+        };
+        let fd = try!(cvt_r(|| unsafe {
+            c::syscall(c::NR_OPENAT2, self.0.raw(), p.as_ptr(), &how,
+                       mem::size_of::<c::open_how>())
+        }));
+        Ok(FileDesc::new(fd as c_int))
+    }
+
+    fn openat(&self, p: &CStr, flags: c_int, mode: mode_t) -> io::Result<FileDesc> {
+        let fd = try!(cvt_r(|| unsafe {
+            c::openat(self.0.raw(), p.as_ptr(), flags | libc::O_CLOEXEC, mode as c_int)
+        }));
+        let fd = FileDesc::new(fd);
+        fd.set_cloexec();
+        Ok(fd)
+    }
+}
+
+impl FromInner<c_int> for DirFd {
+    fn from_inner(fd: c_int) -> DirFd {
+        DirFd(FileDesc::new(fd))
+    }
+}
+
+// What `open` fails with when `O_NOFOLLOW` hits a symlink isn't the same
+// everywhere, POSIX says ELOOP but the BSDs historically used EMLINK.
+#[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+fn is_nofollow_error(e: &Error) -> bool {
+    e.raw_os_error() == Some(libc::EMLINK)
+}
+
+#[cfg(not(any(target_os = "freebsd", target_os = "dragonfly")))]
+fn is_nofollow_error(e: &Error) -> bool {
+    e.raw_os_error() == Some(libc::ELOOP)
+}
+
+fn escapes_root() -> Error {
+    Error::new(ErrorKind::PermissionDenied,
+               "path would escape the directory it is resolved beneath")
+}
+
+fn cstr(path: &Path) -> io::Result<CString> {
+    path.as_os_str().to_cstring().ok_or(
+        io::Error::new(io::ErrorKind::InvalidInput, "path contained a null"))
+}
+
+impl FromInner<c_int> for File {
+    fn from_inner(fd: c_int) -> File {
+        File(FileDesc::new(fd))
+    }
+}
+
+// Finds out the path of the file open as `fd`, if the platform can tell.
+#[cfg(target_os = "linux")]
+fn fd_path(fd: c_int) -> Option<PathBuf> {
+    use string::ToString;
+    let mut p = PathBuf::from("/proc/self/fd");
+    p.push(&fd.to_string());
+    readlink(&p).ok()
+}
+
+#[cfg(target_os = "macos")]
+fn fd_path(fd: c_int) -> Option<PathBuf> {
+    // FIXME: The use of PATH_MAX is generally not encouraged, but it
+    // is inevitable in this case because OS X defines `fcntl` with
+    // `F_GETPATH` in terms of `MAXPATHLEN`, and there are no
+    // alternatives. If a better method is invented, it should be used
+    // instead.
+    let mut buf = vec![0;libc::PATH_MAX as usize];
+    let n = unsafe { libc::fcntl(fd, libc::F_GETPATH, buf.as_ptr()) };
+    if n == -1 {
+        return None;
+    }
+    let l = buf.iter().position(|&c| c == 0).unwrap();
+    buf.truncate(l as usize);
+    buf.shrink_to_fit();
+    Some(PathBuf::from(OsString::from_vec(buf)))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn fd_path(_fd: c_int) -> Option<PathBuf> {
+    // FIXME(#24570): implement this for other Unix platforms
+    None
+}
+
+impl fmt::Debug for File {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        fn get_mode(fd: c_int) -> Option<(bool, bool)> {
+            let mode = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+            if mode == -1 {
+                return None;
+            }
+            match mode & libc::O_ACCMODE {
+                libc::O_RDONLY => Some((true, false)),
+                libc::O_RDWR => Some((true, true)),
+                libc::O_WRONLY => Some((false, true)),
+                _ => None
+            }
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        fn get_mode(_fd: c_int) -> Option<(bool, bool)> {
+            // FIXME(#24570): implement this for other Unix platforms
+            None
+        }
+
+        let fd = self.0.raw();
+        let mut b = f.debug_struct("File");
+        b.field("fd", &fd);
+        if let Some(path) = fd_path(fd) {
+            b.field("path", &path);
         }
         if let Some((read, write)) = get_mode(fd) {
             b.field("read", &read).field("write", &write);
@@ -439,7 +1450,7 @@ pub fn readdir(p: &Path) -> io::Result<ReadDir> {
         if ptr.is_null() {
             Err(Error::last_os_error())
         } else {
-            Ok(ReadDir { dirp: Dir(ptr), root: root })
+            Ok(ReadDir { dirp: Dir(ptr), root: root, done: false })
         }
     }
 }
@@ -450,6 +1461,28 @@ pub fn unlink(p: &Path) -> io::Result<()> {
     Ok(())
 }
 
+// Permissions of the file itself never stop it from being unlinked.
+pub fn unlink_force(p: &Path) -> io::Result<()> {
+    unlink(p)
+}
+
+// Whether a directory can be removed is up to its parent's permissions.
+pub fn rmdir_force(p: &Path) -> io::Result<()> {
+    rmdir(p)
+}
+
+// Nothing can hold a file open in a way which stops it from being unlinked,
+// so retrying wouldn't help.
+pub fn unlink_retry(p: &Path, _attempts: u32, _delay: Duration) -> io::Result<()> {
+    unlink(p)
+}
+
+// Same as for `unlink_retry`, open handles don't get in the way of a rename.
+pub fn rename_retry(old: &Path, new: &Path, _attempts: u32, _delay: Duration)
+                    -> io::Result<()> {
+    rename(old, new)
+}
+
 pub fn rename(old: &Path, new: &Path) -> io::Result<()> {
     let old = try!(cstr(old));
     let new = try!(cstr(new));
@@ -457,18 +1490,292 @@ pub fn rename(old: &Path, new: &Path) -> io::Result<()> {
     Ok(())
 }
 
+#[cfg(target_os = "linux")]
+pub fn splice(src: &File, src_off: Option<u64>, dst: &File, dst_off: Option<u64>,
+              len: usize) -> io::Result<usize> {
+    let (mut src_off, mut dst_off) = try!(transfer_offsets(src_off, dst_off));
+    let len = cmp::min(len, isize::max_value() as usize);
+    let ret = cvt(unsafe {
+        c::splice(src.0.raw(), offset_ptr(&mut src_off), dst.0.raw(),
+                  offset_ptr(&mut dst_off), len as size_t, 0)
+    });
+    match ret {
+        Err(ref e) if e.raw_os_error() == Some(libc::EINVAL) => {
+            let is_pipe = |f: &File| {
+                f.file_attr().map(|a| a.file_type().is(libc::S_IFIFO)).unwrap_or(false)
+            };
+            if !is_pipe(src) && !is_pipe(dst) {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                                      "splice needs a pipe on at least one end"))
+            }
+        }
+        _ => {}
+    }
+    ret.map(|n| n as usize)
+}
+
+#[cfg(target_os = "linux")]
+pub fn copy_file_range(src: &File, src_off: Option<u64>, dst: &File,
+                       dst_off: Option<u64>, len: usize) -> io::Result<usize> {
+    let (mut src_off, mut dst_off) = try!(transfer_offsets(src_off, dst_off));
+    let len = cmp::min(len, isize::max_value() as usize);
+    let n = try!(cvt(unsafe {
+        c::syscall(c::NR_COPY_FILE_RANGE, src.0.raw(), offset_ptr(&mut src_off),
+                   dst.0.raw(), offset_ptr(&mut dst_off), len as size_t, 0 as libc::c_uint)
+    }));
+    Ok(n as usize)
+}
+
+// Both splice and copy_file_range take an explicit offset as a pointer to a
+// loff_t, which they advance, or null to use and advance the file position.
+#[cfg(target_os = "linux")]
+fn transfer_offsets(a: Option<u64>, b: Option<u64>) -> io::Result<(Option<i64>, Option<i64>)> {
+    fn convert(off: Option<u64>) -> io::Result<Option<i64>> {
+        match off {
+            Some(off) if off > i64::max_value() as u64 => {
+                Err(Error::new(ErrorKind::InvalidInput, "offset is too large"))
+            }
+            off => Ok(off.map(|off| off as i64)),
+        }
+    }
+    Ok((try!(convert(a)), try!(convert(b))))
+}
+
+#[cfg(target_os = "linux")]
+fn offset_ptr(off: &mut Option<i64>) -> *mut i64 {
+    match *off {
+        Some(ref mut off) => off,
+        None => ptr::null_mut(),
+    }
+}
+
+pub fn rename_over_symlink(tmp: &Path, link: &Path) -> io::Result<()> {
+    rename(tmp, link)
+}
+
+pub fn rename_noreplace(old: &Path, new: &Path) -> io::Result<()> {
+    let old = try!(cstr(old));
+    let new = try!(cstr(new));
+    if let Some(ret) = renameat2_noreplace(&old, &new) {
+        return ret
+    }
+
+    // FIXME: without renameat2 there's a window between the check and the
+    //        rename in which `new` can be created and then replaced.
+    let mut stat: raw::stat = unsafe { mem::zeroed() };
+    if unsafe { libc::lstat(new.as_ptr(), &mut stat as *mut _ as *mut _) } == 0 {
+        return Err(Error::from_raw_os_error(libc::EEXIST))
+    }
+    try!(cvt(unsafe { libc::rename(old.as_ptr(), new.as_ptr()) }));
+    return Ok(());
+
+    #[cfg(target_os = "linux")]
+    fn renameat2_noreplace(old: &CStr, new: &CStr) -> Option<io::Result<()>> {
+        use sync::atomic::{AtomicBool, Ordering};
+
+        // Set once the kernel or the file system turns out not to support
+        // renameat2, so that we don't keep on trying.
+        static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+        if UNAVAILABLE.load(Ordering::Relaxed) {
+            return None
+        }
+        let ret = cvt(unsafe {
+            c::syscall(c::NR_RENAMEAT2, c::AT_FDCWD, old.as_ptr(),
+                       c::AT_FDCWD, new.as_ptr(), c::RENAME_NOREPLACE)
+        });
+        match ret {
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOSYS) => {
+                UNAVAILABLE.store(true, Ordering::Relaxed);
+                None
+            }
+            // Some file systems don't support any flags.
+            Err(ref e) if e.raw_os_error() == Some(libc::EINVAL) => None,
+            ret => Some(ret.map(|_| ())),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn renameat2_noreplace(_old: &CStr, _new: &CStr) -> Option<io::Result<()>> {
+        None
+    }
+}
+
 pub fn set_perm(p: &Path, perm: FilePermissions) -> io::Result<()> {
     let p = try!(cstr(p));
     try!(cvt_r(|| unsafe { libc::chmod(p.as_ptr(), perm.mode) }));
     Ok(())
 }
 
+#[cfg(any(target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          target_os = "netbsd"))]
+pub fn set_perm_nofollow(p: &Path, perm: FilePermissions) -> io::Result<()> {
+    let p = try!(cstr(p));
+    try!(cvt_r(|| unsafe { c::lchmod(p.as_ptr(), perm.mode) }));
+    Ok(())
+}
+
+#[cfg(any(target_os = "bitrig", target_os = "openbsd"))]
+pub fn set_perm_nofollow(p: &Path, perm: FilePermissions) -> io::Result<()> {
+    let p = try!(cstr(p));
+    try!(cvt_r(|| unsafe {
+        c::fchmodat(c::AT_FDCWD, p.as_ptr(), perm.mode, c::AT_SYMLINK_NOFOLLOW)
+    }));
+    Ok(())
+}
+
+// Linux doesn't have a notion of permissions on a symlink itself (they're
+// always 0o777 and ignored), so there is nothing that could be changed here.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "nacl"))]
+pub fn set_perm_nofollow(_p: &Path, _perm: FilePermissions) -> io::Result<()> {
+    Err(Error::from_raw_os_error(libc::EOPNOTSUPP))
+}
+
 pub fn rmdir(p: &Path) -> io::Result<()> {
     let p = try!(cstr(p));
     try!(cvt(unsafe { libc::rmdir(p.as_ptr()) }));
     Ok(())
 }
 
+// These follow the `major()` and `minor()` macros of each platform's libc.
+//
+// glibc and musl agree on Linux: the 12 bit major and 20 bit minor numbers
+// of the original 16 bit encoding stay where they were, and the remaining
+// bits of each are stored above them. Bionic only has the low 32 bits of
+// that, which comes out the same.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn dev_major(dev: u64) -> u32 {
+    (((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff)) as u32
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn dev_minor(dev: u64) -> u32 {
+    ((dev & 0xff) | ((dev >> 12) & !0xff)) as u32
+}
+
+// The inverse of the above, as glibc's `makedev()`.
+#[cfg(target_os = "linux")]
+fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (major as u64, minor as u64);
+    ((major & 0xfffff000) << 32) | ((major & 0xfff) << 8) |
+        ((minor & 0xffffff00) << 12) | (minor & 0xff)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn dev_major(dev: u64) -> u32 {
+    ((dev >> 24) & 0xff) as u32
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn dev_minor(dev: u64) -> u32 {
+    (dev & 0xffffff) as u32
+}
+
+// FreeBSD 12 widened dev_t to 64 bits; this reads both that and the old
+// 32 bit encoding, which has nothing in the high bits.
+#[cfg(target_os = "freebsd")]
+pub fn dev_major(dev: u64) -> u32 {
+    (((dev >> 32) & 0xffffff00) | ((dev >> 8) & 0xff)) as u32
+}
+
+#[cfg(target_os = "freebsd")]
+pub fn dev_minor(dev: u64) -> u32 {
+    (((dev >> 24) & 0xff00) | (dev & 0xffff00ff)) as u32
+}
+
+#[cfg(target_os = "dragonfly")]
+pub fn dev_major(dev: u64) -> u32 {
+    ((dev >> 8) & 0xff) as u32
+}
+
+#[cfg(target_os = "dragonfly")]
+pub fn dev_minor(dev: u64) -> u32 {
+    (dev & 0xffff00ff) as u32
+}
+
+#[cfg(target_os = "netbsd")]
+pub fn dev_major(dev: u64) -> u32 {
+    ((dev & 0x000fff00) >> 8) as u32
+}
+
+#[cfg(target_os = "netbsd")]
+pub fn dev_minor(dev: u64) -> u32 {
+    ((dev & 0x000000ff) | ((dev & 0xfff00000) >> 12)) as u32
+}
+
+#[cfg(any(target_os = "bitrig", target_os = "openbsd"))]
+pub fn dev_major(dev: u64) -> u32 {
+    ((dev >> 8) & 0xff) as u32
+}
+
+#[cfg(any(target_os = "bitrig", target_os = "openbsd"))]
+pub fn dev_minor(dev: u64) -> u32 {
+    ((dev & 0xff) | ((dev & 0xffff0000) >> 8)) as u32
+}
+
+// Linux only reports the file system's magic number, so the name has to be
+// derived from that. ext2 and ext3 share their magic number with ext4.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn fs_type(p: &Path) -> io::Result<(String, Option<u64>)> {
+    let p = try!(cstr(p));
+    let mut buf: c::statfs = unsafe { mem::zeroed() };
+    try!(cvt_r(|| unsafe { c::statfs(p.as_ptr(), &mut buf) }));
+    // f_type is a signed long, but the magic numbers are all written as
+    // unsigned 32 bit values.
+    let magic = buf.f_type as u32 as u64;
+    let name = match magic {
+        0xEF53 => "ext4",
+        0x58465342 => "xfs",
+        0x9123683E => "btrfs",
+        0x2FC12FC1 => "zfs",
+        0xF2F52010 => "f2fs",
+        0x01021994 => "tmpfs",
+        0x858458F6 => "ramfs",
+        0x6969 => "nfs",
+        0xFF534D42 => "cifs",
+        0xFE534D42 => "smb2",
+        0x517B => "smbfs",
+        0x65735546 => "fuse",
+        0x794C7630 => "overlay",
+        0x4D44 => "vfat",
+        0x2011BAB0 => "exfat",
+        0x5346544E => "ntfs",
+        0x9FA0 => "proc",
+        0x62656572 => "sysfs",
+        _ => "unknown",
+    };
+    Ok((String::from(name), Some(magic)))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+pub fn fs_type(p: &Path) -> io::Result<(String, Option<u64>)> {
+    let p = try!(cstr(p));
+    let mut buf: c::statfs = unsafe { mem::zeroed() };
+    try!(cvt_r(|| unsafe { c::statfs(p.as_ptr(), &mut buf) }));
+    let name = unsafe { CStr::from_ptr(buf.f_fstypename.as_ptr()) };
+    Ok((String::from_utf8_lossy(name.to_bytes()).into_owned(), None))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos",
+              target_os = "ios", target_os = "freebsd")))]
+pub fn fs_type(_p: &Path) -> io::Result<(String, Option<u64>)> {
+    Err(Error::new(ErrorKind::Other,
+                   "querying the file system type is not supported on this platform"))
+}
+
+pub fn symlink_target_len(p: &Path) -> io::Result<u64> {
+    readlink(p).map(|target| target.as_os_str().as_bytes().len() as u64)
+}
+
+pub fn readlink_full(p: &Path) -> io::Result<(PathBuf, bool, Option<bool>)> {
+    let target = try!(readlink(p));
+    let relative = !target.is_absolute();
+    Ok((target, relative, None))
+}
+
 pub fn readlink(p: &Path) -> io::Result<PathBuf> {
     let c_path = try!(cstr(p));
     let p = c_path.as_ptr();
@@ -502,6 +1809,15 @@ pub fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
     Ok(())
 }
 
+// The kind of the target doesn't matter on Unix.
+pub fn symlink_auto(src: &Path, dst: &Path) -> io::Result<()> {
+    symlink(src, dst)
+}
+
+pub fn copy_symlink(link: &Path, dst: &Path) -> io::Result<()> {
+    symlink(&try!(readlink(link)), dst)
+}
+
 pub fn link(src: &Path, dst: &Path) -> io::Result<()> {
     let src = try!(cstr(src));
     let dst = try!(cstr(dst));
@@ -509,36 +1825,584 @@ pub fn link(src: &Path, dst: &Path) -> io::Result<()> {
     Ok(())
 }
 
+pub fn dir_entry_count_hint(p: &Path) -> io::Result<Option<u64>> {
+    let attr = try!(stat(p));
+    if !attr.file_type().is_dir() {
+        return Err(Error::new(ErrorKind::InvalidInput, "not a directory"))
+    }
+    // Traditionally a directory is linked to from its parent, from its own
+    // `.` and from the `..` of each subdirectory. File systems that don't
+    // keep track of this, like btrfs, report a link count of 1 instead.
+    let nlink = attr.stat.st_nlink as u64;
+    Ok(if nlink >= 2 { Some(nlink - 2) } else { None })
+}
+
 pub fn stat(p: &Path) -> io::Result<FileAttr> {
     let p = try!(cstr(p));
+    if let Some(ret) = try_statx(c::AT_FDCWD, &p, 0) {
+        return ret
+    }
     let mut stat: raw::stat = unsafe { mem::zeroed() };
     try!(cvt(unsafe {
         libc::stat(p.as_ptr(), &mut stat as *mut _ as *mut _)
     }));
-    Ok(FileAttr { stat: stat })
+    Ok(FileAttr::from_stat(stat))
+}
+
+/// Runs `stat` on each of `paths`, returning the results in the same order.
+///
+/// On Linux 5.6 and later the lookups are handed to the kernel together as
+/// `statx` operations on an `io_uring`, rather than one system call each.
+#[cfg(target_os = "linux")]
+pub fn stat_batch(paths: &[&Path]) -> Vec<io::Result<FileAttr>> {
+    use sys::uring::{self, Ring};
+
+    // Setting up a ring takes a handful of system calls of its own.
+    const MIN_BATCH: usize = 8;
+    const RING_ENTRIES: u32 = 256;
+
+    if paths.len() < MIN_BATCH {
+        return paths.iter().map(|p| stat(p)).collect()
+    }
+    let mut ring = match Ring::with_ops(RING_ENTRIES, &[c::IORING_OP_STATX]) {
+        Some(ring) => ring,
+        None => return paths.iter().map(|p| stat(p)).collect(),
+    };
+
+    // Paths which can't be turned into a `CString` get their error right
+    // away; `None` is for those still to be looked up.
+    let mut ret: Vec<Option<io::Result<FileAttr>>> = Vec::with_capacity(paths.len());
+    let mut cpaths = Vec::with_capacity(paths.len());
+    let mut which = Vec::with_capacity(paths.len());
+    for (i, p) in paths.iter().enumerate() {
+        match cstr(p) {
+            Ok(p) => { cpaths.push(p); which.push(i); ret.push(None) }
+            Err(e) => ret.push(Some(Err(e))),
+        }
+    }
+
+    let mut bufs: Vec<c::statx> = paths.iter().map(|_| unsafe { mem::zeroed() }).collect();
+    let sqes: Vec<_> = cpaths.iter().zip(&which).map(|(p, &i)| {
+        let mut sqe = uring::sqe(c::IORING_OP_STATX);
+        sqe.fd = c::AT_FDCWD;
+        sqe.addr = p.as_ptr() as u64;
+        sqe.len = c::STATX_BASIC_STATS;
+        sqe.off = &mut bufs[i] as *mut c::statx as u64;
+        sqe
+    }).collect();
+    let mut results = vec![None; sqes.len()];
+    let failed = ring.run(&sqes, &mut results).is_err();
+
+    for (&i, res) in which.iter().zip(&results) {
+        ret[i] = match *res {
+            Some(0) => Some(Ok(FileAttr::from_statx(&bufs[i]))),
+            Some(err) => Some(Err(Error::from_raw_os_error(-err))),
+            None => None,
+        };
+    }
+    if failed {
+        // Lookups the ring didn't report on may still use these.
+        mem::forget(cpaths);
+        mem::forget(bufs);
+    }
+    // Anything the ring didn't get to is looked up one by one.
+    ret.into_iter().zip(paths).map(|(r, p)| r.unwrap_or_else(|| stat(p))).collect()
+}
+
+/// Reads up to `len` bytes from the start of each of `paths`, returning the
+/// results in the same order.
+///
+/// On Linux 5.6 and later the files are opened, read and closed through an
+/// `io_uring`, a batch at a time, rather than with a system call each.
+#[cfg(target_os = "linux")]
+pub fn read_prefixes(paths: &[&Path], len: usize) -> Vec<io::Result<Vec<u8>>> {
+    use sys::uring::Ring;
+
+    // Setting up a ring takes a handful of system calls of its own.
+    const MIN_BATCH: usize = 8;
+    // At most this many of the files are open at once.
+    const BATCH: usize = 64;
+
+    let ops = [c::IORING_OP_OPENAT, c::IORING_OP_READ, c::IORING_OP_CLOSE];
+    let mut ring = if paths.len() < MIN_BATCH {
+        None
+    } else {
+        // Reads go through the file position, as `read` does, which lets
+        // them work on pipes and the like too.
+        Ring::with_ops(BATCH as u32, &ops).and_then(|ring| {
+            if ring.has_feature(c::IORING_FEAT_RW_CUR_POS) { Some(ring) } else { None }
+        })
+    };
+
+    let mut ret = Vec::with_capacity(paths.len());
+    for chunk in paths.chunks(BATCH) {
+        let usable = match ring {
+            Some(ref mut ring) => {
+                let (prefixes, usable) = read_prefixes_ring(ring, chunk, len);
+                ret.extend(prefixes);
+                usable
+            }
+            None => {
+                ret.extend(chunk.iter().map(|p| read_prefix(p, len)));
+                true
+            }
+        };
+        if !usable {
+            ring = None;
+        }
+    }
+    ret
+}
+
+// Reads the prefixes of a batch of `paths`, opening, reading and closing all
+// of them together. Also returns whether `ring` can still be used.
+#[cfg(target_os = "linux")]
+fn read_prefixes_ring(ring: &mut ::sys::uring::Ring, paths: &[&Path], len: usize)
+                      -> (Vec<io::Result<Vec<u8>>>, bool) {
+    use sys::uring;
+
+    // `None` is for files which still have to be read one by one.
+    let mut ret: Vec<Option<io::Result<Vec<u8>>>> = Vec::with_capacity(paths.len());
+    let mut fds: Vec<Option<FileDesc>> = Vec::with_capacity(paths.len());
+    let mut cpaths = Vec::with_capacity(paths.len());
+    let mut which = Vec::with_capacity(paths.len());
+    for (i, p) in paths.iter().enumerate() {
+        fds.push(None);
+        match cstr(p) {
+            Ok(p) => { cpaths.push(p); which.push(i); ret.push(None) }
+            Err(e) => ret.push(Some(Err(e))),
+        }
+    }
+
+    let sqes: Vec<_> = cpaths.iter().map(|p| {
+        let mut sqe = uring::sqe(c::IORING_OP_OPENAT);
+        sqe.fd = c::AT_FDCWD;
+        sqe.addr = p.as_ptr() as u64;
+        sqe.op_flags = (libc::O_RDONLY | libc::O_CLOEXEC) as u32;
+        sqe
+    }).collect();
+    let mut results = vec![None; sqes.len()];
+    let mut ok = ring.run(&sqes, &mut results).is_ok();
+    for (&i, res) in which.iter().zip(&results) {
+        match *res {
+            Some(fd) if fd >= 0 => {
+                fds[i] = Some(FileDesc::new(fd));
+                ret[i] = Some(Ok(Vec::with_capacity(cmp::min(len, PREFIX_CAPACITY))));
+            }
+            Some(err) => ret[i] = Some(Err(Error::from_raw_os_error(-err))),
+            None => {}
+        }
+    }
+    if !ok {
+        // Opens the ring didn't report on may still use these.
+        mem::forget(cpaths);
+    }
+
+    // Keep reading until every file is either full or at its end; a read
+    // can come up short like any other.
+    let mut eof = vec![false; paths.len()];
+    while ok {
+        let mut sqes = Vec::new();
+        let mut which = Vec::new();
+        for (i, (fd, buf)) in fds.iter().zip(ret.iter_mut()).enumerate() {
+            let (fd, buf) = match (fd, buf) {
+                (&Some(ref fd), &mut Some(Ok(ref mut buf))) => (fd, buf),
+                _ => continue,
+            };
+            if eof[i] || buf.len() == len {
+                continue
+            }
+            // No read is in flight here, so the buffer is free to move.
+            if buf.len() == buf.capacity() {
+                let more = cmp::min(len - buf.len(), buf.capacity());
+                buf.reserve_exact(more);
+            }
+            let room = cmp::min(len, buf.capacity()) - buf.len();
+            let mut sqe = uring::sqe(c::IORING_OP_READ);
+            sqe.fd = fd.raw();
+            sqe.addr = unsafe { buf.as_mut_ptr().offset(buf.len() as isize) } as u64;
+            sqe.len = cmp::min(room, u32::max_value() as usize) as u32;
+            // An offset of -1 reads from the file position.
+            sqe.off = !0;
+            sqes.push(sqe);
+            which.push(i);
+        }
+        if sqes.is_empty() {
+            break
+        }
+        let mut results = vec![None; sqes.len()];
+        ok = ring.run(&sqes, &mut results).is_ok();
+        for (&i, res) in which.iter().zip(&results) {
+            match *res {
+                Some(0) => eof[i] = true,
+                Some(n) if n > 0 => {
+                    if let Some(Ok(ref mut buf)) = ret[i] {
+                        let filled = buf.len() + n as usize;
+                        unsafe { buf.set_len(filled) }
+                    }
+                }
+                Some(err) if err == -libc::EINTR => {}
+                Some(err) => ret[i] = Some(Err(Error::from_raw_os_error(-err))),
+                None => {
+                    // The read may still be going on, so its buffer can't
+                    // be freed; the file is read again from the start below.
+                    if let Some(Ok(buf)) = ret[i].take() {
+                        mem::forget(buf);
+                    }
+                }
+            }
+        }
+    }
+
+    if !ok {
+        // Files the ring stopped on before they were read in full are read
+        // again from the start; their buffers aren't in use any more.
+        for (i, r) in ret.iter_mut().enumerate() {
+            let finished = match *r {
+                Some(Ok(ref buf)) => eof[i] || buf.len() == len,
+                _ => true,
+            };
+            if !finished {
+                *r = None;
+            }
+        }
+    }
+
+    if ok {
+        let mut sqes = Vec::new();
+        let mut which = Vec::new();
+        for (i, fd) in fds.iter().enumerate() {
+            if let Some(ref fd) = *fd {
+                let mut sqe = uring::sqe(c::IORING_OP_CLOSE);
+                sqe.fd = fd.raw();
+                sqes.push(sqe);
+                which.push(i);
+            }
+        }
+        let mut results = vec![None; sqes.len()];
+        ok = ring.run(&sqes, &mut results).is_ok();
+        // Errors from closing are ignored, as when a `File` is dropped. A
+        // close the ring didn't report on may still happen at any moment,
+        // so those descriptors are given up on too.
+        for &i in &which {
+            if let Some(fd) = fds[i].take() {
+                fd.into_raw();
+            }
+        }
+    }
+
+    let ret = ret.into_iter().zip(paths).map(|(r, p)| {
+        r.unwrap_or_else(|| read_prefix(p, len))
+    }).collect();
+    (ret, ok)
+}
+
+// Prefix buffers start out no bigger than this and grow as the data comes
+// in, so that a large `len` doesn't cost that much memory for every file up
+// front, however small it is.
+#[cfg(target_os = "linux")]
+const PREFIX_CAPACITY: usize = 64 * 1024;
+
+#[cfg(target_os = "linux")]
+fn read_prefix(p: &Path, len: usize) -> io::Result<Vec<u8>> {
+    use iter;
+
+    let mut opts = OpenOptions::new();
+    opts.read(true);
+    let file = try!(File::open(p, &opts));
+    let mut buf = Vec::new();
+    let mut filled = 0;
+    while filled < len {
+        if filled == buf.len() {
+            let more = cmp::min(len - filled, cmp::max(filled, PREFIX_CAPACITY));
+            buf.extend(iter::repeat(0).take(more));
+        }
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    buf.truncate(filled);
+    Ok(buf)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn stat_batch(paths: &[&Path]) -> Vec<io::Result<FileAttr>> {
+    paths.iter().map(|p| stat(p)).collect()
+}
+
+/// Returns the device and inode number of the file `p` resolves to.
+pub fn file_id(p: &Path) -> io::Result<(u64, u64)> {
+    let attr = try!(stat(p));
+    Ok((attr.stat.st_dev as u64, attr.stat.st_ino as u64))
+}
+
+/// Returns whether `e` means that a hard link can't be created between the
+/// two paths, as opposed to something being wrong with either of them.
+pub fn is_link_impossible(e: &io::Error) -> bool {
+    match e.raw_os_error() {
+        Some(libc::EXDEV) | Some(libc::EMLINK) => true,
+        _ => false,
+    }
+}
+
+/// Returns whether `e` means that a path, or one of its parents, doesn't
+/// exist.
+///
+/// `ENOTDIR` is included because it's what a lookup of `a/b` fails with if
+/// `a` is a regular file, e.g. when a symlink to a directory now points at
+/// a file.
+pub fn is_not_found(e: &io::Error) -> bool {
+    match e.raw_os_error() {
+        Some(libc::ENOENT) | Some(libc::ENOTDIR) => true,
+        _ => false,
+    }
+}
+
+/// Returns whether `e`, from removing a directory, means that it doesn't
+/// exist.
+///
+/// Unlike `is_not_found` this leaves out `ENOTDIR`, since that's also what
+/// `rmdir` fails with if the path is a file, which does exist. Callers have
+/// to tell that apart from a path through a file themselves.
+pub fn is_dir_not_found(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::ENOENT)
 }
 
 pub fn lstat(p: &Path) -> io::Result<FileAttr> {
     let p = try!(cstr(p));
+    if let Some(ret) = try_statx(c::AT_FDCWD, &p, STATX_NOFOLLOW) {
+        return ret
+    }
     let mut stat: raw::stat = unsafe { mem::zeroed() };
     try!(cvt(unsafe {
         libc::lstat(p.as_ptr(), &mut stat as *mut _ as *mut _)
     }));
-    Ok(FileAttr { stat: stat })
+    Ok(FileAttr::from_stat(stat))
+}
+
+#[cfg(target_os = "linux")]
+pub fn lstat_no_automount(p: &Path) -> io::Result<FileAttr> {
+    let p = try!(cstr(p));
+    let flags = c::AT_SYMLINK_NOFOLLOW | c::AT_NO_AUTOMOUNT;
+    if let Some(ret) = try_statx(c::AT_FDCWD, &p, flags) {
+        return ret
+    }
+    let mut stat: raw::stat = unsafe { mem::zeroed() };
+    try!(cvt(unsafe {
+        c::fstatat(c::AT_FDCWD, p.as_ptr(), &mut stat as *mut _ as *mut _, flags)
+    }));
+    Ok(FileAttr::from_stat(stat))
+}
+
+// Flags for `try_statx`, which only looks at them on Linux.
+#[cfg(target_os = "linux")]
+const STATX_NOFOLLOW: c_int = c::AT_SYMLINK_NOFOLLOW;
+#[cfg(target_os = "linux")]
+const STATX_EMPTY_PATH: c_int = c::AT_EMPTY_PATH;
+#[cfg(not(target_os = "linux"))]
+const STATX_NOFOLLOW: c_int = 0;
+#[cfg(not(target_os = "linux"))]
+const STATX_EMPTY_PATH: c_int = 0;
+
+// The `time_t` in `stat` is only 32 bits wide on 32-bit Linux, so the times
+// there would wrap in 2038. `statx` always reports 64-bit seconds, so it's
+// used instead wherever the kernel has it (4.11 and up), returning `None` if
+// the plain `stat` family should be used.
+#[cfg(all(target_os = "linux", target_pointer_width = "32"))]
+fn try_statx(dirfd: c_int, p: &CStr, flags: c_int) -> Option<io::Result<FileAttr>> {
+    use sync::atomic::{AtomicBool, Ordering};
+
+    static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+    if UNAVAILABLE.load(Ordering::Relaxed) {
+        return None
+    }
+    let mut stx: c::statx = unsafe { mem::zeroed() };
+    let ret = cvt(unsafe {
+        c::syscall(c::NR_STATX, dirfd, p.as_ptr(), flags, c::STATX_BASIC_STATS,
+                   &mut stx as *mut c::statx)
+    });
+    match ret {
+        // The kernel is too old, or a seccomp filter, as is common in
+        // containers, blocks the call. `statx` itself never fails with
+        // EPERM.
+        Err(ref e) if e.raw_os_error() == Some(libc::ENOSYS) ||
+                      e.raw_os_error() == Some(libc::EPERM) => {
+            UNAVAILABLE.store(true, Ordering::Relaxed);
+            None
+        }
+        Err(e) => Some(Err(e)),
+        Ok(_) => Some(Ok(FileAttr::from_statx(&stx))),
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_pointer_width = "32")))]
+fn try_statx(_dirfd: c_int, _p: &CStr, _flags: c_int) -> Option<io::Result<FileAttr>> {
+    None
 }
 
 pub fn canonicalize(p: &Path) -> io::Result<PathBuf> {
+    let mut buf = PathBuf::new();
+    try!(canonicalize_into(p, &mut buf));
+    Ok(buf)
+}
+
+pub fn canonicalize_into(p: &Path, buf: &mut PathBuf) -> io::Result<()> {
     let path = try!(CString::new(p.as_os_str().as_bytes()));
-    let buf;
+    let mut bytes = mem::replace(buf, PathBuf::new()).into_os_string().into_vec();
+    bytes.clear();
     unsafe {
         let r = c::realpath(path.as_ptr(), ptr::null_mut());
         if r.is_null() {
             return Err(io::Error::last_os_error())
         }
-        buf = CStr::from_ptr(r).to_bytes().to_vec();
+        bytes.extend(CStr::from_ptr(r).to_bytes().iter().cloned());
         libc::free(r as *mut _);
     }
-    Ok(PathBuf::from(OsString::from_vec(buf)))
+    *buf = PathBuf::from(OsString::from_vec(bytes));
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn canonicalize_by_fd(p: &Path) -> io::Result<PathBuf> {
+    use string::ToString;
+
+    let path = try!(cstr(p));
+    let fd = try!(cvt_r(|| unsafe {
+        libc::open(path.as_ptr(), c::O_PATH | libc::O_CLOEXEC, 0)
+    }));
+    let fd = FileDesc::new(fd);
+    let mut link = PathBuf::from("/proc/self/fd");
+    link.push(&fd.raw().to_string());
+    match readlink(&link) {
+        Ok(path) => Ok(path),
+        // `/proc` isn't mounted (e.g. inside of a chroot), so fall back to
+        // letting libc resolve the path for us.
+        Err(ref e) if e.kind() == ErrorKind::NotFound => canonicalize(p),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn canonicalize_by_fd(p: &Path) -> io::Result<PathBuf> {
+    canonicalize(p)
+}
+
+/// Copies every extended attribute of `from` onto `to`.
+///
+/// Attributes that can't be read or written for lack of privilege, or
+/// because the destination file system doesn't support them, are skipped.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+pub fn copy_xattrs(from: &File, to: &File) -> io::Result<()> {
+    let (from, to) = (from.0.raw(), to.0.raw());
+    let names = match xattr_buf(|buf, len| unsafe { xattr::list(from, buf, len) }) {
+        Ok(names) => names,
+        Err(ref e) if is_xattr_skippable(e) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for name in names.split(|&b| b == 0).filter(|name| !name.is_empty()) {
+        // The list is NUL-separated, so the names can't contain one.
+        let name = CString::new(name).unwrap();
+        let value = match xattr_buf(|buf, len| unsafe {
+            xattr::get(from, name.as_ptr(), buf, len)
+        }) {
+            Ok(value) => value,
+            Err(ref e) if is_xattr_skippable(e) => continue,
+            Err(e) => return Err(e),
+        };
+        match cvt(unsafe {
+            xattr::set(to, name.as_ptr(), value.as_ptr() as *const _, value.len())
+        }) {
+            Ok(_) => {}
+            Err(ref e) if is_xattr_skippable(e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    return Ok(());
+
+    // Calls `f` once to learn the size of the result and again to fetch it,
+    // retrying if it grew in between.
+    fn xattr_buf<F>(mut f: F) -> io::Result<Vec<u8>>
+        where F: FnMut(*mut libc::c_void, size_t) -> libc::ssize_t
+    {
+        loop {
+            let len = try!(cvt(f(ptr::null_mut(), 0))) as usize;
+            if len == 0 {
+                return Ok(Vec::new())
+            }
+            let mut buf = Vec::with_capacity(len);
+            match cvt(f(buf.as_mut_ptr() as *mut _, len as size_t)) {
+                Ok(n) => {
+                    unsafe { buf.set_len(n as usize) };
+                    return Ok(buf)
+                }
+                Err(ref e) if e.raw_os_error() == Some(libc::ERANGE) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn is_xattr_skippable(e: &io::Error) -> bool {
+        match e.raw_os_error() {
+            Some(libc::EPERM) | Some(libc::EACCES) | Some(libc::EOPNOTSUPP) => true,
+            Some(code) => is_enotsup(code),
+            None => false,
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn is_enotsup(code: c_int) -> bool { code == libc::ENOTSUP }
+    #[cfg(not(target_os = "macos"))]
+    fn is_enotsup(_code: c_int) -> bool { false }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+pub fn copy_xattrs(_from: &File, _to: &File) -> io::Result<()> {
+    // FIXME: the BSDs have extattr_* instead.
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod xattr {
+    use libc::{c_char, c_int, c_void, size_t, ssize_t};
+    use sys::c;
+
+    pub unsafe fn list(fd: c_int, buf: *mut c_void, len: size_t) -> ssize_t {
+        c::flistxattr(fd, buf as *mut c_char, len)
+    }
+
+    pub unsafe fn get(fd: c_int, name: *const c_char, buf: *mut c_void,
+                      len: size_t) -> ssize_t {
+        c::fgetxattr(fd, name, buf, len)
+    }
+
+    pub unsafe fn set(fd: c_int, name: *const c_char, value: *const c_void,
+                      len: size_t) -> c_int {
+        c::fsetxattr(fd, name, value, len, 0)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod xattr {
+    use libc::{c_char, c_int, c_void, size_t, ssize_t};
+    use sys::c;
+
+    pub unsafe fn list(fd: c_int, buf: *mut c_void, len: size_t) -> ssize_t {
+        c::flistxattr(fd, buf as *mut c_char, len, 0)
+    }
+
+    pub unsafe fn get(fd: c_int, name: *const c_char, buf: *mut c_void,
+                      len: size_t) -> ssize_t {
+        c::fgetxattr(fd, name, buf, len, 0, 0)
+    }
+
+    pub unsafe fn set(fd: c_int, name: *const c_char, value: *const c_void,
+                      len: size_t) -> c_int {
+        c::fsetxattr(fd, name, value, len, 0, 0)
+    }
 }
 
 pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
@@ -549,10 +2413,98 @@ pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
     }
 
     let mut reader = try!(File::open(from));
+    let metadata = try!(reader.metadata());
+    // Opening `to` truncates it, which would wipe out the source as well if
+    // both are the same file, e.g. through a hard link.
+    if let Ok(dest) = stat(to) {
+        let src = metadata.as_inner();
+        if dest.stat.st_dev == src.stat.st_dev && dest.stat.st_ino == src.stat.st_ino {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "the source and destination are the same file"))
+        }
+    }
     let mut writer = try!(File::create(to));
-    let perm = try!(reader.metadata()).permissions();
+    let perm = metadata.permissions();
+
+    // Reserve all the space up front so large copies aren't fragmented. Not
+    // every filesystem (or destination, e.g. `/dev/null`) supports this, in
+    // which case the data is just streamed as usual.
+    let preallocated = metadata.len() > 0 &&
+                       writer.as_inner().allocate(metadata.len()).is_ok();
 
     let ret = try!(io::copy(&mut reader, &mut writer));
+    if preallocated {
+        // The source may have shrunk since its size was queried, so don't
+        // leave the preallocated tail around.
+        try!(writer.set_len(ret));
+    }
     try!(set_permissions(to, perm));
     Ok(ret)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prelude::v1::*;
+
+    use env;
+    use fs as std_fs;
+    use libc;
+    use path::PathBuf;
+    use rand::{self, Rng};
+
+    macro_rules! t {
+        ($e:expr) => {
+            match $e {
+                Ok(t) => t,
+                Err(e) => panic!("received error for `{}`: {}", stringify!($e), e),
+            }
+        }
+    }
+
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            t!(std_fs::remove_dir_all(&self.0));
+        }
+    }
+
+    fn dir_with_entries(n: usize) -> TempDir {
+        let mut r = rand::thread_rng();
+        let dir = env::temp_dir().join(&format!("rust-{}", r.next_u32()));
+        t!(std_fs::create_dir(&dir));
+        for i in 0..n {
+            t!(std_fs::File::create(dir.join(&i.to_string())));
+        }
+        TempDir(dir)
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn dev_major_minor() {
+        // What glibc's makedev(8, 1) and makedev(0x1234, 0x56789) give.
+        assert_eq!((dev_major(0x801), dev_minor(0x801)), (8, 1));
+        let dev = 0x0000_1000_5672_3489;
+        assert_eq!((dev_major(dev), dev_minor(dev)), (0x1234, 0x56789));
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn read_dir_fuses_after_stream_error() {
+        use os::unix::io::AsRawFd;
+        use sys::c;
+
+        let dir = dir_with_entries(3);
+        let file = t!(std_fs::File::open(dir.0.join("0")));
+        let mut rd = t!(readdir(&dir.0));
+        // Swap a regular file in for the directory under the stream, so
+        // the next getdents call fails.
+        unsafe {
+            assert!(libc::dup2(file.as_raw_fd(), c::dirfd(rd.dirp.0)) != -1);
+        }
+        assert!(rd.next().unwrap().is_err());
+        assert!(rd.next().is_none());
+        assert!(rd.next().is_none());
+    }
+}
@@ -46,6 +46,8 @@ pub mod sync;
 pub mod thread;
 pub mod thread_local;
 pub mod time;
+#[cfg(target_os = "linux")]
+pub mod uring;
 pub mod stdio;
 
 pub fn init() {
@@ -75,6 +77,9 @@ pub fn decode_error_kind(errno: i32) -> ErrorKind {
         libc::EINTR => ErrorKind::Interrupted,
         libc::EINVAL => ErrorKind::InvalidInput,
         libc::ETIMEDOUT => ErrorKind::TimedOut,
+        libc::ELOOP => ErrorKind::FilesystemLoop,
+        libc::ENOTDIR => ErrorKind::NotADirectory,
+        libc::ETXTBSY => ErrorKind::ExecutableBusy,
         libc::consts::os::posix88::EEXIST => ErrorKind::AlreadyExists,
 
         // These two constants can have the same value on some systems,
@@ -0,0 +1,247 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A minimal `io_uring` instance, used to hand the kernel many independent
+//! file system operations in a handful of system calls.
+//!
+//! Only what the batched functions in `fs` need is here: operations are
+//! queued, submitted and waited for together, and a `Ring` is thrown away
+//! after the batch it was made for.
+
+use cmp;
+use intrinsics;
+use io;
+use libc::{self, c_int, c_uint, c_void, size_t};
+use mem;
+use ptr;
+use sync::atomic::{AtomicBool, Ordering};
+use sys::c;
+use sys::cvt;
+use sys::fd::FileDesc;
+
+pub struct Ring {
+    fd: FileDesc,
+    features: u32,
+    _sq: Mapping,
+    _cq: Mapping,
+    sqes: Mapping,
+    sq_head: *const u32,
+    sq_tail: *mut u32,
+    sq_mask: u32,
+    sq_entries: u32,
+    sq_array: *mut u32,
+    cq_head: *mut u32,
+    cq_tail: *const u32,
+    cq_mask: u32,
+    cqes: *const c::io_uring_cqe,
+}
+
+struct Mapping {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Ring {
+    /// Sets up a ring with room for at least `entries` queued operations,
+    /// returning `None` if the kernel doesn't support `io_uring` or any of
+    /// `ops`.
+    ///
+    /// Kernels before 5.6 can't be asked which operations they support, so
+    /// they're treated as not having `io_uring` at all.
+    pub fn with_ops(entries: u32, ops: &[u8]) -> Option<Ring> {
+        static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+        if UNAVAILABLE.load(Ordering::Relaxed) {
+            return None
+        }
+        match Ring::new(entries) {
+            Ok(ring) => if ring.supports(ops) { Some(ring) } else { None },
+            // No `io_uring` at all, or one which has been turned off.
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOSYS) ||
+                          e.raw_os_error() == Some(libc::EPERM) => {
+                UNAVAILABLE.store(true, Ordering::Relaxed);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    fn new(entries: u32) -> io::Result<Ring> {
+        let mut p: c::io_uring_params = unsafe { mem::zeroed() };
+        let fd = try!(cvt(unsafe {
+            c::syscall(c::NR_IO_URING_SETUP, entries as c_uint,
+                       &mut p as *mut c::io_uring_params)
+        }));
+        // The descriptor is created close-on-exec.
+        let fd = FileDesc::new(fd as c_int);
+
+        let sq_len = p.sq_off.array as usize + p.sq_entries as usize * 4;
+        let cq_len = p.cq_off.cqes as usize +
+                     p.cq_entries as usize * mem::size_of::<c::io_uring_cqe>();
+        let sqes_len = p.sq_entries as usize * mem::size_of::<c::io_uring_sqe>();
+        let sq = try!(Mapping::new(&fd, sq_len, c::IORING_OFF_SQ_RING));
+        let cq = try!(Mapping::new(&fd, cq_len, c::IORING_OFF_CQ_RING));
+        let sqes = try!(Mapping::new(&fd, sqes_len, c::IORING_OFF_SQES));
+
+        unsafe {
+            Ok(Ring {
+                sq_head: sq.at(p.sq_off.head),
+                sq_tail: sq.at(p.sq_off.tail),
+                sq_mask: *sq.at::<u32>(p.sq_off.ring_mask),
+                sq_entries: *sq.at::<u32>(p.sq_off.ring_entries),
+                sq_array: sq.at(p.sq_off.array),
+                cq_head: cq.at(p.cq_off.head),
+                cq_tail: cq.at(p.cq_off.tail),
+                cq_mask: *cq.at::<u32>(p.cq_off.ring_mask),
+                cqes: cq.at(p.cq_off.cqes),
+                fd: fd,
+                features: p.features,
+                _sq: sq,
+                _cq: cq,
+                sqes: sqes,
+            })
+        }
+    }
+
+    fn supports(&self, ops: &[u8]) -> bool {
+        let mut probe: c::io_uring_probe = unsafe { mem::zeroed() };
+        let ret = unsafe {
+            c::syscall(c::NR_IO_URING_REGISTER, self.fd.raw(), c::IORING_REGISTER_PROBE,
+                       &mut probe as *mut c::io_uring_probe,
+                       probe.ops.len() as c_uint)
+        };
+        ret == 0 && ops.iter().all(|&op| {
+            op <= probe.last_op &&
+                probe.ops[op as usize].flags & c::IO_URING_OP_SUPPORTED != 0
+        })
+    }
+
+    /// Returns whether the kernel set `feature`, one of the
+    /// `IORING_FEAT_*` flags, when the ring was set up.
+    pub fn has_feature(&self, feature: u32) -> bool {
+        self.features & feature != 0
+    }
+
+    /// Runs all of `sqes` and stores the result of each, the return value
+    /// of the corresponding system call or a negated errno, at the same
+    /// position in `results`.
+    ///
+    /// The operations may run in any order and concurrently with each
+    /// other, so they mustn't depend on one another. Any memory they point
+    /// to has to stay valid until this returns.
+    ///
+    /// If this fails, the results of the operations which did complete are
+    /// still stored, which lets callers clean up after them, while the rest
+    /// of `results` is left as `None`. Those may still be running in the
+    /// kernel, so the memory they point to has to be leaked rather than
+    /// freed. The ring shouldn't be used again.
+    pub fn run(&mut self, sqes: &[c::io_uring_sqe], results: &mut [Option<i32>])
+               -> io::Result<()> {
+        assert_eq!(sqes.len(), results.len());
+        let mut start = 0;
+        while start < sqes.len() {
+            let end = cmp::min(sqes.len(), start + self.sq_entries as usize);
+            try!(self.run_chunk(&sqes[start..end], start, results));
+            start = end;
+        }
+        Ok(())
+    }
+
+    // Runs at most a full submission queue's worth of operations. The
+    // completion queue is at least as big, so it can't overflow.
+    fn run_chunk(&mut self, sqes: &[c::io_uring_sqe], base: usize,
+                 results: &mut [Option<i32>]) -> io::Result<()> {
+        let n = sqes.len() as u32;
+        unsafe {
+            // Only the kernel moves the head and only we move the tail.
+            let tail = *self.sq_tail;
+            debug_assert_eq!(intrinsics::atomic_load_acq(self.sq_head), tail);
+            for (i, sqe) in sqes.iter().enumerate() {
+                let idx = tail.wrapping_add(i as u32) & self.sq_mask;
+                let mut sqe = *sqe;
+                sqe.user_data = (base + i) as u64;
+                ptr::write(self.sqes.at::<c::io_uring_sqe>(0).offset(idx as isize), sqe);
+                *self.sq_array.offset(idx as isize) = idx;
+            }
+            intrinsics::atomic_store_rel(self.sq_tail, tail.wrapping_add(n));
+        }
+
+        let (mut submitted, mut done) = (0, 0);
+        while done < n {
+            let ret = unsafe {
+                c::syscall(c::NR_IO_URING_ENTER, self.fd.raw(), n - submitted,
+                           n - done, c::IORING_ENTER_GETEVENTS,
+                           ptr::null::<c_void>(), 0 as size_t)
+            };
+            match cvt(ret) {
+                Ok(count) => submitted += count as u32,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // Out of resources for now, but the operations already in
+                // flight will finish and free some up.
+                Err(ref e) if submitted > done &&
+                              (e.raw_os_error() == Some(libc::EAGAIN) ||
+                               e.raw_os_error() == Some(libc::EBUSY)) => {}
+                Err(e) => return Err(e),
+            }
+            done += self.reap(results);
+        }
+        Ok(())
+    }
+
+    fn reap(&mut self, results: &mut [Option<i32>]) -> u32 {
+        let mut count = 0;
+        unsafe {
+            let mut head = *self.cq_head;
+            let tail = intrinsics::atomic_load_acq(self.cq_tail);
+            while head != tail {
+                let cqe = &*self.cqes.offset((head & self.cq_mask) as isize);
+                results[cqe.user_data as usize] = Some(cqe.res);
+                head = head.wrapping_add(1);
+                count += 1;
+            }
+            intrinsics::atomic_store_rel(self.cq_head, head);
+        }
+        count
+    }
+}
+
+impl Mapping {
+    fn new(fd: &FileDesc, len: usize, offset: libc::off_t) -> io::Result<Mapping> {
+        let ptr = unsafe {
+            libc::mmap(ptr::null_mut(), len as size_t, libc::PROT_READ | libc::PROT_WRITE,
+                       libc::MAP_SHARED, fd.raw(), offset)
+        };
+        if ptr == libc::MAP_FAILED {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Mapping { ptr: ptr, len: len })
+        }
+    }
+
+    unsafe fn at<T>(&self, offset: u32) -> *mut T {
+        (self.ptr as *mut u8).offset(offset as isize) as *mut T
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = libc::munmap(self.ptr, self.len as size_t);
+        }
+    }
+}
+
+/// Returns a submission queue entry for `opcode` with everything else
+/// cleared.
+pub fn sqe(opcode: u8) -> c::io_uring_sqe {
+    let mut sqe: c::io_uring_sqe = unsafe { mem::zeroed() };
+    sqe.opcode = opcode;
+    sqe
+}
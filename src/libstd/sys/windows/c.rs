@@ -52,17 +52,31 @@ pub const WSA_FLAG_OVERLAPPED: libc::DWORD = 0x01;
 pub const WSA_FLAG_NO_HANDLE_INHERIT: libc::DWORD = 0x80;
 
 pub const ERROR_NO_MORE_FILES: libc::DWORD = 18;
+pub const ERROR_PATH_NOT_FOUND: libc::c_int = 3;
+pub const ERROR_FILE_EXISTS: libc::c_int = 80;
+pub const ERROR_NOT_SAME_DEVICE: libc::c_int = 17;
+pub const ERROR_TOO_MANY_LINKS: libc::c_int = 1142;
+pub const ERROR_CANT_RESOLVE_FILENAME: libc::c_int = 1921;
+pub const ERROR_DIRECTORY: libc::c_int = 267;
+pub const ERROR_SHARING_VIOLATION: libc::c_int = 32;
+pub const ERROR_HANDLE_EOF: libc::DWORD = 38;
+pub const ERROR_MORE_DATA: libc::DWORD = 234;
 pub const TOKEN_READ: libc::DWORD = 0x20008;
 pub const FILE_FLAG_OPEN_REPARSE_POINT: libc::DWORD = 0x00200000;
 pub const FILE_FLAG_BACKUP_SEMANTICS: libc::DWORD = 0x02000000;
 pub const MAXIMUM_REPARSE_DATA_BUFFER_SIZE: usize = 16 * 1024;
+pub const MAX_PATH: usize = 260;
 pub const FSCTL_GET_REPARSE_POINT: libc::DWORD = 0x900a8;
 pub const IO_REPARSE_TAG_SYMLINK: libc::DWORD = 0xa000000c;
 pub const IO_REPARSE_TAG_MOUNT_POINT: libc::DWORD = 0xa0000003;
 pub const FSCTL_SET_REPARSE_POINT: libc::DWORD = 0x900a4;
 pub const FSCTL_DELETE_REPARSE_POINT: libc::DWORD = 0x900ac;
+pub const FSCTL_SET_SPARSE: libc::DWORD = 0x900c4;
+pub const FSCTL_SET_ZERO_DATA: libc::DWORD = 0x980c8;
+pub const FSCTL_QUERY_ALLOCATED_RANGES: libc::DWORD = 0x940cf;
 
 pub const SYMBOLIC_LINK_FLAG_DIRECTORY: libc::DWORD = 0x1;
+pub const SYMLINK_FLAG_RELATIVE: libc::c_ulong = 0x1;
 
 // Note that these are not actually HANDLEs, just values to pass to GetStdHandle
 pub const STD_INPUT_HANDLE: libc::DWORD = -10i32 as libc::DWORD;
@@ -239,11 +253,38 @@ pub enum FILE_INFO_BY_HANDLE_CLASS {
     MaximumFileInfoByHandlesClass
 }
 
+#[repr(C)]
+pub struct FILE_BASIC_INFO {
+    pub CreationTime: libc::LARGE_INTEGER,
+    pub LastAccessTime: libc::LARGE_INTEGER,
+    pub LastWriteTime: libc::LARGE_INTEGER,
+    pub ChangeTime: libc::LARGE_INTEGER,
+    pub FileAttributes: libc::DWORD,
+}
+
 #[repr(C)]
 pub struct FILE_END_OF_FILE_INFO {
     pub EndOfFile: libc::LARGE_INTEGER,
 }
 
+#[repr(C)]
+pub struct FILE_ALLOCATION_INFO {
+    pub AllocationSize: libc::LARGE_INTEGER,
+}
+
+#[repr(C)]
+pub struct FILE_ZERO_DATA_INFORMATION {
+    pub FileOffset: libc::LARGE_INTEGER,
+    pub BeyondFinalZero: libc::LARGE_INTEGER,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FILE_ALLOCATED_RANGE_BUFFER {
+    pub FileOffset: libc::LARGE_INTEGER,
+    pub Length: libc::LARGE_INTEGER,
+}
+
 #[repr(C)]
 pub struct REPARSE_DATA_BUFFER {
     pub ReparseTag: libc::c_uint,
@@ -262,6 +303,15 @@ pub struct SYMBOLIC_LINK_REPARSE_BUFFER {
     pub PathBuffer: libc::WCHAR,
 }
 
+#[repr(C)]
+pub struct MOUNT_POINT_REPARSE_BUFFER {
+    pub SubstituteNameOffset: libc::c_ushort,
+    pub SubstituteNameLength: libc::c_ushort,
+    pub PrintNameOffset: libc::c_ushort,
+    pub PrintNameLength: libc::c_ushort,
+    pub PathBuffer: libc::WCHAR,
+}
+
 pub type PCONDITION_VARIABLE = *mut CONDITION_VARIABLE;
 pub type PSRWLOCK = *mut SRWLOCK;
 pub type ULONG = c_ulong;
@@ -447,6 +497,28 @@ extern "system" {
     pub fn GetFileInformationByHandle(hFile: libc::HANDLE,
                             lpFileInformation: LPBY_HANDLE_FILE_INFORMATION)
                             -> libc::BOOL;
+    pub fn GetFileSizeEx(hFile: libc::HANDLE,
+                         lpFileSize: *mut libc::LARGE_INTEGER) -> libc::BOOL;
+    pub fn GetCompressedFileSizeW(lpFileName: libc::LPCWSTR,
+                                  lpFileSizeHigh: libc::LPDWORD) -> libc::DWORD;
+    pub fn FlushViewOfFile(lpBaseAddress: libc::LPCVOID,
+                           dwNumberOfBytesToFlush: libc::SIZE_T) -> libc::BOOL;
+    pub fn GetVolumePathNameW(lpszFileName: libc::LPCWSTR,
+                              lpszVolumePathName: libc::LPWSTR,
+                              cchBufferLength: libc::DWORD) -> libc::BOOL;
+    pub fn GetVolumeInformationW(lpRootPathName: libc::LPCWSTR,
+                                 lpVolumeNameBuffer: libc::LPWSTR,
+                                 nVolumeNameSize: libc::DWORD,
+                                 lpVolumeSerialNumber: libc::LPDWORD,
+                                 lpMaximumComponentLength: libc::LPDWORD,
+                                 lpFileSystemFlags: libc::LPDWORD,
+                                 lpFileSystemNameBuffer: libc::LPWSTR,
+                                 nFileSystemNameSize: libc::DWORD) -> libc::BOOL;
+    pub fn GetDiskFreeSpaceW(lpRootPathName: libc::LPCWSTR,
+                             lpSectorsPerCluster: libc::LPDWORD,
+                             lpBytesPerSector: libc::LPDWORD,
+                             lpNumberOfFreeClusters: libc::LPDWORD,
+                             lpTotalNumberOfClusters: libc::LPDWORD) -> libc::BOOL;
 
     pub fn SetLastError(dwErrCode: libc::DWORD);
     pub fn GetCommandLineW() -> *mut libc::LPCWSTR;
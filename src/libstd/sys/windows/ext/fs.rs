@@ -12,12 +12,120 @@
 
 #![stable(feature = "rust1", since = "1.0.0")]
 
-use fs::{OpenOptions, Metadata};
+use fs::{self, OpenOptions, Metadata, Permissions};
 use io;
+use os::raw::c_void;
 use path::Path;
 use sys;
 use sys_common::{AsInnerMut, AsInner};
 
+/// Windows-specific extensions to `fs::File`
+#[unstable(feature = "file_offset", reason = "recently added API", issue = "0")]
+pub trait FileExt {
+    /// Seeks to a given position and reads a number of bytes, returning the
+    /// number of bytes read.
+    ///
+    /// The offset is relative to the start of the file. Unlike `pread` on
+    /// Unix, this moves the cursor of the file to just after the bytes read.
+    ///
+    /// Note that similar to `Read::read`, it is not an error to return with
+    /// a short read.
+    fn seek_read(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    /// Seeks to a given position and writes a number of bytes, returning the
+    /// number of bytes written.
+    ///
+    /// The offset is relative to the start of the file. Unlike `pwrite` on
+    /// Unix, this moves the cursor of the file to just after the bytes
+    /// written.
+    ///
+    /// Note that similar to `Write::write`, it is not an error to return a
+    /// short write.
+    fn seek_write(&self, buf: &[u8], offset: u64) -> io::Result<usize>;
+
+    /// Reads the exact number of bytes required to fill `buf` from the given
+    /// offset, calling `seek_read` as many times as necessary.
+    ///
+    /// This is the positional counterpart of `Read::read_exact`.
+    ///
+    /// The cursor of the file is moved as well, see `seek_read`.
+    ///
+    /// # Errors
+    ///
+    /// If end of file is reached before `buf` is filled, an error of kind
+    /// `ErrorKind::UnexpectedEOF` is returned and the contents of `buf` are
+    /// unspecified.
+    fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.seek_read(buf, offset) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                    offset += n as u64;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if !buf.is_empty() {
+            Err(io::Error::new(io::ErrorKind::UnexpectedEOF,
+                               "failed to fill whole buffer"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Writes all of `buf` starting at the given offset, calling `seek_write` as
+    /// many times as necessary.
+    ///
+    /// This is the positional counterpart of `Write::write_all`.
+    ///
+    /// The cursor of the file is moved as well, see `seek_write`.
+    fn write_all_at(&self, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.seek_write(buf, offset) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                   "failed to write whole buffer")),
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Zeroes `len` bytes starting at `offset` and releases the disk space
+    /// backing them.
+    ///
+    /// The file is marked sparse first if it isn't already, after which the
+    /// range is cleared with `FSCTL_SET_ZERO_DATA`. Reads from the range
+    /// return zeroes afterwards and the length of the file is unchanged.
+    /// Whether any space is actually freed depends on the file system;
+    /// NTFS only releases whole clusters.
+    ///
+    /// The file must have been opened with write access.
+    #[unstable(feature = "file_zero_range", reason = "recently added API",
+               issue = "0")]
+    fn zero_range(&self, offset: u64, len: u64) -> io::Result<()>;
+}
+
+#[unstable(feature = "file_offset", reason = "recently added API", issue = "0")]
+impl FileExt for fs::File {
+    fn seek_read(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.as_inner().read_at(buf, offset)
+    }
+    fn seek_write(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        self.as_inner().write_at(buf, offset)
+    }
+    fn zero_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        self.as_inner().zero_range(offset, len)
+    }
+}
+
 /// Windows-specific extensions to `OpenOptions`
 #[unstable(feature = "open_options_ext",
            reason = "may require more thought/methods",
@@ -47,6 +155,16 @@ pub trait OpenOptionsExt {
     /// This will override any values of the standard flags on the
     /// `OpenOptions` structure.
     fn share_mode(&mut self, val: u32) -> &mut Self;
+
+    /// Refuses to open the path if it names a symbolic link or a junction.
+    ///
+    /// The path is opened with `FILE_FLAG_OPEN_REPARSE_POINT`, and if the
+    /// opened file turns out to be a link it is closed again and the open
+    /// fails with an error of kind `SymlinkNotFollowed`, the same as on Unix.
+    /// Links in the preceding components of the path are still followed.
+    #[unstable(feature = "open_options_no_follow", reason = "recently added API",
+               issue = "0")]
+    fn no_follow(&mut self, no_follow: bool) -> &mut Self;
 }
 
 impl OpenOptionsExt for OpenOptions {
@@ -62,6 +180,9 @@ impl OpenOptionsExt for OpenOptions {
     fn share_mode(&mut self, access: u32) -> &mut OpenOptions {
         self.as_inner_mut().share_mode(access); self
     }
+    fn no_follow(&mut self, no_follow: bool) -> &mut OpenOptions {
+        self.as_inner_mut().no_follow(no_follow); self
+    }
 }
 
 /// Extension methods for `fs::Metadata` to access the raw fields contained
@@ -102,6 +223,35 @@ pub trait MetadataExt {
     /// The returned value does not have meaning for directories.
     #[stable(feature = "metadata_ext", since = "1.1.0")]
     fn file_size(&self) -> u64;
+
+    /// Returns the value of the `dwVolumeSerialNumber` field of the
+    /// `BY_HANDLE_FILE_INFORMATION` for this file.
+    ///
+    /// This is only available for metadata obtained through an open file
+    /// (`File::metadata` or `fs::metadata` on a symlink), and is `None`
+    /// otherwise.
+    #[unstable(feature = "windows_by_handle", reason = "recently added API",
+               issue = "0")]
+    fn volume_serial_number(&self) -> Option<u32>;
+
+    /// Returns the value of the `nNumberOfLinks` field of the
+    /// `BY_HANDLE_FILE_INFORMATION` for this file.
+    ///
+    /// Like `volume_serial_number`, this is `None` unless the metadata was
+    /// obtained through an open file.
+    #[unstable(feature = "windows_by_handle", reason = "recently added API",
+               issue = "0")]
+    fn number_of_links(&self) -> Option<u32>;
+
+    /// Returns the value of the `nFileIndex{High,Low}` fields of the
+    /// `BY_HANDLE_FILE_INFORMATION` for this file.
+    ///
+    /// Together with `volume_serial_number` this uniquely identifies a file
+    /// on a machine. Like `volume_serial_number`, this is `None` unless the
+    /// metadata was obtained through an open file.
+    #[unstable(feature = "windows_by_handle", reason = "recently added API",
+               issue = "0")]
+    fn file_index(&self) -> Option<u64>;
 }
 
 #[stable(feature = "metadata_ext", since = "1.1.0")]
@@ -111,6 +261,13 @@ impl MetadataExt for Metadata {
     fn last_access_time(&self) -> u64 { self.as_inner().accessed() }
     fn last_write_time(&self) -> u64 { self.as_inner().modified() }
     fn file_size(&self) -> u64 { self.as_inner().size() }
+    fn volume_serial_number(&self) -> Option<u32> {
+        self.as_inner().volume_serial_number()
+    }
+    fn number_of_links(&self) -> Option<u32> {
+        self.as_inner().number_of_links()
+    }
+    fn file_index(&self) -> Option<u64> { self.as_inner().file_index() }
 }
 
 /// Creates a new file symbolic link on the filesystem.
@@ -154,3 +311,88 @@ pub fn symlink_dir<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q)
                                                    -> io::Result<()> {
     sys::fs::symlink_inner(src.as_ref(), dst.as_ref(), true)
 }
+
+/// Forgets the properties of volumes cached by the standard library.
+///
+/// Properties that only change when a volume is reformatted, such as the
+/// cluster size behind `Metadata::preferred_io_block_size`, are looked up
+/// once per volume and process and remembered from then on. Call this if a
+/// volume may have been reformatted or replaced by another one with the same
+/// serial number since, so that they are looked up again.
+#[unstable(feature = "windows_volume_cache", reason = "recently added API",
+           issue = "0")]
+pub fn clear_volume_cache() {
+    sys::fs::volume::invalidate()
+}
+
+/// Windows-specific extensions to `Permissions`
+#[unstable(feature = "permissions_apply_bits", reason = "recently added API",
+           issue = "0")]
+pub trait PermissionsExt {
+    /// Returns the file attributes these permissions were built from, as
+    /// the `dwFileAttributes` bits reported by `GetFileAttributesEx`.
+    fn attributes(&self) -> u32;
+
+    /// Replaces the attribute bits selected by `mask` with the corresponding
+    /// bits of `other`, leaving all other attributes as they are.
+    ///
+    /// For example a mask of `FILE_ATTRIBUTE_READONLY | FILE_ATTRIBUTE_HIDDEN`
+    /// (`0x3`) copies the read-only and hidden flags from `other` without
+    /// touching the archive or system attributes.
+    fn apply_attribute_bits_from(&mut self, other: &Permissions, mask: u32);
+}
+
+impl PermissionsExt for Permissions {
+    fn attributes(&self) -> u32 { self.as_inner().attrs() }
+
+    fn apply_attribute_bits_from(&mut self, other: &Permissions, mask: u32) {
+        self.as_inner_mut().apply_bits_from(other.as_inner(), mask)
+    }
+}
+
+/// Windows-specific extensions to `fs::DirEntry`.
+#[unstable(feature = "dir_entry_name_bytes", reason = "recently added API",
+           issue = "0")]
+pub trait DirEntryExt {
+    /// Returns the UTF-16 code units of this entry's file name, borrowed
+    /// from the entry itself.
+    ///
+    /// Unlike `file_name` this doesn't allocate, which makes it cheap to
+    /// filter entries by name and only allocate for the ones kept. The
+    /// returned slice lives only as long as the `DirEntry`, and like any
+    /// Windows file name it isn't necessarily valid UTF-16.
+    fn file_name_wide(&self) -> &[u16];
+}
+
+impl DirEntryExt for fs::DirEntry {
+    fn file_name_wide(&self) -> &[u16] { self.as_inner().name_wide() }
+}
+
+/// Windows-specific extensions to `fs::DirBuilder`.
+#[unstable(feature = "dir_builder", reason = "recently added API",
+           issue = "27710")]
+pub trait DirBuilderExt {
+    /// Sets the `lpSecurityAttributes` argument passed to
+    /// `CreateDirectoryW` for every directory created by this builder,
+    /// including the parents created when building recursively.
+    ///
+    /// Passing a null pointer restores the default of inheriting the parent
+    /// directory's security descriptor.
+    ///
+    /// # Safety
+    ///
+    /// Only the pointer is stored, and `CreateDirectoryW` reads through it
+    /// each time the builder creates a directory. Unless it's null, `attrs`
+    /// must point to a valid `SECURITY_ATTRIBUTES` structure, along with
+    /// the security descriptor it refers to, for as long as the builder is
+    /// used. The builder stays `Send` and `Sync`, so if it's used from
+    /// another thread, the structure must be readable from there too.
+    unsafe fn security_attributes(&mut self, attrs: *mut c_void) -> &mut Self;
+}
+
+impl DirBuilderExt for fs::DirBuilder {
+    unsafe fn security_attributes(&mut self, attrs: *mut c_void) -> &mut fs::DirBuilder {
+        self.as_inner_mut().set_security_attributes(attrs as *mut _);
+        self
+    }
+}
@@ -11,25 +11,56 @@
 use io::prelude::*;
 use os::windows::prelude::*;
 
-use ffi::OsString;
+use ascii::AsciiExt;
+use ffi::{OsString, OsStr};
 use fmt;
 use io::{self, Error, SeekFrom};
 use libc::{self, HANDLE};
 use mem;
+use ops::Range;
 use path::{Path, PathBuf};
 use ptr;
 use slice;
+use string::String;
 use sync::Arc;
 use sys::handle::Handle;
 use sys::{c, cvt};
-use sys_common::FromInner;
+use sys_common::{FromInner, IntoInner};
+use thread;
+use time::Duration;
 use vec::Vec;
 
 pub struct File { handle: Handle }
 
+pub struct Mmap {
+    // Start of the view, which is aligned to the allocation granularity and
+    // thus may lie `delta` bytes before the data that was asked for. Null
+    // for empty maps, as empty views can't be created.
+    view: *mut libc::c_void,
+    delta: usize,
+    len: usize,
+    // A handle to the file for writable maps, as flushing the view alone
+    // doesn't make the changes durable.
+    file: Option<Handle>,
+}
+
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
 pub struct FileAttr {
     data: c::WIN32_FILE_ATTRIBUTE_DATA,
     reparse_tag: libc::DWORD,
+    // Only known when the attributes were queried through a handle.
+    volume_serial_number: Option<u32>,
+    number_of_links: Option<u32>,
+    file_index: Option<u64>,
+    // Filled in when the attributes were queried through a handle, which is
+    // the only time the volume can be found from it.
+    cluster_size: Option<u32>,
+    // The absolute, nul-terminated path the attributes were queried by, if
+    // any, which the cluster size is looked up from when it's asked for.
+    // `lstat` has built it anyway, so keeping it costs no allocation.
+    path: Option<Vec<u16>>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
@@ -37,10 +68,22 @@ pub enum FileType {
     Dir, File, Symlink, ReparsePoint, MountPoint,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct FileIdentity {
+    volume_serial_number: Option<u32>,
+    file_index: Option<u64>,
+    size: u64,
+    last_write_time: u64,
+}
+
 pub struct ReadDir {
     handle: FindNextFileHandle,
     root: Arc<PathBuf>,
     first: Option<libc::WIN32_FIND_DATAW>,
+    // Set once FindNextFileW has run out of entries or failed. A failed find
+    // handle can't be moved past the entry it choked on, so any error ends
+    // the iteration.
+    done: bool,
 }
 
 struct FindNextFileHandle(libc::HANDLE);
@@ -64,26 +107,51 @@ pub struct OpenOptions {
     share_mode: Option<libc::DWORD>,
     creation_disposition: Option<libc::DWORD>,
     flags_and_attributes: Option<libc::DWORD>,
+    no_follow: bool,
+    create_new: bool,
     security_attributes: usize, // *mut T doesn't have a Default impl
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct FilePermissions { attrs: libc::DWORD }
 
-pub struct DirBuilder;
+pub struct DirBuilder {
+    // A `usize` keeps the builder Send/Sync like on other platforms; the
+    // unsafe `DirBuilderExt::security_attributes` makes that the caller's
+    // concern.
+    security_attributes: usize,
+}
+
+impl ReadDir {
+    pub fn next_matching<F>(&mut self, mut f: F) -> Option<io::Result<DirEntry>>
+        where F: FnMut(&OsStr) -> bool
+    {
+        self.next_raw(|name| f(&OsString::from_wide(name)))
+    }
 
-impl Iterator for ReadDir {
-    type Item = io::Result<DirEntry>;
-    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+    // The find data already holds everything there is to know.
+    pub fn entry_metadata(&self, entry: &DirEntry) -> io::Result<FileAttr> {
+        entry.metadata()
+    }
+
+    // The predicate is handed the raw UTF-16 name so that plain iteration
+    // doesn't pay for converting names that nobody looks at.
+    fn next_raw<F>(&mut self, mut f: F) -> Option<io::Result<DirEntry>>
+        where F: FnMut(&[u16]) -> bool
+    {
         if let Some(first) = self.first.take() {
-            if let Some(e) = DirEntry::new(&self.root, &first) {
+            if let Some(e) = DirEntry::new(&self.root, &first, &mut f) {
                 return Some(Ok(e));
             }
         }
+        if self.done {
+            return None
+        }
         unsafe {
             let mut wfd = mem::zeroed();
             loop {
                 if libc::FindNextFileW(self.handle.0, &mut wfd) == 0 {
+                    self.done = true;
                     if libc::GetLastError() ==
                         c::ERROR_NO_MORE_FILES as libc::DWORD {
                         return None
@@ -91,7 +159,7 @@ impl Iterator for ReadDir {
                         return Some(Err(Error::last_os_error()))
                     }
                 }
-                if let Some(e) = DirEntry::new(&self.root, &wfd) {
+                if let Some(e) = DirEntry::new(&self.root, &wfd, &mut f) {
                     return Some(Ok(e))
                 }
             }
@@ -99,6 +167,13 @@ impl Iterator for ReadDir {
     }
 }
 
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+        self.next_raw(|_| true)
+    }
+}
+
 impl Drop for FindNextFileHandle {
     fn drop(&mut self) {
         let r = unsafe { libc::FindClose(self.0) };
@@ -107,13 +182,19 @@ impl Drop for FindNextFileHandle {
 }
 
 impl DirEntry {
-    fn new(root: &Arc<PathBuf>, wfd: &libc::WIN32_FIND_DATAW) -> Option<DirEntry> {
+    fn new<F>(root: &Arc<PathBuf>, wfd: &libc::WIN32_FIND_DATAW, f: &mut F)
+              -> Option<DirEntry>
+        where F: FnMut(&[u16]) -> bool
+    {
         match &wfd.cFileName[0..3] {
             // check for '.' and '..'
             [46, 0, ..] |
             [46, 46, 0, ..] => return None,
             _ => {}
         }
+        if !f(super::truncate_utf16_at_nul(&wfd.cFileName)) {
+            return None
+        }
 
         Some(DirEntry {
             root: root.clone(),
@@ -125,9 +206,19 @@ impl DirEntry {
         self.root.join(&self.file_name())
     }
 
+    pub fn into_path(self) -> PathBuf {
+        let name = self.file_name();
+        let mut path = Arc::try_unwrap(self.root).unwrap_or_else(|root| (*root).clone());
+        path.push(&name);
+        path
+    }
+
     pub fn file_name(&self) -> OsString {
-        let filename = super::truncate_utf16_at_nul(&self.data.cFileName);
-        OsString::from_wide(filename)
+        OsString::from_wide(self.name_wide())
+    }
+
+    pub fn name_wide(&self) -> &[u16] {
+        super::truncate_utf16_at_nul(&self.data.cFileName)
     }
 
     pub fn file_type(&self) -> io::Result<FileType> {
@@ -146,31 +237,53 @@ impl DirEntry {
                 nFileSizeLow: self.data.nFileSizeLow,
             },
             reparse_tag: self.data.dwReserved0,
+            volume_serial_number: None,
+            number_of_links: None,
+            file_index: None,
+            cluster_size: None,
+            path: None,
         })
     }
 }
 
 impl OpenOptions {
     pub fn new() -> OpenOptions { Default::default() }
-    pub fn read(&mut self, read: bool) { self.read = read; }
-    pub fn write(&mut self, write: bool) { self.write = write; }
-    pub fn append(&mut self, append: bool) { self.append = append; }
-    pub fn create(&mut self, create: bool) { self.create = create; }
-    pub fn truncate(&mut self, truncate: bool) { self.truncate = truncate; }
-    pub fn creation_disposition(&mut self, val: u32) {
+    pub fn read(&mut self, read: bool) -> &mut OpenOptions { self.read = read; self }
+    pub fn write(&mut self, write: bool) -> &mut OpenOptions { self.write = write; self }
+    pub fn append(&mut self, append: bool) -> &mut OpenOptions { self.append = append; self }
+    pub fn create(&mut self, create: bool) -> &mut OpenOptions { self.create = create; self }
+    pub fn truncate(&mut self, truncate: bool) -> &mut OpenOptions {
+        self.truncate = truncate;
+        self
+    }
+    pub fn create_new(&mut self, create_new: bool) -> &mut OpenOptions {
+        self.create_new = create_new;
+        self
+    }
+    pub fn creation_disposition(&mut self, val: u32) -> &mut OpenOptions {
         self.creation_disposition = Some(val);
+        self
     }
-    pub fn flags_and_attributes(&mut self, val: u32) {
+    pub fn flags_and_attributes(&mut self, val: u32) -> &mut OpenOptions {
         self.flags_and_attributes = Some(val);
+        self
     }
-    pub fn desired_access(&mut self, val: u32) {
+    pub fn desired_access(&mut self, val: u32) -> &mut OpenOptions {
         self.desired_access = Some(val);
+        self
     }
-    pub fn share_mode(&mut self, val: u32) {
+    pub fn share_mode(&mut self, val: u32) -> &mut OpenOptions {
         self.share_mode = Some(val);
+        self
     }
-    pub fn security_attributes(&mut self, attrs: libc::LPSECURITY_ATTRIBUTES) {
+    pub fn security_attributes(&mut self, attrs: libc::LPSECURITY_ATTRIBUTES)
+                               -> &mut OpenOptions {
         self.security_attributes = attrs as usize;
+        self
+    }
+    pub fn no_follow(&mut self, no_follow: bool) -> &mut OpenOptions {
+        self.no_follow = no_follow;
+        self
     }
 
     fn get_desired_access(&self) -> libc::DWORD {
@@ -197,6 +310,7 @@ impl OpenOptions {
     fn get_creation_disposition(&self) -> libc::DWORD {
         self.creation_disposition.unwrap_or({
             match (self.create, self.truncate) {
+                _ if self.create_new => libc::CREATE_NEW,
                 (true, true) => libc::CREATE_ALWAYS,
                 (true, false) => libc::OPEN_ALWAYS,
                 (false, false) => libc::OPEN_EXISTING,
@@ -212,24 +326,45 @@ impl OpenOptions {
     }
 
     fn get_flags_and_attributes(&self) -> libc::DWORD {
-        self.flags_and_attributes.unwrap_or(libc::FILE_ATTRIBUTE_NORMAL)
+        // CREATE_NEW fails as soon as anything exists under the name,
+        // symlinks included, without resolving them, so it needs no help
+        // here; adding FILE_FLAG_OPEN_REPARSE_POINT would only change how the
+        // newly created file is opened. Refusing links for the other
+        // dispositions is what the separate `no_follow` option is for.
+        self.flags_and_attributes.unwrap_or(libc::FILE_ATTRIBUTE_NORMAL) |
+            if self.no_follow {
+                c::FILE_FLAG_OPEN_REPARSE_POINT
+            } else {
+                0
+            }
     }
 }
 
 impl File {
     fn open_reparse_point(path: &Path, write: bool) -> io::Result<File> {
         let mut opts = OpenOptions::new();
-        opts.read(!write);
-        opts.write(write);
-        opts.flags_and_attributes(c::FILE_FLAG_OPEN_REPARSE_POINT |
+        opts.read(!write)
+            .write(write)
+            .flags_and_attributes(c::FILE_FLAG_OPEN_REPARSE_POINT |
                                   c::FILE_FLAG_BACKUP_SEMANTICS);
         File::open(path, &opts)
     }
 
+    pub fn open_path(p: &Path, no_follow: bool) -> io::Result<File> {
+        let mut opts = OpenOptions::new();
+        opts.desired_access(0);
+        opts.flags_and_attributes(if no_follow {
+            c::FILE_FLAG_BACKUP_SEMANTICS | c::FILE_FLAG_OPEN_REPARSE_POINT
+        } else {
+            c::FILE_FLAG_BACKUP_SEMANTICS
+        });
+        File::open(p, &opts)
+    }
+
     pub fn open(path: &Path, opts: &OpenOptions) -> io::Result<File> {
-        let path = to_utf16(path);
+        let wide = to_utf16(path);
         let handle = unsafe {
-            libc::CreateFileW(path.as_ptr(),
+            libc::CreateFileW(wide.as_ptr(),
                               opts.get_desired_access(),
                               opts.get_share_mode(),
                               opts.security_attributes as *mut _,
@@ -238,10 +373,26 @@ impl File {
                               ptr::null_mut())
         };
         if handle == libc::INVALID_HANDLE_VALUE {
-            Err(Error::last_os_error())
-        } else {
-            Ok(File { handle: Handle::new(handle) })
+            let err = Error::last_os_error();
+            let writing = libc::FILE_WRITE_DATA | libc::FILE_APPEND_DATA;
+            if err.raw_os_error() == Some(c::ERROR_SHARING_VIOLATION) &&
+               opts.get_desired_access() & writing != 0 && is_executable_image(path) {
+                return Err(Error::new(io::ErrorKind::ExecutableBusy, err))
+            }
+            return require_directory(path, Err(err))
         }
+        let file = File { handle: Handle::new(handle) };
+        if has_trailing_separator(path) &&
+           try!(file.file_attr()).attrs() & c::FILE_ATTRIBUTE_DIRECTORY == 0 {
+            return Err(Error::from_raw_os_error(c::ERROR_DIRECTORY))
+        }
+        // FILE_FLAG_OPEN_REPARSE_POINT opens the link itself rather than
+        // failing, so it's up to us to refuse it.
+        if opts.no_follow && try!(file.file_attr()).file_type().is_symlink() {
+            return Err(Error::new(io::ErrorKind::SymlinkNotFollowed,
+                                  "path is a symbolic link"))
+        }
+        Ok(file)
     }
 
     pub fn fsync(&self) -> io::Result<()> {
@@ -251,6 +402,12 @@ impl File {
 
     pub fn datasync(&self) -> io::Result<()> { self.fsync() }
 
+    pub fn full_fsync(&self) -> io::Result<()> { self.fsync() }
+
+    // NTFS journals the size along with the file's other metadata, which
+    // FlushFileBuffers already flushes, and directories can't be flushed.
+    pub fn sync_parent_dir(&self) -> io::Result<()> { Ok(()) }
+
     pub fn truncate(&self, size: u64) -> io::Result<()> {
         let mut info = c::FILE_END_OF_FILE_INFO {
             EndOfFile: size as libc::LARGE_INTEGER,
@@ -265,6 +422,185 @@ impl File {
         Ok(())
     }
 
+    // Reserves the clusters for `size` bytes without moving the end of the
+    // file, unlike `fallocate` on Linux.
+    pub fn allocate(&self, size: u64) -> io::Result<()> {
+        let mut info = c::FILE_ALLOCATION_INFO {
+            AllocationSize: size as libc::LARGE_INTEGER,
+        };
+        let size = mem::size_of_val(&info);
+        try!(cvt(unsafe {
+            c::SetFileInformationByHandle(self.handle.raw(),
+                                          c::FileAllocationInfo,
+                                          &mut info as *mut _ as *mut _,
+                                          size as libc::DWORD)
+        }));
+        Ok(())
+    }
+
+    pub fn set_perm(&self, perm: FilePermissions) -> io::Result<()> {
+        self.set_attributes(perm.attrs)
+    }
+
+    pub fn set_readonly(&self, readonly: bool) -> io::Result<()> {
+        // Start from the current attributes so that bits like hidden or
+        // system aren't lost along the way.
+        let mut perm = try!(self.file_attr()).perm();
+        perm.set_readonly(readonly);
+        self.set_perm(perm)
+    }
+
+    fn set_attributes(&self, attrs: libc::DWORD) -> io::Result<()> {
+        // Zeroed timestamps are left untouched, and an attribute value of 0
+        // would mean the same, so ask for a "normal" file instead.
+        let mut info = c::FILE_BASIC_INFO {
+            CreationTime: 0,
+            LastAccessTime: 0,
+            LastWriteTime: 0,
+            ChangeTime: 0,
+            FileAttributes: if attrs == 0 {libc::FILE_ATTRIBUTE_NORMAL} else {attrs},
+        };
+        let size = mem::size_of_val(&info);
+        try!(cvt(unsafe {
+            c::SetFileInformationByHandle(self.handle.raw(),
+                                          c::FileBasicInfo,
+                                          &mut info as *mut _ as *mut _,
+                                          size as libc::DWORD)
+        }));
+        Ok(())
+    }
+
+    pub fn size(&self) -> io::Result<u64> {
+        let mut size = 0;
+        try!(cvt(unsafe { c::GetFileSizeEx(self.handle.raw(), &mut size) }));
+        Ok(size as u64)
+    }
+
+    pub fn zero_range(&self, offset: u64, len: u64) -> io::Result<()> {
+        let end = try!(offset.checked_add(len).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "range overflows u64")
+        }));
+        let attrs = try!(self.file_attr()).attrs();
+        let mut bytes = 0;
+        if attrs & libc::FILE_ATTRIBUTE_SPARSE_FILE == 0 {
+            // A null input buffer means "make the file sparse".
+            try!(cvt(unsafe {
+                c::DeviceIoControl(self.handle.raw(),
+                                   c::FSCTL_SET_SPARSE,
+                                   ptr::null_mut(),
+                                   0,
+                                   ptr::null_mut(),
+                                   0,
+                                   &mut bytes,
+                                   ptr::null_mut())
+            }));
+        }
+        let mut info = c::FILE_ZERO_DATA_INFORMATION {
+            FileOffset: offset as libc::LARGE_INTEGER,
+            BeyondFinalZero: end as libc::LARGE_INTEGER,
+        };
+        try!(cvt(unsafe {
+            c::DeviceIoControl(self.handle.raw(),
+                               c::FSCTL_SET_ZERO_DATA,
+                               &mut info as *mut _ as libc::LPVOID,
+                               mem::size_of_val(&info) as libc::DWORD,
+                               ptr::null_mut(),
+                               0,
+                               &mut bytes,
+                               ptr::null_mut())
+        }));
+        Ok(())
+    }
+
+    pub fn allocated_ranges(&self) -> io::Result<Vec<Range<u64>>> {
+        let len = try!(self.size());
+        let mut ranges = Vec::new();
+        let mut out = [c::FILE_ALLOCATED_RANGE_BUFFER {
+            FileOffset: 0,
+            Length: 0,
+        }; 64];
+        let mut offset = 0;
+        while offset < len {
+            let mut query = c::FILE_ALLOCATED_RANGE_BUFFER {
+                FileOffset: offset as libc::LARGE_INTEGER,
+                Length: (len - offset) as libc::LARGE_INTEGER,
+            };
+            let mut bytes = 0;
+            let more = unsafe {
+                c::DeviceIoControl(self.handle.raw(),
+                                   c::FSCTL_QUERY_ALLOCATED_RANGES,
+                                   &mut query as *mut _ as libc::LPVOID,
+                                   mem::size_of_val(&query) as libc::DWORD,
+                                   out.as_mut_ptr() as libc::LPVOID,
+                                   mem::size_of_val(&out) as libc::DWORD,
+                                   &mut bytes,
+                                   ptr::null_mut()) == 0
+            };
+            if more && unsafe { libc::GetLastError() } != c::ERROR_MORE_DATA {
+                return Err(io::Error::last_os_error())
+            }
+            let n = bytes as usize / mem::size_of::<c::FILE_ALLOCATED_RANGE_BUFFER>();
+            for range in &out[..n] {
+                let start = range.FileOffset as u64;
+                ranges.push(start..start + range.Length as u64);
+            }
+            if !more || n == 0 {
+                break
+            }
+            offset = ranges[ranges.len() - 1].end;
+        }
+        Ok(ranges)
+    }
+
+    pub fn memory_map(&self, offset: u64, len: usize, writable: bool) -> io::Result<Mmap> {
+        if len == 0 {
+            return Ok(Mmap { view: ptr::null_mut(), delta: 0, len: 0, file: None })
+        }
+        let granularity = unsafe {
+            let mut info: libc::SYSTEM_INFO = mem::zeroed();
+            libc::GetSystemInfo(&mut info);
+            info.dwAllocationGranularity as u64
+        };
+        let aligned = offset - offset % granularity;
+        let delta = (offset - aligned) as usize;
+        let map_len = match delta.checked_add(len) {
+            Some(map_len) => map_len,
+            None => return Err(Error::new(io::ErrorKind::InvalidInput,
+                                          "range to map is too large")),
+        };
+        let (protect, access) = if writable {
+            (libc::PAGE_READWRITE, libc::FILE_MAP_WRITE)
+        } else {
+            (libc::PAGE_READONLY, libc::FILE_MAP_READ)
+        };
+        // A size of zero makes the mapping as large as the file. The view
+        // keeps the mapping alive on its own, so its handle can be closed
+        // right away.
+        let mapping = unsafe {
+            libc::CreateFileMappingW(self.handle.raw(), ptr::null_mut(),
+                                     protect, 0, 0, ptr::null())
+        };
+        if mapping.is_null() {
+            return Err(Error::last_os_error())
+        }
+        let mapping = Handle::new(mapping);
+        let view = unsafe {
+            libc::MapViewOfFile(mapping.raw(), access,
+                                (aligned >> 32) as libc::DWORD,
+                                aligned as libc::DWORD,
+                                map_len as libc::SIZE_T)
+        };
+        if view.is_null() {
+            return Err(Error::last_os_error())
+        }
+        let mut map = Mmap { view: view, delta: delta, len: len, file: None };
+        if writable {
+            map.file = Some(try!(self.handle.duplicate(0, false,
+                                                       libc::DUPLICATE_SAME_ACCESS)));
+        }
+        Ok(map)
+    }
+
     pub fn file_attr(&self) -> io::Result<FileAttr> {
         unsafe {
             let mut info: c::BY_HANDLE_FILE_INFORMATION = mem::zeroed();
@@ -280,6 +616,13 @@ impl File {
                     nFileSizeLow: info.nFileSizeLow,
                 },
                 reparse_tag: 0,
+                volume_serial_number: Some(info.dwVolumeSerialNumber),
+                number_of_links: Some(info.nNumberOfLinks),
+                file_index: Some(((info.nFileIndexHigh as u64) << 32) |
+                                 (info.nFileIndexLow as u64)),
+                cluster_size: volume::cluster_size_of_handle(&self.handle,
+                                                             info.dwVolumeSerialNumber),
+                path: None,
             };
             if attr.is_reparse_point() {
                 let mut b = [0; c::MAXIMUM_REPARSE_DATA_BUFFER_SIZE];
@@ -295,29 +638,98 @@ impl File {
         self.handle.read(buf)
     }
 
+    // ReadFileScatter has the same restrictions as WriteFileGather, see
+    // `write_vectored`.
+    pub fn read_vectored(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+        let mut total = 0;
+        for buf in bufs {
+            match self.handle.read(buf) {
+                Ok(n) => {
+                    total += n;
+                    if n < buf.len() {
+                        break
+                    }
+                }
+                Err(_) if total > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(total)
+    }
+
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.handle.read_at(buf, offset)
+    }
+
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        self.handle.write_at(buf, offset)
+    }
+
+    pub fn read_to_spare(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        self.handle.read_to_spare(buf)
+    }
+
     pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
         self.handle.write(buf)
     }
 
+    // WriteFileGather only takes page-sized, page-aligned buffers on handles
+    // opened without buffering, so this writes the slices one by one,
+    // stopping at the first short write.
+    pub fn write_vectored(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+        let mut total = 0;
+        for buf in bufs {
+            match self.handle.write(buf) {
+                Ok(n) => {
+                    total += n;
+                    if n < buf.len() {
+                        break
+                    }
+                }
+                // What has been written so far is reported, the error will
+                // show up again on the next call.
+                Err(_) if total > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(total)
+    }
+
     pub fn flush(&self) -> io::Result<()> { Ok(()) }
 
     pub fn seek(&self, pos: SeekFrom) -> io::Result<u64> {
+        let mut newpos = 0;
+        try!(self.set_file_pointer(pos, &mut newpos));
+        Ok(newpos as u64)
+    }
+
+    // SetFilePointerEx only reports the new position if asked to.
+    pub fn seek_discard(&self, pos: SeekFrom) -> io::Result<()> {
+        self.set_file_pointer(pos, ptr::null_mut())
+    }
+
+    fn set_file_pointer(&self, pos: SeekFrom, newpos: libc::PLARGE_INTEGER)
+                        -> io::Result<()> {
         let (whence, pos) = match pos {
             SeekFrom::Start(n) => (libc::FILE_BEGIN, n as i64),
             SeekFrom::End(n) => (libc::FILE_END, n),
             SeekFrom::Current(n) => (libc::FILE_CURRENT, n),
         };
         let pos = pos as libc::LARGE_INTEGER;
-        let mut newpos = 0;
         try!(cvt(unsafe {
-            libc::SetFilePointerEx(self.handle.raw(), pos,
-                                   &mut newpos, whence)
+            libc::SetFilePointerEx(self.handle.raw(), pos, newpos, whence)
         }));
-        Ok(newpos as u64)
+        Ok(())
     }
 
+    /// Borrows the handle of this file, e.g. for code elsewhere in std
+    /// reaching it through `AsInner`. The handle stays owned by this `File`:
+    /// callers must not close it, nor keep its raw value around for longer
+    /// than the borrow, as the value may be reused once it's closed.
     pub fn handle(&self) -> &Handle { &self.handle }
 
+    /// Gives up ownership of the handle, which is then closed when the
+    /// returned `Handle` is dropped.
     pub fn into_handle(self) -> Handle { self.handle }
 
     fn reparse_point<'a>(&self,
@@ -340,22 +752,44 @@ impl File {
     }
 
     fn readlink(&self) -> io::Result<PathBuf> {
+        match try!(self.link_target()) {
+            (target, c::IO_REPARSE_TAG_SYMLINK, _) => Ok(target),
+            _ => Err(io::Error::new(io::ErrorKind::Other, "not a symlink")),
+        }
+    }
+
+    /// Reads the target of a symlink or junction, returning it along with
+    /// the reparse tag and whether the target is relative.
+    fn link_target(&self) -> io::Result<(PathBuf, libc::DWORD, bool)> {
         let mut space = [0u8; c::MAXIMUM_REPARSE_DATA_BUFFER_SIZE];
         let (_bytes, buf) = try!(self.reparse_point(&mut space));
-        if buf.ReparseTag != c::IO_REPARSE_TAG_SYMLINK {
-            return Err(io::Error::new(io::ErrorKind::Other, "not a symlink"))
-        }
 
         unsafe {
-            let info: *const c::SYMBOLIC_LINK_REPARSE_BUFFER =
-                    &buf.rest as *const _ as *const _;
-            let path_buffer = &(*info).PathBuffer as *const _ as *const u16;
-            let subst_off = (*info).SubstituteNameOffset / 2;
-            let subst_ptr = path_buffer.offset(subst_off as isize);
-            let subst_len = (*info).SubstituteNameLength / 2;
-            let subst = slice::from_raw_parts(subst_ptr, subst_len as usize);
+            let (path_buffer, subst_off, subst_len, relative) = match buf.ReparseTag {
+                c::IO_REPARSE_TAG_SYMLINK => {
+                    let info: *const c::SYMBOLIC_LINK_REPARSE_BUFFER =
+                            &buf.rest as *const _ as *const _;
+                    (&(*info).PathBuffer as *const _ as *const u16,
+                     (*info).SubstituteNameOffset,
+                     (*info).SubstituteNameLength,
+                     (*info).Flags & c::SYMLINK_FLAG_RELATIVE != 0)
+                }
+                // Junctions always point to an absolute path.
+                c::IO_REPARSE_TAG_MOUNT_POINT => {
+                    let info: *const c::MOUNT_POINT_REPARSE_BUFFER =
+                            &buf.rest as *const _ as *const _;
+                    (&(*info).PathBuffer as *const _ as *const u16,
+                     (*info).SubstituteNameOffset,
+                     (*info).SubstituteNameLength,
+                     false)
+                }
+                _ => return Err(io::Error::new(io::ErrorKind::Other,
+                                               "not a symlink or junction")),
+            };
+            let subst_ptr = path_buffer.offset(subst_off as isize / 2);
+            let subst = slice::from_raw_parts(subst_ptr, subst_len as usize / 2);
 
-            Ok(PathBuf::from(OsString::from_wide(subst)))
+            Ok((PathBuf::from(OsString::from_wide(subst)), buf.ReparseTag, relative))
         }
     }
 }
@@ -401,6 +835,35 @@ impl FileAttr {
     pub fn accessed(&self) -> u64 { self.to_u64(&self.data.ftLastAccessTime) }
     pub fn modified(&self) -> u64 { self.to_u64(&self.data.ftLastWriteTime) }
 
+    pub fn volume_serial_number(&self) -> Option<u32> {
+        self.volume_serial_number
+    }
+    pub fn number_of_links(&self) -> Option<u32> { self.number_of_links }
+    pub fn file_index(&self) -> Option<u64> { self.file_index }
+
+    pub fn identity(&self) -> FileIdentity {
+        FileIdentity {
+            volume_serial_number: self.volume_serial_number,
+            file_index: self.file_index,
+            size: self.size(),
+            last_write_time: self.modified(),
+        }
+    }
+
+    pub fn preferred_io_block_size(&self) -> Option<u64> {
+        let size = match (self.cluster_size, &self.path) {
+            (Some(size), _) => Some(size),
+            (None, &Some(ref path)) => volume::of_path(path).and_then(|v| v.cluster_size),
+            (None, &None) => None,
+        };
+        size.map(|size| size as u64)
+    }
+
+    // NTFS doesn't link directories to their subdirectories at all.
+    pub fn subdir_count(&self) -> Option<u64> {
+        None
+    }
+
     fn to_u64(&self, ft: &libc::FILETIME) -> u64 {
         (ft.dwLowDateTime as u64) | ((ft.dwHighDateTime as u64) << 32)
     }
@@ -422,6 +885,13 @@ impl FilePermissions {
             self.attrs &= !c::FILE_ATTRIBUTE_READONLY;
         }
     }
+
+    pub fn attrs(&self) -> u32 { self.attrs as u32 }
+
+    pub fn apply_bits_from(&mut self, other: &FilePermissions, mask: u32) {
+        let mask = mask as libc::DWORD;
+        self.attrs = (self.attrs & !mask) | (other.attrs & mask);
+    }
 }
 
 impl FileType {
@@ -444,18 +914,81 @@ impl FileType {
     pub fn is_symlink(&self) -> bool {
         *self == FileType::Symlink || *self == FileType::MountPoint
     }
+
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            FileType::Dir => "directory",
+            FileType::File => "file",
+            FileType::Symlink => "symlink",
+            FileType::MountPoint => "junction",
+            FileType::ReparsePoint => "reparse point",
+        }
+    }
+}
+
+impl Mmap {
+    pub fn as_slice(&self) -> &[u8] {
+        if self.view.is_null() {
+            return &[]
+        }
+        unsafe {
+            slice::from_raw_parts((self.view as *const u8).offset(self.delta as isize),
+                                  self.len)
+        }
+    }
+
+    // Only called on maps created as writable.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.view.is_null() {
+            return &mut []
+        }
+        unsafe {
+            slice::from_raw_parts_mut((self.view as *mut u8).offset(self.delta as isize),
+                                      self.len)
+        }
+    }
+
+    pub fn flush(&self, offset: usize, len: usize) -> io::Result<()> {
+        if len == 0 {
+            return Ok(())
+        }
+        try!(cvt(unsafe {
+            let start = (self.view as *const u8).offset((self.delta + offset) as isize);
+            c::FlushViewOfFile(start as *const libc::c_void, len as libc::SIZE_T)
+        }));
+        // FlushViewOfFile only hands the pages over to the file system
+        // without waiting for them to reach the disk.
+        if let Some(ref file) = self.file {
+            try!(cvt(unsafe { libc::FlushFileBuffers(file.raw()) }));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if !self.view.is_null() {
+            unsafe { let _ = libc::UnmapViewOfFile(self.view); }
+        }
+    }
 }
 
 impl DirBuilder {
-    pub fn new() -> DirBuilder { DirBuilder }
+    pub fn new() -> DirBuilder { DirBuilder { security_attributes: 0 } }
 
     pub fn mkdir(&self, p: &Path) -> io::Result<()> {
         let p = to_utf16(p);
         try!(cvt(unsafe {
-            libc::CreateDirectoryW(p.as_ptr(), ptr::null_mut())
+            libc::CreateDirectoryW(p.as_ptr(),
+                                   self.security_attributes as *mut _)
         }));
         Ok(())
     }
+
+    pub fn set_security_attributes(&mut self,
+                                   attrs: libc::LPSECURITY_ATTRIBUTES) {
+        self.security_attributes = attrs as usize;
+    }
 }
 
 pub fn readdir(p: &Path) -> io::Result<ReadDir> {
@@ -471,6 +1004,7 @@ pub fn readdir(p: &Path) -> io::Result<ReadDir> {
                 handle: FindNextFileHandle(find_handle),
                 root: Arc::new(root),
                 first: Some(wfd),
+                done: false,
             })
         } else {
             Err(Error::last_os_error())
@@ -484,6 +1018,81 @@ pub fn unlink(p: &Path) -> io::Result<()> {
     Ok(())
 }
 
+pub fn unlink_retry(p: &Path, attempts: u32, delay: Duration) -> io::Result<()> {
+    let mut cleared_readonly = false;
+    let mut attempt = 1;
+    loop {
+        let err = match unlink(p) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        match err.raw_os_error() {
+            // DeleteFileW refuses read-only files with this error, so that's
+            // always worth fixing up before anything else.
+            Some(libc::ERROR_ACCESS_DENIED) if !cleared_readonly => {
+                cleared_readonly = true;
+                if try!(clear_readonly(p)).is_some() {
+                    continue
+                }
+            }
+            // Some other process has the file open without sharing delete
+            // access, or the file is still pending deletion, both of which
+            // can go away on their own.
+            Some(libc::ERROR_ACCESS_DENIED) |
+            Some(c::ERROR_SHARING_VIOLATION) => {}
+            _ => return Err(err),
+        }
+        if attempt >= attempts {
+            return Err(err)
+        }
+        attempt += 1;
+        thread::sleep(delay);
+    }
+}
+
+pub fn unlink_force(p: &Path) -> io::Result<()> {
+    remove_force(p, unlink)
+}
+
+pub fn rmdir_force(p: &Path) -> io::Result<()> {
+    remove_force(p, rmdir)
+}
+
+// Runs `remove` on `p`, and if that was refused because `p` is read-only,
+// clears the attribute and tries once more.
+fn remove_force(p: &Path, remove: fn(&Path) -> io::Result<()>) -> io::Result<()> {
+    let err = match remove(p) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    if err.raw_os_error() != Some(libc::ERROR_ACCESS_DENIED) {
+        return Err(err)
+    }
+    let perm = match try!(clear_readonly(p)) {
+        Some(perm) => perm,
+        None => return Err(err),
+    };
+    remove(p).map_err(|e| {
+        // Put things back the way they were; the original error is more
+        // interesting than a failure to do so.
+        let _ = set_perm(p, perm);
+        e
+    })
+}
+
+// Clears the read-only attribute of `p`, returning the attributes it had
+// before if that was necessary.
+fn clear_readonly(p: &Path) -> io::Result<Option<FilePermissions>> {
+    let perm = try!(lstat(p)).perm();
+    if !perm.readonly() {
+        return Ok(None)
+    }
+    let mut writable = perm.clone();
+    writable.set_readonly(false);
+    try!(set_perm(p, writable));
+    Ok(Some(perm))
+}
+
 pub fn rename(old: &Path, new: &Path) -> io::Result<()> {
     let old = to_utf16(old);
     let new = to_utf16(new);
@@ -494,21 +1103,116 @@ pub fn rename(old: &Path, new: &Path) -> io::Result<()> {
     Ok(())
 }
 
+pub fn rename_retry(old: &Path, new: &Path, attempts: u32, delay: Duration)
+                    -> io::Result<()> {
+    let mut attempt = 1;
+    loop {
+        let err = match rename(old, new) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        match err.raw_os_error() {
+            // A file in a directory being moved, or the file itself, is open
+            // somewhere, which often only lasts as long as a virus scanner
+            // or indexer looks at it.
+            Some(libc::ERROR_ACCESS_DENIED) |
+            Some(c::ERROR_SHARING_VIOLATION) => {}
+            _ => return Err(err),
+        }
+        if attempt >= attempts {
+            return Err(err)
+        }
+        attempt += 1;
+        thread::sleep(delay);
+    }
+}
+
+// MoveFileExW refuses to replace directories, which includes directory
+// symlinks and junctions, so those have to be removed first. That leaves a
+// short window in which `link` doesn't exist.
+pub fn rename_over_symlink(tmp: &Path, link: &Path) -> io::Result<()> {
+    let err = match rename(tmp, link) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    let is_dir_link = lstat(link).map(|attr| {
+        attr.file_type().is_symlink() &&
+            attr.attrs() & c::FILE_ATTRIBUTE_DIRECTORY != 0
+    }).unwrap_or(false);
+    if err.raw_os_error() != Some(libc::ERROR_ACCESS_DENIED) || !is_dir_link {
+        return Err(err)
+    }
+    try!(rmdir(link));
+    rename(tmp, link)
+}
+
+pub fn rename_noreplace(old: &Path, new: &Path) -> io::Result<()> {
+    let old = to_utf16(old);
+    let new = to_utf16(new);
+    try!(cvt(unsafe {
+        libc::MoveFileExW(old.as_ptr(), new.as_ptr(), 0)
+    }));
+    Ok(())
+}
+
 pub fn rmdir(p: &Path) -> io::Result<()> {
     let p = to_utf16(p);
     try!(cvt(unsafe { c::RemoveDirectoryW(p.as_ptr()) }));
     Ok(())
 }
 
+// Directories on Windows don't carry any kind of entry count.
+pub fn dir_entry_count_hint(p: &Path) -> io::Result<Option<u64>> {
+    if !try!(stat(p)).file_type().is_dir() {
+        return Err(Error::new(io::ErrorKind::InvalidInput, "not a directory"))
+    }
+    Ok(None)
+}
+
+pub fn fs_type(p: &Path) -> io::Result<(String, Option<u64>)> {
+    // GetVolumePathNameW happily finds the volume a path would be on even
+    // if nothing's there, while `statfs` fails.
+    try!(stat(p));
+    // GetVolumePathNameW would otherwise take relative paths to be on the
+    // boot volume.
+    let p = if p.is_absolute() {
+        p.to_path_buf()
+    } else {
+        try!(super::os::getcwd()).join(p)
+    };
+    match volume::of_path(&to_utf16(&p)) {
+        Some(info) => Ok((info.fs_name, None)),
+        None => Err(Error::last_os_error()),
+    }
+}
+
+pub fn symlink_target_len(p: &Path) -> io::Result<u64> {
+    readlink(p).map(|target| target.as_os_str().encode_wide().count() as u64 * 2)
+}
+
 pub fn readlink(p: &Path) -> io::Result<PathBuf> {
     let file = try!(File::open_reparse_point(p, false));
     file.readlink()
 }
 
+pub fn readlink_full(p: &Path) -> io::Result<(PathBuf, bool, Option<bool>)> {
+    let file = try!(File::open_reparse_point(p, false));
+    let (target, _tag, relative) = try!(file.link_target());
+    let attr = try!(file.file_attr());
+    Ok((target, relative, Some(attr.attrs() & c::FILE_ATTRIBUTE_DIRECTORY != 0)))
+}
+
 pub fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
     symlink_inner(src, dst, false)
 }
 
+// The copy is a directory symlink if the original is one, whether or not
+// its target exists. Junctions are copied as directory symlinks.
+pub fn copy_symlink(link: &Path, dst: &Path) -> io::Result<()> {
+    let (target, _relative, dir) = try!(readlink_full(link));
+    symlink_inner(&target, dst, dir.unwrap_or(false))
+}
+
 pub fn symlink_inner(src: &Path, dst: &Path, dir: bool) -> io::Result<()> {
     let src = to_utf16(src);
     let dst = to_utf16(dst);
@@ -519,6 +1223,18 @@ pub fn symlink_inner(src: &Path, dst: &Path, dir: bool) -> io::Result<()> {
     Ok(())
 }
 
+/// Creates a symlink that is a directory link if `src` currently refers to a
+/// directory, resolving a relative `src` against the parent of `dst` just
+/// like the link itself will be resolved.
+pub fn symlink_auto(src: &Path, dst: &Path) -> io::Result<()> {
+    let target = match dst.parent() {
+        Some(parent) => parent.join(src),
+        None => src.to_path_buf(),
+    };
+    let dir = stat(&target).map(|attr| attr.file_type().is_dir()).unwrap_or(false);
+    symlink_inner(src, dst, dir)
+}
+
 pub fn link(src: &Path, dst: &Path) -> io::Result<()> {
     let src = to_utf16(src);
     let dst = to_utf16(dst);
@@ -528,6 +1244,10 @@ pub fn link(src: &Path, dst: &Path) -> io::Result<()> {
     Ok(())
 }
 
+pub fn stat_batch(paths: &[&Path]) -> Vec<io::Result<FileAttr>> {
+    paths.iter().map(|p| stat(p)).collect()
+}
+
 pub fn stat(p: &Path) -> io::Result<FileAttr> {
     let attr = try!(lstat(p));
 
@@ -546,13 +1266,334 @@ pub fn stat(p: &Path) -> io::Result<FileAttr> {
     }
 }
 
+/// Returns the volume serial number and file index of the file `p` resolves
+/// to. Only a handle knows these, so this has to open the file.
+pub fn file_id(p: &Path) -> io::Result<(u64, u64)> {
+    let mut opts = OpenOptions::new();
+    opts.desired_access(0).flags_and_attributes(c::FILE_FLAG_BACKUP_SEMANTICS);
+    let attr = try!(try!(File::open(p, &opts)).file_attr());
+    match (attr.volume_serial_number, attr.file_index) {
+        (Some(serial), Some(index)) => Ok((serial as u64, index)),
+        _ => Err(io::Error::new(io::ErrorKind::Other, "file has no file index")),
+    }
+}
+
+/// Returns whether `e` means that a hard link can't be created between the
+/// two paths, as opposed to something being wrong with either of them.
+pub fn is_link_impossible(e: &io::Error) -> bool {
+    match e.raw_os_error() {
+        Some(c::ERROR_NOT_SAME_DEVICE) |
+        Some(c::ERROR_TOO_MANY_LINKS) => true,
+        _ => false,
+    }
+}
+
+/// Returns whether `e` means that a path, or one of its parents, doesn't
+/// exist.
+pub fn is_not_found(e: &io::Error) -> bool {
+    match e.raw_os_error() {
+        Some(libc::ERROR_FILE_NOT_FOUND) |
+        Some(c::ERROR_PATH_NOT_FOUND) => true,
+        _ => false,
+    }
+}
+
+/// Returns whether `e`, from removing a directory, means that it doesn't
+/// exist. A file in its place fails with `ERROR_DIRECTORY` instead.
+pub fn is_dir_not_found(e: &io::Error) -> bool {
+    is_not_found(e)
+}
+
+// Cache of the properties of each volume that has been asked about, such as
+// its cluster size and file system. They only change when the volume is
+// reformatted, so they are looked up once per volume and then remembered for
+// the life of the process, unless `invalidate` is called. Anything that
+// changes on its own, like the free space, doesn't belong in here.
+//
+// Volumes are identified by their serial number, and a volume that can't be
+// queried is remembered as such too. Looking the serial number up for a path
+// takes `GetVolumeInformationW`, itself not free, so the serial number of
+// each volume root seen is remembered as well.
+//
+// `File::metadata` looks its volume up every time, so once a volume is known
+// that only takes a read lock, which doesn't hold up other threads doing the
+// same.
+pub mod volume {
+    use prelude::v1::*;
+    use os::windows::prelude::*;
+
+    use ffi::OsString;
+    use libc;
+    use ptr;
+    use sync::StaticRwLock;
+    use sys::c;
+    use sys::handle::Handle;
+
+    #[derive(Clone)]
+    pub struct Info {
+        // `None` if the volume doesn't say, as some network shares don't.
+        pub cluster_size: Option<u32>,
+        // The name of the file system, such as "NTFS".
+        pub fs_name: String,
+    }
+
+    static LOCK: StaticRwLock = StaticRwLock::new();
+    // Both are leaked on purpose; there are only ever a handful of volumes.
+    static mut BY_SERIAL: *mut Vec<(u32, Option<Info>)> = 0 as *mut _;
+    static mut SERIAL_BY_ROOT: *mut Vec<(Vec<u16>, u32)> = 0 as *mut _;
+
+    /// Returns the cluster size of the volume with the serial number
+    /// `serial`, which `handle` is open on.
+    pub fn cluster_size_of_handle(handle: &Handle, serial: u32) -> Option<u32> {
+        let known = {
+            let _g = LOCK.read();
+            unsafe {
+                if BY_SERIAL.is_null() {
+                    None
+                } else {
+                    (*BY_SERIAL).iter().find(|e| e.0 == serial)
+                                .map(|e| e.1.as_ref().and_then(|info| info.cluster_size))
+                }
+            }
+        };
+        if let Some(size) = known {
+            return size
+        }
+        let path = super::super::fill_utf16_buf(|buf, sz| unsafe {
+            c::GetFinalPathNameByHandleW(handle.raw(), buf, sz,
+                                         libc::VOLUME_NAME_DOS)
+        }, |path| path.iter().cloned().chain(Some(0)).collect::<Vec<u16>>());
+        let info = path.ok().and_then(|path| volume_root(&path))
+                       .and_then(|root| query(&root));
+        remember(serial, info).and_then(|info| info.cluster_size)
+    }
+
+    pub fn of_path(path: &[u16]) -> Option<Info> {
+        // Finding the volume doesn't take long, querying it may, e.g. on a
+        // network share.
+        let root = match volume_root(path) {
+            Some(root) => root,
+            None => return None,
+        };
+        let known = {
+            let _g = LOCK.read();
+            unsafe {
+                if SERIAL_BY_ROOT.is_null() {
+                    None
+                } else {
+                    (*SERIAL_BY_ROOT).iter().find(|e| e.0 == root).map(|e| e.1)
+                }
+            }
+        };
+        let serial = match known.or_else(|| serial_number(&root)) {
+            Some(serial) => serial,
+            None => return None,
+        };
+        let info = match cached(serial) {
+            Some(info) => info,
+            None => remember(serial, query(&root)),
+        };
+        remember_root(root, serial);
+        info
+    }
+
+    /// Forgets everything cached, e.g. after a volume has been reformatted.
+    pub fn invalidate() {
+        let _g = LOCK.write();
+        unsafe {
+            if !BY_SERIAL.is_null() {
+                (*BY_SERIAL).clear();
+            }
+            if !SERIAL_BY_ROOT.is_null() {
+                (*SERIAL_BY_ROOT).clear();
+            }
+        }
+    }
+
+    // `Some(None)` for a volume which is known not to answer.
+    fn cached(serial: u32) -> Option<Option<Info>> {
+        let _g = LOCK.read();
+        unsafe {
+            if BY_SERIAL.is_null() {
+                return None
+            }
+            (*BY_SERIAL).iter().find(|e| e.0 == serial).map(|e| e.1.clone())
+        }
+    }
+
+    // Must be called with `LOCK` held for writing.
+    unsafe fn init() {
+        if BY_SERIAL.is_null() {
+            BY_SERIAL = Box::into_raw(Box::new(Vec::new()));
+            SERIAL_BY_ROOT = Box::into_raw(Box::new(Vec::new()));
+        }
+    }
+
+    // Another thread may have raced us to it, in which case either result
+    // is as good as the other.
+    fn remember(serial: u32, info: Option<Info>) -> Option<Info> {
+        let _g = LOCK.write();
+        unsafe {
+            init();
+            if !(*BY_SERIAL).iter().any(|e| e.0 == serial) {
+                (*BY_SERIAL).push((serial, info.clone()));
+            }
+        }
+        info
+    }
+
+    fn remember_root(root: Vec<u16>, serial: u32) {
+        let _g = LOCK.write();
+        unsafe {
+            init();
+            if !(*SERIAL_BY_ROOT).iter().any(|e| e.0 == root) {
+                (*SERIAL_BY_ROOT).push((root, serial));
+            }
+        }
+    }
+
+    fn query(root: &[u16]) -> Option<Info> {
+        let mut name = [0u16; c::MAX_PATH + 1];
+        let ok = unsafe {
+            c::GetVolumeInformationW(root.as_ptr(), ptr::null_mut(), 0,
+                                     ptr::null_mut(), ptr::null_mut(),
+                                     ptr::null_mut(), name.as_mut_ptr(),
+                                     name.len() as libc::DWORD)
+        };
+        if ok == 0 {
+            return None
+        }
+        let name = OsString::from_wide(super::super::truncate_utf16_at_nul(&name));
+        Some(Info {
+            cluster_size: disk_cluster_size(root),
+            fs_name: name.to_string_lossy().into_owned(),
+        })
+    }
+
+    // Returns the nul-terminated root of the volume `path` lives on, which
+    // also takes mounted folders into account.
+    fn volume_root(path: &[u16]) -> Option<Vec<u16>> {
+        // The root can't be longer than the path itself, apart from the
+        // trailing backslash that gets added.
+        let mut root = vec![0u16; path.len() + 1];
+        let ok = unsafe {
+            c::GetVolumePathNameW(path.as_ptr(), root.as_mut_ptr(),
+                                  root.len() as libc::DWORD)
+        };
+        if ok == 0 {
+            return None
+        }
+        let len = root.iter().position(|&c| c == 0).unwrap_or(root.len());
+        root.truncate(len + 1);
+        Some(root)
+    }
+
+    fn serial_number(root: &[u16]) -> Option<u32> {
+        let mut serial = 0;
+        let ok = unsafe {
+            c::GetVolumeInformationW(root.as_ptr(), ptr::null_mut(), 0,
+                                     &mut serial, ptr::null_mut(),
+                                     ptr::null_mut(), ptr::null_mut(), 0)
+        };
+        if ok == 0 { None } else { Some(serial as u32) }
+    }
+
+    fn disk_cluster_size(root: &[u16]) -> Option<u32> {
+        let mut sectors_per_cluster = 0;
+        let mut bytes_per_sector = 0;
+        let ok = unsafe {
+            c::GetDiskFreeSpaceW(root.as_ptr(), &mut sectors_per_cluster,
+                                 &mut bytes_per_sector, ptr::null_mut(),
+                                 ptr::null_mut())
+        };
+        if ok == 0 {
+            return None
+        }
+        (sectors_per_cluster as u32).checked_mul(bytes_per_sector as u32)
+                                    .and_then(|size| if size == 0 { None } else { Some(size) })
+    }
+}
+
+// POSIX requires a path ending with a separator to name a directory, which
+// Windows doesn't reliably enforce: depending on the API, `file.txt\` either
+// finds the file or fails with ERROR_INVALID_NAME. Both are turned into
+// ERROR_DIRECTORY here.
+fn has_trailing_separator(p: &Path) -> bool {
+    match p.as_os_str().encode_wide().last() {
+        Some(c) => c == b'/' as u16 || c == b'\\' as u16,
+        None => false,
+    }
+}
+
+fn require_directory<T>(p: &Path, ret: io::Result<T>) -> io::Result<T> {
+    let invalid_name = match ret {
+        Err(ref e) => e.raw_os_error() == Some(libc::ERROR_INVALID_NAME),
+        Ok(_) => false,
+    };
+    if !invalid_name || !has_trailing_separator(p) {
+        return ret
+    }
+    let mut trimmed = p.as_os_str().encode_wide().collect::<Vec<u16>>();
+    while trimmed.last() == Some(&(b'/' as u16)) || trimmed.last() == Some(&(b'\\' as u16)) {
+        trimmed.pop();
+    }
+    match lstat_inner(&PathBuf::from(OsString::from_wide(&trimmed))) {
+        Ok(ref a) if a.attrs() & c::FILE_ATTRIBUTE_DIRECTORY == 0 => {
+            Err(Error::from_raw_os_error(c::ERROR_DIRECTORY))
+        }
+        _ => ret,
+    }
+}
+
+// Windows can't tell a running executable apart from any other file opened
+// without write sharing, so the closest one can get is to check that the
+// file is an executable image at all. Those start with the "MZ" of their
+// DOS header, whatever their extension.
+fn is_executable_image(p: &Path) -> bool {
+    let mut opts = OpenOptions::new();
+    opts.read(true);
+    let file = match File::open(p, &opts) {
+        Ok(file) => file,
+        Err(..) => return false,
+    };
+    let mut magic = [0; 2];
+    match file.read(&mut magic) {
+        Ok(2) => &magic == b"MZ",
+        _ => false,
+    }
+}
+
 pub fn lstat(p: &Path) -> io::Result<FileAttr> {
+    let attr = try!(require_directory(p, lstat_inner(p)));
+    if has_trailing_separator(p) && attr.attrs() & c::FILE_ATTRIBUTE_DIRECTORY == 0 {
+        return Err(Error::from_raw_os_error(c::ERROR_DIRECTORY))
+    }
+    Ok(attr)
+}
+
+fn lstat_inner(p: &Path) -> io::Result<FileAttr> {
     let utf16 = to_utf16(p);
     unsafe {
-        let mut attr: FileAttr = mem::zeroed();
+        let mut attr = FileAttr {
+            data: mem::zeroed(),
+            reparse_tag: 0,
+            volume_serial_number: None,
+            number_of_links: None,
+            file_index: None,
+            cluster_size: None,
+            path: None,
+        };
         try!(cvt(c::GetFileAttributesExW(utf16.as_ptr(),
                                          c::GetFileExInfoStandard,
                                          &mut attr.data as *mut _ as *mut _)));
+        // GetVolumePathNameW would take a relative path to be on the boot
+        // volume, and the current directory may have changed by the time
+        // the volume is looked up.
+        attr.path = if p.is_absolute() {
+            Some(utf16)
+        } else {
+            super::os::getcwd().ok().map(|cwd| to_utf16(&cwd.join(p)))
+        };
         if attr.is_reparse_point() {
             attr.reparse_tag = File::open_reparse_point(p, false).and_then(|f| {
                 let mut b = [0; c::MAXIMUM_REPARSE_DATA_BUFFER_SIZE];
@@ -571,6 +1612,15 @@ pub fn set_perm(p: &Path, perm: FilePermissions) -> io::Result<()> {
     }
 }
 
+pub fn set_perm_nofollow(p: &Path, perm: FilePermissions) -> io::Result<()> {
+    let mut opts = OpenOptions::new();
+    opts.desired_access(libc::FILE_WRITE_ATTRIBUTES)
+        .flags_and_attributes(c::FILE_FLAG_OPEN_REPARSE_POINT |
+                              c::FILE_FLAG_BACKUP_SEMANTICS);
+    let file = try!(File::open(p, &opts));
+    file.set_attributes(perm.attrs)
+}
+
 fn get_path(f: &File) -> io::Result<PathBuf> {
     super::fill_utf16_buf(|buf, sz| unsafe {
         c::GetFinalPathNameByHandleW(f.handle.raw(), buf, sz,
@@ -581,12 +1631,126 @@ fn get_path(f: &File) -> io::Result<PathBuf> {
 }
 
 pub fn canonicalize(p: &Path) -> io::Result<PathBuf> {
+    let mut buf = PathBuf::new();
+    try!(canonicalize_into(p, &mut buf));
+    Ok(buf)
+}
+
+pub fn canonicalize_into(p: &Path, buf: &mut PathBuf) -> io::Result<()> {
     let mut opts = OpenOptions::new();
     opts.read(true);
     // This flag is so we can open directories too
     opts.flags_and_attributes(c::FILE_FLAG_BACKUP_SEMANTICS);
     let f = try!(File::open(p, &opts));
-    get_path(&f)
+
+    let mut os = mem::replace(buf, PathBuf::new()).into_os_string().into_inner();
+    let ret = super::fill_utf16_buf(|b, sz| unsafe {
+        c::GetFinalPathNameByHandleW(f.handle.raw(), b, sz,
+                                     libc::VOLUME_NAME_DOS)
+    }, |wide| {
+        os.inner.assign_wide(&simplify_final_path(wide))
+    });
+    *buf = PathBuf::from(OsString::from_inner(os));
+    ret
+}
+
+// GetFinalPathNameByHandleW always returns a verbatim path such as
+// `\\?\c:\foo` or `\\?\UNC\server\share\foo`. Turn that into the plain DOS
+// form people expect, with an uppercase drive letter, as long as the result
+// still means the same thing: paths of MAX_PATH or more characters and
+// components that the Win32 layer would mangle (trailing dots or spaces,
+// device names) need the prefix and keep it.
+fn simplify_final_path(wide: &[u16]) -> Vec<u16> {
+    const SEP: u16 = b'\\' as u16;
+    const VERBATIM: &'static [u16] = &[SEP, SEP, b'?' as u16, SEP];
+    const VERBATIM_UNC: &'static [u16] = &[SEP, SEP, b'?' as u16, SEP,
+                                           b'U' as u16, b'N' as u16, b'C' as u16, SEP];
+
+    let mut path: Vec<u16> = wide.iter().map(|&c| {
+        if c == b'/' as u16 { SEP } else { c }
+    }).collect();
+
+    let is_letter = |c: u16| {
+        (c >= b'a' as u16 && c <= b'z' as u16) || (c >= b'A' as u16 && c <= b'Z' as u16)
+    };
+    // Only `X:\`; a bare `X:` would become relative to that drive's
+    // current directory.
+    let has_drive = |path: &[u16], i: usize| {
+        path.len() > i + 2 && is_letter(path[i]) && path[i + 1] == b':' as u16 &&
+            path[i + 2] == SEP
+    };
+    let (prefix_len, drive) = if path.starts_with(VERBATIM_UNC) {
+        (VERBATIM_UNC.len(), None)
+    } else if path.starts_with(VERBATIM) {
+        // Anything else after the prefix, like `Volume{GUID}` or
+        // `GLOBALROOT`, has no DOS form, and stripping the prefix would
+        // leave a relative path.
+        if !has_drive(&path, VERBATIM.len()) {
+            return path
+        }
+        (VERBATIM.len(), Some(VERBATIM.len()))
+    } else {
+        (0, if has_drive(&path, 0) { Some(0) } else { None })
+    };
+    if let Some(i) = drive {
+        if path[i] >= b'a' as u16 && path[i] <= b'z' as u16 {
+            path[i] -= (b'a' - b'A') as u16;
+        }
+    }
+    if prefix_len == 0 || !is_plain_dos_path(&path[prefix_len..]) {
+        return path
+    }
+
+    let stripped_len = if drive.is_some() {
+        path.len() - prefix_len
+    } else {
+        path.len() - prefix_len + 2
+    };
+    if stripped_len >= c::MAX_PATH {
+        return path
+    }
+    if drive.is_some() {
+        path.drain(..prefix_len);
+    } else {
+        // `\\?\UNC\server` becomes `\\server`.
+        path.drain(2..prefix_len);
+    }
+    path
+}
+
+fn is_plain_dos_path(path: &[u16]) -> bool {
+    const RESERVED: &'static [&'static str] = &["CON", "PRN", "AUX", "NUL",
+        "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+        "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"];
+
+    path.split(|&c| c == b'\\' as u16).all(|component| {
+        match component.last() {
+            Some(&c) if c == b'.' as u16 || c == b' ' as u16 => {
+                // `.` and `..` don't show up in final paths, so any trailing
+                // dot here would be stripped by the Win32 layer.
+                return false
+            }
+            None => return true,
+            _ => {}
+        }
+        let stem = component.split(|&c| c == b'.' as u16).next().unwrap();
+        !RESERVED.iter().any(|name| {
+            name.len() == stem.len() &&
+                name.bytes().zip(stem).all(|(a, &b)| {
+                    b < 0x80 && (b as u8).to_ascii_uppercase() == a
+                })
+        })
+    })
+}
+
+// Windows has no extended attributes in the Unix sense; alternate data
+// streams are already copied by CopyFileEx.
+pub fn copy_xattrs(_from: &File, _to: &File) -> io::Result<()> {
+    Ok(())
+}
+
+fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    Ok(try!(file_id(a)) == try!(file_id(b)))
 }
 
 pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
@@ -604,6 +1768,10 @@ pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
         *(lpData as *mut i64) = TotalBytesTransferred;
         c::PROGRESS_CONTINUE
     }
+    if let Ok(true) = same_file(from, to) {
+        return Err(Error::new(io::ErrorKind::InvalidInput,
+                              "the source and destination are the same file"))
+    }
     let pfrom = to_utf16(from);
     let pto = to_utf16(to);
     let mut size = 0i64;
@@ -614,6 +1782,23 @@ pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
     Ok(size as u64)
 }
 
+#[test]
+fn simplify_final_path_prefixes() {
+    let simplify = |s: &str| {
+        let wide = s.utf16_units().collect::<Vec<u16>>();
+        String::from_utf16(&simplify_final_path(&wide)).unwrap()
+    };
+    assert_eq!(simplify(r"\\?\c:\foo\bar"), r"C:\foo\bar");
+    assert_eq!(simplify(r"\\?\C:\"), r"C:\");
+    assert_eq!(simplify(r"\\?\C:"), r"\\?\C:");
+    assert_eq!(simplify(r"\\?\UNC\server\share\foo"), r"\\server\share\foo");
+    assert_eq!(simplify(r"\\?\UNC\server\share\nul"), r"\\?\UNC\server\share\nul");
+    assert_eq!(simplify(r"\\?\Volume{01234567-89ab-cdef-0123-456789abcdef}\x"),
+               r"\\?\Volume{01234567-89ab-cdef-0123-456789abcdef}\x");
+    assert_eq!(simplify(r"\\?\GLOBALROOT\Device\Foo\x"), r"\\?\GLOBALROOT\Device\Foo\x");
+    assert_eq!(simplify(r"\\?\c:\foo."), r"\\?\C:\foo.");
+}
+
 #[test]
 fn directory_junctions_are_directories() {
     use ffi::OsStr;
@@ -8,6 +8,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use cmp;
 use io::ErrorKind;
 use io;
 use libc::funcs::extra::kernel32::{GetCurrentProcess, DuplicateHandle};
@@ -15,7 +16,8 @@ use libc::{self, HANDLE};
 use mem;
 use ops::Deref;
 use ptr;
-use sys::cvt;
+use sys::{c, cvt};
+use vec::Vec;
 
 /// An owned container for `HANDLE` object, closing them on Drop.
 ///
@@ -64,12 +66,29 @@ impl RawHandle {
     pub fn raw(&self) -> HANDLE { self.0 }
 
     pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
-        let mut read = 0;
-        let res = cvt(unsafe {
-            libc::ReadFile(self.0, buf.as_ptr() as libc::LPVOID,
-                           buf.len() as libc::DWORD, &mut read,
-                           ptr::null_mut())
+        unsafe { self.read_raw(buf.as_mut_ptr(), buf.len()) }
+    }
+
+    /// Reads into the spare capacity of `buf`, extending its length by the
+    /// number of bytes read.
+    ///
+    /// The uninitialized region is handed straight to `ReadFile`, so it never
+    /// has to be zeroed first.
+    pub fn read_to_spare(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let len = buf.len();
+        let read = try!(unsafe {
+            self.read_raw(buf.as_mut_ptr().offset(len as isize),
+                          buf.capacity() - len)
         });
+        unsafe { buf.set_len(len + read); }
+        Ok(read)
+    }
+
+    unsafe fn read_raw(&self, buf: *mut u8, len: usize) -> io::Result<usize> {
+        let mut read = 0;
+        let res = cvt(libc::ReadFile(self.0, buf as libc::LPVOID,
+                                     clamp_len(len), &mut read,
+                                     ptr::null_mut()));
 
         match res {
             Ok(_) => Ok(read as usize),
@@ -84,11 +103,37 @@ impl RawHandle {
         }
     }
 
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let mut read = 0;
+        let mut overlapped = overlapped_at(offset);
+        let res = cvt(unsafe {
+            libc::ReadFile(self.0, buf.as_mut_ptr() as libc::LPVOID,
+                           clamp_len(buf.len()), &mut read,
+                           &mut overlapped)
+        });
+        match res {
+            Ok(_) => Ok(read as usize),
+            Err(ref e) if e.raw_os_error() == Some(c::ERROR_HANDLE_EOF as i32) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        let mut amt = 0;
+        let mut overlapped = overlapped_at(offset);
+        try!(cvt(unsafe {
+            libc::WriteFile(self.0, buf.as_ptr() as libc::LPVOID,
+                            clamp_len(buf.len()), &mut amt,
+                            &mut overlapped)
+        }));
+        Ok(amt as usize)
+    }
+
     pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
         let mut amt = 0;
         try!(cvt(unsafe {
             libc::WriteFile(self.0, buf.as_ptr() as libc::LPVOID,
-                            buf.len() as libc::DWORD, &mut amt,
+                            clamp_len(buf.len()), &mut amt,
                             ptr::null_mut())
         }));
         Ok(amt as usize)
@@ -106,3 +151,20 @@ impl RawHandle {
         Ok(Handle::new(ret))
     }
 }
+
+// For a handle opened for synchronous I/O, the offset in an OVERLAPPED is
+// where the operation happens, and the file pointer ends up right after it.
+fn overlapped_at(offset: u64) -> libc::OVERLAPPED {
+    let mut overlapped: libc::OVERLAPPED = unsafe { mem::zeroed() };
+    overlapped.Offset = offset as libc::DWORD;
+    overlapped.OffsetHigh = (offset >> 32) as libc::DWORD;
+    overlapped
+}
+
+// ReadFile and WriteFile take a DWORD length, which a plain cast would
+// truncate for buffers of 4GB and more, down to 0 at exactly 4GB, making a
+// read look like the end of the file. Transferring less than asked for is
+// fine, so larger buffers are only partly used.
+fn clamp_len(len: usize) -> libc::DWORD {
+    cmp::min(len, <libc::DWORD>::max_value() as usize) as libc::DWORD
+}
@@ -49,10 +49,13 @@ pub fn decode_error_kind(errno: i32) -> ErrorKind {
     match errno as libc::c_int {
         libc::ERROR_ACCESS_DENIED => ErrorKind::PermissionDenied,
         libc::ERROR_ALREADY_EXISTS => ErrorKind::AlreadyExists,
+        c::ERROR_FILE_EXISTS => ErrorKind::AlreadyExists,
         libc::ERROR_BROKEN_PIPE => ErrorKind::BrokenPipe,
         libc::ERROR_FILE_NOT_FOUND => ErrorKind::NotFound,
         libc::ERROR_NO_DATA => ErrorKind::BrokenPipe,
         libc::ERROR_OPERATION_ABORTED => ErrorKind::TimedOut,
+        c::ERROR_CANT_RESOLVE_FILENAME => ErrorKind::FilesystemLoop,
+        c::ERROR_DIRECTORY => ErrorKind::NotADirectory,
 
         libc::WSAEACCES => ErrorKind::PermissionDenied,
         libc::WSAEADDRINUSE => ErrorKind::AddrInUse,